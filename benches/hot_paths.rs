@@ -0,0 +1,89 @@
+// Run with `cargo bench --features bench --bench hot_paths`.
+
+use bitflyer::api::{ApiRequest, GetBoard, GetChildOrders, SendChildOrder};
+use bitflyer::entity::{Board, OrderState, ProductCode, Side};
+use bitflyer::signing::sign_request;
+use criterion::{criterion_group, criterion_main, Criterion};
+use rust_decimal_macros::dec;
+use serde_json::json;
+use std::hint::black_box;
+
+const MID_PRICE: u64 = 10_000_000;
+
+// A book of `levels` levels a side around MID_PRICE, as the REST API returns it.
+fn board_json(levels: u64) -> String {
+    let side = |sign: i64| {
+        (1..=levels)
+            .map(|i| json!({ "price": MID_PRICE as i64 + sign * i as i64 * 100, "size": 0.01 * i as f64 }))
+            .collect::<Vec<_>>()
+    };
+    json!({ "mid_price": MID_PRICE, "bids": side(-1), "asks": side(1) }).to_string()
+}
+
+fn board_deserialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("board_deserialization");
+    for levels in [100, 1000] {
+        let body = board_json(levels);
+        group.bench_function(format!("full_{levels}"), |b| {
+            b.iter(|| serde_json::from_str::<Board>(black_box(&body)).unwrap())
+        });
+        let request = GetBoard::new(ProductCode::BtcJpy).with_depth(20);
+        group.bench_function(format!("depth_20_of_{levels}"), |b| {
+            b.iter(|| request.parse_response(black_box(&body)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn signing(c: &mut Criterion) {
+    let body = serde_json::to_string(&SendChildOrder::limit(
+        ProductCode::BtcJpy,
+        Side::Buy,
+        dec!(0.01),
+        dec!(10000000),
+    ))
+    .unwrap();
+    let mut group = c.benchmark_group("signing");
+    group.bench_function("get", |b| {
+        b.iter(|| {
+            sign_request(
+                black_box("secret"),
+                1_700_000_000,
+                "GET",
+                "/v1/me/getchildorders",
+                Some("product_code=BTC_JPY&child_order_state=ACTIVE"),
+                None,
+            )
+        })
+    });
+    group.bench_function("post", |b| {
+        b.iter(|| {
+            sign_request(
+                black_box("secret"),
+                1_700_000_000,
+                "POST",
+                "/v1/me/sendchildorder",
+                None,
+                Some(&body),
+            )
+        })
+    });
+    group.finish();
+}
+
+fn query_building(c: &mut Criterion) {
+    let request = GetChildOrders {
+        product_code: Some(ProductCode::FxBtcJpy),
+        count: Some(100),
+        before: Some(2_000_000),
+        after: Some(1_000_000),
+        child_order_state: Some(OrderState::Active),
+        ..Default::default()
+    };
+    c.bench_function("query_building", |b| {
+        b.iter(|| black_box(&request).url().unwrap())
+    });
+}
+
+criterion_group!(benches, board_deserialization, signing, query_building);
+criterion_main!(benches);
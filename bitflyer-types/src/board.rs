@@ -0,0 +1,199 @@
+use crate::entity::{Board, BoardElement, Side};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+impl BookSide {
+    pub fn taken_by(side: Side) -> Self {
+        match side {
+            Side::Buy => BookSide::Ask,
+            Side::Sell => BookSide::Bid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImpactEstimate {
+    pub side: Side,
+    pub requested_size: Decimal,
+    pub filled_size: Decimal,
+    pub average_price: Option<Decimal>,
+    pub best_price: Option<Decimal>,
+    pub worst_price: Option<Decimal>,
+    pub levels_consumed: usize,
+}
+
+impl ImpactEstimate {
+    pub fn is_fully_filled(&self) -> bool {
+        self.filled_size >= self.requested_size
+    }
+
+    pub fn unfilled_size(&self) -> Decimal {
+        self.requested_size - self.filled_size
+    }
+
+    pub fn notional(&self) -> Decimal {
+        self.average_price.unwrap_or_default() * self.filled_size
+    }
+
+    pub fn slippage(&self) -> Option<Decimal> {
+        let slippage = self.average_price? - self.best_price?;
+        Some(match self.side {
+            Side::Buy => slippage,
+            Side::Sell => -slippage,
+        })
+    }
+
+    pub fn slippage_bps(&self) -> Option<Decimal> {
+        let best = self.best_price?;
+        if best.is_zero() {
+            return None;
+        }
+        Some(self.slippage()? / best * dec!(10000))
+    }
+}
+
+impl Board {
+    pub fn levels(&self, side: BookSide) -> &[BoardElement] {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<&BoardElement> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&BoardElement> {
+        self.asks.first()
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        if self.mid_price.is_zero() {
+            return None;
+        }
+        Some(self.spread()? / self.mid_price * dec!(10000))
+    }
+
+    pub fn depth_within_bps(&self, side: BookSide, bps: Decimal) -> Decimal {
+        let distance = self.mid_price * bps / dec!(10000);
+        let limit = match side {
+            BookSide::Bid => self.mid_price - distance,
+            BookSide::Ask => self.mid_price + distance,
+        };
+        self.cumulative_size_to_price(side, limit)
+    }
+
+    pub fn cumulative_size_to_price(&self, side: BookSide, price: Decimal) -> Decimal {
+        self.levels(side)
+            .iter()
+            .take_while(|x| match side {
+                BookSide::Bid => x.price >= price,
+                BookSide::Ask => x.price <= price,
+            })
+            .map(|x| x.size)
+            .sum()
+    }
+
+    pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
+        let bid: Decimal = self.bids.iter().take(levels).map(|x| x.size).sum();
+        let ask: Decimal = self.asks.iter().take(levels).map(|x| x.size).sum();
+        let total = bid + ask;
+        if total.is_zero() {
+            None
+        } else {
+            Some((bid - ask) / total)
+        }
+    }
+
+    pub fn vwap(&self, side: BookSide, levels: usize) -> Option<Decimal> {
+        weighted_levels(self.levels(side), levels).map(|(price, _)| price)
+    }
+}
+
+pub trait OrderBook {
+    fn bid_levels(&self) -> &[BoardElement];
+    fn ask_levels(&self) -> &[BoardElement];
+
+    fn mid(&self) -> Option<Decimal> {
+        let bid = self.bid_levels().first()?;
+        let ask = self.ask_levels().first()?;
+        Some((bid.price + ask.price) / dec!(2))
+    }
+
+    fn micro_price(&self) -> Option<Decimal> {
+        let bid = self.bid_levels().first()?;
+        let ask = self.ask_levels().first()?;
+        let size = bid.size + ask.size;
+        if size.is_zero() {
+            return None;
+        }
+        Some((bid.price * ask.size + ask.price * bid.size) / size)
+    }
+
+    fn estimate_impact(&self, side: Side, size: Decimal) -> ImpactEstimate {
+        let levels = match BookSide::taken_by(side) {
+            BookSide::Bid => self.bid_levels(),
+            BookSide::Ask => self.ask_levels(),
+        };
+        let mut filled_size = Decimal::ZERO;
+        let mut notional = Decimal::ZERO;
+        let mut worst_price = None;
+        let mut levels_consumed = 0;
+        for level in levels {
+            if filled_size >= size {
+                break;
+            }
+            let take = level.size.min(size - filled_size);
+            filled_size += take;
+            notional += take * level.price;
+            worst_price = Some(level.price);
+            levels_consumed += 1;
+        }
+        ImpactEstimate {
+            side,
+            requested_size: size,
+            filled_size,
+            average_price: (!filled_size.is_zero()).then(|| notional / filled_size),
+            best_price: levels.first().map(|x| x.price),
+            worst_price,
+            levels_consumed,
+        }
+    }
+
+    fn weighted_mid(&self, depth: usize) -> Option<Decimal> {
+        let (bid_price, bid_size) = weighted_levels(self.bid_levels(), depth)?;
+        let (ask_price, ask_size) = weighted_levels(self.ask_levels(), depth)?;
+        Some((bid_price * ask_size + ask_price * bid_size) / (bid_size + ask_size))
+    }
+}
+
+fn weighted_levels(levels: &[BoardElement], depth: usize) -> Option<(Decimal, Decimal)> {
+    let levels = &levels[..depth.min(levels.len())];
+    let size: Decimal = levels.iter().map(|x| x.size).sum();
+    if size.is_zero() {
+        return None;
+    }
+    let price = levels.iter().map(|x| x.price * x.size).sum::<Decimal>() / size;
+    Some((price, size))
+}
+
+impl OrderBook for Board {
+    fn bid_levels(&self) -> &[BoardElement] {
+        &self.bids
+    }
+
+    fn ask_levels(&self) -> &[BoardElement] {
+        &self.asks
+    }
+}
@@ -0,0 +1,1177 @@
+use crate::deserializer::{decimal, timestamp, timestamp_option};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{de, Deserialize, Serialize};
+
+// `#[serde(other)]` takes any string, so the known values are only listed as one option.
+#[cfg(feature = "schema")]
+fn open_enum(schema: &mut schemars::Schema) {
+    if let Some(known) = schema.remove("enum") {
+        schema.remove("type");
+        schema.insert(
+            "anyOf".to_string(),
+            serde_json::json!([{ "type": "string", "enum": known }, { "type": "string" }]),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn get_reverse(&self) -> Self {
+        use Side::*;
+        match *self {
+            Buy => Sell,
+            Sell => Buy,
+        }
+    }
+}
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(&self)
+            .unwrap()
+            .trim_matches('"')
+            .to_string();
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(transform = open_enum))]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExecutionSide {
+    Buy,
+    Sell,
+    #[serde(other)]
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ParentOrderSide {
+    Buy,
+    Sell,
+    BuySell,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum MarketType {
+    Spot,
+    #[serde(rename = "FX")]
+    Fx,
+    Futures,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(transform = open_enum))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum ProductCode {
+    BtcJpy,
+    XrpJpy,
+    EthJpy,
+    XlmJpy,
+    MonaJpy,
+    EthBtc,
+    BchBtc,
+    FxBtcJpy,
+    #[serde(other)]
+    Other,
+}
+
+impl ProductCode {
+    pub fn base_currency(&self) -> Option<&'static str> {
+        use ProductCode::*;
+        match self {
+            BtcJpy | FxBtcJpy => Some("BTC"),
+            XrpJpy => Some("XRP"),
+            EthJpy | EthBtc => Some("ETH"),
+            XlmJpy => Some("XLM"),
+            MonaJpy => Some("MONA"),
+            BchBtc => Some("BCH"),
+            Other => None,
+        }
+    }
+
+    pub fn quote_currency(&self) -> Option<&'static str> {
+        use ProductCode::*;
+        match self {
+            BtcJpy | FxBtcJpy | XrpJpy | EthJpy | XlmJpy | MonaJpy => Some("JPY"),
+            EthBtc | BchBtc => Some("BTC"),
+            Other => None,
+        }
+    }
+
+    pub fn min_order_size(&self) -> Option<Decimal> {
+        use ProductCode::*;
+        match self {
+            BtcJpy => Some(dec!(0.001)),
+            FxBtcJpy | EthJpy | EthBtc | BchBtc => Some(dec!(0.01)),
+            XrpJpy | XlmJpy | MonaJpy => Some(dec!(0.1)),
+            Other => None,
+        }
+    }
+
+    pub fn size_increment(&self) -> Option<Decimal> {
+        match self {
+            ProductCode::Other => None,
+            _ => Some(dec!(0.00000001)),
+        }
+    }
+
+    pub fn is_spot(&self) -> bool {
+        use ProductCode::*;
+        matches!(
+            self,
+            BtcJpy | XrpJpy | EthJpy | XlmJpy | MonaJpy | EthBtc | BchBtc
+        )
+    }
+
+    // Traded on margin: FX_BTC_JPY and every code that reads as `Other`, e.g. the futures.
+    pub fn is_fx(&self) -> bool {
+        !self.is_spot()
+    }
+
+    pub fn spot(base_currency: &str, quote_currency: &str) -> Option<Self> {
+        use ProductCode::*;
+        [BtcJpy, XrpJpy, EthJpy, XlmJpy, MonaJpy, EthBtc, BchBtc]
+            .into_iter()
+            .find(|x| {
+                x.base_currency() == Some(base_currency)
+                    && x.quote_currency() == Some(quote_currency)
+            })
+    }
+}
+
+impl std::fmt::Display for ProductCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(&self)
+            .unwrap()
+            .trim_matches('"')
+            .to_string();
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "schema",
+    schemars(extend("enum" = [
+        "NORMAL", "BUSY", "VERY_BUSY", "VERY BUSY", "SUPER_BUSY", "SUPER BUSY", "NO_ORDER",
+        "NO ORDER", "STOP"
+    ]))
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum Health {
+    Normal,
+    Busy,
+    // The API spells these with spaces.
+    #[serde(alias = "VERY BUSY")]
+    VeryBusy,
+    #[serde(alias = "SUPER BUSY")]
+    SuperBusy,
+    #[serde(alias = "NO ORDER")]
+    NoOrder,
+    Stop,
+}
+
+impl Health {
+    pub fn is_orderable(&self) -> bool {
+        !matches!(self, Health::NoOrder | Health::Stop)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum State {
+    Running,
+    Closed,
+    Starting,
+    Preopen,
+    #[serde(rename = "CIRCUT BREAK")]
+    CircutBreak,
+    #[serde(rename = "AWAITING SQ")]
+    AwaitingSq,
+    Matured,
+}
+
+impl State {
+    pub fn is_running(&self) -> bool {
+        *self == State::Running
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE", tag = "child_order_type")]
+pub enum ChildOrderType {
+    Limit { price: Decimal },
+    Market,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+#[non_exhaustive]
+pub enum ParentOrderType {
+    Limit,
+    Market,
+    Stop,
+    StopLimit,
+    Trail,
+    Simple,
+    Ifd,
+    Oco,
+    Ifdoco,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TimeInForce {
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "u64", into = "u64")]
+pub struct MinuteToExpire(u64);
+
+impl MinuteToExpire {
+    pub const MIN: Self = Self(1);
+    pub const MAX: Self = Self(43200);
+
+    pub fn from_minutes(minutes: u64) -> Result<Self, InvalidExpiry> {
+        if (Self::MIN.0..=Self::MAX.0).contains(&minutes) {
+            Ok(Self(minutes))
+        } else {
+            Err(InvalidExpiry::OutOfRange { minutes })
+        }
+    }
+
+    pub fn from_duration(duration: std::time::Duration) -> Result<Self, InvalidExpiry> {
+        if duration.subsec_nanos() != 0 || !duration.as_secs().is_multiple_of(60) {
+            return Err(InvalidExpiry::NotWholeMinutes(duration));
+        }
+        Self::from_minutes(duration.as_secs() / 60)
+    }
+
+    pub fn minutes(&self) -> u64 {
+        self.0
+    }
+
+    pub fn as_duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.0 * 60)
+    }
+}
+
+impl Default for MinuteToExpire {
+    fn default() -> Self {
+        Self::MAX
+    }
+}
+
+impl TryFrom<u64> for MinuteToExpire {
+    type Error = InvalidExpiry;
+
+    fn try_from(minutes: u64) -> Result<Self, Self::Error> {
+        Self::from_minutes(minutes)
+    }
+}
+
+impl TryFrom<std::time::Duration> for MinuteToExpire {
+    type Error = InvalidExpiry;
+
+    fn try_from(duration: std::time::Duration) -> Result<Self, Self::Error> {
+        Self::from_duration(duration)
+    }
+}
+
+impl TryFrom<chrono::Duration> for MinuteToExpire {
+    type Error = InvalidExpiry;
+
+    fn try_from(duration: chrono::Duration) -> Result<Self, Self::Error> {
+        let duration = duration
+            .to_std()
+            .map_err(|_| InvalidExpiry::Negative(duration))?;
+        Self::from_duration(duration)
+    }
+}
+
+impl From<MinuteToExpire> for u64 {
+    fn from(value: MinuteToExpire) -> Self {
+        value.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidExpiry {
+    OutOfRange { minutes: u64 },
+    NotWholeMinutes(std::time::Duration),
+    Negative(chrono::Duration),
+}
+
+impl std::fmt::Display for InvalidExpiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidExpiry::OutOfRange { minutes } => write!(
+                f,
+                "minute_to_expire must be between {} and {}: {minutes}",
+                MinuteToExpire::MIN.0,
+                MinuteToExpire::MAX.0
+            ),
+            InvalidExpiry::NotWholeMinutes(duration) => {
+                write!(f, "expiry is not a whole number of minutes: {duration:?}")
+            }
+            InvalidExpiry::Negative(duration) => write!(f, "expiry is negative: {duration}"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidExpiry {}
+
+// Numbers the ids of fabricated orders and executions so they stay unique within a process.
+static ACCEPTANCE_IDS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+fn next_acceptance_id() -> String {
+    let id = ACCEPTANCE_IDS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    format!("JRF{}-{id:06}", Utc::now().format("%Y%m%d-%H%M%S"))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct ChildOrderAcceptanceId(String);
+
+impl ChildOrderAcceptanceId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl std::fmt::Display for ChildOrderAcceptanceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for ChildOrderAcceptanceId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for ChildOrderAcceptanceId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<ChildOrderAcceptanceId> for String {
+    fn from(id: ChildOrderAcceptanceId) -> Self {
+        id.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE", tag = "order_method")]
+pub enum ParentOrderMethod {
+    Simple {
+        parameters: [ParentOrderConditionType; 1],
+    },
+    Ifd {
+        parameters: [ParentOrderConditionType; 2],
+    },
+    Oco {
+        parameters: [ParentOrderConditionType; 2],
+    },
+    Ifdoco {
+        parameters: [ParentOrderConditionType; 3],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE", tag = "condition_type")]
+pub enum ParentOrderConditionType {
+    Limit {
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+    },
+    Market {
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+    },
+    Stop {
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        trigger_price: Decimal,
+    },
+    StopLimit {
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+        trigger_price: Decimal,
+    },
+    Trail {
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        offset: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+#[non_exhaustive]
+pub enum OrderState {
+    Active,
+    Completed,
+    Canceled,
+    Expired,
+    Rejected,
+}
+
+impl OrderState {
+    pub fn is_active(&self) -> bool {
+        *self == OrderState::Active
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !self.is_active()
+    }
+}
+
+impl std::fmt::Display for OrderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(&self)
+            .unwrap()
+            .trim_matches('"')
+            .to_string();
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct BoardElement {
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub price: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub size: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Board {
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub mid_price: Decimal,
+    pub bids: Vec<BoardElement>,
+    pub asks: Vec<BoardElement>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardDepth(pub usize);
+
+impl<'de> de::DeserializeSeed<'de> for BoardDepth {
+    type Value = Board;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for BoardDepth {
+    type Value = Board;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a board")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        #[derive(Deserialize)]
+        struct MidPrice(#[serde(with = "decimal")] Decimal);
+
+        let mut mid_price = None;
+        let mut bids = None;
+        let mut asks = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "mid_price" => mid_price = Some(map.next_value::<MidPrice>()?.0),
+                "bids" => bids = Some(map.next_value_seed(BoardLevels(self.0))?),
+                "asks" => asks = Some(map.next_value_seed(BoardLevels(self.0))?),
+                _ => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(Board {
+            mid_price: mid_price.ok_or_else(|| de::Error::missing_field("mid_price"))?,
+            bids: bids.ok_or_else(|| de::Error::missing_field("bids"))?,
+            asks: asks.ok_or_else(|| de::Error::missing_field("asks"))?,
+        })
+    }
+}
+
+struct BoardLevels(usize);
+
+impl<'de> de::DeserializeSeed<'de> for BoardLevels {
+    type Value = Vec<BoardElement>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for BoardLevels {
+    type Value = Vec<BoardElement>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a list of board levels")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut levels = Vec::with_capacity(self.0.min(seq.size_hint().unwrap_or(0)));
+        while levels.len() < self.0 {
+            match seq.next_element::<BoardElement>()? {
+                Some(level) => levels.push(level),
+                None => return Ok(levels),
+            }
+        }
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+        Ok(levels)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Market {
+    product_code: ProductCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    market_type: MarketType,
+}
+
+impl Market {
+    pub fn product_code(&self) -> &ProductCode {
+        &self.product_code
+    }
+
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_deref()
+    }
+
+    pub fn market_type(&self) -> MarketType {
+        self.market_type
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Ticker {
+    pub product_code: ProductCode,
+    pub state: State,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub timestamp: DateTime<Utc>,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub tick_id: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub best_bid: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub best_ask: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub best_bid_size: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub best_ask_size: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub total_bid_depth: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub total_ask_depth: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub market_bid_size: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub market_ask_size: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub ltp: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub volume: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub volume_by_product: Decimal,
+}
+
+// Constructors fill in plausible values for everything not given, so test data can be
+// fabricated without going through JSON. Fields are public for anything else.
+impl Ticker {
+    pub fn new(product_code: ProductCode, best_bid: Decimal, best_ask: Decimal) -> Self {
+        Self {
+            product_code,
+            state: State::Running,
+            timestamp: Utc::now(),
+            tick_id: Decimal::ONE,
+            best_bid,
+            best_ask,
+            best_bid_size: dec!(0.01),
+            best_ask_size: dec!(0.01),
+            total_bid_depth: dec!(100),
+            total_ask_depth: dec!(100),
+            market_bid_size: Decimal::ZERO,
+            market_ask_size: Decimal::ZERO,
+            ltp: (best_bid + best_ask) / dec!(2),
+            volume: Decimal::ZERO,
+            volume_by_product: Decimal::ZERO,
+        }
+    }
+
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_tick_id(mut self, tick_id: Decimal) -> Self {
+        self.tick_id = tick_id;
+        self
+    }
+
+    pub fn with_ltp(mut self, ltp: Decimal) -> Self {
+        self.ltp = ltp;
+        self
+    }
+
+    pub fn with_best_sizes(mut self, best_bid_size: Decimal, best_ask_size: Decimal) -> Self {
+        self.best_bid_size = best_bid_size;
+        self.best_ask_size = best_ask_size;
+        self
+    }
+
+    pub fn with_depths(mut self, total_bid_depth: Decimal, total_ask_depth: Decimal) -> Self {
+        self.total_bid_depth = total_bid_depth;
+        self.total_ask_depth = total_ask_depth;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: Decimal) -> Self {
+        self.volume = volume;
+        self.volume_by_product = volume;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Execution {
+    pub id: u64,
+    pub side: ExecutionSide,
+    pub price: Decimal,
+    pub size: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub exec_date: DateTime<Utc>,
+    pub buy_child_order_acceptance_id: String,
+    pub sell_child_order_acceptance_id: String,
+}
+
+impl Execution {
+    // The taker side is `side`; both acceptance ids are generated.
+    pub fn new(id: u64, side: Side, price: Decimal, size: Decimal) -> Self {
+        Self {
+            id,
+            side: match side {
+                Side::Buy => ExecutionSide::Buy,
+                Side::Sell => ExecutionSide::Sell,
+            },
+            price,
+            size,
+            exec_date: Utc::now(),
+            buy_child_order_acceptance_id: next_acceptance_id(),
+            sell_child_order_acceptance_id: next_acceptance_id(),
+        }
+    }
+
+    // Executions at the itayose auction have no taker side.
+    pub fn with_side(mut self, side: ExecutionSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    pub fn with_exec_date(mut self, exec_date: DateTime<Utc>) -> Self {
+        self.exec_date = exec_date;
+        self
+    }
+
+    pub fn with_acceptance_ids(
+        mut self,
+        buy_child_order_acceptance_id: impl Into<String>,
+        sell_child_order_acceptance_id: impl Into<String>,
+    ) -> Self {
+        self.buy_child_order_acceptance_id = buy_child_order_acceptance_id.into();
+        self.sell_child_order_acceptance_id = sell_child_order_acceptance_id.into();
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct PrivateExecution {
+    pub id: u64,
+    pub child_order_id: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub commission: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub exec_date: DateTime<Utc>,
+    pub child_order_acceptance_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct BoardState {
+    health: Health,
+    state: State,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<BoardStateData>,
+}
+
+impl BoardState {
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn special_quotation(&self) -> Option<Decimal> {
+        self.data.as_ref().map(|x| x.special_quotation)
+    }
+
+    pub fn is_orderable(&self) -> bool {
+        self.state.is_running() && self.health.is_orderable()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct BoardStateData {
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    special_quotation: Decimal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct BoardHealth {
+    status: Health,
+}
+
+impl BoardHealth {
+    pub fn status(&self) -> Health {
+        self.status
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Balance {
+    pub currency_code: String,
+    pub amount: Decimal,
+    pub available: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Collateral {
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub collateral: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub open_position_pnl: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub require_collateral: Decimal,
+    pub keep_rate: f64,
+    #[cfg_attr(feature = "schema", schemars(with = "Decimal"))]
+    #[serde(with = "decimal")]
+    pub margin_call_amount: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "Option<DateTime<Utc>>"))]
+    #[serde(with = "timestamp_option")]
+    pub margin_call_due_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct CollateralAccount {
+    pub currency_code: String,
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct ChildOrder {
+    pub id: u64,
+    pub child_order_id: String,
+    pub product_code: ProductCode,
+    pub side: Side,
+    #[serde(flatten)]
+    pub child_order_type: ChildOrderType,
+    pub average_price: Decimal,
+    pub size: Decimal,
+    pub child_order_state: OrderState,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub expire_date: DateTime<Utc>,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub child_order_date: DateTime<Utc>,
+    pub child_order_acceptance_id: String,
+    pub outstanding_size: Decimal,
+    pub cancel_size: Decimal,
+    pub executed_size: Decimal,
+    pub total_commission: Decimal,
+    pub time_in_force: TimeInForce,
+}
+
+impl ChildOrder {
+    // An active, unfilled GTC order accepted now, expiring in 30 days like the API's default.
+    pub fn new(
+        product_code: ProductCode,
+        side: Side,
+        child_order_type: ChildOrderType,
+        size: Decimal,
+    ) -> Self {
+        let id = ACCEPTANCE_IDS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let child_order_date = Utc::now();
+        Self {
+            id,
+            child_order_id: format!("JOR{}-{id:06}", child_order_date.format("%Y%m%d-%H%M%S")),
+            product_code,
+            side,
+            child_order_type,
+            average_price: Decimal::ZERO,
+            size,
+            child_order_state: OrderState::Active,
+            expire_date: child_order_date + chrono::Duration::days(30),
+            child_order_date,
+            child_order_acceptance_id: format!(
+                "JRF{}-{id:06}",
+                child_order_date.format("%Y%m%d-%H%M%S")
+            ),
+            outstanding_size: size,
+            cancel_size: Decimal::ZERO,
+            executed_size: Decimal::ZERO,
+            total_commission: Decimal::ZERO,
+            time_in_force: TimeInForce::Gtc,
+        }
+    }
+
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_child_order_id(mut self, child_order_id: impl Into<String>) -> Self {
+        self.child_order_id = child_order_id.into();
+        self
+    }
+
+    pub fn with_acceptance_id(mut self, child_order_acceptance_id: impl Into<String>) -> Self {
+        self.child_order_acceptance_id = child_order_acceptance_id.into();
+        self
+    }
+
+    pub fn with_state(mut self, child_order_state: OrderState) -> Self {
+        self.child_order_state = child_order_state;
+        self
+    }
+
+    // Moves the expiry along with the order date.
+    pub fn with_child_order_date(mut self, child_order_date: DateTime<Utc>) -> Self {
+        self.expire_date += child_order_date - self.child_order_date;
+        self.child_order_date = child_order_date;
+        self
+    }
+
+    pub fn with_expire_date(mut self, expire_date: DateTime<Utc>) -> Self {
+        self.expire_date = expire_date;
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    // Sizes beyond the order size are capped; a fully filled order becomes completed.
+    pub fn with_fill(mut self, executed_size: Decimal, average_price: Decimal) -> Self {
+        self.executed_size = executed_size.min(self.size);
+        self.average_price = average_price;
+        self.outstanding_size = self.size - self.executed_size - self.cancel_size;
+        if self.outstanding_size <= Decimal::ZERO {
+            self.outstanding_size = Decimal::ZERO;
+            self.child_order_state = OrderState::Completed;
+        }
+        self
+    }
+
+    // Cancels whatever is still outstanding.
+    pub fn canceled(mut self) -> Self {
+        self.cancel_size += self.outstanding_size;
+        self.outstanding_size = Decimal::ZERO;
+        self.child_order_state = OrderState::Canceled;
+        self
+    }
+
+    pub fn with_commission(mut self, total_commission: Decimal) -> Self {
+        self.total_commission = total_commission;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct Position {
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub commission: Decimal,
+    pub swap_point_accumulate: Decimal,
+    pub require_collateral: Decimal,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub open_date: DateTime<Utc>,
+    pub leverage: Decimal,
+    pub pnl: Decimal,
+    pub sfd: Decimal,
+}
+
+impl Position {
+    // Opened now at 2x leverage with no swap, fees or pnl yet.
+    pub fn new(product_code: ProductCode, side: Side, price: Decimal, size: Decimal) -> Self {
+        let leverage = dec!(2);
+        Self {
+            product_code,
+            side,
+            price,
+            size,
+            commission: Decimal::ZERO,
+            swap_point_accumulate: Decimal::ZERO,
+            require_collateral: price * size / leverage,
+            open_date: Utc::now(),
+            leverage,
+            pnl: Decimal::ZERO,
+            sfd: Decimal::ZERO,
+        }
+    }
+
+    // Recomputes the required collateral.
+    pub fn with_leverage(mut self, leverage: Decimal) -> Self {
+        self.leverage = leverage;
+        self.require_collateral = self.price * self.size / leverage;
+        self
+    }
+
+    pub fn with_open_date(mut self, open_date: DateTime<Utc>) -> Self {
+        self.open_date = open_date;
+        self
+    }
+
+    pub fn with_pnl(mut self, pnl: Decimal) -> Self {
+        self.pnl = pnl;
+        self
+    }
+
+    // Unrealized pnl against `mark_price`.
+    pub fn marked_at(mut self, mark_price: Decimal) -> Self {
+        let pnl = (mark_price - self.price) * self.size;
+        self.pnl = match self.side {
+            Side::Buy => pnl,
+            Side::Sell => -pnl,
+        };
+        self
+    }
+
+    pub fn with_swap_point_accumulate(mut self, swap_point_accumulate: Decimal) -> Self {
+        self.swap_point_accumulate = swap_point_accumulate;
+        self
+    }
+
+    pub fn with_commission(mut self, commission: Decimal) -> Self {
+        self.commission = commission;
+        self
+    }
+
+    pub fn with_sfd(mut self, sfd: Decimal) -> Self {
+        self.sfd = sfd;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(transform = open_enum))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum TradeType {
+    Buy,
+    Sell,
+    Deposit,
+    Withdraw,
+    Fee,
+    PostColl,
+    CancelColl,
+    Payment,
+    Transfer,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct BalanceHistory {
+    pub id: u64,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub trade_date: DateTime<Utc>,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub event_date: DateTime<Utc>,
+    pub product_code: ProductCode,
+    pub currency_code: String,
+    pub trade_type: TradeType,
+    pub price: Decimal,
+    pub amount: Decimal,
+    pub quantity: Decimal,
+    pub commission: Decimal,
+    pub balance: Decimal,
+    pub order_id: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(transform = open_enum))]
+#[serde(rename_all = "UPPERCASE")]
+#[non_exhaustive]
+pub enum CoinTransferStatus {
+    Pending,
+    Completed,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct CoinIn {
+    pub id: u64,
+    pub order_id: String,
+    pub currency_code: String,
+    pub amount: Decimal,
+    pub address: String,
+    pub tx_hash: String,
+    pub status: CoinTransferStatus,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub event_date: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct CoinOut {
+    pub id: u64,
+    pub order_id: String,
+    pub currency_code: String,
+    pub amount: Decimal,
+    pub address: String,
+    pub tx_hash: String,
+    pub fee: Decimal,
+    pub additional_fee: Decimal,
+    pub status: CoinTransferStatus,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
+    #[serde(with = "timestamp")]
+    pub event_date: DateTime<Utc>,
+}
@@ -0,0 +1,166 @@
+pub mod board;
+pub mod entity;
+
+pub mod deserializer {
+    use chrono::{DateTime, Utc};
+    use core::fmt;
+    use serde::de;
+
+    pub(super) struct TimeStampVisitor;
+
+    impl<'de> de::Visitor<'de> for TimeStampVisitor {
+        type Value = DateTime<Utc>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a UTC or custom UTC(2015-07-08T02:50:59.97) or epoch milliseconds")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            use std::str::FromStr;
+            match DateTime::<Utc>::from_str(value) {
+                Ok(datetime) => Ok(datetime),
+                Err(_) => {
+                    let value = format!("{value}+00:00");
+                    DateTime::<Utc>::from_str(&value).map_err(de::Error::custom)
+                }
+            }
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            use chrono::TimeZone;
+            Utc.timestamp_millis_opt(value).single().ok_or_else(|| {
+                de::Error::custom(format!("epoch milliseconds out of range: {value}"))
+            })
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let value = i64::try_from(value).map_err(de::Error::custom)?;
+            self.visit_i64(value)
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            if !value.is_finite() || value.abs() >= i64::MAX as f64 {
+                return Err(de::Error::custom(format!(
+                    "epoch milliseconds out of range: {value}"
+                )));
+            }
+            let millis = value.trunc();
+            let nanos = ((value - millis) * 1_000_000.0).round() as i64;
+            let datetime = self.visit_i64::<E>(millis as i64)?;
+            Ok(datetime + chrono::Duration::nanoseconds(nanos))
+        }
+    }
+
+    pub(super) struct DecimalVisitor;
+
+    impl<'de> de::Visitor<'de> for DecimalVisitor {
+        type Value = rust_decimal::Decimal;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter
+                .write_str("a decimal number or a numeric string (plain or exponential notation)")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            use std::str::FromStr;
+            let value = value.trim();
+            match rust_decimal::Decimal::from_str(value) {
+                Ok(decimal) => Ok(decimal),
+                Err(_) => rust_decimal::Decimal::from_scientific(value).map_err(de::Error::custom),
+            }
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(value.into())
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(value.into())
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            rust_decimal::Decimal::try_from(value).map_err(de::Error::custom)
+        }
+    }
+
+    pub mod decimal {
+        use super::DecimalVisitor;
+        use rust_decimal::Decimal;
+        use serde::{de, ser};
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Decimal, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            d.deserialize_any(DecimalVisitor)
+        }
+
+        // Written as a string so the value survives a round trip exactly.
+        pub fn serialize<S>(value: &Decimal, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            s.serialize_str(&value.to_string())
+        }
+    }
+
+    pub mod timestamp {
+        use super::TimeStampVisitor;
+        use chrono::{DateTime, Utc};
+        use serde::{de, ser};
+
+        pub fn deserialize<'de, D>(d: D) -> Result<DateTime<Utc>, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            d.deserialize_any(TimeStampVisitor)
+        }
+
+        pub fn serialize<S>(value: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            ser::Serialize::serialize(value, s)
+        }
+    }
+
+    pub mod timestamp_option {
+        use chrono::{DateTime, Utc};
+        use serde::de;
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<DateTime<Utc>>, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            use serde::Deserialize;
+            #[derive(Deserialize)]
+            struct Helper(#[serde(with = "super::timestamp")] DateTime<Utc>);
+            let helper = Option::deserialize(d)?;
+            Ok(helper.map(|Helper(x)| x))
+        }
+    }
+}
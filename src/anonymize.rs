@@ -0,0 +1,237 @@
+use crate::redact::Redactor;
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+
+// Balances and collateral, which say how large the account is.
+pub const DEFAULT_AMOUNT_FIELDS: [&str; 9] = [
+    "amount",
+    "available",
+    "balance",
+    "collateral",
+    "margin_call_amount",
+    "open_position_pnl",
+    "pnl",
+    "quantity",
+    "require_collateral",
+];
+
+fn is_id(key: &str) -> bool {
+    key == "id" || key.ends_with("_id")
+}
+
+// Scrubs recorded private responses so they can be shared as fixtures. Numeric ids are
+// renumbered from 1 in their original order, and string ids such as JRF20261016-090000-123456
+// keep their prefix and date but get a new serial. The same id is always replaced the same way,
+// so orders, executions and balance history still refer to each other. Wallet fields are
+// redacted, and amounts are rounded only if asked to.
+#[derive(Clone, Debug)]
+pub struct Anonymizer {
+    redactor: Redactor,
+    amount_fields: Vec<String>,
+    significant_digits: Option<u32>,
+    numeric_ids: HashMap<u64, u64>,
+    string_ids: HashMap<String, String>,
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self {
+            redactor: Redactor::default(),
+            amount_fields: DEFAULT_AMOUNT_FIELDS.map(String::from).to_vec(),
+            significant_digits: None,
+            numeric_ids: HashMap::new(),
+            string_ids: HashMap::new(),
+        }
+    }
+
+    // JSON fields whose values are replaced, `address` and `tx_hash` by default.
+    pub fn with_redacted_fields(
+        mut self,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.redactor.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_amount_fields(
+        mut self,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.amount_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // Rounds amounts to this many significant digits, e.g. 1024078 to 1000000 with 1.
+    pub fn with_amount_buckets(mut self, significant_digits: u32) -> Self {
+        self.significant_digits = Some(significant_digits);
+        self
+    }
+
+    // Ids seen in earlier values keep their replacement; new ones are numbered after them.
+    pub fn anonymize_value(&mut self, value: &mut Value) {
+        self.redactor.redact_value(value);
+        let mut ids = BTreeSet::new();
+        collect_numeric_ids(value, &mut ids);
+        let mut next = self.numeric_ids.values().max().copied().unwrap_or(0);
+        for id in ids {
+            self.numeric_ids.entry(id).or_insert_with(|| {
+                next += 1;
+                next
+            });
+        }
+        self.replace(value);
+    }
+
+    pub fn anonymize(&mut self, body: &str) -> Result<String> {
+        let mut value: Value = serde_json::from_str(body)?;
+        self.anonymize_value(&mut value);
+        Ok(value.to_string())
+    }
+
+    // Rewrites a JSON file, e.g. a fixture for `tests/golden`, in place.
+    pub fn anonymize_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read the fixture {}", path.display()))?;
+        let mut value: Value = serde_json::from_slice(&data)?;
+        self.anonymize_value(&mut value);
+        std::fs::write(path, serde_json::to_string_pretty(&value)? + "\n")?;
+        Ok(())
+    }
+
+    // Also rewrites ids in request bodies and queries, so the cassette still replays.
+    #[cfg(feature = "test-util")]
+    pub fn anonymize_cassette(&mut self, cassette: &mut crate::vcr::Cassette) -> Result<()> {
+        for interaction in &mut cassette.interactions {
+            for body in [&mut interaction.response, &mut interaction.body]
+                .into_iter()
+                .flatten()
+            {
+                if serde_json::from_str::<Value>(body).is_ok() {
+                    *body = self.anonymize(body)?;
+                }
+            }
+            if let Some(query) = &mut interaction.query {
+                *query = self.anonymize_query(query);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "test-util")]
+    fn anonymize_query(&mut self, query: &str) -> String {
+        let pairs = reqwest::Url::parse(&format!("http://localhost/?{query}"))
+            .map(|x| x.query_pairs().into_owned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut serializer = reqwest::Url::parse("http://localhost/").unwrap();
+        for (key, value) in pairs {
+            let value = match is_id(&key) {
+                true => self.string_id(&value),
+                false => value,
+            };
+            serializer.query_pairs_mut().append_pair(&key, &value);
+        }
+        serializer.query().unwrap_or_default().to_string()
+    }
+
+    fn string_id(&mut self, id: &str) -> String {
+        if id.is_empty() {
+            return String::new();
+        }
+        if let Some(x) = self.string_ids.get(id) {
+            return x.clone();
+        }
+        let serial = self.string_ids.len() + 1;
+        let prefix_len = id
+            .find(|x: char| !x.is_ascii_uppercase())
+            .unwrap_or(id.len());
+        let (prefix, rest) = id.split_at(prefix_len);
+        let is_dated = rest.len() > 15
+            && rest[..8].bytes().all(|x| x.is_ascii_digit())
+            && rest.as_bytes()[8] == b'-'
+            && rest[9..15].bytes().all(|x| x.is_ascii_digit());
+        let replacement = match is_dated {
+            true => format!("{prefix}{}-{serial:06}", &rest[..15]),
+            false => format!("{prefix}{serial}"),
+        };
+        self.string_ids.insert(id.to_string(), replacement.clone());
+        replacement
+    }
+
+    fn bucket(&self, value: &mut Value) {
+        let Some(digits) = self.significant_digits else {
+            return;
+        };
+        let (text, is_string) = match &*value {
+            Value::Number(x) => (x.to_string(), false),
+            Value::String(x) => (x.clone(), true),
+            _ => return,
+        };
+        let Some(rounded) = Decimal::from_str(&text)
+            .or_else(|_| Decimal::from_scientific(&text))
+            .ok()
+            .and_then(|x| x.round_sf(digits))
+        else {
+            return;
+        };
+        let rounded = rounded.normalize().to_string();
+        *value = match is_string {
+            true => Value::String(rounded),
+            false => serde_json::from_str(&rounded).unwrap_or(Value::String(rounded)),
+        };
+    }
+
+    fn replace(&mut self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if is_id(key) {
+                        match value {
+                            Value::Number(x) => {
+                                if let Some(id) = x.as_u64().and_then(|x| self.numeric_ids.get(&x))
+                                {
+                                    *value = Value::from(*id);
+                                }
+                            }
+                            Value::String(x) => *x = self.string_id(x),
+                            _ => self.replace(value),
+                        }
+                    } else if self.amount_fields.iter().any(|x| x == key) {
+                        self.bucket(value);
+                    } else {
+                        self.replace(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|x| self.replace(x)),
+            _ => {}
+        }
+    }
+}
+
+fn collect_numeric_ids(value: &Value, ids: &mut BTreeSet<u64>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value.as_u64() {
+                    Some(id) if is_id(key) => {
+                        ids.insert(id);
+                    }
+                    _ => collect_numeric_ids(value, ids),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|x| collect_numeric_ids(x, ids)),
+        _ => {}
+    }
+}
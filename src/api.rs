@@ -1,15 +1,20 @@
+use crate::clock::{Clock, SystemClock};
 use crate::deserializer::timestamp;
 use crate::entity::*;
+use crate::error::ApiError;
+use crate::har::{CapturedResponse, HarCapture};
+use crate::market_state::MarketStateGuard;
+use crate::rate_limit::RateLimitStatus;
+use crate::risk::RiskChecker;
+use crate::signing::{Credentials, SignedHeaders};
 use anyhow::{anyhow, Context as _, Result};
 use chrono::{DateTime, Utc};
 use hmac::{Hmac, Mac};
-use reqwest::{
-    header::{HeaderMap, CONTENT_TYPE},
-    Method, Url,
-};
+use reqwest::{header::HeaderMap, Method, Url};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::future::Future;
 
 const ENTRY_POINT: &str = "https://api.bitflyer.com";
 
@@ -17,6 +22,13 @@ pub struct Client {
     client: reqwest::Client,
     api_key: String,
     hasher: Option<Hmac<Sha256>>,
+    risk_checker: Option<std::sync::Arc<RiskChecker>>,
+    market_state_guard: Option<std::sync::Arc<MarketStateGuard>>,
+    clock: std::sync::Arc<dyn Clock>,
+    capture: Option<std::sync::Arc<HarCapture>>,
+    base_url: Option<Url>,
+    rate_limit: std::sync::Mutex<Option<RateLimitStatus>>,
+    order_rate_limit: std::sync::Mutex<Option<RateLimitStatus>>,
 }
 
 impl std::fmt::Debug for Client {
@@ -36,72 +48,619 @@ impl Client {
             client: reqwest::Client::new(),
             api_key: std::env::var("API_KEY").ok().unwrap_or_default(),
             hasher,
+            risk_checker: None,
+            market_state_guard: None,
+            clock: std::sync::Arc::new(SystemClock),
+            capture: None,
+            base_url: None,
+            rate_limit: std::sync::Mutex::new(None),
+            order_rate_limit: std::sync::Mutex::new(None),
         })
     }
 
+    // Uses the given credentials instead of API_KEY and API_SECRET.
+    pub fn with_credentials(
+        mut self,
+        api_key: impl Into<String>,
+        api_secret: impl AsRef<[u8]>,
+    ) -> Result<Self> {
+        self.api_key = api_key.into();
+        self.hasher = Some(Hmac::<Sha256>::new_from_slice(api_secret.as_ref())?);
+        Ok(self)
+    }
+
+    // Timestamps private requests, e.g. a `ManualClock` for reproducible signatures.
+    pub fn with_clock(mut self, clock: std::sync::Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_risk_checker(mut self, risk_checker: std::sync::Arc<RiskChecker>) -> Self {
+        self.risk_checker = Some(risk_checker);
+        self
+    }
+
+    pub fn risk_checker(&self) -> Option<&std::sync::Arc<RiskChecker>> {
+        self.risk_checker.as_ref()
+    }
+
+    pub fn with_market_state_guard(mut self, guard: std::sync::Arc<MarketStateGuard>) -> Self {
+        self.market_state_guard = Some(guard);
+        self
+    }
+
+    pub fn market_state_guard(&self) -> Option<&std::sync::Arc<MarketStateGuard>> {
+        self.market_state_guard.as_ref()
+    }
+
+    // Opt-in wire capture for bug reports about unexpected responses.
+    pub fn with_capture(mut self, capture: std::sync::Arc<HarCapture>) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    pub fn capture(&self) -> Option<&std::sync::Arc<HarCapture>> {
+        self.capture.as_ref()
+    }
+
+    // Sends every request to another host, e.g. a `StubServer`. Paths and queries, and so the
+    // signatures, stay the same.
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
+    // From the latest response that carried the headers; None before the first one.
+    pub fn rate_limit_status(&self) -> Option<RateLimitStatus> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    pub fn order_rate_limit_status(&self) -> Option<RateLimitStatus> {
+        *self.order_rate_limit.lock().unwrap()
+    }
+
+    fn observe_rate_limits(&self, headers: &HeaderMap) {
+        let now = self.clock.now();
+        if let Some(status) = RateLimitStatus::from_headers(headers, now) {
+            *self.rate_limit.lock().unwrap() = Some(status);
+        }
+        if let Some(status) = RateLimitStatus::order_from_headers(headers, now) {
+            *self.order_rate_limit.lock().unwrap() = Some(status);
+        }
+    }
+
+    fn rebase(&self, url: &Url) -> Result<Url> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(url.clone());
+        };
+        let mut url = url.clone();
+        url.set_scheme(base_url.scheme())
+            .map_err(|_| anyhow!("unsupported scheme of the base url {base_url}"))?;
+        url.set_host(base_url.host_str())?;
+        url.set_port(base_url.port())
+            .map_err(|_| anyhow!("the base url {base_url} cannot have a port"))?;
+        Ok(url)
+    }
+
+    async fn board_state(&self, product_code: &ProductCode) -> Result<BoardState> {
+        let url = self.rebase(
+            &GetBoardState {
+                product_code: Some(product_code.clone()),
+            }
+            .url()?,
+        )?;
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        GetBoardState::deserialize_response_body(&body)
+    }
+
     #[tracing::instrument]
     pub async fn send<T>(&self, request: T) -> Result<<T as ApiRequest>::Response>
     where
         T: ApiRequest + std::fmt::Debug,
         <T as ApiRequest>::Response: for<'a> Deserialize<'a>,
     {
-        let url = request.url()?;
-        let response = if T::IS_PRIVATE {
-            let timestamp = Utc::now().timestamp();
-            let body = request.body()?;
-            let data = format!(
-                "{}{}{}{}{}",
-                timestamp,
-                T::METHOD.as_str(),
-                T::PATH,
-                url.query().map(|x| format!("?{x}")).unwrap_or_default(),
-                body.clone().unwrap_or_default()
-            );
-            let mut hasher = self.hasher.clone().context("hasher is none")?;
-            hasher.update(data.as_bytes());
-            let hash = hasher.finalize().into_bytes();
-            let hash = hash
-                .iter()
-                .map(|n| format!("{:02x}", n))
-                .collect::<String>();
-            let mut headers = HeaderMap::new();
-            headers.insert("ACCESS-KEY", self.api_key.parse()?);
-            headers.insert("ACCESS-TIMESTAMP", timestamp.to_string().parse()?);
-            headers.insert("ACCESS-SIGN", hash.parse()?);
-            if let Some(body) = body {
-                headers.insert(CONTENT_TYPE, "application/json".parse()?);
-                self.client
-                    .request(T::METHOD, url)
-                    .headers(headers)
-                    .body(body)
-                    .send()
-                    .await?
-            } else {
-                self.client
-                    .request(T::METHOD, url)
-                    .headers(headers)
-                    .send()
-                    .await?
+        let (body, entry) = self.execute_captured(&RawRequest::new(&request)?).await?;
+        let result: Result<<T as ApiRequest>::Response> = request.parse_response(&body);
+        match result {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                if let (Some(capture), Some(entry)) = (&self.capture, entry) {
+                    capture.comment(entry, format!("failed to deserialize the response: {e:#}"));
+                }
+                Err(anyhow!(
+                    "desesrialize error. error = {e:?}. request = {request:?}. response body = {body}"
+                ))
+            }
+        }
+    }
+
+    // Authentication headers of a private request, timestamped by the client's clock.
+    pub fn signed_headers(&self, request: &RawRequest) -> Result<HeaderMap> {
+        SignedHeaders::with_hasher(
+            self.api_key.clone(),
+            self.hasher.clone().context("hasher is none")?,
+            self.clock.now().timestamp(),
+            request.method.as_str(),
+            request.path,
+            request.url.query(),
+            request.body.as_deref(),
+        )
+        .to_header_map()
+    }
+
+    async fn execute(&self, request: &RawRequest) -> Result<String> {
+        self.execute_captured(request).await.map(|(body, _)| body)
+    }
+
+    // Also returns the index of the captured entry, if capturing.
+    async fn execute_captured(&self, request: &RawRequest) -> Result<(String, Option<usize>)> {
+        let rebased;
+        let request = match &self.base_url {
+            Some(_) => {
+                rebased = RawRequest {
+                    url: self.rebase(&request.url)?,
+                    ..request.clone()
+                };
+                &rebased
+            }
+            None => request,
+        };
+        let recorded = match &self.risk_checker {
+            Some(risk_checker) => risk_checker.check_and_record(&request.order_intents)?,
+            None => false,
+        };
+        let result = self.send_checked(request).await;
+        if let (Some(risk_checker), true, Err(_)) = (&self.risk_checker, recorded, &result) {
+            risk_checker.release_order();
+        }
+        result
+    }
+
+    async fn send_checked(&self, request: &RawRequest) -> Result<(String, Option<usize>)> {
+        let checked = request.order_intents.iter().filter(|x| !x.emergency);
+        if let Some(guard) = &self.market_state_guard {
+            let mut product_codes: Vec<&ProductCode> = vec![];
+            for intent in checked {
+                if !product_codes.contains(&&intent.product_code) {
+                    product_codes.push(&intent.product_code);
+                }
             }
+            for product_code in product_codes {
+                guard
+                    .admit(product_code, || self.board_state(product_code))
+                    .await?;
+            }
+        }
+        let headers = if request.is_private {
+            self.signed_headers(request)?
         } else {
-            self.client.request(T::METHOD, url).send().await?
+            HeaderMap::new()
         };
-        if response.status().is_success() {
-            let body = response.text().await?;
-            let result: Result<<T as ApiRequest>::Response> = T::deserialize_response_body(&body);
-            match result {
-                Ok(v) => Ok(v),
-                Err(e) => Err(anyhow!("desesrialize error. error = {e:?}. request = {request:?}. response body = {body}")),
+        let mut builder = self
+            .client
+            .request(request.method.clone(), request.url.clone())
+            .headers(headers.clone());
+        if let (true, Some(body)) = (request.is_private, &request.body) {
+            builder = builder.body(body.clone());
+        }
+        let started = self.clock.now();
+        let instant = std::time::Instant::now();
+        let response = match builder.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                if let Some(capture) = &self.capture {
+                    let error = format!("{e:#}");
+                    capture.record(request, &headers, started, instant.elapsed(), Err(error));
+                }
+                return Err(e.into());
             }
+        };
+        let status = response.status();
+        let version = response.version();
+        let response_headers = response.headers().clone();
+        self.observe_rate_limits(&response_headers);
+        let text = response.text().await;
+        let entry = self.capture.as_ref().map(|capture| {
+            let captured = match &text {
+                Ok(body) => Ok(CapturedResponse {
+                    status,
+                    version,
+                    headers: &response_headers,
+                    body,
+                }),
+                Err(e) => Err(format!("{e:#}")),
+            };
+            capture.record(request, &headers, started, instant.elapsed(), captured)
+        });
+        if status.is_success() {
+            Ok((text?, entry))
         } else {
-            Err(anyhow::anyhow!(
-                "request is failed: status -> {}\nrequest -> {:?}\nrequest.body -> {:?}\nresponse -> {:?}",
-                response.status(),
-                request,
-                request.body(),
-                response.text().await
-            ))
+            Err(ApiError::new(status, request.url.clone(), request.body.clone(), text?).into())
+        }
+    }
+}
+
+// A request as sent over the wire, for the object-safe `DynBitflyerApi`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawRequest {
+    pub method: Method,
+    pub path: &'static str,
+    pub url: Url,
+    pub body: Option<String>,
+    pub is_private: bool,
+    // Kept so risk checks still apply to requests sent through a trait object.
+    pub order_intents: Vec<OrderIntent>,
+}
+
+// What to send for a request with another HTTP client. Private requests carry the
+// authentication headers and their body; public ones neither.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpParts {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+}
+
+impl RawRequest {
+    pub fn new<T: ApiRequest>(request: &T) -> Result<Self> {
+        Ok(Self {
+            method: T::METHOD,
+            path: T::PATH,
+            url: request.url()?,
+            body: request.body()?,
+            is_private: T::IS_PRIVATE,
+            order_intents: request.order_intents(),
+        })
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.url.query()
+    }
+
+    // The request as `Client` would send it, signed with `credentials` when private and
+    // timestamped now.
+    pub fn to_http_parts(&self, credentials: &Credentials) -> Result<HttpParts> {
+        self.to_http_parts_at(credentials, Utc::now())
+    }
+
+    // Timestamped at `timestamp`, e.g. for reproducible signatures.
+    pub fn to_http_parts_at(
+        &self,
+        credentials: &Credentials,
+        timestamp: DateTime<Utc>,
+    ) -> Result<HttpParts> {
+        if !self.is_private {
+            return Ok(HttpParts {
+                method: self.method.clone(),
+                url: self.url.clone(),
+                headers: HeaderMap::new(),
+                body: None,
+            });
         }
+        let headers = credentials
+            .sign(
+                timestamp.timestamp(),
+                self.method.as_str(),
+                self.path,
+                self.query(),
+                self.body.as_deref(),
+            )
+            .to_header_map()?;
+        Ok(HttpParts {
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers,
+            body: self.body.clone(),
+        })
+    }
+
+    pub fn query_param(&self, key: &str) -> Option<String> {
+        self.url
+            .query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    }
+}
+
+pub type BoxFuture<'a, T> = std::pin::Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// Object-safe counterpart of `BitflyerApi` that returns the raw response body. Boxed or
+// shared trait objects implement `BitflyerApi`, so everything generic over it also accepts
+// `Arc<dyn DynBitflyerApi>`, including hand-written mocks that never touch HTTP.
+pub trait DynBitflyerApi: Send + Sync {
+    fn send_raw(&self, request: RawRequest) -> BoxFuture<'_, Result<String>>;
+}
+
+impl DynBitflyerApi for Client {
+    fn send_raw(&self, request: RawRequest) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { self.execute(&request).await })
+    }
+}
+
+async fn send_dyn<T>(api: &dyn DynBitflyerApi, request: T) -> Result<T::Response>
+where
+    T: ApiRequest + std::fmt::Debug + Send + Sync,
+{
+    let body = api.send_raw(RawRequest::new(&request)?).await?;
+    request
+        .parse_response(&body)
+        .with_context(|| format!("failed to parse the response to {request:?}: {body}"))
+}
+
+impl BitflyerApi for Box<dyn DynBitflyerApi> {
+    fn send<T>(&self, request: T) -> impl Future<Output = Result<T::Response>> + Send
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        send_dyn(self.as_ref(), request)
+    }
+}
+
+impl BitflyerApi for std::sync::Arc<dyn DynBitflyerApi> {
+    fn send<T>(&self, request: T) -> impl Future<Output = Result<T::Response>> + Send
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        send_dyn(self.as_ref(), request)
+    }
+}
+
+pub trait BitflyerApi: Send + Sync {
+    fn send<T>(&self, request: T) -> impl Future<Output = Result<T::Response>> + Send
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static;
+
+    fn child_order<'a>(
+        &'a self,
+        product_code: &'a ProductCode,
+        child_order_acceptance_id: &'a str,
+    ) -> impl Future<Output = Result<Option<ChildOrder>>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::child_order(self, product_code, child_order_acceptance_id)
+    }
+
+    fn wait_for_fill<'a>(
+        &'a self,
+        product_code: &'a ProductCode,
+        child_order_acceptance_id: &'a str,
+        timeout: std::time::Duration,
+    ) -> impl Future<Output = Result<ChildOrder>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::wait_for_fill(self, product_code, child_order_acceptance_id, timeout)
+    }
+
+    fn open_orders<'a>(
+        &'a self,
+        product_code: &'a ProductCode,
+    ) -> impl Future<Output = Result<Vec<ChildOrder>>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::open_orders(self, product_code)
+    }
+
+    fn open_orders_with_parents<'a>(
+        &'a self,
+        product_code: &'a ProductCode,
+    ) -> impl Future<Output = Result<crate::orders::OpenOrders>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::open_orders_with_parents(self, product_code)
+    }
+
+    // A parent order with the child orders each of its parameters placed.
+    fn parent_order_detail<'a>(
+        &'a self,
+        parent_order_acceptance_id: &'a str,
+    ) -> impl Future<Output = Result<crate::orders::ParentOrderDetail>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::parent_order_detail(self, parent_order_acceptance_id)
+    }
+
+    fn buy_market(
+        &self,
+        product_code: ProductCode,
+        size: Decimal,
+    ) -> impl Future<Output = Result<ChildOrderAcceptanceId>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::orders::submit_child_order(
+            self,
+            SendChildOrder::market(product_code, Side::Buy, size),
+        )
+    }
+
+    fn sell_market(
+        &self,
+        product_code: ProductCode,
+        size: Decimal,
+    ) -> impl Future<Output = Result<ChildOrderAcceptanceId>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::orders::submit_child_order(
+            self,
+            SendChildOrder::market(product_code, Side::Sell, size),
+        )
+    }
+
+    fn buy_limit(
+        &self,
+        product_code: ProductCode,
+        size: Decimal,
+        price: Decimal,
+    ) -> impl Future<Output = Result<ChildOrderAcceptanceId>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::orders::submit_child_order(
+            self,
+            SendChildOrder::limit(product_code, Side::Buy, size, price),
+        )
+    }
+
+    fn sell_limit(
+        &self,
+        product_code: ProductCode,
+        size: Decimal,
+        price: Decimal,
+    ) -> impl Future<Output = Result<ChildOrderAcceptanceId>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::orders::submit_child_order(
+            self,
+            SendChildOrder::limit(product_code, Side::Sell, size, price),
+        )
+    }
+
+    fn send_protected_market_order<'a>(
+        &'a self,
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        protection: &'a crate::orders::SlippageProtection,
+    ) -> impl Future<Output = Result<ChildOrderAcceptanceId>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::send_protected_market_order(self, product_code, side, size, protection)
+    }
+
+    fn ensure_order(
+        &self,
+        request: SendChildOrder,
+    ) -> impl Future<Output = Result<crate::orders::EnsureOutcome>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::orders::ensure_order(self, request)
+    }
+
+    fn close_position<'a>(
+        &'a self,
+        product_code: &'a ProductCode,
+        options: &'a crate::orders::CloseOptions,
+    ) -> impl Future<Output = Result<crate::orders::ClosePositionReport>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::close_position(self, product_code, options)
+    }
+
+    fn amend_child_order<'a>(
+        &'a self,
+        product_code: ProductCode,
+        child_order_acceptance_id: &'a str,
+        new_price: Option<Decimal>,
+        new_size: Option<Decimal>,
+    ) -> impl Future<Output = Result<crate::orders::AmendOutcome>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::orders::amend_child_order(
+            self,
+            product_code,
+            child_order_acceptance_id,
+            new_price,
+            new_size,
+        )
+    }
+
+    fn portfolio<'a>(
+        &'a self,
+        product_codes: &'a [ProductCode],
+    ) -> impl Future<Output = Result<crate::portfolio::Portfolio>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::portfolio::portfolio(self, product_codes)
+    }
+
+    fn kill_switch<'a>(
+        &'a self,
+        product_codes: &'a [ProductCode],
+        options: &'a crate::kill_switch::KillSwitchOptions,
+    ) -> impl Future<Output = Result<crate::kill_switch::KillSwitchReport>> + Send + 'a
+    where
+        Self: Sized,
+    {
+        crate::kill_switch::kill_switch(self, product_codes, options)
+    }
+
+    fn ticker_poller(
+        &self,
+        product_code: ProductCode,
+        interval: std::time::Duration,
+    ) -> impl futures::Stream<Item = Ticker> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::poller::ticker_poller(self, product_code, interval)
+    }
+
+    fn board_poller(
+        &self,
+        product_code: ProductCode,
+        interval: std::time::Duration,
+        depth: Option<usize>,
+    ) -> impl futures::Stream<Item = Board> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::poller::board_poller(self, product_code, interval, depth)
+    }
+
+    fn pager<'a, T>(&'a self, request: T) -> crate::pager::Pager<'a, Self, T>
+    where
+        Self: Sized,
+        T: crate::pager::Paginated + 'a,
+    {
+        crate::pager::Pager::new(self, request)
+    }
+
+    fn executions_stream(
+        &self,
+        product_code: ProductCode,
+        range: crate::executions::ExecutionRange,
+    ) -> impl futures::Stream<Item = Result<Execution>> + Send + '_
+    where
+        Self: Sized,
+    {
+        crate::executions::executions_stream(self, product_code, range)
+    }
+}
+
+impl BitflyerApi for Client {
+    fn send<T>(&self, request: T) -> impl Future<Output = Result<T::Response>> + Send
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        Client::send(self, request)
     }
 }
 
@@ -133,8 +692,95 @@ pub trait ApiRequest {
     }
 
     fn deserialize_response_body(body: &str) -> Result<Self::Response> {
+        if body.trim().is_empty() {
+            return Self::Response::deserialize(EmptyBody)
+                .map_err(|e| anyhow!("empty response body: {e}"));
+        }
         Ok(serde_json::from_str(body)?)
     }
+
+    fn parse_response(&self, body: &str) -> Result<Self::Response> {
+        Self::deserialize_response_body(body)
+    }
+
+    fn order_intents(&self) -> Vec<OrderIntent> {
+        vec![]
+    }
+
+    // The request compiled for another HTTP client; see `RawRequest::to_http_parts`.
+    fn to_http_parts(&self, credentials: &Credentials) -> Result<HttpParts>
+    where
+        Self: Sized,
+    {
+        RawRequest::new(self)?.to_http_parts(credentials)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderIntent {
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub size: Decimal,
+    pub price: Option<Decimal>,
+    // From an `EmergencyOrder`, which neither the risk checker nor the market state guard
+    // hold back. Only the crate sets it, so callers can't opt out of the checks.
+    pub(crate) emergency: bool,
+}
+
+impl OrderIntent {
+    pub fn new(
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        price: Option<Decimal>,
+    ) -> Self {
+        Self {
+            product_code,
+            side,
+            size,
+            price,
+            emergency: false,
+        }
+    }
+}
+
+impl From<&ParentOrderConditionType> for OrderIntent {
+    fn from(condition: &ParentOrderConditionType) -> Self {
+        use ParentOrderConditionType::*;
+        let (product_code, side, size, price) = match condition {
+            Limit {
+                product_code,
+                side,
+                size,
+                price,
+            }
+            | StopLimit {
+                product_code,
+                side,
+                size,
+                price,
+                ..
+            } => (product_code, side, size, Some(*price)),
+            Stop {
+                product_code,
+                side,
+                size,
+                trigger_price,
+            } => (product_code, side, size, Some(*trigger_price)),
+            Market {
+                product_code,
+                side,
+                size,
+            }
+            | Trail {
+                product_code,
+                side,
+                size,
+                ..
+            } => (product_code, side, size, None),
+        };
+        OrderIntent::new(product_code.clone(), *side, *size, price)
+    }
 }
 
 pub trait QueryValue {
@@ -155,7 +801,7 @@ where
     let result = reqwest::get(request.url()?).await?;
     if result.status().is_success() {
         let body = result.text().await?;
-        let v: <T as ApiRequest>::Response = serde_json::from_str(&body)?;
+        let v: <T as ApiRequest>::Response = request.parse_response(&body)?;
         Ok(v)
     } else {
         Err(anyhow::anyhow!(
@@ -166,19 +812,130 @@ where
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
-pub struct Empty;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EmptyResponse;
+
+impl<'de> Deserialize<'de> for EmptyResponse {
+    fn deserialize<D>(d: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = EmptyResponse;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an empty body, null or {}")
+            }
+
+            fn visit_unit<E>(self) -> std::result::Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EmptyResponse)
+            }
+
+            fn visit_none<E>(self) -> std::result::Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(EmptyResponse)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                match map.next_key::<serde::de::IgnoredAny>()? {
+                    None => Ok(EmptyResponse),
+                    Some(_) => Err(serde::de::Error::invalid_length(1, &self)),
+                }
+            }
+        }
+
+        d.deserialize_any(Visitor)
+    }
+}
+
+#[deprecated(note = "renamed to `EmptyResponse`")]
+pub type Empty = EmptyResponse;
+
+// An empty body reads as a unit, which only `EmptyResponse` and `()` accept; every other
+// response still fails on it.
+struct EmptyBody;
+
+impl<'de> serde::Deserializer<'de> for EmptyBody {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    // An `Option` would take the unit for `None`.
+    fn deserialize_option<V>(self, _: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::custom("expected a body"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for EmptyResponse {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "EmptyResponse".into()
+    }
 
-#[derive(Clone, Copy, Debug, Default)]
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "An empty body, null or {}.",
+            "type": ["object", "null"],
+            "maxProperties": 0
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetMarkets;
 impl ApiRequest for GetMarkets {
     const PATH: &'static str = "/v1/markets";
     type Response = Vec<Market>;
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct GetBoard {
     pub product_code: Option<ProductCode>,
+    // Applied to the response by the client, never sent.
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub depth: Option<usize>,
+}
+
+impl GetBoard {
+    pub fn new(product_code: ProductCode) -> Self {
+        Self {
+            product_code: Some(product_code),
+            depth: None,
+        }
+    }
+
+    // Keeps only the best `depth` levels of each side.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
 }
 impl ApiRequest for GetBoard {
     const PATH: &'static str = "/v1/board";
@@ -187,9 +944,23 @@ impl ApiRequest for GetBoard {
     fn url_params(&self) -> Vec<Option<(String, String)>> {
         vec![self.product_code.to_query_parameter("product_code")]
     }
+
+    fn parse_response(&self, body: &str) -> Result<Self::Response> {
+        match self.depth {
+            Some(depth) => {
+                use serde::de::DeserializeSeed;
+                let mut deserializer = serde_json::Deserializer::from_str(body);
+                let board = BoardDepth(depth).deserialize(&mut deserializer)?;
+                deserializer.end()?;
+                Ok(board)
+            }
+            None => Self::deserialize_response_body(body),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetTicker {
     pub product_code: Option<ProductCode>,
 }
@@ -202,7 +973,8 @@ impl ApiRequest for GetTicker {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetExecutions {
     pub product_code: Option<ProductCode>,
     pub count: Option<u64>,
@@ -223,7 +995,8 @@ impl ApiRequest for GetExecutions {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetBoardState {
     pub product_code: Option<ProductCode>,
 }
@@ -236,7 +1009,8 @@ impl ApiRequest for GetBoardState {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetBoardHealth {
     pub product_code: Option<ProductCode>,
 }
@@ -249,7 +1023,8 @@ impl ApiRequest for GetBoardHealth {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetPermissions;
 impl ApiRequest for GetPermissions {
     const PATH: &'static str = "/v1/me/getpermissions";
@@ -257,7 +1032,8 @@ impl ApiRequest for GetPermissions {
     const IS_PRIVATE: bool = true;
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetBalance;
 impl ApiRequest for GetBalance {
     const PATH: &'static str = "/v1/me/getbalance";
@@ -265,7 +1041,8 @@ impl ApiRequest for GetBalance {
     const IS_PRIVATE: bool = true;
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetCollateral;
 impl ApiRequest for GetCollateral {
     const PATH: &'static str = "/v1/me/getcollateral";
@@ -273,7 +1050,8 @@ impl ApiRequest for GetCollateral {
     const IS_PRIVATE: bool = true;
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetCollateralAccounts;
 impl ApiRequest for GetCollateralAccounts {
     const PATH: &'static str = "/v1/me/getcollateralaccounts";
@@ -282,11 +1060,22 @@ impl ApiRequest for GetCollateralAccounts {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct SendChildOrderResponse {
     pub child_order_acceptance_id: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+impl SendChildOrderResponse {
+    pub fn new(child_order_acceptance_id: impl Into<String>) -> Self {
+        Self {
+            child_order_acceptance_id: child_order_acceptance_id.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SendChildOrder {
     #[serde(flatten)]
     pub child_order_type: ChildOrderType,
@@ -294,10 +1083,72 @@ pub struct SendChildOrder {
     pub side: Side,
     pub size: Decimal,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub minute_to_expire: Option<u64>,
+    pub minute_to_expire: Option<MinuteToExpire>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub time_in_force: Option<TimeInForce>,
 }
+impl SendChildOrder {
+    pub fn market(product_code: ProductCode, side: Side, size: Decimal) -> Self {
+        Self {
+            child_order_type: ChildOrderType::Market,
+            product_code,
+            side,
+            size,
+            minute_to_expire: None,
+            time_in_force: None,
+        }
+    }
+
+    pub fn limit(product_code: ProductCode, side: Side, size: Decimal, price: Decimal) -> Self {
+        Self {
+            child_order_type: ChildOrderType::Limit { price },
+            ..Self::market(product_code, side, size)
+        }
+    }
+
+    // The same order again, e.g. to resubmit one that expired or was canceled. It keeps the
+    // time in force but not the expiry, which counts from the new order.
+    pub fn from_child_order(order: &ChildOrder) -> Self {
+        Self {
+            child_order_type: order.child_order_type.clone(),
+            product_code: order.product_code.clone(),
+            side: order.side,
+            size: order.size,
+            minute_to_expire: None,
+            time_in_force: Some(order.time_in_force),
+        }
+    }
+
+    // Makes the order a limit order at `price`.
+    pub fn with_price(mut self, price: Decimal) -> Self {
+        self.child_order_type = ChildOrderType::Limit { price };
+        self
+    }
+
+    // E.g. the `outstanding_size` of a partially filled order.
+    pub fn with_size(mut self, size: Decimal) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_minute_to_expire(mut self, minute_to_expire: MinuteToExpire) -> Self {
+        self.minute_to_expire = Some(minute_to_expire);
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.size <= Decimal::ZERO {
+            return Err(anyhow!("order size must be positive: {}", self.size));
+        }
+        if let ChildOrderType::Limit { price } = self.child_order_type {
+            if price <= Decimal::ZERO {
+                return Err(anyhow!("order price must be positive: {price}"));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl ApiRequest for SendChildOrder {
     const PATH: &'static str = "/v1/me/sendchildorder";
     const METHOD: Method = Method::POST;
@@ -308,9 +1159,51 @@ impl ApiRequest for SendChildOrder {
         let json = serde_json::to_string(&self)?;
         Ok(Some(json))
     }
+
+    fn order_intents(&self) -> Vec<OrderIntent> {
+        let price = match self.child_order_type {
+            ChildOrderType::Limit { price } => Some(price),
+            ChildOrderType::Market => None,
+        };
+        vec![OrderIntent::new(
+            self.product_code.clone(),
+            self.side,
+            self.size,
+            price,
+        )]
+    }
+}
+
+// A child order that skips the risk checker and the market state guard, for getting out of
+// the market when they would refuse to, e.g. after the daily loss limit. Only the kill switch
+// closes positions with it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct EmergencyOrder(pub(crate) SendChildOrder);
+
+impl ApiRequest for EmergencyOrder {
+    const PATH: &'static str = SendChildOrder::PATH;
+    const METHOD: Method = SendChildOrder::METHOD;
+    type Response = SendChildOrderResponse;
+    const IS_PRIVATE: bool = SendChildOrder::IS_PRIVATE;
+
+    fn body(&self) -> Result<Option<String>> {
+        self.0.body()
+    }
+
+    fn order_intents(&self) -> Vec<OrderIntent> {
+        self.0
+            .order_intents()
+            .into_iter()
+            .map(|x| OrderIntent {
+                emergency: true,
+                ..x
+            })
+            .collect()
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CancelChildOrder {
     pub product_code: ProductCode,
     pub child_order_acceptance_id: String,
@@ -318,34 +1211,37 @@ pub struct CancelChildOrder {
 impl ApiRequest for CancelChildOrder {
     const PATH: &'static str = "/v1/me/cancelchildorder";
     const METHOD: Method = Method::POST;
-    type Response = Empty;
+    type Response = EmptyResponse;
     const IS_PRIVATE: bool = true;
 
     fn body(&self) -> Result<Option<String>> {
         let json = serde_json::to_string(&self)?;
         Ok(Some(json))
     }
-
-    fn deserialize_response_body(body: &str) -> Result<Self::Response> {
-        if body.is_empty() {
-            Ok(Empty {})
-        } else {
-            Err(anyhow!("body is not empty"))
-        }
-    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct SendParentOrderResponse {
     pub parent_order_acceptance_id: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+impl SendParentOrderResponse {
+    pub fn new(parent_order_acceptance_id: impl Into<String>) -> Self {
+        Self {
+            parent_order_acceptance_id: parent_order_acceptance_id.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SendParentOrder {
     #[serde(flatten)]
     pub order_method: ParentOrderMethod,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub minute_to_expire: Option<u64>,
+    pub minute_to_expire: Option<MinuteToExpire>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub time_in_force: Option<TimeInForce>,
 }
@@ -359,9 +1255,20 @@ impl ApiRequest for SendParentOrder {
         let json = serde_json::to_string(&self)?;
         Ok(Some(json))
     }
+
+    fn order_intents(&self) -> Vec<OrderIntent> {
+        let parameters: &[ParentOrderConditionType] = match &self.order_method {
+            ParentOrderMethod::Simple { parameters } => parameters,
+            ParentOrderMethod::Ifd { parameters } => parameters,
+            ParentOrderMethod::Oco { parameters } => parameters,
+            ParentOrderMethod::Ifdoco { parameters } => parameters,
+        };
+        parameters.iter().map(OrderIntent::from).collect()
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CancelParentOrder {
     pub product_code: ProductCode,
     pub parent_order_acceptance_id: String,
@@ -369,54 +1276,43 @@ pub struct CancelParentOrder {
 impl ApiRequest for CancelParentOrder {
     const PATH: &'static str = "/v1/me/cancelparentorder";
     const METHOD: Method = Method::POST;
-    type Response = Empty;
+    type Response = EmptyResponse;
     const IS_PRIVATE: bool = true;
 
     fn body(&self) -> Result<Option<String>> {
         let json = serde_json::to_string(&self)?;
         Ok(Some(json))
     }
-
-    fn deserialize_response_body(body: &str) -> Result<Self::Response> {
-        if body.is_empty() {
-            Ok(Empty {})
-        } else {
-            Err(anyhow!("body is not empty"))
-        }
-    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CancelAllChildOrders {
     pub product_code: ProductCode,
 }
 impl ApiRequest for CancelAllChildOrders {
     const PATH: &'static str = "/v1/me/cancelallchildorders";
     const METHOD: Method = Method::POST;
-    type Response = Empty;
+    type Response = EmptyResponse;
     const IS_PRIVATE: bool = true;
 
     fn body(&self) -> Result<Option<String>> {
         let json = serde_json::to_string(&self)?;
         Ok(Some(json))
     }
-
-    fn deserialize_response_body(body: &str) -> Result<Self::Response> {
-        if body.is_empty() {
-            Ok(Empty {})
-        } else {
-            Err(anyhow!("body is not empty"))
-        }
-    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetChildOrders {
     pub product_code: Option<ProductCode>,
     pub count: Option<u64>,
     pub before: Option<u64>,
     pub after: Option<u64>,
+    pub child_order_state: Option<OrderState>,
     pub child_order_acceptance_id: Option<String>,
+    pub child_order_id: Option<String>,
+    // Lists the child orders a parent order placed.
     pub parent_order_id: Option<String>,
 }
 impl ApiRequest for GetChildOrders {
@@ -431,14 +1327,19 @@ impl ApiRequest for GetChildOrders {
             self.count.to_query_parameter("count"),
             self.before.to_query_parameter("before"),
             self.after.to_query_parameter("after"),
+            self.child_order_state
+                .to_query_parameter("child_order_state"),
             self.child_order_acceptance_id
                 .to_query_parameter("child_order_acceptance_id"),
-            self.parent_order_id.to_query_parameter("child_order_id"),
+            self.child_order_id.to_query_parameter("child_order_id"),
+            self.parent_order_id.to_query_parameter("parent_order_id"),
         ]
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct GetParentOrdersResponseParameter {
     pub id: u64,
     pub parent_order_id: String,
@@ -449,8 +1350,10 @@ pub struct GetParentOrdersResponseParameter {
     pub average_price: Decimal,
     pub size: Decimal,
     pub parent_order_state: OrderState,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
     #[serde(with = "timestamp")]
     pub expire_date: DateTime<Utc>,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
     #[serde(with = "timestamp")]
     pub parent_order_date: DateTime<Utc>,
     pub parent_order_acceptance_id: String,
@@ -460,7 +1363,8 @@ pub struct GetParentOrdersResponseParameter {
     pub total_commission: Decimal,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetParentOrders {
     pub product_code: Option<ProductCode>,
     pub count: Option<u64>,
@@ -487,9 +1391,12 @@ impl ApiRequest for GetParentOrders {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct GetParentOrdersResponse {
     pub id: u64,
     pub parent_order_id: String,
+    #[cfg_attr(feature = "schema", schemars(with = "DateTime<Utc>"))]
     #[serde(with = "timestamp")]
     pub expire_date: DateTime<Utc>,
     pub time_in_force: TimeInForce,
@@ -498,7 +1405,8 @@ pub struct GetParentOrdersResponse {
     pub parent_order_acceptance_id: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetParentOrder {
     pub parent_order_id: Option<String>,
     pub parent_order_acceptance_id: Option<String>,
@@ -518,7 +1426,8 @@ impl ApiRequest for GetParentOrder {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GetPositions {}
 impl ApiRequest for GetPositions {
     const PATH: &'static str = "/v1/me/getpositions";
@@ -530,3 +1439,100 @@ impl ApiRequest for GetPositions {
         vec![Some(ProductCode::FxBtcJpy).to_query_parameter("product_code")]
     }
 }
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GetPrivateExecutions {
+    pub product_code: Option<ProductCode>,
+    pub count: Option<u64>,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+    pub child_order_id: Option<String>,
+    pub child_order_acceptance_id: Option<String>,
+}
+impl ApiRequest for GetPrivateExecutions {
+    const PATH: &'static str = "/v1/me/getexecutions";
+    const METHOD: Method = Method::GET;
+    type Response = Vec<PrivateExecution>;
+    const IS_PRIVATE: bool = true;
+
+    fn url_params(&self) -> Vec<Option<(String, String)>> {
+        vec![
+            self.product_code.to_query_parameter("product_code"),
+            self.count.to_query_parameter("count"),
+            self.before.to_query_parameter("before"),
+            self.after.to_query_parameter("after"),
+            self.child_order_id.to_query_parameter("child_order_id"),
+            self.child_order_acceptance_id
+                .to_query_parameter("child_order_acceptance_id"),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GetBalanceHistory {
+    pub currency_code: Option<String>,
+    pub count: Option<u64>,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+impl ApiRequest for GetBalanceHistory {
+    const PATH: &'static str = "/v1/me/getbalancehistory";
+    const METHOD: Method = Method::GET;
+    type Response = Vec<BalanceHistory>;
+    const IS_PRIVATE: bool = true;
+
+    fn url_params(&self) -> Vec<Option<(String, String)>> {
+        vec![
+            self.currency_code.to_query_parameter("currency_code"),
+            self.count.to_query_parameter("count"),
+            self.before.to_query_parameter("before"),
+            self.after.to_query_parameter("after"),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GetCoinIns {
+    pub count: Option<u64>,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+impl ApiRequest for GetCoinIns {
+    const PATH: &'static str = "/v1/me/getcoinins";
+    const METHOD: Method = Method::GET;
+    type Response = Vec<CoinIn>;
+    const IS_PRIVATE: bool = true;
+
+    fn url_params(&self) -> Vec<Option<(String, String)>> {
+        vec![
+            self.count.to_query_parameter("count"),
+            self.before.to_query_parameter("before"),
+            self.after.to_query_parameter("after"),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GetCoinOuts {
+    pub count: Option<u64>,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+impl ApiRequest for GetCoinOuts {
+    const PATH: &'static str = "/v1/me/getcoinouts";
+    const METHOD: Method = Method::GET;
+    type Response = Vec<CoinOut>;
+    const IS_PRIVATE: bool = true;
+
+    fn url_params(&self) -> Vec<Option<(String, String)>> {
+        vec![
+            self.count.to_query_parameter("count"),
+            self.before.to_query_parameter("before"),
+            self.after.to_query_parameter("after"),
+        ]
+    }
+}
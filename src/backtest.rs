@@ -0,0 +1,300 @@
+use crate::api::{BitflyerApi, GetChildOrders, GetPrivateExecutions};
+use crate::candle::CandleBuilder;
+use crate::clock::ManualClock;
+use crate::entity::{Execution, OrderState, PrivateExecution, ProductCode};
+use crate::events::{new_fills, order_changes, Event};
+use crate::export::csv::{decimal, timestamp, CsvRecord};
+use crate::pnl::{CostMethod, Realization};
+use crate::risk::RiskChecker;
+use crate::sim::SimClient;
+use crate::strategy::{Strategy, StrategyContext};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BacktestPoint {
+    pub time: DateTime<Utc>,
+    // Price of the last trade, used to mark the position.
+    pub price: Decimal,
+    // Positive when long, negative when short.
+    pub position: Decimal,
+    pub exposure: Decimal,
+    pub realized_pnl: Decimal,
+    pub unrealized_pnl: Decimal,
+    pub commission: Decimal,
+}
+
+impl BacktestPoint {
+    pub fn total_pnl(&self) -> Decimal {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+impl CsvRecord for BacktestPoint {
+    const HEADER: &'static [&'static str] = &[
+        "time",
+        "price",
+        "position",
+        "exposure",
+        "realized_pnl",
+        "unrealized_pnl",
+        "commission",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            timestamp(&self.time),
+            decimal(self.price),
+            decimal(self.position),
+            decimal(self.exposure),
+            decimal(self.realized_pnl),
+            decimal(self.unrealized_pnl),
+            decimal(self.commission),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktestReport {
+    pub product_code: ProductCode,
+    // The state after the last trade of each sample interval.
+    pub points: Vec<BacktestPoint>,
+    pub fills: Vec<PrivateExecution>,
+    pub realizations: Vec<Realization>,
+}
+
+impl BacktestReport {
+    pub fn last_point(&self) -> Option<&BacktestPoint> {
+        self.points.last()
+    }
+
+    pub fn total_pnl(&self) -> Decimal {
+        self.last_point()
+            .map(BacktestPoint::total_pnl)
+            .unwrap_or_default()
+    }
+
+    // Largest fall of the total PnL from a previous high, as sampled.
+    pub fn max_drawdown(&self) -> Decimal {
+        let mut high = Decimal::ZERO;
+        let mut drawdown = Decimal::ZERO;
+        for point in &self.points {
+            high = high.max(point.total_pnl());
+            drawdown = drawdown.max(high - point.total_pnl());
+        }
+        drawdown
+    }
+
+    pub fn max_exposure(&self) -> Decimal {
+        self.points
+            .iter()
+            .map(|x| x.exposure.abs())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+// Replays recorded trades, e.g. from `HistoryDownloader`, through a strategy trading on a
+// `SimClient`. The strategy sees the same events in the same order as under a `Runner` with
+// trade, candle and order polling: each trade, the candles it closes, then the fills and order
+// state changes it caused. The simulator's clock follows the trades, so expiries, timer ticks
+// and `StrategyContext::now` are those of the recorded day. Orders the strategy sends are
+// answered at once and their fills surface with the next trade, like the next poll. Unlike
+// under a `Runner`, an error of the strategy ends the backtest.
+#[derive(Debug)]
+pub struct Backtest {
+    sim: SimClient,
+    clock: Arc<ManualClock>,
+    product_code: ProductCode,
+    executions: Vec<Execution>,
+    candle_interval: Option<Duration>,
+    timer_interval: Option<TimeDelta>,
+    sample_interval: TimeDelta,
+    cost_method: CostMethod,
+    risk_checker: Option<Arc<RiskChecker>>,
+}
+
+impl Backtest {
+    // `sim` keeps its fill model, fees and starting balances; its clock is replaced.
+    pub fn new(sim: SimClient, product_code: ProductCode, mut executions: Vec<Execution>) -> Self {
+        executions.sort_by_key(|x| x.id);
+        executions.dedup_by_key(|x| x.id);
+        let start = executions
+            .first()
+            .map(|x| x.exec_date)
+            .unwrap_or_else(Utc::now);
+        let clock = Arc::new(ManualClock::new(start));
+        Self {
+            sim: sim.with_clock(clock.clone()),
+            clock,
+            product_code,
+            executions,
+            candle_interval: None,
+            timer_interval: None,
+            sample_interval: TimeDelta::minutes(1),
+            cost_method: CostMethod::default(),
+            risk_checker: None,
+        }
+    }
+
+    // Reads the JSON lines written by `HistoryDownloader`.
+    pub fn from_file(
+        sim: SimClient,
+        product_code: ProductCode,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let executions = crate::history::read_executions(path)?;
+        Ok(Self::new(sim, product_code, executions))
+    }
+
+    // Emits `Event::CandleClose` for candles of this interval.
+    pub fn with_candle_interval(mut self, interval: Duration) -> Self {
+        self.candle_interval = Some(interval);
+        self
+    }
+
+    // Calls `Strategy::on_timer` every interval of replayed time, from the first trade on.
+    pub fn with_timer(mut self, interval: Duration) -> Self {
+        self.timer_interval = TimeDelta::from_std(interval).ok();
+        self
+    }
+
+    pub fn with_sample_interval(mut self, interval: Duration) -> Self {
+        self.sample_interval = TimeDelta::from_std(interval).unwrap_or(TimeDelta::MAX);
+        self
+    }
+
+    pub fn with_cost_method(mut self, cost_method: CostMethod) -> Self {
+        self.cost_method = cost_method;
+        self
+    }
+
+    // Checks the strategy's orders in the simulator. The daily loss limit follows the wall
+    // clock, not the replayed one.
+    pub fn with_risk_checker(mut self, risk_checker: Arc<RiskChecker>) -> Self {
+        self.sim = self.sim.with_risk_checker(risk_checker.clone());
+        self.risk_checker = Some(risk_checker);
+        self
+    }
+
+    pub async fn run<S: Strategy>(self, strategy: &mut S) -> Result<BacktestReport> {
+        let product_code = self.product_code.clone();
+        let sim = Arc::new(self.sim);
+        let mut context = StrategyContext::new(sim.clone())
+            .with_clock(self.clock.clone())
+            .with_cost_method(self.cost_method);
+        if let Some(risk_checker) = self.risk_checker {
+            context = context.with_risk_checker(risk_checker);
+        }
+        let mut candles = self.candle_interval.map(CandleBuilder::new);
+        let mut fills = vec![];
+        // Nothing happened before the replay, so there is no baseline to skip.
+        let mut last_fill_id = Some(0);
+        let mut order_states = Some(HashMap::<String, OrderState>::new());
+        let mut next_timer = self
+            .timer_interval
+            .zip(self.executions.first())
+            .and_then(|(interval, x)| x.exec_date.checked_add_signed(interval));
+        let mut points: Vec<BacktestPoint> = vec![];
+        let mut sampled_at: Option<DateTime<Utc>> = None;
+
+        strategy.on_start(&context).await?;
+        for execution in &self.executions {
+            while let Some(now) = next_timer.filter(|x| *x <= execution.exec_date) {
+                self.clock.set(now);
+                strategy.on_timer(&context, now).await?;
+                next_timer = self.timer_interval.and_then(|x| now.checked_add_signed(x));
+            }
+            self.clock.set(execution.exec_date);
+            sim.on_execution(&product_code, execution);
+
+            let mut events = vec![Event::Trade {
+                product_code: product_code.clone(),
+                execution: execution.clone(),
+            }];
+            if let Some(candles) = &mut candles {
+                events.extend(candles.on_execution(execution).into_iter().map(|candle| {
+                    Event::CandleClose {
+                        product_code: product_code.clone(),
+                        candle,
+                    }
+                }));
+            }
+            let executions = sim
+                .send(GetPrivateExecutions {
+                    product_code: Some(product_code.clone()),
+                    count: Some(100),
+                    after: last_fill_id,
+                    ..Default::default()
+                })
+                .await?;
+            events.extend(new_fills(&mut last_fill_id, &product_code, executions));
+            let orders = sim
+                .send(GetChildOrders {
+                    product_code: Some(product_code.clone()),
+                    count: Some(100),
+                    ..Default::default()
+                })
+                .await?;
+            events.extend(order_changes(&mut order_states, orders));
+
+            for event in &events {
+                if let Event::MyFill { execution, .. } = event {
+                    fills.push(execution.clone());
+                }
+                context.dispatch(strategy, event).await?;
+            }
+
+            let point = point(&context, &product_code, execution);
+            let interval = self.sample_interval;
+            match (sampled_at, points.last_mut()) {
+                (Some(start), Some(last))
+                    if start
+                        .checked_add_signed(interval)
+                        .is_none_or(|end| point.time < end) =>
+                {
+                    *last = point
+                }
+                _ => {
+                    sampled_at = Some(point.time);
+                    points.push(point);
+                }
+            }
+        }
+        strategy.on_stop(&context).await?;
+        context.cancel_open_orders().await;
+
+        Ok(BacktestReport {
+            product_code,
+            points,
+            fills,
+            realizations: context.realizations(),
+        })
+    }
+}
+
+fn point(
+    context: &StrategyContext<SimClient>,
+    product_code: &ProductCode,
+    execution: &Execution,
+) -> BacktestPoint {
+    let product = context.product_pnl(product_code);
+    let position = product.as_ref().map(|x| x.net_size()).unwrap_or_default();
+    BacktestPoint {
+        time: execution.exec_date,
+        price: execution.price,
+        position,
+        exposure: position * execution.price,
+        realized_pnl: product.as_ref().map(|x| x.realized_pnl).unwrap_or_default(),
+        unrealized_pnl: product
+            .as_ref()
+            .map(|x| x.unrealized_pnl(execution.price))
+            .unwrap_or_default(),
+        commission: product.map(|x| x.commission).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,150 @@
+use crate::api::{BitflyerApi, Client, GetBalance};
+use crate::entity::Balance;
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceChanged {
+    pub currency_code: String,
+    pub amount: Decimal,
+    pub available: Decimal,
+    pub delta: Decimal,
+    pub available_delta: Decimal,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl BalanceChanged {
+    // Only the available part moved, e.g. an order was placed or canceled.
+    pub fn is_reservation(&self) -> bool {
+        self.delta.is_zero()
+    }
+}
+
+pub(crate) type Balances = BTreeMap<String, (Decimal, Decimal)>;
+
+// Amount and available of every currency, keyed by currency code.
+pub(crate) fn balance_map(balances: &[Balance]) -> Balances {
+    balances
+        .iter()
+        .map(|x| (x.currency_code.clone(), (x.amount, x.available)))
+        .collect()
+}
+
+pub(crate) fn balance_changes(
+    previous: &Balances,
+    current: &Balances,
+    observed_at: DateTime<Utc>,
+) -> Vec<BalanceChanged> {
+    let zero = (Decimal::ZERO, Decimal::ZERO);
+    let mut currencies: Vec<&String> = current.keys().chain(previous.keys()).collect();
+    currencies.sort();
+    currencies.dedup();
+    currencies
+        .into_iter()
+        .filter_map(|currency_code| {
+            let (amount, available) = current.get(currency_code).copied().unwrap_or(zero);
+            let (previous_amount, previous_available) =
+                previous.get(currency_code).copied().unwrap_or(zero);
+            let delta = amount - previous_amount;
+            let available_delta = available - previous_available;
+            (!delta.is_zero() || !available_delta.is_zero()).then(|| BalanceChanged {
+                currency_code: currency_code.clone(),
+                amount,
+                available,
+                delta,
+                available_delta,
+                observed_at,
+            })
+        })
+        .collect()
+}
+
+// The first snapshot is the baseline and emits nothing; a currency that disappears is
+// reported as dropping to zero.
+#[derive(Debug)]
+pub struct BalanceWatcher<A = Client> {
+    client: Arc<A>,
+    balances: Mutex<Option<Balances>>,
+    events: broadcast::Sender<BalanceChanged>,
+}
+
+impl<A: BitflyerApi + 'static> BalanceWatcher<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            balances: Mutex::new(None),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BalanceChanged> {
+        self.events.subscribe()
+    }
+
+    // Lagged receivers skip the missed events rather than ending the stream.
+    pub fn stream(&self) -> impl Stream<Item = BalanceChanged> + Send + 'static {
+        futures::stream::unfold(self.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("balance stream missed {n} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    pub fn balance(&self, currency_code: &str) -> Option<(Decimal, Decimal)> {
+        self.balances
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|x| x.get(currency_code).copied())
+    }
+
+    pub fn evaluate(
+        &self,
+        balances: &[Balance],
+        observed_at: DateTime<Utc>,
+    ) -> Vec<BalanceChanged> {
+        let current = balance_map(balances);
+        let previous = self.balances.lock().unwrap().replace(current.clone());
+        let Some(previous) = previous else {
+            return vec![];
+        };
+        let events = balance_changes(&previous, &current, observed_at);
+        for event in &events {
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    pub async fn poll(&self) -> Result<Vec<BalanceChanged>> {
+        let balances = self.client.send(GetBalance).await?;
+        Ok(self.evaluate(&balances, Utc::now()))
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let watcher = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = watcher.poll().await {
+                    tracing::warn!("failed to poll balances: {e:?}");
+                }
+            }
+        })
+    }
+}
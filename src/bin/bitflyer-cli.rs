@@ -0,0 +1,265 @@
+// Reads API_KEY and API_SECRET from the environment or a `.env` file.
+
+use anyhow::{anyhow, Result};
+use bitflyer::api::{
+    CancelAllChildOrders, CancelChildOrder, Client, GetBalance, GetBoard, GetPositions, GetTicker,
+    SendChildOrder,
+};
+use bitflyer::entity::{ChildOrderType, MinuteToExpire, ProductCode, Side, TimeInForce};
+use clap::{Args, Parser, Subcommand};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+
+#[derive(Debug, Parser)]
+#[command(name = "bitflyer-cli", about = "Query and trade on bitFlyer Lightning")]
+struct Cli {
+    #[arg(long, global = true, help = "Print JSON instead of a table")]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = "Show the ticker")]
+    Ticker {
+        #[arg(short, long, default_value = "BTC_JPY", value_parser = product_code)]
+        product: ProductCode,
+    },
+    #[command(about = "Show the order book")]
+    Board {
+        #[arg(short, long, default_value = "BTC_JPY", value_parser = product_code)]
+        product: ProductCode,
+        #[arg(short, long, default_value_t = 10, help = "Levels shown on each side")]
+        depth: usize,
+    },
+    #[command(about = "Show the balances")]
+    Balance,
+    #[command(subcommand, about = "Send a child order")]
+    Order(OrderCommand),
+    #[command(about = "Cancel a child order, or all of them")]
+    Cancel {
+        #[arg(short, long, default_value = "BTC_JPY", value_parser = product_code)]
+        product: ProductCode,
+        #[arg(
+            long,
+            required_unless_present = "all",
+            conflicts_with = "all",
+            help = "The child order acceptance id"
+        )]
+        id: Option<String>,
+        #[arg(long, help = "Cancel every open order of the product")]
+        all: bool,
+    },
+    #[command(about = "Show the FX_BTC_JPY positions")]
+    Positions,
+}
+
+#[derive(Debug, Subcommand)]
+enum OrderCommand {
+    #[command(about = "Buy SIZE at market, or at --price")]
+    Buy(OrderArgs),
+    #[command(about = "Sell SIZE at market, or at --price")]
+    Sell(OrderArgs),
+}
+
+#[derive(Debug, Args)]
+struct OrderArgs {
+    size: Decimal,
+    #[arg(long, help = "Place a limit order at this price")]
+    price: Option<Decimal>,
+    #[arg(short, long, default_value = "BTC_JPY", value_parser = product_code)]
+    product: ProductCode,
+    #[arg(long, value_parser = time_in_force, help = "GTC, IOC or FOK")]
+    time_in_force: Option<TimeInForce>,
+    #[arg(long)]
+    minute_to_expire: Option<u64>,
+}
+
+fn product_code(value: &str) -> Result<ProductCode> {
+    match serde_json::from_value(json!(value.to_uppercase()))? {
+        ProductCode::Other => Err(anyhow!("unknown product code: {value}")),
+        product_code => Ok(product_code),
+    }
+}
+
+fn time_in_force(value: &str) -> Result<TimeInForce> {
+    Ok(serde_json::from_value(json!(value.to_uppercase()))?)
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths = headers.iter().map(|x| x.len()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<String>| {
+        let cells = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        println!("{}", cells.join("  ").trim_end());
+    };
+    line(headers.iter().map(|x| x.to_string()).collect());
+    rows.into_iter().for_each(line);
+}
+
+fn output(json: bool, value: Value, headers: &[&str], rows: Vec<Vec<String>>) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        print_table(headers, rows);
+    }
+    Ok(())
+}
+
+async fn order(client: &Client, side: Side, args: OrderArgs, json: bool) -> Result<()> {
+    let request = SendChildOrder {
+        child_order_type: match args.price {
+            Some(price) => ChildOrderType::Limit { price },
+            None => ChildOrderType::Market,
+        },
+        product_code: args.product,
+        side,
+        size: args.size,
+        minute_to_expire: args
+            .minute_to_expire
+            .map(MinuteToExpire::from_minutes)
+            .transpose()?,
+        time_in_force: args.time_in_force,
+    };
+    request.validate()?;
+    let response = client.send(request).await?;
+    output(
+        json,
+        json!(response),
+        &["child_order_acceptance_id"],
+        vec![vec![response.child_order_acceptance_id]],
+    )
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    let client = Client::new()?;
+    let json = cli.json;
+    match cli.command {
+        Command::Ticker { product } => {
+            let ticker = client
+                .send(GetTicker {
+                    product_code: Some(product),
+                })
+                .await?;
+            let rows = vec![vec![
+                ticker.product_code.to_string(),
+                ticker.ltp.to_string(),
+                ticker.best_bid.to_string(),
+                ticker.best_ask.to_string(),
+                ticker.volume_by_product.to_string(),
+                ticker.timestamp.to_rfc3339(),
+            ]];
+            output(
+                json,
+                json!(ticker),
+                &["product", "ltp", "bid", "ask", "volume", "timestamp"],
+                rows,
+            )
+        }
+        Command::Board { product, depth } => {
+            let board = client
+                .send(GetBoard::new(product).with_depth(depth))
+                .await?;
+            let level =
+                |x: &bitflyer::entity::BoardElement| json!({ "price": x.price, "size": x.size });
+            let value = json!({
+                "mid_price": board.mid_price,
+                "bids": board.bids.iter().map(level).collect::<Vec<_>>(),
+                "asks": board.asks.iter().map(level).collect::<Vec<_>>(),
+            });
+            let mut rows = board
+                .asks
+                .iter()
+                .rev()
+                .map(|x| vec![String::new(), x.price.to_string(), x.size.to_string()])
+                .collect::<Vec<_>>();
+            rows.extend(
+                board
+                    .bids
+                    .iter()
+                    .map(|x| vec![x.size.to_string(), x.price.to_string(), String::new()]),
+            );
+            output(json, value, &["bid", "price", "ask"], rows)
+        }
+        Command::Balance => {
+            let balances = client.send(GetBalance).await?;
+            let value = balances
+                .iter()
+                .map(|x| json!({ "currency_code": x.currency_code, "amount": x.amount, "available": x.available }))
+                .collect();
+            let rows = balances
+                .iter()
+                .filter(|x| !x.amount.is_zero())
+                .map(|x| {
+                    vec![
+                        x.currency_code.clone(),
+                        x.amount.to_string(),
+                        x.available.to_string(),
+                    ]
+                })
+                .collect();
+            output(json, value, &["currency", "amount", "available"], rows)
+        }
+        Command::Order(OrderCommand::Buy(args)) => order(&client, Side::Buy, args, json).await,
+        Command::Order(OrderCommand::Sell(args)) => order(&client, Side::Sell, args, json).await,
+        Command::Cancel { product, id, .. } => {
+            match id {
+                Some(id) => {
+                    client
+                        .send(CancelChildOrder {
+                            product_code: product,
+                            child_order_acceptance_id: id,
+                        })
+                        .await?;
+                }
+                None => {
+                    client
+                        .send(CancelAllChildOrders {
+                            product_code: product,
+                        })
+                        .await?;
+                }
+            }
+            output(
+                json,
+                json!({ "canceled": true }),
+                &["canceled"],
+                vec![vec!["true".to_string()]],
+            )
+        }
+        Command::Positions => {
+            let positions = client.send(GetPositions {}).await?;
+            let rows = positions
+                .iter()
+                .map(|x| {
+                    vec![
+                        x.product_code.to_string(),
+                        x.side.to_string(),
+                        x.size.to_string(),
+                        x.price.to_string(),
+                        x.pnl.to_string(),
+                        x.open_date.to_rfc3339(),
+                    ]
+                })
+                .collect();
+            output(
+                json,
+                json!(positions),
+                &["product", "side", "size", "price", "pnl", "open_date"],
+                rows,
+            )
+        }
+    }
+}
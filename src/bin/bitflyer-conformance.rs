@@ -0,0 +1,29 @@
+// Usage: bitflyer-conformance [PRODUCT_CODE]
+// Compares the live public API with the crate's types and exits with 1 on any drift.
+
+use anyhow::{anyhow, Result};
+use bitflyer::api::Client;
+use bitflyer::conformance::check_public;
+use bitflyer::entity::ProductCode;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let product_code = match std::env::args().nth(1) {
+        Some(x) => match serde_json::from_value(serde_json::json!(x.to_uppercase()))? {
+            ProductCode::Other => return Err(anyhow!("unknown product code: {x}")),
+            product_code => product_code,
+        },
+        None => ProductCode::BtcJpy,
+    };
+    let reports = check_public(&Client::new()?, product_code).await;
+    for report in &reports {
+        match &report.result {
+            Ok(conformance) => println!("{}: {conformance}", report.path),
+            Err(e) => println!("{}: request failed: {e}", report.path),
+        }
+    }
+    if !reports.iter().all(|x| x.is_conformant()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// Usage: bitflyer-schema openapi
+//        bitflyer-schema json-schema [DIR]
+// `openapi` prints an OpenAPI 3.0 document. `json-schema` prints the request and response
+// schemas of every endpoint, or writes them to DIR as <Request>.request.json and
+// <Request>.response.json.
+
+use anyhow::{anyhow, Result};
+use bitflyer::schema::{json_schemas, openapi};
+use serde_json::{json, Map};
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("openapi") | None => println!("{}", serde_json::to_string_pretty(&openapi())?),
+        Some("json-schema") => match args.next().map(PathBuf::from) {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir)?;
+                for endpoint in json_schemas() {
+                    for (kind, schema) in [
+                        ("request", &endpoint.request),
+                        ("response", &endpoint.response),
+                    ] {
+                        let path = dir.join(format!("{}.{kind}.json", endpoint.name));
+                        std::fs::write(path, serde_json::to_string_pretty(schema)?)?;
+                    }
+                }
+            }
+            None => {
+                let schemas = json_schemas()
+                    .into_iter()
+                    .map(|x| {
+                        let schemas = json!({ "request": x.request, "response": x.response });
+                        (x.name, schemas)
+                    })
+                    .collect::<Map<_, _>>();
+                println!("{}", serde_json::to_string_pretty(&schemas)?);
+            }
+        },
+        Some(x) => {
+            return Err(anyhow!(
+                "unknown command: {x}, expected openapi or json-schema"
+            ))
+        }
+    }
+    Ok(())
+}
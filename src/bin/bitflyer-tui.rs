@@ -0,0 +1,300 @@
+// Usage: bitflyer-tui [PRODUCT_CODE] [DEPTH]
+// Open orders are shown when API_KEY and API_SECRET are set, in the environment or a `.env` file.
+// Press q or Esc to quit.
+
+use anyhow::{anyhow, Result};
+use bitflyer::api::{Client, GetChildOrders};
+use bitflyer::entity::{Board, ChildOrder, ChildOrderType, OrderState, ProductCode, Side, Ticker};
+use bitflyer::poller::{board_poller, ticker_poller};
+use futures::StreamExt;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const TICKER_PERIOD: Duration = Duration::from_secs(1);
+const BOARD_PERIOD: Duration = Duration::from_secs(1);
+const ORDERS_PERIOD: Duration = Duration::from_secs(5);
+
+enum Update {
+    Ticker(Ticker),
+    Board(Board),
+    Orders(Vec<ChildOrder>),
+    Error(String),
+    Quit,
+}
+
+struct App {
+    product_code: ProductCode,
+    ticker: Option<Ticker>,
+    board: Option<Board>,
+    orders: Option<Vec<ChildOrder>>,
+    status: String,
+}
+
+impl App {
+    fn apply(&mut self, update: Update) -> bool {
+        match update {
+            Update::Ticker(ticker) => self.ticker = Some(ticker),
+            Update::Board(board) => self.board = Some(board),
+            Update::Orders(orders) => self.orders = Some(orders),
+            Update::Error(status) => self.status = status,
+            Update::Quit => return false,
+        }
+        true
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [board, orders] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(body);
+
+        frame.render_widget(self.ticker_line(), header);
+        frame.render_widget(self.board_table(), board);
+        frame.render_widget(self.orders_table(), orders);
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                "q".bold(),
+                " quit  ".into(),
+                self.status.clone().red(),
+            ])),
+            footer,
+        );
+    }
+
+    fn ticker_line(&self) -> Paragraph<'_> {
+        let block = Block::bordered().title(format!(" {} ", self.product_code));
+        let Some(ticker) = &self.ticker else {
+            return Paragraph::new("waiting for the ticker...").block(block);
+        };
+        Paragraph::new(Line::from(vec![
+            "LTP ".dark_gray(),
+            ticker.ltp.to_string().bold(),
+            "  bid ".dark_gray(),
+            ticker.best_bid.to_string().green(),
+            "  ask ".dark_gray(),
+            ticker.best_ask.to_string().red(),
+            "  spread ".dark_gray(),
+            (ticker.best_ask - ticker.best_bid).to_string().into(),
+            "  volume ".dark_gray(),
+            ticker.volume_by_product.round_dp(2).to_string().into(),
+            "  ".into(),
+            format!("{:?}", ticker.state).into(),
+            "  ".into(),
+            ticker
+                .timestamp
+                .format("%H:%M:%S UTC")
+                .to_string()
+                .dark_gray(),
+        ]))
+        .block(block)
+    }
+
+    fn board_table(&self) -> Table<'_> {
+        let block = Block::bordered().title(" Board ");
+        let widths = [
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ];
+        let header = Row::new(["bid", "price", "ask"]).bold();
+        let Some(board) = &self.board else {
+            return Table::new(Vec::<Row>::new(), widths)
+                .header(header)
+                .block(block);
+        };
+        let asks = board.asks.iter().rev().map(|x| {
+            Row::new([String::new(), x.price.to_string(), x.size.to_string()])
+                .style(Style::new().fg(Color::Red))
+        });
+        let bids = board.bids.iter().map(|x| {
+            Row::new([x.size.to_string(), x.price.to_string(), String::new()])
+                .style(Style::new().fg(Color::Green))
+        });
+        Table::new(asks.chain(bids).collect::<Vec<_>>(), widths)
+            .header(header)
+            .block(block)
+    }
+
+    fn orders_table(&self) -> Table<'_> {
+        let block = Block::bordered().title(" Open orders ");
+        let widths = [
+            Constraint::Length(5),
+            Constraint::Length(7),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(2),
+        ];
+        let header = Row::new(["side", "type", "price", "size", "filled", "acceptance id"]).bold();
+        let rows = self
+            .orders
+            .iter()
+            .flatten()
+            .map(|x| {
+                let (kind, price) = match &x.child_order_type {
+                    ChildOrderType::Limit { price } => ("LIMIT", price.to_string()),
+                    ChildOrderType::Market => ("MARKET", String::new()),
+                };
+                let color = match x.side {
+                    Side::Buy => Color::Green,
+                    Side::Sell => Color::Red,
+                };
+                Row::new([
+                    x.side.to_string(),
+                    kind.to_string(),
+                    price,
+                    x.size.to_string(),
+                    x.executed_size.to_string(),
+                    x.child_order_acceptance_id.clone(),
+                ])
+                .style(Style::new().fg(color))
+            })
+            .collect::<Vec<_>>();
+        let table = Table::new(rows, widths).header(header);
+        match &self.orders {
+            Some(_) => table.block(block),
+            None => table.block(block.title_bottom(" needs API_KEY and API_SECRET ")),
+        }
+    }
+}
+
+fn spawn_pollers(
+    client: Arc<Client>,
+    product_code: ProductCode,
+    depth: usize,
+    tx: mpsc::Sender<Update>,
+) {
+    {
+        let (client, product_code, tx) = (client.clone(), product_code.clone(), tx.clone());
+        tokio::spawn(async move {
+            let tickers = ticker_poller(client.as_ref(), product_code, TICKER_PERIOD);
+            futures::pin_mut!(tickers);
+            while let Some(ticker) = tickers.next().await {
+                if tx.send(Update::Ticker(ticker)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    {
+        let (client, product_code, tx) = (client.clone(), product_code.clone(), tx.clone());
+        tokio::spawn(async move {
+            let boards = board_poller(client.as_ref(), product_code, BOARD_PERIOD, Some(depth));
+            futures::pin_mut!(boards);
+            while let Some(board) = boards.next().await {
+                if tx.send(Update::Board(board)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    if std::env::var("API_KEY").is_err() || std::env::var("API_SECRET").is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ORDERS_PERIOD);
+        loop {
+            interval.tick().await;
+            let update = match client
+                .send(GetChildOrders {
+                    product_code: Some(product_code.clone()),
+                    child_order_state: Some(OrderState::Active),
+                    ..Default::default()
+                })
+                .await
+            {
+                Ok(orders) => Update::Orders(orders),
+                Err(e) => Update::Error(format!("failed to get the open orders: {e}")),
+            };
+            if tx.send(update).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// crossterm blocks, so keys are read on a thread of their own.
+fn spawn_keys(tx: mpsc::Sender<Update>) {
+    std::thread::spawn(move || loop {
+        match event::poll(Duration::from_millis(200)) {
+            Ok(false) => {
+                if tx.is_closed() {
+                    break;
+                }
+                continue;
+            }
+            Ok(true) => {}
+            Err(_) => break,
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        let quit = key.kind == KeyEventKind::Press
+            && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                || (key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)));
+        if quit {
+            let _ = tx.blocking_send(Update::Quit);
+            break;
+        }
+    });
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    mut app: App,
+    mut rx: mpsc::Receiver<Update>,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.render(frame))?;
+        let Some(update) = rx.recv().await else {
+            return Ok(());
+        };
+        if !app.apply(update) {
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let mut args = std::env::args().skip(1);
+    let product_code = match args.next() {
+        Some(x) => match serde_json::from_value(serde_json::json!(x.to_uppercase()))? {
+            ProductCode::Other => return Err(anyhow!("unknown product code: {x}")),
+            product_code => product_code,
+        },
+        None => ProductCode::BtcJpy,
+    };
+    let depth = args.next().map(|x| x.parse()).transpose()?.unwrap_or(20);
+
+    let client = Arc::new(Client::new()?);
+    let (tx, rx) = mpsc::channel(64);
+    spawn_pollers(client, product_code.clone(), depth, tx.clone());
+    spawn_keys(tx);
+
+    let app = App {
+        product_code,
+        ticker: None,
+        board: None,
+        orders: None,
+        status: String::new(),
+    };
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, app, rx).await;
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,224 @@
+use crate::api::SendParentOrder;
+use crate::entity::{
+    MinuteToExpire, ParentOrderConditionType, ParentOrderMethod, ProductCode, Side, TimeInForce,
+};
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+// How a bracket closes its position at a loss.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StopLoss {
+    Stop {
+        trigger_price: Decimal,
+    },
+    StopLimit {
+        trigger_price: Decimal,
+        price: Decimal,
+    },
+}
+
+impl StopLoss {
+    fn trigger_price(&self) -> Decimal {
+        match *self {
+            StopLoss::Stop { trigger_price } | StopLoss::StopLimit { trigger_price, .. } => {
+                trigger_price
+            }
+        }
+    }
+}
+
+// An entry with a take profit limit and a stop loss, sent as one IFDOCO parent order. The exits
+// take the product and size of the entry and the other side, and come in the order bitFlyer
+// expects: entry, take profit, stop loss.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BracketOrder {
+    entry: ParentOrderConditionType,
+    take_profit: Option<Decimal>,
+    stop_loss: Option<StopLoss>,
+    minute_to_expire: Option<MinuteToExpire>,
+    time_in_force: Option<TimeInForce>,
+}
+
+impl BracketOrder {
+    pub fn new(entry: ParentOrderConditionType) -> Self {
+        Self {
+            entry,
+            take_profit: None,
+            stop_loss: None,
+            minute_to_expire: None,
+            time_in_force: None,
+        }
+    }
+
+    // The limit price the position is closed at with a profit.
+    pub fn take_profit(mut self, price: Decimal) -> Self {
+        self.take_profit = Some(price);
+        self
+    }
+
+    // Closes the position with a market order once the price reaches `trigger_price`.
+    pub fn stop_loss(mut self, trigger_price: Decimal) -> Self {
+        self.stop_loss = Some(StopLoss::Stop { trigger_price });
+        self
+    }
+
+    // Closes the position with a limit order at `price` once the price reaches `trigger_price`.
+    pub fn stop_loss_limit(mut self, trigger_price: Decimal, price: Decimal) -> Self {
+        self.stop_loss = Some(StopLoss::StopLimit {
+            trigger_price,
+            price,
+        });
+        self
+    }
+
+    pub fn with_minute_to_expire(mut self, minute_to_expire: MinuteToExpire) -> Self {
+        self.minute_to_expire = Some(minute_to_expire);
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    fn entry(&self) -> (&ProductCode, Side, Decimal) {
+        use ParentOrderConditionType::*;
+        match &self.entry {
+            Limit {
+                product_code,
+                side,
+                size,
+                ..
+            }
+            | Market {
+                product_code,
+                side,
+                size,
+            }
+            | Stop {
+                product_code,
+                side,
+                size,
+                ..
+            }
+            | StopLimit {
+                product_code,
+                side,
+                size,
+                ..
+            }
+            | Trail {
+                product_code,
+                side,
+                size,
+                ..
+            } => (product_code, *side, *size),
+        }
+    }
+
+    // The price the entry is expected to fill at; None for market and trail entries.
+    fn entry_price(&self) -> Option<Decimal> {
+        use ParentOrderConditionType::*;
+        match self.entry {
+            Limit { price, .. } | StopLimit { price, .. } => Some(price),
+            Stop { trigger_price, .. } => Some(trigger_price),
+            Market { .. } | Trail { .. } => None,
+        }
+    }
+
+    fn exits(&self) -> Result<(Decimal, StopLoss)> {
+        let (_, side, size) = self.entry();
+        if size <= Decimal::ZERO {
+            return Err(anyhow!("order size must be positive: {size}"));
+        }
+        let take_profit = self
+            .take_profit
+            .ok_or_else(|| anyhow!("the bracket has no take profit"))?;
+        let stop_loss = self
+            .stop_loss
+            .ok_or_else(|| anyhow!("the bracket has no stop loss"))?;
+        let mut prices = vec![take_profit, stop_loss.trigger_price()];
+        prices.extend(self.entry_price());
+        if let StopLoss::StopLimit { price, .. } = stop_loss {
+            prices.push(price);
+        }
+        if let Some(price) = prices.into_iter().find(|x| *x <= Decimal::ZERO) {
+            return Err(anyhow!("order price must be positive: {price}"));
+        }
+
+        // Without an entry price the exits are only checked against each other.
+        let trigger_price = stop_loss.trigger_price();
+        let (low, high) = match side {
+            Side::Buy => (trigger_price, take_profit),
+            Side::Sell => (take_profit, trigger_price),
+        };
+        let is_ordered = low < high && self.entry_price().is_none_or(|x| low <= x && x <= high);
+        if !is_ordered {
+            let (above, below) = match side {
+                Side::Buy => ("take profit", "stop loss"),
+                Side::Sell => ("stop loss", "take profit"),
+            };
+            let entry = self
+                .entry_price()
+                .map(|x| format!(", entry {x}"))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "a {side} bracket needs the {above} above the entry and the {below} below it: \
+                 take profit {take_profit}, stop loss {trigger_price}{entry}"
+            ));
+        }
+        Ok((take_profit, stop_loss))
+    }
+
+    // Both exits must be set, and for a buy entry the take profit must be above the entry
+    // price and the stop loss below it; the other way round for a sell entry.
+    pub fn validate(&self) -> Result<()> {
+        self.exits().map(|_| ())
+    }
+
+    pub fn build(&self) -> Result<SendParentOrder> {
+        use ParentOrderConditionType::*;
+        let (take_profit, stop_loss) = self.exits()?;
+        let (product_code, side, size) = self.entry();
+        let side = side.get_reverse();
+        let take_profit = Limit {
+            product_code: product_code.clone(),
+            side,
+            size,
+            price: take_profit,
+        };
+        let stop_loss = match stop_loss {
+            StopLoss::Stop { trigger_price } => Stop {
+                product_code: product_code.clone(),
+                side,
+                size,
+                trigger_price,
+            },
+            StopLoss::StopLimit {
+                trigger_price,
+                price,
+            } => StopLimit {
+                product_code: product_code.clone(),
+                side,
+                size,
+                price,
+                trigger_price,
+            },
+        };
+        Ok(SendParentOrder {
+            order_method: ParentOrderMethod::Ifdoco {
+                parameters: [self.entry.clone(), take_profit, stop_loss],
+            },
+            minute_to_expire: self.minute_to_expire,
+            time_in_force: self.time_in_force,
+        })
+    }
+}
+
+impl TryFrom<BracketOrder> for SendParentOrder {
+    type Error = anyhow::Error;
+
+    fn try_from(bracket: BracketOrder) -> Result<Self> {
+        bracket.build()
+    }
+}
@@ -0,0 +1,310 @@
+use crate::entity::{Execution, ExecutionSide};
+use chrono::{DateTime, FixedOffset, NaiveTime, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    pub open_time: DateTime<Utc>,
+    pub close_time: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    pub trade_count: u64,
+}
+
+impl Candle {
+    fn open(open_time: DateTime<Utc>, interval: TimeDelta, price: Decimal) -> Self {
+        Self {
+            open_time,
+            close_time: open_time + interval,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Decimal::ZERO,
+            buy_volume: Decimal::ZERO,
+            sell_volume: Decimal::ZERO,
+            trade_count: 0,
+        }
+    }
+
+    fn push(&mut self, execution: &Execution) {
+        self.high = self.high.max(execution.price);
+        self.low = self.low.min(execution.price);
+        self.close = execution.price;
+        self.volume += execution.size;
+        match execution.side {
+            ExecutionSide::Buy => self.buy_volume += execution.size,
+            ExecutionSide::Sell => self.sell_volume += execution.size,
+            ExecutionSide::Empty => {}
+        }
+        self.trade_count += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trade_count == 0
+    }
+
+    // Candles from other sources may not count trades.
+    fn has_trades(&self) -> bool {
+        !self.is_empty() || !self.volume.is_zero()
+    }
+
+    fn merge(&mut self, other: &Candle) {
+        if !other.has_trades() {
+            return;
+        }
+        if self.has_trades() {
+            self.high = self.high.max(other.high);
+            self.low = self.low.min(other.low);
+        } else {
+            self.open = other.open;
+            self.high = other.high;
+            self.low = other.low;
+        }
+        self.close = other.close;
+        self.volume += other.volume;
+        self.buy_volume += other.buy_volume;
+        self.sell_volume += other.sell_volume;
+        self.trade_count += other.trade_count;
+    }
+}
+
+// Buckets are aligned to the Unix epoch and executions are expected in ascending id order.
+#[derive(Clone, Debug)]
+pub struct CandleBuilder {
+    interval: TimeDelta,
+    fill_gaps: bool,
+    forming: Option<Candle>,
+    last_id: Option<u64>,
+}
+
+impl CandleBuilder {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: TimeDelta::from_std(interval)
+                .unwrap_or(TimeDelta::MAX)
+                .max(TimeDelta::milliseconds(1)),
+            fill_gaps: true,
+            forming: None,
+            last_id: None,
+        }
+    }
+
+    // Intervals without executions produce flat zero-volume candles unless disabled.
+    pub fn with_gap_filling(mut self, fill_gaps: bool) -> Self {
+        self.fill_gaps = fill_gaps;
+        self
+    }
+
+    pub fn interval(&self) -> TimeDelta {
+        self.interval
+    }
+
+    pub fn forming(&self) -> Option<&Candle> {
+        self.forming.as_ref()
+    }
+
+    pub fn open_time(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.interval.num_milliseconds();
+        let millis = time.timestamp_millis();
+        let floor = millis - millis.rem_euclid(interval);
+        DateTime::from_timestamp_millis(floor).unwrap_or(time)
+    }
+
+    // Returns the candles closed by this execution.
+    pub fn on_execution(&mut self, execution: &Execution) -> Vec<Candle> {
+        if self.last_id.is_some_and(|x| execution.id <= x) {
+            return vec![];
+        }
+        let open_time = self.open_time(execution.exec_date);
+        if self
+            .forming
+            .as_ref()
+            .is_some_and(|x| open_time < x.open_time)
+        {
+            tracing::debug!(
+                "execution {} is older than the forming candle",
+                execution.id
+            );
+            return vec![];
+        }
+        self.last_id = Some(execution.id);
+        let closed = self.close_until(open_time);
+        let candle = self
+            .forming
+            .get_or_insert_with(|| Candle::open(open_time, self.interval, execution.price));
+        candle.push(execution);
+        closed
+    }
+
+    // Closes the forming candle once its interval has elapsed, even without new executions.
+    pub fn on_time(&mut self, now: DateTime<Utc>) -> Vec<Candle> {
+        let open_time = self.open_time(now);
+        let closed = self.close_until(open_time);
+        if let (Some(last), None) = (closed.last(), &self.forming) {
+            if self.fill_gaps {
+                self.forming = Some(Candle::open(open_time, self.interval, last.close));
+            }
+        }
+        closed
+    }
+
+    fn close_until(&mut self, open_time: DateTime<Utc>) -> Vec<Candle> {
+        let mut closed = vec![];
+        while let Some(candle) = self.forming.take_if(|x| x.open_time < open_time) {
+            let close_time = candle.close_time;
+            let close = candle.close;
+            closed.push(candle);
+            if self.fill_gaps && close_time < open_time {
+                self.forming = Some(Candle::open(close_time, self.interval, close));
+            }
+        }
+        closed
+    }
+
+    pub fn finish(&mut self) -> Option<Candle> {
+        self.forming.take()
+    }
+
+    // Builds candles from a batch such as a REST backfill, including the last forming candle.
+    pub fn build<'a>(
+        interval: Duration,
+        executions: impl IntoIterator<Item = &'a Execution>,
+    ) -> Vec<Candle> {
+        let mut executions: Vec<&Execution> = executions.into_iter().collect();
+        executions.sort_by_key(|x| x.id);
+        let mut builder = Self::new(interval);
+        let mut candles = vec![];
+        for execution in executions {
+            candles.extend(builder.on_execution(execution));
+        }
+        candles.extend(builder.finish());
+        candles
+    }
+}
+
+// Buckets are aligned to local midnight of `offset` (JST by default) plus the session start,
+// so daily candles begin at 00:00 JST. Candles are expected in ascending open time; each is
+// assigned to the bucket containing its open time. Flat zero-volume candles never widen the
+// high/low of a bucket that traded.
+#[derive(Clone, Debug)]
+pub struct CandleResampler {
+    interval: TimeDelta,
+    offset: FixedOffset,
+    session_start: NaiveTime,
+    fill_gaps: bool,
+    forming: Option<Candle>,
+    last_open_time: Option<DateTime<Utc>>,
+}
+
+impl CandleResampler {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: TimeDelta::from_std(interval)
+                .unwrap_or(TimeDelta::MAX)
+                .max(TimeDelta::milliseconds(1)),
+            offset: FixedOffset::east_opt(9 * 60 * 60).unwrap(),
+            session_start: NaiveTime::MIN,
+            fill_gaps: true,
+            forming: None,
+            last_open_time: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // Local time of day at which buckets start, e.g. a trading day that rolls over at 04:00.
+    pub fn with_session_start(mut self, session_start: NaiveTime) -> Self {
+        self.session_start = session_start;
+        self
+    }
+
+    // Buckets without any source candle produce flat zero-volume candles unless disabled.
+    pub fn with_gap_filling(mut self, fill_gaps: bool) -> Self {
+        self.fill_gaps = fill_gaps;
+        self
+    }
+
+    pub fn interval(&self) -> TimeDelta {
+        self.interval
+    }
+
+    pub fn forming(&self) -> Option<&Candle> {
+        self.forming.as_ref()
+    }
+
+    pub fn open_time(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let alignment = (self.session_start - NaiveTime::MIN).num_milliseconds()
+            - i64::from(self.offset.local_minus_utc()) * 1000;
+        let interval = self.interval.num_milliseconds();
+        let millis = time.timestamp_millis() - alignment;
+        let floor = millis - millis.rem_euclid(interval) + alignment;
+        DateTime::from_timestamp_millis(floor).unwrap_or(time)
+    }
+
+    // Returns the resampled candles closed by this candle.
+    pub fn on_candle(&mut self, candle: &Candle) -> Vec<Candle> {
+        if self.last_open_time.is_some_and(|x| candle.open_time <= x) {
+            return vec![];
+        }
+        self.last_open_time = Some(candle.open_time);
+        let open_time = self.open_time(candle.open_time);
+        if candle.close_time > open_time + self.interval {
+            tracing::debug!(
+                "candle at {} spans more than one {} bucket",
+                candle.open_time,
+                self.interval
+            );
+        }
+        let closed = self.close_until(open_time);
+        match &mut self.forming {
+            Some(forming) => forming.merge(candle),
+            None => {
+                let mut forming = Candle::open(open_time, self.interval, candle.open);
+                forming.close = candle.close;
+                forming.merge(candle);
+                self.forming = Some(forming);
+            }
+        }
+        closed
+    }
+
+    fn close_until(&mut self, open_time: DateTime<Utc>) -> Vec<Candle> {
+        let mut closed = vec![];
+        while let Some(candle) = self.forming.take_if(|x| x.open_time < open_time) {
+            let close_time = candle.close_time;
+            let close = candle.close;
+            closed.push(candle);
+            if self.fill_gaps && close_time < open_time {
+                self.forming = Some(Candle::open(close_time, self.interval, close));
+            }
+        }
+        closed
+    }
+
+    pub fn finish(&mut self) -> Option<Candle> {
+        self.forming.take()
+    }
+
+    // Resamples a batch, including the last bucket even if it is still forming.
+    pub fn resample<'a>(mut self, candles: impl IntoIterator<Item = &'a Candle>) -> Vec<Candle> {
+        let mut candles: Vec<&Candle> = candles.into_iter().collect();
+        candles.sort_by_key(|x| x.open_time);
+        let mut resampled = vec![];
+        for candle in candles {
+            resampled.extend(self.on_candle(candle));
+        }
+        resampled.extend(self.finish());
+        resampled
+    }
+}
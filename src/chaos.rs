@@ -0,0 +1,179 @@
+use crate::api::{ApiRequest, BitflyerApi, BoxFuture, DynBitflyerApi, RawRequest};
+use crate::mock::{next_random, Fault};
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// What goes wrong, and how often. The same seed gives the same sequence of delays, drops and
+// reorderings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChaosConfig {
+    pub latency: Duration,
+    // Up to this much more latency, uniformly distributed.
+    pub jitter: Duration,
+    // Probability that a response, or a feed item, is lost.
+    pub drop_rate: f64,
+    // How long a lost response is waited for before the request fails with `Fault::Timeout`.
+    pub timeout: Duration,
+    // Feed items held back and released in random order; 0 keeps the order.
+    pub reorder_window: usize,
+    pub seed: u64,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            drop_rate: 0.0,
+            timeout: Duration::from_secs(10),
+            reorder_window: 0,
+            seed: 0,
+        }
+    }
+}
+
+impl ChaosConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = drop_rate;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_reorder_window(mut self, reorder_window: usize) -> Self {
+        self.reorder_window = reorder_window;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn delay(&self, seed: &mut u64) -> Duration {
+        self.latency + self.jitter.mul_f64(next_random(seed))
+    }
+
+    fn drops(&self, seed: &mut u64) -> bool {
+        next_random(seed) < self.drop_rate
+    }
+}
+
+// Makes an API slow and unreliable, e.g. a `Client` in a staging run or a `MockBitflyer`, to
+// see how a bot copes before it trades real money. Every request is delayed before it is sent.
+// A dropped response is lost after the request was handled, so an order may have been placed
+// although the caller only sees a timeout. Concurrent requests may complete out of order.
+#[derive(Debug)]
+pub struct Chaos<A> {
+    inner: A,
+    config: ChaosConfig,
+    seed: Mutex<u64>,
+}
+
+impl<A> Chaos<A> {
+    pub fn new(inner: A, config: ChaosConfig) -> Self {
+        let seed = Mutex::new(config.seed);
+        Self {
+            inner,
+            config,
+            seed,
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    // The delay before sending, and whether the response will be lost.
+    fn roll(&self) -> (Duration, bool) {
+        let mut seed = self.seed.lock().unwrap();
+        (self.config.delay(&mut seed), self.config.drops(&mut seed))
+    }
+
+    async fn disturb<T>(
+        &self,
+        response: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let (delay, drops) = self.roll();
+        tokio::time::sleep(delay).await;
+        let response = response.await;
+        if drops {
+            tracing::debug!("chaos dropped a response");
+            tokio::time::sleep(self.config.timeout).await;
+            return Err(Fault::Timeout(self.config.timeout).into());
+        }
+        response
+    }
+}
+
+impl<A: DynBitflyerApi> DynBitflyerApi for Chaos<A> {
+    fn send_raw(&self, request: RawRequest) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { self.disturb(self.inner.send_raw(request)).await })
+    }
+}
+
+impl<A: BitflyerApi> BitflyerApi for Chaos<A> {
+    async fn send<T>(&self, request: T) -> Result<T::Response>
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        self.disturb(self.inner.send(request)).await
+    }
+}
+
+// The same for a feed, e.g. a `ticker_poller`: items are dropped, delayed one after another,
+// and shuffled within `reorder_window`. Held back items wait for later ones to arrive and are
+// flushed when the feed ends. `timeout` does not apply.
+pub fn chaotic_stream<S>(stream: S, config: ChaosConfig) -> impl Stream<Item = S::Item> + Send
+where
+    S: Stream + Send,
+    S::Item: Send,
+{
+    let seed = config.seed;
+    futures::stream::unfold(
+        (Box::pin(stream), Vec::new(), false, seed),
+        move |(mut stream, mut held, mut ended, mut seed)| {
+            let config = config.clone();
+            async move {
+                while !ended && held.len() <= config.reorder_window {
+                    match stream.next().await {
+                        Some(item) if !config.drops(&mut seed) => held.push(item),
+                        Some(_) => tracing::debug!("chaos dropped a feed item"),
+                        None => ended = true,
+                    }
+                }
+                if held.is_empty() {
+                    return None;
+                }
+                let index = (next_random(&mut seed) * held.len() as f64) as usize;
+                let item = held.remove(index.min(held.len() - 1));
+                tokio::time::sleep(config.delay(&mut seed)).await;
+                Some((item, (stream, held, ended, seed)))
+            }
+        },
+    )
+}
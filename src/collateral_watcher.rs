@@ -0,0 +1,118 @@
+use crate::api::{BitflyerApi, Client, GetCollateral};
+use crate::entity::Collateral;
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use rust_decimal::Decimal;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollateralChanged {
+    pub collateral: Collateral,
+    pub collateral_delta: Decimal,
+    pub open_position_pnl_delta: Decimal,
+    pub require_collateral_delta: Decimal,
+    pub previous_keep_rate: f64,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl CollateralChanged {
+    pub fn keep_rate_delta(&self) -> f64 {
+        self.collateral.keep_rate - self.previous_keep_rate
+    }
+
+    // Collateral plus open PnL, the figure keep_rate is computed from.
+    pub fn equity(&self) -> Decimal {
+        self.collateral.collateral + self.collateral.open_position_pnl
+    }
+}
+
+// The first snapshot is the baseline and emits nothing; afterwards every change in
+// collateral, open PnL, required collateral or keep rate produces an event.
+#[derive(Debug)]
+pub struct CollateralWatcher<A = Client> {
+    client: Arc<A>,
+    last: Mutex<Option<Collateral>>,
+    events: broadcast::Sender<CollateralChanged>,
+}
+
+impl<A: BitflyerApi + 'static> CollateralWatcher<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            last: Mutex::new(None),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CollateralChanged> {
+        self.events.subscribe()
+    }
+
+    // Lagged receivers skip the missed events rather than ending the stream.
+    pub fn stream(&self) -> impl Stream<Item = CollateralChanged> + Send + 'static {
+        futures::stream::unfold(self.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("collateral stream missed {n} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    pub fn last_collateral(&self) -> Option<Collateral> {
+        self.last.lock().unwrap().clone()
+    }
+
+    pub fn evaluate(
+        &self,
+        collateral: &Collateral,
+        observed_at: DateTime<Utc>,
+    ) -> Option<CollateralChanged> {
+        let previous = self.last.lock().unwrap().replace(collateral.clone())?;
+        let event = CollateralChanged {
+            collateral: collateral.clone(),
+            collateral_delta: collateral.collateral - previous.collateral,
+            open_position_pnl_delta: collateral.open_position_pnl - previous.open_position_pnl,
+            require_collateral_delta: collateral.require_collateral - previous.require_collateral,
+            previous_keep_rate: previous.keep_rate,
+            observed_at,
+        };
+        let unchanged = event.collateral_delta.is_zero()
+            && event.open_position_pnl_delta.is_zero()
+            && event.require_collateral_delta.is_zero()
+            && collateral.keep_rate == previous.keep_rate;
+        if unchanged {
+            return None;
+        }
+        let _ = self.events.send(event.clone());
+        Some(event)
+    }
+
+    pub async fn poll(&self) -> Result<Option<CollateralChanged>> {
+        let collateral = self.client.send(GetCollateral).await?;
+        Ok(self.evaluate(&collateral, Utc::now()))
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let watcher = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = watcher.poll().await {
+                    tracing::warn!("failed to poll collateral: {e:?}");
+                }
+            }
+        })
+    }
+}
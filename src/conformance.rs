@@ -0,0 +1,178 @@
+use crate::api::{
+    ApiRequest, DynBitflyerApi, GetBoard, GetBoardHealth, GetBoardState, GetExecutions, GetMarkets,
+    GetTicker, RawRequest,
+};
+use crate::entity::ProductCode;
+use serde::Deserialize;
+use serde_path_to_error::Segment;
+
+// How a response compares with the crate's types. Array indices are left out of paths, so a
+// field of every execution is reported once as `[].field`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Conformance {
+    // In the response but ignored by the types.
+    pub unknown_fields: Vec<String>,
+    // Why the response does not deserialize, e.g. a missing field or a changed type.
+    pub error: Option<String>,
+}
+
+impl Conformance {
+    pub fn is_conformant(&self) -> bool {
+        self.unknown_fields.is_empty() && self.error.is_none()
+    }
+}
+
+impl std::fmt::Display for Conformance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_conformant() {
+            return write!(f, "conformant");
+        }
+        let mut problems = self
+            .unknown_fields
+            .iter()
+            .map(|x| format!("unknown field {x}"))
+            .collect::<Vec<_>>();
+        problems.extend(self.error.clone());
+        write!(f, "{}", problems.join(", "))
+    }
+}
+
+fn ignored_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, .. } => format!("{}[]", ignored_path(parent)),
+        Path::Map { parent, key } => match ignored_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{parent}.{key}"),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+fn error_path(path: &serde_path_to_error::Path) -> String {
+    let mut result = String::new();
+    for segment in path.iter() {
+        match segment {
+            Segment::Seq { .. } => result.push_str("[]"),
+            Segment::Map { key } | Segment::Enum { variant: key } => {
+                if !result.is_empty() {
+                    result.push('.');
+                }
+                result.push_str(key);
+            }
+            Segment::Unknown => result.push_str(".?"),
+        }
+    }
+    result
+}
+
+// Compares a response body, e.g. one from a HAR capture, with the response type of `T`.
+pub fn check_response<T: ApiRequest>(body: &str) -> Conformance {
+    if body.trim().is_empty() {
+        return Conformance {
+            unknown_fields: vec![],
+            error: T::deserialize_response_body(body)
+                .err()
+                .map(|e| e.to_string()),
+        };
+    }
+    let mut unknown_fields: Vec<String> = vec![];
+    let mut track = serde_path_to_error::Track::new();
+    let mut deserializer = serde_json::Deserializer::from_str(body);
+    let mut ignored = |path: serde_ignored::Path| {
+        let path = ignored_path(&path);
+        if !unknown_fields.contains(&path) {
+            unknown_fields.push(path);
+        }
+    };
+    let result = T::Response::deserialize(serde_ignored::Deserializer::new(
+        serde_path_to_error::Deserializer::new(&mut deserializer, &mut track),
+        &mut ignored,
+    ));
+    let error = result.err().map(|e| match error_path(&track.path()) {
+        path if path.is_empty() => e.to_string(),
+        path => format!("{path}: {e}"),
+    });
+    unknown_fields.sort();
+    Conformance {
+        unknown_fields,
+        error,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointReport {
+    pub path: &'static str,
+    // Err when the request itself failed.
+    pub result: std::result::Result<Conformance, String>,
+}
+
+impl EndpointReport {
+    pub fn is_conformant(&self) -> bool {
+        self.result.as_ref().is_ok_and(|x| x.is_conformant())
+    }
+}
+
+async fn check<A, T>(api: &A, request: T) -> EndpointReport
+where
+    A: DynBitflyerApi + ?Sized,
+    T: ApiRequest,
+{
+    let result = match RawRequest::new(&request) {
+        Ok(request) => api.send_raw(request).await,
+        Err(e) => Err(e),
+    };
+    EndpointReport {
+        path: T::PATH,
+        result: result
+            .map(|body| check_response::<T>(&body))
+            .map_err(|e| format!("{e:#}")),
+    }
+}
+
+// Calls every public endpoint and compares the responses with the crate's types, to catch
+// fields bitFlyer added, removed or retyped.
+pub async fn check_public<A>(api: &A, product_code: ProductCode) -> Vec<EndpointReport>
+where
+    A: DynBitflyerApi + ?Sized,
+{
+    let product_code = Some(product_code);
+    vec![
+        check(api, GetMarkets).await,
+        check(
+            api,
+            GetBoard {
+                product_code: product_code.clone(),
+                depth: None,
+            },
+        )
+        .await,
+        check(
+            api,
+            GetTicker {
+                product_code: product_code.clone(),
+            },
+        )
+        .await,
+        check(
+            api,
+            GetExecutions {
+                product_code: product_code.clone(),
+                count: Some(100),
+                ..Default::default()
+            },
+        )
+        .await,
+        check(
+            api,
+            GetBoardState {
+                product_code: product_code.clone(),
+            },
+        )
+        .await,
+        check(api, GetBoardHealth { product_code }).await,
+    ]
+}
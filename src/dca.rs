@@ -0,0 +1,278 @@
+use crate::api::{BitflyerApi, Client, GetChildOrders, GetTicker, SendChildOrder};
+use crate::entity::{ChildOrder, ChildOrderType, ProductCode, Side};
+use crate::error::{is_not_accepted, is_user_error};
+use crate::shutdown::ShutdownHandle;
+use crate::sizing::{size_from_ticker, SizeRounding};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Days, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Asia::Tokyo;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+const ORDER_LOOKUP_COUNT: u64 = 20;
+// Allows for the exchange's clock being behind ours.
+const ORDER_DATE_SLACK: TimeDelta = TimeDelta::seconds(60);
+
+enum Attempt {
+    Retry(anyhow::Error),
+    Fail(anyhow::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DcaPlan {
+    pub id: String,
+    pub product_code: ProductCode,
+    pub notional: Decimal,
+    // Time of day in JST.
+    pub time: NaiveTime,
+    pub min_size: Decimal,
+}
+
+impl DcaPlan {
+    pub fn next_run(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        next_time_of_day(self.time, after)
+    }
+}
+
+// The first occurrence of `time` (JST) strictly after `after`.
+pub(crate) fn next_time_of_day(time: NaiveTime, after: DateTime<Utc>) -> DateTime<Utc> {
+    let mut date = after.with_timezone(&Tokyo).date_naive();
+    loop {
+        if let Some(at) = Tokyo.from_local_datetime(&date.and_time(time)).earliest() {
+            let at = at.with_timezone(&Utc);
+            if at > after {
+                return at;
+            }
+        }
+        date = date + Days::new(1);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DcaOutcome {
+    Submitted {
+        child_order_acceptance_id: String,
+        price: Decimal,
+        size: Decimal,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DcaRun {
+    pub plan_id: String,
+    pub scheduled_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub attempts: usize,
+    pub outcome: DcaOutcome,
+}
+
+impl DcaRun {
+    pub fn is_success(&self) -> bool {
+        matches!(self.outcome, DcaOutcome::Submitted { .. })
+    }
+}
+
+#[derive(Debug)]
+pub struct DcaScheduler<A = Client> {
+    client: Arc<A>,
+    plans: Mutex<Vec<(DcaPlan, DateTime<Utc>)>>,
+    history: Mutex<Vec<DcaRun>>,
+    max_attempts: usize,
+    retry_interval: Duration,
+    events: broadcast::Sender<DcaRun>,
+}
+
+impl<A: BitflyerApi + 'static> DcaScheduler<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            plans: Mutex::new(vec![]),
+            history: Mutex::new(vec![]),
+            max_attempts: 3,
+            retry_interval: Duration::from_secs(10),
+            events,
+        }
+    }
+
+    pub fn with_retry(mut self, max_attempts: usize, retry_interval: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DcaRun> {
+        self.events.subscribe()
+    }
+
+    pub fn add_plan(&self, plan: DcaPlan) {
+        let next = plan.next_run(Utc::now());
+        let mut plans = self.plans.lock().unwrap();
+        plans.retain(|(x, _)| x.id != plan.id);
+        plans.push((plan, next));
+    }
+
+    pub fn remove_plan(&self, id: &str) -> Option<DcaPlan> {
+        let mut plans = self.plans.lock().unwrap();
+        let position = plans.iter().position(|(x, _)| x.id == id)?;
+        Some(plans.remove(position).0)
+    }
+
+    pub fn plans(&self) -> Vec<(DcaPlan, DateTime<Utc>)> {
+        self.plans.lock().unwrap().clone()
+    }
+
+    pub fn history(&self) -> Vec<DcaRun> {
+        self.history.lock().unwrap().clone()
+    }
+
+    pub async fn run_due(&self, now: DateTime<Utc>) -> Vec<DcaRun> {
+        let due = {
+            let mut plans = self.plans.lock().unwrap();
+            let mut due = vec![];
+            for (plan, next) in plans.iter_mut() {
+                if *next <= now {
+                    due.push((plan.clone(), *next));
+                    *next = plan.next_run(now);
+                }
+            }
+            due
+        };
+        let mut runs = vec![];
+        for (plan, scheduled_at) in due {
+            runs.push(self.execute(&plan, scheduled_at).await);
+        }
+        runs
+    }
+
+    pub async fn execute(&self, plan: &DcaPlan, scheduled_at: DateTime<Utc>) -> DcaRun {
+        let mut attempts = 0;
+        let outcome = self
+            .buy(plan, &mut attempts)
+            .await
+            .unwrap_or_else(|e| DcaOutcome::Failed {
+                error: format!("{e:?}"),
+            });
+        let run = DcaRun {
+            plan_id: plan.id.clone(),
+            scheduled_at,
+            finished_at: Utc::now(),
+            attempts,
+            outcome,
+        };
+        self.history.lock().unwrap().push(run.clone());
+        let _ = self.events.send(run.clone());
+        run
+    }
+
+    // Only sends the order again when the last one was certainly not accepted, or can't be
+    // found after a failure that leaves it unknown.
+    async fn buy(&self, plan: &DcaPlan, attempts: &mut usize) -> Result<DcaOutcome> {
+        loop {
+            *attempts += 1;
+            let error = match self.try_buy(plan).await {
+                Ok(outcome) => return Ok(outcome),
+                Err(Attempt::Retry(e)) => e,
+                Err(Attempt::Fail(e)) => return Err(e),
+            };
+            if *attempts >= self.max_attempts {
+                return Err(error);
+            }
+            tracing::warn!("DCA plan {} attempt {attempts} failed: {error:?}", plan.id);
+            tokio::time::sleep(self.retry_interval).await;
+        }
+    }
+
+    async fn try_buy(&self, plan: &DcaPlan) -> Result<DcaOutcome, Attempt> {
+        let ticker = self
+            .client
+            .send(GetTicker {
+                product_code: Some(plan.product_code.clone()),
+            })
+            .await
+            .map_err(Attempt::Retry)?;
+        let size = size_from_ticker(&ticker, Side::Buy, plan.notional, SizeRounding::Down)
+            .map_err(Attempt::Fail)?;
+        if size < plan.min_size {
+            return Err(Attempt::Fail(anyhow!(
+                "size {size} for {} JPY is below the minimum {}",
+                plan.notional,
+                plan.min_size
+            )));
+        }
+        let submitted = |child_order_acceptance_id| DcaOutcome::Submitted {
+            child_order_acceptance_id,
+            price: ticker.best_ask,
+            size,
+        };
+        let sent_at = Utc::now();
+        let error = match self
+            .client
+            .send(SendChildOrder::market(
+                plan.product_code.clone(),
+                Side::Buy,
+                size,
+            ))
+            .await
+        {
+            Ok(response) => return Ok(submitted(response.child_order_acceptance_id)),
+            Err(e) if is_not_accepted(&e) => return Err(Attempt::Retry(e)),
+            Err(e) if is_user_error(&e) => return Err(Attempt::Fail(e)),
+            Err(e) => e,
+        };
+
+        // The order may have been accepted; look for it before sending another.
+        tokio::time::sleep(self.retry_interval).await;
+        match self.find_order(plan, size, sent_at).await {
+            Ok(Some(order)) => Ok(submitted(order.child_order_acceptance_id)),
+            Ok(None) => Err(Attempt::Retry(error)),
+            Err(e) => Err(Attempt::Fail(
+                error.context(format!("the order could not be looked up: {e:?}")),
+            )),
+        }
+    }
+
+    async fn find_order(
+        &self,
+        plan: &DcaPlan,
+        size: Decimal,
+        sent_at: DateTime<Utc>,
+    ) -> Result<Option<ChildOrder>> {
+        let since = sent_at - ORDER_DATE_SLACK;
+        let orders = self
+            .client
+            .send(GetChildOrders {
+                product_code: Some(plan.product_code.clone()),
+                count: Some(ORDER_LOOKUP_COUNT),
+                ..Default::default()
+            })
+            .await?;
+        Ok(orders.into_iter().find(|x| {
+            x.side == Side::Buy
+                && x.child_order_type == ChildOrderType::Market
+                && x.size == size
+                && x.child_order_date >= since
+        }))
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let scheduler = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                for run in scheduler.run_due(Utc::now()).await {
+                    if !run.is_success() {
+                        tracing::warn!("DCA plan {} failed: {:?}", run.plan_id, run.outcome);
+                    }
+                }
+            }
+        })
+    }
+}
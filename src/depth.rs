@@ -0,0 +1,204 @@
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+use crate::api::{BitflyerApi, Client, GetBoard};
+use crate::board::OrderBook;
+use crate::entity::ProductCode;
+use crate::export::csv::{decimal, label, timestamp};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepthSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub product_code: ProductCode,
+    pub mid_price: Option<Decimal>,
+    // (price, size), best first.
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+impl DepthSnapshot {
+    pub fn from_book(
+        product_code: ProductCode,
+        timestamp: DateTime<Utc>,
+        book: &impl OrderBook,
+        levels: usize,
+    ) -> Self {
+        let top = |x: &[crate::entity::BoardElement]| {
+            x.iter().take(levels).map(|x| (x.price, x.size)).collect()
+        };
+        Self {
+            timestamp,
+            product_code,
+            mid_price: book.mid(),
+            bids: top(book.bid_levels()),
+            asks: top(book.ask_levels()),
+        }
+    }
+}
+
+// One row per snapshot with `levels` price/size pairs per side.
+pub trait DepthWriter {
+    fn write(&mut self, snapshot: &DepthSnapshot) -> Result<()>;
+
+    fn finish(&mut self) -> Result<()>;
+}
+
+pub struct CsvDepthWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+    levels: usize,
+    header_written: bool,
+}
+
+impl<W: Write> std::fmt::Debug for CsvDepthWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CsvDepthWriter {{ levels: {} }}", self.levels)
+    }
+}
+
+impl<W: Write> CsvDepthWriter<W> {
+    pub fn new(writer: W, levels: usize) -> Self {
+        Self {
+            writer: ::csv::Writer::from_writer(writer),
+            levels,
+            header_written: false,
+        }
+    }
+
+    // Skips the header, e.g. when appending to an existing file.
+    pub fn without_header(mut self) -> Self {
+        self.header_written = true;
+        self
+    }
+
+    pub fn header(levels: usize) -> Vec<String> {
+        let mut header = vec![
+            "timestamp".to_string(),
+            "product_code".to_string(),
+            "mid_price".to_string(),
+        ];
+        for side in ["bid", "ask"] {
+            for i in 1..=levels {
+                header.push(format!("{side}_price_{i}"));
+                header.push(format!("{side}_size_{i}"));
+            }
+        }
+        header
+    }
+}
+
+impl<W: Write> DepthWriter for CsvDepthWriter<W> {
+    fn write(&mut self, snapshot: &DepthSnapshot) -> Result<()> {
+        if !self.header_written {
+            self.writer.write_record(Self::header(self.levels))?;
+            self.header_written = true;
+        }
+        let mut record = vec![
+            timestamp(&snapshot.timestamp),
+            label(&snapshot.product_code),
+            snapshot.mid_price.map(decimal).unwrap_or_default(),
+        ];
+        for levels in [&snapshot.bids, &snapshot.asks] {
+            for i in 0..self.levels {
+                match levels.get(i) {
+                    Some((price, size)) => {
+                        record.push(decimal(*price));
+                        record.push(decimal(*size));
+                    }
+                    None => record.extend([String::new(), String::new()]),
+                }
+            }
+        }
+        self.writer.write_record(record)?;
+        // Flushed per row so an interrupted recording keeps every completed sample.
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Samples the top of the book at a fixed interval, whether or not it changed.
+#[derive(Debug)]
+pub struct DepthRecorder<A = Client> {
+    client: Arc<A>,
+    product_code: ProductCode,
+    levels: usize,
+    interval: Duration,
+}
+
+impl<A: BitflyerApi + 'static> DepthRecorder<A> {
+    pub fn new(client: Arc<A>, product_code: ProductCode, levels: usize) -> Self {
+        Self {
+            client,
+            product_code,
+            levels,
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+
+    pub async fn sample(&self) -> Result<DepthSnapshot> {
+        let board = self
+            .client
+            .send(GetBoard::new(self.product_code.clone()).with_depth(self.levels))
+            .await?;
+        Ok(DepthSnapshot::from_book(
+            self.product_code.clone(),
+            Utc::now(),
+            &board,
+            self.levels,
+        ))
+    }
+
+    // Failed samples are logged and skipped, leaving a gap in the series.
+    pub fn snapshots(&self) -> impl Stream<Item = DepthSnapshot> + Send + '_ {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        futures::stream::unfold(interval, move |mut interval| async move {
+            loop {
+                interval.tick().await;
+                match self.sample().await {
+                    Ok(snapshot) => return Some((snapshot, interval)),
+                    Err(e) => {
+                        tracing::warn!("failed to sample the {} book: {e:?}", self.product_code)
+                    }
+                }
+            }
+        })
+    }
+
+    // Records `samples` snapshots, or forever when None, and returns how many were written.
+    pub async fn run(
+        &self,
+        writer: &mut impl DepthWriter,
+        samples: Option<usize>,
+    ) -> Result<usize> {
+        let mut snapshots = std::pin::pin!(self.snapshots().take(samples.unwrap_or(usize::MAX)));
+        let mut written = 0;
+        while let Some(snapshot) = snapshots.next().await {
+            writer.write(&snapshot)?;
+            written += 1;
+        }
+        writer.finish()?;
+        Ok(written)
+    }
+}
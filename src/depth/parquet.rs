@@ -0,0 +1,155 @@
+use super::{DepthSnapshot, DepthWriter};
+use crate::export::csv::label;
+use anyhow::{Context, Result};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::sync::Arc;
+
+const ROW_GROUP_SIZE: usize = 10_000;
+
+// Prices and sizes are stored as doubles; rows are buffered and written per row group,
+// so `finish` must be called to produce a readable file.
+pub struct ParquetDepthWriter<W: Write + Send> {
+    writer: Option<SerializedFileWriter<W>>,
+    levels: usize,
+    row_group_size: usize,
+    rows: Vec<DepthSnapshot>,
+}
+
+impl<W: Write + Send> std::fmt::Debug for ParquetDepthWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ParquetDepthWriter {{ levels: {}, buffered: {} }}",
+            self.levels,
+            self.rows.len()
+        )
+    }
+}
+
+fn schema(levels: usize) -> String {
+    let mut fields = vec![
+        "REQUIRED INT64 timestamp (TIMESTAMP_MILLIS);".to_string(),
+        "REQUIRED BINARY product_code (UTF8);".to_string(),
+        "OPTIONAL DOUBLE mid_price;".to_string(),
+    ];
+    for side in ["bid", "ask"] {
+        for i in 1..=levels {
+            fields.push(format!("OPTIONAL DOUBLE {side}_price_{i};"));
+            fields.push(format!("OPTIONAL DOUBLE {side}_size_{i};"));
+        }
+    }
+    format!("message depth {{ {} }}", fields.join(" "))
+}
+
+// Values and definition levels of an optional column.
+fn optional(values: impl Iterator<Item = Option<Decimal>>) -> (Vec<f64>, Vec<i16>) {
+    let mut present = vec![];
+    let mut definitions = vec![];
+    for value in values {
+        match value.and_then(|x| x.to_f64()) {
+            Some(x) => {
+                present.push(x);
+                definitions.push(1);
+            }
+            None => definitions.push(0),
+        }
+    }
+    (present, definitions)
+}
+
+impl<W: Write + Send> ParquetDepthWriter<W> {
+    pub fn new(writer: W, levels: usize) -> Result<Self> {
+        let schema = Arc::new(parse_message_type(&schema(levels))?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        Ok(Self {
+            writer: Some(SerializedFileWriter::new(writer, schema, properties)?),
+            levels,
+            row_group_size: ROW_GROUP_SIZE,
+            rows: vec![],
+        })
+    }
+
+    pub fn with_row_group_size(mut self, row_group_size: usize) -> Self {
+        self.row_group_size = row_group_size.max(1);
+        self
+    }
+
+    fn flush_rows(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let writer = self.writer.as_mut().context("parquet writer is finished")?;
+        let rows = std::mem::take(&mut self.rows);
+        // Column order follows the schema: timestamp, product, mid, then each side's levels.
+        let mut optional_columns = vec![optional(rows.iter().map(|x| x.mid_price))];
+        for side in [0, 1] {
+            for i in 0..self.levels {
+                let level = |x: &DepthSnapshot| {
+                    let levels = if side == 0 { &x.bids } else { &x.asks };
+                    levels.get(i).copied()
+                };
+                optional_columns.push(optional(rows.iter().map(|x| level(x).map(|x| x.0))));
+                optional_columns.push(optional(rows.iter().map(|x| level(x).map(|x| x.1))));
+            }
+        }
+
+        let mut row_group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match index {
+                0 => {
+                    let values: Vec<i64> = rows
+                        .iter()
+                        .map(|x| x.timestamp.timestamp_millis())
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                1 => {
+                    let values: Vec<ByteArray> = rows
+                        .iter()
+                        .map(|x| ByteArray::from(label(&x.product_code).as_str()))
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                i => {
+                    let (values, definitions) = &optional_columns[i - 2];
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(values, Some(definitions), None)?;
+                }
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> DepthWriter for ParquetDepthWriter<W> {
+    fn write(&mut self, snapshot: &DepthSnapshot) -> Result<()> {
+        self.rows.push(snapshot.clone());
+        if self.rows.len() >= self.row_group_size {
+            self.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush_rows()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
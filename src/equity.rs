@@ -0,0 +1,212 @@
+use crate::api::{BitflyerApi, Client};
+use crate::dca::next_time_of_day;
+use crate::entity::{Balance, Collateral, Position, ProductCode};
+use crate::export::csv::{decimal, label, timestamp};
+use crate::portfolio::{portfolio, Portfolio};
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use chrono::{DateTime, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EquityPoint {
+    pub taken_at: DateTime<Utc>,
+    pub balance_jpy: Decimal,
+    pub collateral_jpy: Decimal,
+    pub total_jpy: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EquitySnapshot {
+    pub taken_at: DateTime<Utc>,
+    pub balances: Vec<Balance>,
+    pub collateral: Collateral,
+    pub positions: Vec<Position>,
+    pub jpy_prices: BTreeMap<String, Decimal>,
+    // Left out of the JPY totals for lack of a price.
+    pub unpriced_currencies: Vec<String>,
+    pub point: EquityPoint,
+}
+
+impl EquitySnapshot {
+    pub fn from_portfolio(portfolio: &Portfolio) -> Self {
+        Self {
+            taken_at: portfolio.timestamp,
+            balances: portfolio.balances.clone(),
+            collateral: portfolio.collateral.clone(),
+            positions: portfolio.positions.clone(),
+            jpy_prices: portfolio
+                .jpy_prices
+                .iter()
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            unpriced_currencies: portfolio.unpriced_currencies(),
+            point: EquityPoint {
+                taken_at: portfolio.timestamp,
+                balance_jpy: portfolio.balance_jpy(),
+                collateral_jpy: portfolio.collateral_jpy(),
+                total_jpy: portfolio.total_jpy(),
+            },
+        }
+    }
+
+    // Decimals are encoded as strings so nothing is lost to floating point.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "taken_at": timestamp(&self.taken_at),
+            "balance_jpy": decimal(self.point.balance_jpy),
+            "collateral_jpy": decimal(self.point.collateral_jpy),
+            "total_jpy": decimal(self.point.total_jpy),
+            "balances": self.balances.iter().map(|x| json!({
+                "currency_code": x.currency_code,
+                "amount": decimal(x.amount),
+                "available": decimal(x.available),
+            })).collect::<Vec<_>>(),
+            "collateral": {
+                "collateral": decimal(self.collateral.collateral),
+                "open_position_pnl": decimal(self.collateral.open_position_pnl),
+                "require_collateral": decimal(self.collateral.require_collateral),
+                "keep_rate": self.collateral.keep_rate,
+            },
+            "positions": self.positions.iter().map(|x| json!({
+                "product_code": label(&x.product_code),
+                "side": label(&x.side),
+                "price": decimal(x.price),
+                "size": decimal(x.size),
+                "pnl": decimal(x.pnl),
+            })).collect::<Vec<_>>(),
+            "jpy_prices": self.jpy_prices.iter()
+                .map(|(k, v)| (k.clone(), Value::String(decimal(*v))))
+                .collect::<serde_json::Map<_, _>>(),
+            "unpriced_currencies": self.unpriced_currencies,
+        })
+    }
+}
+
+pub trait SnapshotSink: Send + Sync {
+    fn append(&self, snapshot: &EquitySnapshot) -> Result<()>;
+}
+
+// Appends one JSON object per line, reopening the file each time so it can be rotated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonLinesSink {
+    path: PathBuf,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+impl SnapshotSink for JsonLinesSink {
+    fn append(&self, snapshot: &EquitySnapshot) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", snapshot.to_json())?;
+        Ok(())
+    }
+}
+
+// Takes a portfolio snapshot every day at `time` (JST) and appends it to the sink.
+#[derive(Debug)]
+pub struct EquityRecorder<S, A = Client> {
+    client: Arc<A>,
+    sink: S,
+    product_codes: Vec<ProductCode>,
+    time: NaiveTime,
+    max_attempts: usize,
+    retry_interval: Duration,
+    last: Mutex<Option<EquityPoint>>,
+}
+
+impl<S: SnapshotSink + 'static, A: BitflyerApi + 'static> EquityRecorder<S, A> {
+    // `product_codes` are the products whose open orders are included.
+    pub fn new(client: Arc<A>, sink: S, product_codes: Vec<ProductCode>) -> Self {
+        Self {
+            client,
+            sink,
+            product_codes,
+            time: NaiveTime::MIN,
+            max_attempts: 3,
+            retry_interval: Duration::from_secs(30),
+            last: Mutex::new(None),
+        }
+    }
+
+    // Time of day in JST.
+    pub fn with_time(mut self, time: NaiveTime) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn with_retry(mut self, max_attempts: usize, retry_interval: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn last(&self) -> Option<EquityPoint> {
+        *self.last.lock().unwrap()
+    }
+
+    pub fn next_run(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        next_time_of_day(self.time, after)
+    }
+
+    pub async fn take(&self) -> Result<EquitySnapshot> {
+        let portfolio = portfolio(self.client.as_ref(), &self.product_codes).await?;
+        let snapshot = EquitySnapshot::from_portfolio(&portfolio);
+        self.sink.append(&snapshot)?;
+        *self.last.lock().unwrap() = Some(snapshot.point);
+        Ok(snapshot)
+    }
+
+    pub async fn take_with_retry(&self) -> Result<EquitySnapshot> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.take().await {
+                Ok(snapshot) => return Ok(snapshot),
+                Err(e) if attempts >= self.max_attempts => return Err(e),
+                Err(e) => {
+                    tracing::warn!("equity snapshot attempt {attempts} failed: {e:?}");
+                    tokio::time::sleep(self.retry_interval).await;
+                }
+            }
+        }
+    }
+
+    // A snapshot being taken, retries included, completes before the task stops.
+    pub fn spawn(self: &Arc<Self>) -> ShutdownHandle {
+        let recorder = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            loop {
+                let next = recorder.next_run(Utc::now());
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                if !shutdown.sleep(wait).await {
+                    break;
+                }
+                if let Err(e) = recorder.take_with_retry().await {
+                    tracing::warn!("failed to take the equity snapshot due at {next}: {e:?}");
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,176 @@
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+
+// The `status` bitFlyer puts in the body of a rejected request. Codes not listed here are kept
+// as `Other` and classified by the HTTP status alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    // -2, the exchange is under maintenance.
+    Maintenance,
+    // -106, the price is outside the allowed range of the product.
+    PriceOutOfRange,
+    // -110, the size is below the minimum order size.
+    SizeTooSmall,
+    // -130, no such order, e.g. cancelling one that already finished.
+    OrderNotFound,
+    // -200, not enough balance for a spot order.
+    InsufficientFunds,
+    // -205, not enough collateral for a margin order.
+    InsufficientMargin,
+    // -208, the order was not accepted because the exchange is busy.
+    OrderNotAccepted,
+    // -500, unknown API key or bad signature.
+    Unauthorized,
+    Other(i64),
+}
+
+impl ErrorCode {
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            -2 => ErrorCode::Maintenance,
+            -106 => ErrorCode::PriceOutOfRange,
+            -110 => ErrorCode::SizeTooSmall,
+            -130 => ErrorCode::OrderNotFound,
+            -200 => ErrorCode::InsufficientFunds,
+            -205 => ErrorCode::InsufficientMargin,
+            -208 => ErrorCode::OrderNotAccepted,
+            -500 => ErrorCode::Unauthorized,
+            code => ErrorCode::Other(code),
+        }
+    }
+
+    pub fn code(&self) -> i64 {
+        match self {
+            ErrorCode::Maintenance => -2,
+            ErrorCode::PriceOutOfRange => -106,
+            ErrorCode::SizeTooSmall => -110,
+            ErrorCode::OrderNotFound => -130,
+            ErrorCode::InsufficientFunds => -200,
+            ErrorCode::InsufficientMargin => -205,
+            ErrorCode::OrderNotAccepted => -208,
+            ErrorCode::Unauthorized => -500,
+            ErrorCode::Other(code) => *code,
+        }
+    }
+
+    // The same request may succeed later without changes.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorCode::Maintenance | ErrorCode::OrderNotAccepted)
+    }
+
+    // The request or the account needs fixing; retrying as is will fail again.
+    pub fn is_user_error(&self) -> bool {
+        !self.is_retryable() && !matches!(self, ErrorCode::Other(_))
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    status: i64,
+    error_message: Option<String>,
+}
+
+// A response with an error status. The client's errors carry it, so callers can tell failures
+// apart with `downcast_ref::<ApiError>()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    // From the body, when it is bitFlyer's error JSON.
+    pub code: Option<ErrorCode>,
+    pub message: Option<String>,
+    pub url: Url,
+    pub request_body: Option<String>,
+    pub body: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, url: Url, request_body: Option<String>, body: String) -> Self {
+        let parsed = serde_json::from_str::<ErrorBody>(&body).ok();
+        Self {
+            status,
+            code: parsed.as_ref().map(|x| ErrorCode::from_code(x.status)),
+            message: parsed.and_then(|x| x.error_message),
+            url,
+            request_body,
+            body,
+        }
+    }
+
+    // Too many requests, a server error, or a retryable code.
+    pub fn is_retryable(&self) -> bool {
+        match self.code {
+            Some(ErrorCode::Other(_)) | None => {
+                self.status == StatusCode::TOO_MANY_REQUESTS || self.status.is_server_error()
+            }
+            Some(code) => code.is_retryable(),
+        }
+    }
+
+    // A known user error, or a client error other than too many requests.
+    pub fn is_user_error(&self) -> bool {
+        match self.code {
+            Some(ErrorCode::Other(_)) | None => {
+                self.status.is_client_error() && self.status != StatusCode::TOO_MANY_REQUESTS
+            }
+            Some(code) => code.is_user_error(),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "request is failed: status -> {}\nrequest -> {:?}\nrequest.body -> {:?}\nresponse -> {:?}",
+            self.status, self.url, self.request_body, self.body
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// Whether an error from a request is worth retrying: an `ApiError` that says so, a timeout or
+// a failed connection, or an injected `Fault` other than a client error.
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<ApiError>() {
+        return error.is_retryable();
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return error.is_timeout() || error.is_connect();
+    }
+    #[cfg(feature = "test-util")]
+    if let Some(fault) = error.downcast_ref::<crate::mock::Fault>() {
+        return fault
+            .status()
+            .is_none_or(|x| x == StatusCode::TOO_MANY_REQUESTS || x.is_server_error());
+    }
+    false
+}
+
+// Whether an order that failed was certainly not accepted, so sending it again can't place it
+// twice: rate limited, turned away with a retryable code, or never connected. Anything else,
+// e.g. a timeout or a server error, may have been accepted.
+pub fn is_not_accepted(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<ApiError>() {
+        return error.status == StatusCode::TOO_MANY_REQUESTS
+            || error.code.is_some_and(|x| x.is_retryable());
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return error.is_connect();
+    }
+    #[cfg(feature = "test-util")]
+    if let Some(fault) = error.downcast_ref::<crate::mock::Fault>() {
+        return matches!(
+            fault,
+            crate::mock::Fault::RateLimited | crate::mock::Fault::Maintenance
+        );
+    }
+    false
+}
+
+// Whether a request was refused for something retrying won't change.
+pub fn is_user_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ApiError>()
+        .is_some_and(ApiError::is_user_error)
+}
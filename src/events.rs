@@ -0,0 +1,459 @@
+use crate::api::{
+    BitflyerApi, GetBalance, GetBoardState, GetChildOrders, GetExecutions, GetPrivateExecutions,
+};
+use crate::balance_watcher::{balance_changes, balance_map, BalanceChanged, Balances};
+use crate::candle::{Candle, CandleBuilder};
+use crate::entity::{
+    Board, BoardState, ChildOrder, Execution, OrderState, PrivateExecution, ProductCode, Ticker,
+};
+use crate::pager::Pager;
+use crate::poller::{board_poller, ticker_poller};
+use crate::shutdown::{take_until_cancelled, CancellationToken};
+use anyhow::Result;
+use chrono::Utc;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+// One event model for every backend, so strategies do not care whether the data was polled
+// or pushed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    TickerUpdate(Ticker),
+    Trade {
+        product_code: ProductCode,
+        execution: Execution,
+    },
+    CandleClose {
+        product_code: ProductCode,
+        candle: Candle,
+    },
+    BookUpdate {
+        product_code: ProductCode,
+        board: Board,
+    },
+    MyFill {
+        product_code: ProductCode,
+        execution: PrivateExecution,
+    },
+    // `previous` is None for an order first seen after the baseline.
+    OrderStateChange {
+        previous: Option<OrderState>,
+        order: ChildOrder,
+    },
+    BalanceChange(BalanceChanged),
+    // `previous` is None for the first observation.
+    HealthChange {
+        product_code: ProductCode,
+        previous: Option<BoardState>,
+        current: BoardState,
+    },
+}
+
+impl Event {
+    // None for balances, which are per account.
+    pub fn product_code(&self) -> Option<&ProductCode> {
+        match self {
+            Event::TickerUpdate(ticker) => Some(&ticker.product_code),
+            Event::Trade { product_code, .. }
+            | Event::CandleClose { product_code, .. }
+            | Event::BookUpdate { product_code, .. }
+            | Event::MyFill { product_code, .. }
+            | Event::HealthChange { product_code, .. } => Some(product_code),
+            Event::OrderStateChange { order, .. } => Some(&order.product_code),
+            Event::BalanceChange(_) => None,
+        }
+    }
+}
+
+// Polls `fetch` every `period` and yields what `diff` makes of each result, given the state
+// kept between polls. Failures are logged and retried at the next tick.
+fn polling<'a, S, T, F, Fut, D>(
+    what: &'static str,
+    period: Duration,
+    state: S,
+    fetch: F,
+    diff: D,
+) -> impl Stream<Item = Event> + Send + 'a
+where
+    S: Send + 'a,
+    F: FnMut(&S) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<T>> + Send + 'a,
+    D: FnMut(&mut S, T) -> Vec<Event> + Send + 'a,
+{
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    futures::stream::unfold(
+        (interval, state, fetch, diff),
+        move |(mut interval, mut state, mut fetch, mut diff)| async move {
+            loop {
+                interval.tick().await;
+                match fetch(&state).await {
+                    Ok(response) => {
+                        let events = diff(&mut state, response);
+                        if !events.is_empty() {
+                            return Some((events, (interval, state, fetch, diff)));
+                        }
+                    }
+                    Err(e) => tracing::warn!("failed to poll {what}: {e:?}"),
+                }
+            }
+        },
+    )
+    .flat_map(futures::stream::iter)
+}
+
+pub fn ticker_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    ticker_poller(api, product_code, period).map(Event::TickerUpdate)
+}
+
+pub fn book_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+    depth: Option<usize>,
+) -> impl Stream<Item = Event> + Send + '_ {
+    board_poller(api, product_code.clone(), period, depth).map(move |board| Event::BookUpdate {
+        product_code: product_code.clone(),
+        board,
+    })
+}
+
+// Public trades after the first poll, oldest first. More than 500 trades between two polls
+// lose the oldest ones.
+pub fn trade_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    let request_product_code = product_code.clone();
+    polling(
+        "executions",
+        period,
+        None::<u64>,
+        move |last_id| {
+            api.send(GetExecutions {
+                product_code: Some(request_product_code.clone()),
+                count: Some(500),
+                after: *last_id,
+                ..Default::default()
+            })
+        },
+        move |last_id, mut executions| {
+            executions.sort_by_key(|x| x.id);
+            let is_baseline = last_id.is_none();
+            *last_id = executions.last().map(|x| x.id).or(*last_id).or(Some(0));
+            if is_baseline {
+                return vec![];
+            }
+            executions
+                .into_iter()
+                .map(|execution| Event::Trade {
+                    product_code: product_code.clone(),
+                    execution,
+                })
+                .collect()
+        },
+    )
+}
+
+// Candles closed by the trades of `events`, after the trade that closed them. Other events
+// pass through, so this works on a whole bus as well as on `trade_events`.
+pub fn with_candle_closes<'a>(
+    events: impl Stream<Item = Event> + Send + 'a,
+    interval: Duration,
+) -> impl Stream<Item = Event> + Send + 'a {
+    let mut builders: HashMap<ProductCode, CandleBuilder> = HashMap::new();
+    events.flat_map(move |event| {
+        let mut events = vec![];
+        if let Event::Trade {
+            product_code,
+            execution,
+        } = &event
+        {
+            let builder = builders
+                .entry(product_code.clone())
+                .or_insert_with(|| CandleBuilder::new(interval));
+            events.extend(builder.on_execution(execution).into_iter().map(|candle| {
+                Event::CandleClose {
+                    product_code: product_code.clone(),
+                    candle,
+                }
+            }));
+        }
+        events.insert(0, event);
+        futures::stream::iter(events)
+    })
+}
+
+// Fills after the first poll, oldest first. A poll pages back to the last fill it saw, so a
+// burst between two polls loses none of them.
+pub fn fill_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    let request_product_code = product_code.clone();
+    polling(
+        "fills",
+        period,
+        None::<u64>,
+        move |last_id| {
+            let last_id = *last_id;
+            let request = GetPrivateExecutions {
+                product_code: Some(request_product_code.clone()),
+                after: last_id,
+                ..Default::default()
+            };
+            async move {
+                match last_id {
+                    // The baseline only needs the latest id.
+                    None => {
+                        api.send(GetPrivateExecutions {
+                            count: Some(1),
+                            ..request
+                        })
+                        .await
+                    }
+                    Some(_) => Pager::new(api, request).pages().try_concat().await,
+                }
+            }
+        },
+        move |last_id, executions| new_fills(last_id, &product_code, executions),
+    )
+}
+
+// Fills newer than `last_id`, which is None before the baseline.
+pub(crate) fn new_fills(
+    last_id: &mut Option<u64>,
+    product_code: &ProductCode,
+    mut executions: Vec<PrivateExecution>,
+) -> Vec<Event> {
+    executions.sort_by_key(|x| x.id);
+    let is_baseline = last_id.is_none();
+    *last_id = executions.last().map(|x| x.id).or(*last_id).or(Some(0));
+    if is_baseline {
+        return vec![];
+    }
+    executions
+        .into_iter()
+        .map(|execution| Event::MyFill {
+            product_code: product_code.clone(),
+            execution,
+        })
+        .collect()
+}
+
+// State changes of the latest 100 child orders. The first poll is the baseline and emits
+// nothing.
+pub fn order_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    polling(
+        "child orders",
+        period,
+        None::<HashMap<String, OrderState>>,
+        move |_| {
+            api.send(GetChildOrders {
+                product_code: Some(product_code.clone()),
+                count: Some(100),
+                ..Default::default()
+            })
+        },
+        order_changes,
+    )
+}
+
+// Orders whose state differs from `states`, which is None before the baseline.
+pub(crate) fn order_changes(
+    states: &mut Option<HashMap<String, OrderState>>,
+    mut orders: Vec<ChildOrder>,
+) -> Vec<Event> {
+    orders.sort_by_key(|x| x.id);
+    let is_baseline = states.is_none();
+    let states = states.get_or_insert_with(HashMap::new);
+    let mut events = vec![];
+    for order in orders {
+        let previous = states.insert(
+            order.child_order_acceptance_id.clone(),
+            order.child_order_state.clone(),
+        );
+        if !is_baseline && previous.as_ref() != Some(&order.child_order_state) {
+            events.push(Event::OrderStateChange { previous, order });
+        }
+    }
+    events
+}
+
+// The first poll is the baseline and emits nothing, like `BalanceWatcher`.
+pub fn balance_events<A: BitflyerApi>(
+    api: &A,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    polling(
+        "balances",
+        period,
+        None::<Balances>,
+        move |_| api.send(GetBalance),
+        |previous, balances| {
+            let current = balance_map(&balances);
+            match previous.replace(current.clone()) {
+                Some(previous) => balance_changes(&previous, &current, Utc::now())
+                    .into_iter()
+                    .map(Event::BalanceChange)
+                    .collect(),
+                None => vec![],
+            }
+        },
+    )
+}
+
+// Emits when the health or the state of the board changes, and for the first poll.
+pub fn health_events<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Event> + Send + '_ {
+    let request_product_code = product_code.clone();
+    polling(
+        "the board state",
+        period,
+        None::<BoardState>,
+        move |_| {
+            api.send(GetBoardState {
+                product_code: Some(request_product_code.clone()),
+            })
+        },
+        move |previous, current| {
+            let changed = previous
+                .as_ref()
+                .is_none_or(|x| x.health() != current.health() || x.state() != current.state());
+            if !changed {
+                return vec![];
+            }
+            vec![Event::HealthChange {
+                product_code: product_code.clone(),
+                previous: previous.replace(current.clone()),
+                current,
+            }]
+        },
+    )
+}
+
+// Merges event sources into one stream, in the order events arrive. The polling sources are
+// built in; any other backend, e.g. a realtime feed, joins through `with_source`.
+#[derive(Default)]
+pub struct EventBus<'a> {
+    sources: Vec<BoxStream<'a, Event>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl std::fmt::Debug for EventBus<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("sources", &self.sources.len())
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl<'a> EventBus<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source(mut self, source: impl Stream<Item = Event> + Send + 'a) -> Self {
+        self.sources.push(source.boxed());
+        self
+    }
+
+    pub fn with_ticker_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+    ) -> Self {
+        self.with_source(ticker_events(api, product_code, period))
+    }
+
+    pub fn with_book_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+        depth: Option<usize>,
+    ) -> Self {
+        self.with_source(book_events(api, product_code, period, depth))
+    }
+
+    pub fn with_trade_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+    ) -> Self {
+        self.with_source(trade_events(api, product_code, period))
+    }
+
+    // Trades and the candles they close.
+    pub fn with_candle_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+        interval: Duration,
+    ) -> Self {
+        self.with_source(with_candle_closes(
+            trade_events(api, product_code, period),
+            interval,
+        ))
+    }
+
+    // Fills and order state changes.
+    pub fn with_order_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+    ) -> Self {
+        self.with_source(fill_events(api, product_code.clone(), period))
+            .with_source(order_events(api, product_code, period))
+    }
+
+    pub fn with_balance_polling<A: BitflyerApi>(self, api: &'a A, period: Duration) -> Self {
+        self.with_source(balance_events(api, period))
+    }
+
+    pub fn with_health_polling<A: BitflyerApi>(
+        self,
+        api: &'a A,
+        product_code: ProductCode,
+        period: Duration,
+    ) -> Self {
+        self.with_source(health_events(api, product_code, period))
+    }
+
+    // Ends the stream once `token` is cancelled, which also stops a `Runner` on it.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    // Ends when every source has ended, or on cancellation; the polling sources never end.
+    pub fn into_stream(self) -> impl Stream<Item = Event> + Send + 'a {
+        let events = futures::stream::select_all(self.sources);
+        match self.cancellation {
+            Some(token) => take_until_cancelled(events, token).left_stream(),
+            None => events.right_stream(),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::api::{
+    BitflyerApi, CancelAllChildOrders, CancelChildOrder, Client, GetBalance, GetExecutions,
+    GetTicker, SendChildOrder,
+};
+use crate::entity::{
+    ChildOrder, ChildOrderType, Execution, ExecutionSide, OrderState, ProductCode, Side,
+    TimeInForce,
+};
+use crate::orders::submit_child_order;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::future::Future;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstrumentKind {
+    Spot,
+    Perpetual,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Instrument {
+    pub base: String,
+    pub quote: String,
+    pub kind: InstrumentKind,
+}
+
+impl Instrument {
+    pub fn spot(base: impl Into<String>, quote: impl Into<String>) -> Self {
+        Self {
+            base: base.into().to_uppercase(),
+            quote: quote.into().to_uppercase(),
+            kind: InstrumentKind::Spot,
+        }
+    }
+
+    pub fn perpetual(base: impl Into<String>, quote: impl Into<String>) -> Self {
+        Self {
+            kind: InstrumentKind::Perpetual,
+            ..Self::spot(base, quote)
+        }
+    }
+
+    // FX_BTC_JPY is the only perpetual.
+    pub fn from_product_code(product_code: &ProductCode) -> Option<Self> {
+        let (base, quote) = (
+            product_code.base_currency()?,
+            product_code.quote_currency()?,
+        );
+        Some(match product_code.is_fx() {
+            true => Self::perpetual(base, quote),
+            false => Self::spot(base, quote),
+        })
+    }
+
+    pub fn product_code(&self) -> Result<ProductCode> {
+        let product_code = match self.kind {
+            InstrumentKind::Spot => ProductCode::spot(&self.base, &self.quote),
+            InstrumentKind::Perpetual => {
+                Some(ProductCode::FxBtcJpy).filter(|_| self.base == "BTC" && self.quote == "JPY")
+            }
+        };
+        product_code.ok_or_else(|| anyhow!("bitFlyer does not list {self}"))
+    }
+}
+
+impl std::fmt::Display for Instrument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)?;
+        if self.kind == InstrumentKind::Perpetual {
+            write!(f, " perpetual")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderKind {
+    Market,
+    Limit { price: Decimal },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderRequest {
+    pub instrument: Instrument,
+    pub side: Side,
+    pub kind: OrderKind,
+    pub size: Decimal,
+    pub time_in_force: Option<TimeInForce>,
+}
+
+impl OrderRequest {
+    pub fn market(instrument: Instrument, side: Side, size: Decimal) -> Self {
+        Self {
+            instrument,
+            side,
+            kind: OrderKind::Market,
+            size,
+            time_in_force: None,
+        }
+    }
+
+    pub fn limit(instrument: Instrument, side: Side, size: Decimal, price: Decimal) -> Self {
+        Self {
+            kind: OrderKind::Limit { price },
+            ..Self::market(instrument, side, size)
+        }
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Expired,
+    Rejected,
+}
+
+impl OrderStatus {
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::Open | OrderStatus::PartiallyFilled)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Order {
+    pub id: String,
+    pub instrument: Instrument,
+    pub side: Side,
+    pub kind: OrderKind,
+    pub size: Decimal,
+    pub filled: Decimal,
+    pub average_price: Decimal,
+    pub fee: Decimal,
+    pub status: OrderStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Order {
+    fn from_child_order(order: ChildOrder) -> Result<Self> {
+        let instrument = Instrument::from_product_code(&order.product_code)
+            .ok_or_else(|| anyhow!("unknown product code of order {}", order.child_order_id))?;
+        let status = match order.child_order_state {
+            OrderState::Active if order.executed_size > Decimal::ZERO => {
+                OrderStatus::PartiallyFilled
+            }
+            OrderState::Active => OrderStatus::Open,
+            OrderState::Completed => OrderStatus::Filled,
+            OrderState::Canceled => OrderStatus::Canceled,
+            OrderState::Expired => OrderStatus::Expired,
+            OrderState::Rejected => OrderStatus::Rejected,
+            state => return Err(anyhow!("unknown order state: {state:?}")),
+        };
+        Ok(Self {
+            id: order.child_order_acceptance_id,
+            instrument,
+            side: order.side,
+            kind: match order.child_order_type {
+                ChildOrderType::Limit { price } => OrderKind::Limit { price },
+                ChildOrderType::Market => OrderKind::Market,
+            },
+            size: order.size,
+            filled: order.executed_size,
+            average_price: order.average_price,
+            fee: order.total_commission,
+            status,
+            created_at: order.child_order_date,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetBalance {
+    pub asset: String,
+    pub total: Decimal,
+    pub free: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quote {
+    pub instrument: Instrument,
+    pub bid: Decimal,
+    pub bid_size: Decimal,
+    pub ask: Decimal,
+    pub ask_size: Decimal,
+    pub last: Decimal,
+    pub volume: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+// `side` is the taker side, None for trades of an itayose auction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trade {
+    pub id: String,
+    pub instrument: Instrument,
+    pub side: Option<Side>,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Trade {
+    fn from_execution(instrument: &Instrument, execution: Execution) -> Self {
+        Self {
+            id: execution.id.to_string(),
+            instrument: instrument.clone(),
+            side: match execution.side {
+                ExecutionSide::Buy => Some(Side::Buy),
+                ExecutionSide::Sell => Some(Side::Sell),
+                ExecutionSide::Empty => None,
+            },
+            price: execution.price,
+            size: execution.size,
+            timestamp: execution.exec_date,
+        }
+    }
+}
+
+// Exchange-neutral operations, so code written against several venues can take bitFlyer
+// without knowing its request types. Order ids are whatever the venue returns on placement.
+pub trait Exchange: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn place_order(&self, order: OrderRequest) -> impl Future<Output = Result<String>> + Send;
+
+    fn cancel_order(
+        &self,
+        instrument: &Instrument,
+        id: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn cancel_all_orders(&self, instrument: &Instrument)
+        -> impl Future<Output = Result<()>> + Send;
+
+    fn order(
+        &self,
+        instrument: &Instrument,
+        id: &str,
+    ) -> impl Future<Output = Result<Option<Order>>> + Send;
+
+    fn open_orders(
+        &self,
+        instrument: &Instrument,
+    ) -> impl Future<Output = Result<Vec<Order>>> + Send;
+
+    fn balances(&self) -> impl Future<Output = Result<Vec<AssetBalance>>> + Send;
+
+    fn ticker(&self, instrument: &Instrument) -> impl Future<Output = Result<Quote>> + Send;
+
+    // The most recent trades, newest first.
+    fn trades(
+        &self,
+        instrument: &Instrument,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<Trade>>> + Send;
+}
+
+// Spot balances come from `GetBalance`; Lightning FX margin is not a balance and is left to
+// `GetCollateral`.
+#[derive(Clone, Debug)]
+pub struct BitflyerExchange<A = Client> {
+    api: A,
+}
+
+impl<A: BitflyerApi> BitflyerExchange<A> {
+    pub fn new(api: A) -> Self {
+        Self { api }
+    }
+
+    pub fn api(&self) -> &A {
+        &self.api
+    }
+
+    pub fn into_inner(self) -> A {
+        self.api
+    }
+}
+
+impl<A: BitflyerApi> Exchange for BitflyerExchange<A> {
+    fn name(&self) -> &str {
+        "bitflyer"
+    }
+
+    async fn place_order(&self, order: OrderRequest) -> Result<String> {
+        let product_code = order.instrument.product_code()?;
+        let mut request = match order.kind {
+            OrderKind::Market => SendChildOrder::market(product_code, order.side, order.size),
+            OrderKind::Limit { price } => {
+                SendChildOrder::limit(product_code, order.side, order.size, price)
+            }
+        };
+        request.time_in_force = order.time_in_force;
+        Ok(submit_child_order(&self.api, request).await?.to_string())
+    }
+
+    async fn cancel_order(&self, instrument: &Instrument, id: &str) -> Result<()> {
+        self.api
+            .send(CancelChildOrder {
+                product_code: instrument.product_code()?,
+                child_order_acceptance_id: id.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self, instrument: &Instrument) -> Result<()> {
+        self.api
+            .send(CancelAllChildOrders {
+                product_code: instrument.product_code()?,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn order(&self, instrument: &Instrument, id: &str) -> Result<Option<Order>> {
+        let product_code = instrument.product_code()?;
+        self.api
+            .child_order(&product_code, id)
+            .await?
+            .map(Order::from_child_order)
+            .transpose()
+    }
+
+    async fn open_orders(&self, instrument: &Instrument) -> Result<Vec<Order>> {
+        let product_code = instrument.product_code()?;
+        self.api
+            .open_orders(&product_code)
+            .await?
+            .into_iter()
+            .map(Order::from_child_order)
+            .collect()
+    }
+
+    async fn balances(&self) -> Result<Vec<AssetBalance>> {
+        let balances = self.api.send(GetBalance).await?;
+        Ok(balances
+            .into_iter()
+            .map(|x| AssetBalance {
+                asset: x.currency_code,
+                total: x.amount,
+                free: x.available,
+            })
+            .collect())
+    }
+
+    async fn ticker(&self, instrument: &Instrument) -> Result<Quote> {
+        let ticker = self
+            .api
+            .send(GetTicker {
+                product_code: Some(instrument.product_code()?),
+            })
+            .await?;
+        Ok(Quote {
+            instrument: instrument.clone(),
+            bid: ticker.best_bid,
+            bid_size: ticker.best_bid_size,
+            ask: ticker.best_ask,
+            ask_size: ticker.best_ask_size,
+            last: ticker.ltp,
+            volume: ticker.volume_by_product,
+            timestamp: ticker.timestamp,
+        })
+    }
+
+    async fn trades(&self, instrument: &Instrument, limit: usize) -> Result<Vec<Trade>> {
+        let executions = self
+            .api
+            .send(GetExecutions {
+                product_code: Some(instrument.product_code()?),
+                count: Some(limit as u64),
+                ..Default::default()
+            })
+            .await?;
+        Ok(executions
+            .into_iter()
+            .take(limit)
+            .map(|x| Trade::from_execution(instrument, x))
+            .collect())
+    }
+}
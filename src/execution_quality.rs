@@ -0,0 +1,255 @@
+use crate::board::OrderBook;
+use crate::entity::{ChildOrder, ProductCode, Side};
+use crate::order_manager::OrderEvent;
+use crate::shutdown::ShutdownHandle;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrivalPrice {
+    pub observed_at: DateTime<Utc>,
+    pub mid: Decimal,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderQuality {
+    pub acceptance_id: String,
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub decided_at: DateTime<Utc>,
+    pub arrival: Option<ArrivalPrice>,
+    pub size: Decimal,
+    pub executed_size: Decimal,
+    pub average_price: Option<Decimal>,
+    // Valued at the average price, in the quote currency.
+    pub commission: Decimal,
+}
+
+impl OrderQuality {
+    // Per-unit cost against the arrival mid; positive when the fill was worse.
+    pub fn slippage(&self) -> Option<Decimal> {
+        let arrival = self.arrival.as_ref()?.mid;
+        let average_price = self.average_price?;
+        Some(match self.side {
+            Side::Buy => average_price - arrival,
+            Side::Sell => arrival - average_price,
+        })
+    }
+
+    pub fn slippage_bps(&self) -> Option<Decimal> {
+        let arrival = self.arrival.as_ref()?.mid;
+        (!arrival.is_zero()).then_some(self.slippage()? / arrival * dec!(10000))
+    }
+
+    // Slippage over the executed size plus commissions.
+    pub fn shortfall(&self) -> Option<Decimal> {
+        Some(self.slippage()? * self.executed_size + self.commission)
+    }
+
+    pub fn fill_ratio(&self) -> Option<Decimal> {
+        (!self.size.is_zero()).then(|| self.executed_size / self.size)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QualitySummary {
+    pub order_count: usize,
+    // Orders with both an arrival price and fills.
+    pub measured_count: usize,
+    pub executed_size: Decimal,
+    pub notional: Decimal,
+    pub shortfall: Decimal,
+    pub commission: Decimal,
+    // Weighted by executed notional.
+    pub average_slippage_bps: Option<Decimal>,
+    pub worst_slippage_bps: Option<Decimal>,
+    pub best_slippage_bps: Option<Decimal>,
+}
+
+impl QualitySummary {
+    pub fn from_orders<'a>(orders: impl IntoIterator<Item = &'a OrderQuality>) -> Self {
+        let mut summary = QualitySummary::default();
+        let mut weighted_bps = Decimal::ZERO;
+        for order in orders {
+            summary.order_count += 1;
+            let (Some(bps), Some(shortfall), Some(average_price)) =
+                (order.slippage_bps(), order.shortfall(), order.average_price)
+            else {
+                continue;
+            };
+            let notional = average_price * order.executed_size;
+            summary.measured_count += 1;
+            summary.executed_size += order.executed_size;
+            summary.notional += notional;
+            summary.shortfall += shortfall;
+            summary.commission += order.commission;
+            weighted_bps += bps * notional;
+            summary.worst_slippage_bps =
+                Some(summary.worst_slippage_bps.map_or(bps, |x| x.max(bps)));
+            summary.best_slippage_bps = Some(summary.best_slippage_bps.map_or(bps, |x| x.min(bps)));
+        }
+        if !summary.notional.is_zero() {
+            summary.average_slippage_bps = Some(weighted_bps / summary.notional);
+        }
+        summary
+    }
+}
+
+// Compares fills against the mid price seen when each order was decided.
+// Book snapshots come from `on_book`, order lifecycle from the OrderManager events.
+#[derive(Debug, Default)]
+pub struct ExecutionQuality {
+    mids: Mutex<HashMap<ProductCode, ArrivalPrice>>,
+    orders: Mutex<HashMap<String, OrderQuality>>,
+}
+
+impl ExecutionQuality {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_book(&self, product_code: &ProductCode, book: &impl OrderBook) {
+        let Some(mid) = book.mid() else {
+            return;
+        };
+        self.mids.lock().unwrap().insert(
+            product_code.clone(),
+            ArrivalPrice {
+                observed_at: Utc::now(),
+                mid,
+                best_bid: book.bid_levels().first().map(|x| x.price),
+                best_ask: book.ask_levels().first().map(|x| x.price),
+            },
+        );
+    }
+
+    pub fn arrival_price(&self, product_code: &ProductCode) -> Option<ArrivalPrice> {
+        self.mids.lock().unwrap().get(product_code).cloned()
+    }
+
+    // Records the decision with the latest book mid unless an explicit arrival price is given.
+    pub fn record_decision(
+        &self,
+        acceptance_id: &str,
+        product_code: &ProductCode,
+        side: Side,
+        size: Decimal,
+        arrival: Option<ArrivalPrice>,
+    ) {
+        let arrival = arrival.or_else(|| self.arrival_price(product_code));
+        self.orders
+            .lock()
+            .unwrap()
+            .entry(acceptance_id.to_string())
+            .or_insert_with(|| OrderQuality {
+                acceptance_id: acceptance_id.to_string(),
+                product_code: product_code.clone(),
+                side,
+                decided_at: Utc::now(),
+                arrival,
+                size,
+                executed_size: Decimal::ZERO,
+                average_price: None,
+                commission: Decimal::ZERO,
+            });
+    }
+
+    pub fn on_order(&self, order: &ChildOrder) {
+        let mut orders = self.orders.lock().unwrap();
+        let Some(quality) = orders.get_mut(&order.child_order_acceptance_id) else {
+            return;
+        };
+        quality.executed_size = order.executed_size;
+        if !order.executed_size.is_zero() {
+            quality.average_price = Some(order.average_price);
+            quality.commission = order.total_commission * order.average_price;
+        }
+    }
+
+    pub fn on_event(&self, event: &OrderEvent) {
+        match event {
+            OrderEvent::Submitted {
+                acceptance_id,
+                request,
+            } => self.record_decision(
+                acceptance_id,
+                &request.product_code,
+                request.side,
+                request.size,
+                None,
+            ),
+            OrderEvent::StatusChanged { order, .. } => self.on_order(order),
+            OrderEvent::CancelRequested { .. } => {}
+        }
+    }
+
+    // Pass `OrderManager::subscribe()`; the task ends when the manager is dropped. On shutdown
+    // the events already sent are still taken in.
+    pub fn spawn_listener(
+        self: &Arc<Self>,
+        mut events: broadcast::Receiver<OrderEvent>,
+    ) -> ShutdownHandle {
+        let quality = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            loop {
+                let event = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => break,
+                    event = events.recv() => event,
+                };
+                match event {
+                    Ok(event) => quality.on_event(&event),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("execution quality missed {n} order events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+            loop {
+                match events.try_recv() {
+                    Ok(event) => quality.on_event(&event),
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                    Err(_) => return,
+                }
+            }
+        })
+    }
+
+    pub fn order(&self, acceptance_id: &str) -> Option<OrderQuality> {
+        self.orders.lock().unwrap().get(acceptance_id).cloned()
+    }
+
+    pub fn orders(&self) -> Vec<OrderQuality> {
+        let mut orders: Vec<OrderQuality> = self.orders.lock().unwrap().values().cloned().collect();
+        orders.sort_by_key(|x| x.decided_at);
+        orders
+    }
+
+    pub fn summary(&self) -> QualitySummary {
+        QualitySummary::from_orders(&self.orders())
+    }
+
+    pub fn summary_by_product(&self) -> HashMap<ProductCode, QualitySummary> {
+        let mut products: HashMap<ProductCode, Vec<OrderQuality>> = HashMap::new();
+        for order in self.orders() {
+            products
+                .entry(order.product_code.clone())
+                .or_default()
+                .push(order);
+        }
+        products
+            .into_iter()
+            .map(|(product_code, orders)| (product_code, QualitySummary::from_orders(&orders)))
+            .collect()
+    }
+
+    pub fn forget(&self, acceptance_id: &str) -> Option<OrderQuality> {
+        self.orders.lock().unwrap().remove(acceptance_id)
+    }
+}
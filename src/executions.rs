@@ -0,0 +1,64 @@
+use crate::api::{BitflyerApi, GetExecutions};
+use crate::entity::{Execution, ProductCode};
+use crate::pager::Pager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+// Id bounds are exclusive, time bounds are inclusive.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionRange {
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ExecutionRange {
+    pub fn ids(after: u64, before: u64) -> Self {
+        Self {
+            after: Some(after),
+            before: Some(before),
+            ..Default::default()
+        }
+    }
+
+    pub fn time(since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        Self {
+            since: Some(since),
+            until: Some(until),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_past(&self, execution: &Execution) -> bool {
+        self.after.is_some_and(|x| execution.id <= x)
+            || self.since.is_some_and(|x| execution.exec_date < x)
+    }
+
+    pub fn contains(&self, execution: &Execution) -> bool {
+        !self.is_past(execution)
+            && self.before.is_none_or(|x| execution.id < x)
+            && self.until.is_none_or(|x| execution.exec_date <= x)
+    }
+}
+
+// Yields executions newest first, paging backwards through `before` until the range is exhausted.
+pub fn executions_stream<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    range: ExecutionRange,
+) -> impl Stream<Item = Result<Execution>> + Send + '_ {
+    let request = GetExecutions {
+        product_code: Some(product_code),
+        before: range.before,
+        after: range.after,
+        ..Default::default()
+    };
+    let past = range.clone();
+    Pager::new(api, request)
+        .items()
+        .try_take_while(move |x| futures::future::ready(Ok(!past.is_past(x))))
+        .try_filter(move |x| futures::future::ready(range.contains(x)))
+}
@@ -0,0 +1,242 @@
+use crate::entity::{
+    Balance, BalanceHistory, ChildOrder, ChildOrderType, Execution, Position, PrivateExecution,
+};
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+// Columns are fixed per type so exports from different versions line up.
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn record(&self) -> Vec<String>;
+}
+
+pub fn write<'a, W, T>(writer: W, rows: impl IntoIterator<Item = &'a T>) -> Result<()>
+where
+    W: Write,
+    T: CsvRecord + 'a,
+{
+    let mut writer = ::csv::Writer::from_writer(writer);
+    writer.write_record(T::HEADER)?;
+    for row in rows {
+        writer.write_record(row.record())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_file<'a, T>(
+    path: impl AsRef<Path>,
+    rows: impl IntoIterator<Item = &'a T>,
+) -> Result<()>
+where
+    T: CsvRecord + 'a,
+{
+    write(std::fs::File::create(path)?, rows)
+}
+
+pub fn to_string<'a, T>(rows: impl IntoIterator<Item = &'a T>) -> Result<String>
+where
+    T: CsvRecord + 'a,
+{
+    let mut buffer = vec![];
+    write(&mut buffer, rows)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+pub(crate) fn decimal(value: Decimal) -> String {
+    value.normalize().to_string()
+}
+
+pub(crate) fn timestamp(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// The API spelling of an enum, e.g. `BUY` or `FX_BTC_JPY`.
+pub(crate) fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(x)) => x,
+        Ok(x) => x.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+impl CsvRecord for Execution {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "exec_date",
+        "side",
+        "price",
+        "size",
+        "buy_child_order_acceptance_id",
+        "sell_child_order_acceptance_id",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            timestamp(&self.exec_date),
+            label(&self.side),
+            decimal(self.price),
+            decimal(self.size),
+            self.buy_child_order_acceptance_id.clone(),
+            self.sell_child_order_acceptance_id.clone(),
+        ]
+    }
+}
+
+impl CsvRecord for PrivateExecution {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "exec_date",
+        "child_order_id",
+        "child_order_acceptance_id",
+        "side",
+        "price",
+        "size",
+        "commission",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            timestamp(&self.exec_date),
+            self.child_order_id.clone(),
+            self.child_order_acceptance_id.clone(),
+            label(&self.side),
+            decimal(self.price),
+            decimal(self.size),
+            decimal(self.commission),
+        ]
+    }
+}
+
+impl CsvRecord for ChildOrder {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "child_order_id",
+        "child_order_acceptance_id",
+        "product_code",
+        "side",
+        "child_order_type",
+        "price",
+        "average_price",
+        "size",
+        "child_order_state",
+        "expire_date",
+        "child_order_date",
+        "outstanding_size",
+        "cancel_size",
+        "executed_size",
+        "total_commission",
+        "time_in_force",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        let (child_order_type, price) = match self.child_order_type {
+            ChildOrderType::Limit { price } => ("LIMIT", decimal(price)),
+            ChildOrderType::Market => ("MARKET", String::new()),
+        };
+        vec![
+            self.id.to_string(),
+            self.child_order_id.clone(),
+            self.child_order_acceptance_id.clone(),
+            label(&self.product_code),
+            label(&self.side),
+            child_order_type.to_string(),
+            price,
+            decimal(self.average_price),
+            decimal(self.size),
+            label(&self.child_order_state),
+            timestamp(&self.expire_date),
+            timestamp(&self.child_order_date),
+            decimal(self.outstanding_size),
+            decimal(self.cancel_size),
+            decimal(self.executed_size),
+            decimal(self.total_commission),
+            label(&self.time_in_force),
+        ]
+    }
+}
+
+impl CsvRecord for Balance {
+    const HEADER: &'static [&'static str] = &["currency_code", "amount", "available"];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.currency_code.clone(),
+            decimal(self.amount),
+            decimal(self.available),
+        ]
+    }
+}
+
+impl CsvRecord for Position {
+    const HEADER: &'static [&'static str] = &[
+        "product_code",
+        "side",
+        "price",
+        "size",
+        "commission",
+        "swap_point_accumulate",
+        "require_collateral",
+        "open_date",
+        "leverage",
+        "pnl",
+        "sfd",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            label(&self.product_code),
+            label(&self.side),
+            decimal(self.price),
+            decimal(self.size),
+            decimal(self.commission),
+            decimal(self.swap_point_accumulate),
+            decimal(self.require_collateral),
+            timestamp(&self.open_date),
+            decimal(self.leverage),
+            decimal(self.pnl),
+            decimal(self.sfd),
+        ]
+    }
+}
+
+impl CsvRecord for BalanceHistory {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "trade_date",
+        "event_date",
+        "product_code",
+        "currency_code",
+        "trade_type",
+        "price",
+        "amount",
+        "quantity",
+        "commission",
+        "balance",
+        "order_id",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            timestamp(&self.trade_date),
+            timestamp(&self.event_date),
+            label(&self.product_code),
+            self.currency_code.clone(),
+            label(&self.trade_type),
+            decimal(self.price),
+            decimal(self.amount),
+            decimal(self.quantity),
+            decimal(self.commission),
+            decimal(self.balance),
+            self.order_id.clone(),
+        ]
+    }
+}
@@ -0,0 +1,113 @@
+use crate::api::BitflyerApi;
+use crate::entity::{ProductCode, Side};
+use crate::kill_switch::net_positions;
+use crate::portfolio::{jpy_prices, portfolio, Portfolio};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CurrencyExposure {
+    pub currency_code: String,
+    pub balance: Decimal,
+    pub collateral: Decimal,
+    pub position: Decimal,
+    pub jpy_price: Option<Decimal>,
+}
+
+impl CurrencyExposure {
+    pub fn total(&self) -> Decimal {
+        self.balance + self.collateral + self.position
+    }
+
+    pub fn jpy_value(&self) -> Option<Decimal> {
+        self.jpy_price.map(|x| x * self.total())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExposureReport {
+    pub timestamp: DateTime<Utc>,
+    pub currencies: Vec<CurrencyExposure>,
+}
+
+impl ExposureReport {
+    pub fn from_portfolio(portfolio: &Portfolio) -> Self {
+        let mut currencies: BTreeMap<String, CurrencyExposure> = BTreeMap::new();
+        for balance in &portfolio.balances {
+            entry(&mut currencies, portfolio, &balance.currency_code).balance += balance.amount;
+        }
+        for account in &portfolio.collateral_accounts {
+            entry(&mut currencies, portfolio, &account.currency_code).collateral += account.amount;
+        }
+        // A margin position is long the base currency and short its entry notional in the quote currency.
+        for position in &portfolio.positions {
+            let (Some(base), Some(quote)) = (
+                position.product_code.base_currency(),
+                position.product_code.quote_currency(),
+            ) else {
+                continue;
+            };
+            let size = match position.side {
+                Side::Buy => position.size,
+                Side::Sell => -position.size,
+            };
+            entry(&mut currencies, portfolio, base).position += size;
+            entry(&mut currencies, portfolio, quote).position -= size * position.price;
+        }
+        Self {
+            timestamp: portfolio.timestamp,
+            currencies: currencies.into_values().collect(),
+        }
+    }
+
+    pub fn currency(&self, currency_code: &str) -> Option<&CurrencyExposure> {
+        self.currencies
+            .iter()
+            .find(|x| x.currency_code == currency_code)
+    }
+
+    pub fn total_jpy(&self) -> Decimal {
+        self.currencies.iter().filter_map(|x| x.jpy_value()).sum()
+    }
+
+    pub fn unpriced_currencies(&self) -> Vec<String> {
+        self.currencies
+            .iter()
+            .filter(|x| !x.total().is_zero() && x.jpy_price.is_none())
+            .map(|x| x.currency_code.clone())
+            .collect()
+    }
+}
+
+fn entry<'a>(
+    currencies: &'a mut BTreeMap<String, CurrencyExposure>,
+    portfolio: &Portfolio,
+    currency_code: &str,
+) -> &'a mut CurrencyExposure {
+    currencies
+        .entry(currency_code.to_string())
+        .or_insert_with(|| CurrencyExposure {
+            currency_code: currency_code.to_string(),
+            jpy_price: portfolio.jpy_price(currency_code),
+            ..Default::default()
+        })
+}
+
+pub async fn exposure_report<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+) -> Result<ExposureReport> {
+    let mut portfolio = portfolio(api, product_codes).await?;
+    let missing: Vec<&str> = net_positions(&portfolio.positions)
+        .iter()
+        .filter_map(|(x, _)| x.base_currency())
+        .filter(|x| portfolio.jpy_price(x).is_none())
+        .collect();
+    if !missing.is_empty() {
+        let prices = jpy_prices(api, missing).await?;
+        portfolio.jpy_prices.extend(prices);
+    }
+    Ok(ExposureReport::from_portfolio(&portfolio))
+}
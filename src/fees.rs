@@ -0,0 +1,262 @@
+use crate::api::{BitflyerApi, GetBalanceHistory, GetCoinOuts, GetPositions, GetPrivateExecutions};
+use crate::entity::{BalanceHistory, CoinOut, Position, PrivateExecution, ProductCode, TradeType};
+use crate::export::csv::{decimal, CsvRecord};
+use crate::pager::{Pager, Paginated};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use chrono_tz::Asia::Tokyo;
+use futures::{future, TryStreamExt};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FeeKind {
+    Commission,
+    SfdPaid,
+    SfdReceived,
+    WithdrawalFee,
+}
+
+impl FeeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeeKind::Commission => "COMMISSION",
+            FeeKind::SfdPaid => "SFD_PAID",
+            FeeKind::SfdReceived => "SFD_RECEIVED",
+            FeeKind::WithdrawalFee => "WITHDRAWAL_FEE",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeRow {
+    // Calendar month in JST, e.g. `2024-03`.
+    pub month: String,
+    // Product code for trading fees, currency code for withdrawals.
+    pub source: String,
+    pub kind: FeeKind,
+    pub currency_code: String,
+    pub amount: Decimal,
+    // None when the fee is charged in a currency without a JPY price at hand.
+    pub jpy_amount: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeSummary {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    rows: BTreeMap<(String, String, FeeKind), FeeRow>,
+}
+
+fn month(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Tokyo).format("%Y-%m").to_string()
+}
+
+impl FeeSummary {
+    pub fn new(from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        Self {
+            from,
+            to,
+            rows: BTreeMap::new(),
+        }
+    }
+
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.from <= time && time < self.to
+    }
+
+    fn add(
+        &mut self,
+        time: DateTime<Utc>,
+        source: String,
+        kind: FeeKind,
+        currency_code: &str,
+        amount: Decimal,
+        jpy_amount: Option<Decimal>,
+    ) {
+        if amount.is_zero() || !self.contains(time) {
+            return;
+        }
+        let month = month(time);
+        let row = self
+            .rows
+            .entry((month.clone(), source.clone(), kind))
+            .or_insert_with(|| FeeRow {
+                month,
+                source,
+                kind,
+                currency_code: currency_code.to_string(),
+                amount: Decimal::ZERO,
+                jpy_amount: Some(Decimal::ZERO),
+            });
+        row.amount += amount;
+        row.jpy_amount = row.jpy_amount.zip(jpy_amount).map(|(x, y)| x + y);
+    }
+
+    // Commissions are charged in the base currency and valued at the execution price.
+    pub fn add_execution(&mut self, product_code: &ProductCode, execution: &PrivateExecution) {
+        let currency_code = product_code.base_currency().unwrap_or_default();
+        let jpy_amount = (product_code.quote_currency() == Some("JPY"))
+            .then(|| execution.commission * execution.price);
+        self.add(
+            execution.exec_date,
+            product_code.to_string(),
+            FeeKind::Commission,
+            currency_code,
+            execution.commission,
+            jpy_amount,
+        );
+    }
+
+    // SFD accumulated on open positions, attributed to the month of `as_of`.
+    pub fn add_position(&mut self, position: &Position, as_of: DateTime<Utc>) {
+        let kind = if position.sfd.is_sign_negative() {
+            FeeKind::SfdPaid
+        } else {
+            FeeKind::SfdReceived
+        };
+        self.add(
+            as_of,
+            position.product_code.to_string(),
+            kind,
+            "JPY",
+            position.sfd.abs(),
+            Some(position.sfd.abs()),
+        );
+    }
+
+    pub fn add_coin_out(&mut self, coin_out: &CoinOut) {
+        let fee = coin_out.fee + coin_out.additional_fee;
+        self.add(
+            coin_out.event_date,
+            coin_out.currency_code.clone(),
+            FeeKind::WithdrawalFee,
+            &coin_out.currency_code,
+            fee,
+            (coin_out.currency_code == "JPY").then_some(fee),
+        );
+    }
+
+    // Only fiat withdrawal fees are taken from the balance history; trading fees come from executions.
+    pub fn add_balance_history(&mut self, history: &BalanceHistory) {
+        if history.trade_type != TradeType::Withdraw {
+            return;
+        }
+        let fee = history.commission.abs();
+        self.add(
+            history.event_date,
+            history.currency_code.clone(),
+            FeeKind::WithdrawalFee,
+            &history.currency_code,
+            fee,
+            (history.currency_code == "JPY").then_some(fee),
+        );
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &FeeRow> {
+        self.rows.values()
+    }
+
+    pub fn total_jpy(&self, kind: FeeKind) -> Decimal {
+        let total: Decimal = self
+            .rows()
+            .filter(|x| x.kind == kind)
+            .filter_map(|x| x.jpy_amount)
+            .sum();
+        match kind {
+            FeeKind::SfdReceived => -total,
+            _ => total,
+        }
+    }
+
+    // Fees paid minus SFD received, over rows with a JPY value.
+    pub fn net_jpy(&self) -> Decimal {
+        [
+            FeeKind::Commission,
+            FeeKind::SfdPaid,
+            FeeKind::SfdReceived,
+            FeeKind::WithdrawalFee,
+        ]
+        .iter()
+        .map(|x| self.total_jpy(*x))
+        .sum()
+    }
+
+    pub fn unpriced_rows(&self) -> impl Iterator<Item = &FeeRow> {
+        self.rows().filter(|x| x.jpy_amount.is_none())
+    }
+}
+
+impl CsvRecord for FeeRow {
+    const HEADER: &'static [&'static str] = &[
+        "month",
+        "source",
+        "kind",
+        "currency_code",
+        "amount",
+        "jpy_amount",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.month.clone(),
+            self.source.clone(),
+            self.kind.as_str().to_string(),
+            self.currency_code.clone(),
+            decimal(self.amount),
+            self.jpy_amount.map(decimal).unwrap_or_default(),
+        ]
+    }
+}
+
+async fn since<A, T>(
+    api: &A,
+    request: T,
+    from: DateTime<Utc>,
+    date: fn(&T::Item) -> DateTime<Utc>,
+) -> Result<Vec<T::Item>>
+where
+    A: BitflyerApi,
+    T: Paginated,
+{
+    Pager::new(api, request)
+        .items()
+        .try_take_while(|x| future::ready(Ok(date(x) >= from)))
+        .try_collect()
+        .await
+}
+
+pub async fn fee_summary<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<FeeSummary> {
+    let mut summary = FeeSummary::new(from, to);
+    for product_code in product_codes {
+        let request = GetPrivateExecutions {
+            product_code: Some(product_code.clone()),
+            ..Default::default()
+        };
+        for execution in since(api, request, from, |x| x.exec_date).await? {
+            summary.add_execution(product_code, &execution);
+        }
+    }
+    for coin_out in since(api, GetCoinOuts::default(), from, |x| x.event_date).await? {
+        summary.add_coin_out(&coin_out);
+    }
+    let request = GetBalanceHistory {
+        currency_code: Some("JPY".to_string()),
+        ..Default::default()
+    };
+    for history in since(api, request, from, |x| x.event_date).await? {
+        summary.add_balance_history(&history);
+    }
+    let now = Utc::now();
+    if product_codes.iter().any(|x| x.is_fx()) && summary.contains(now) {
+        for position in api.send(GetPositions {}).await? {
+            summary.add_position(&position, now);
+        }
+    }
+    Ok(summary)
+}
@@ -0,0 +1,188 @@
+use crate::api::{BitflyerApi, GetChildOrders, GetPrivateExecutions};
+use crate::entity::{ChildOrder, PrivateExecution, ProductCode};
+use crate::orders::child_order;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+
+const RECONCILE_COUNT: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissedFill {
+    pub child_order_acceptance_id: String,
+    pub recorded_size: Decimal,
+    pub executed_size: Decimal,
+}
+
+impl MissedFill {
+    pub fn missed_size(&self) -> Decimal {
+        self.executed_size - self.recorded_size
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FillReconciliationReport {
+    pub product_code: Option<ProductCode>,
+    pub missed_fills: Vec<MissedFill>,
+    pub partial_fills: Vec<ChildOrder>,
+    pub orphaned_orders: Vec<ChildOrder>,
+    pub missing_orders: Vec<String>,
+    pub missed_executions: Vec<PrivateExecution>,
+}
+
+impl FillReconciliationReport {
+    pub fn is_clean(&self) -> bool {
+        self.missed_fills.is_empty()
+            && self.orphaned_orders.is_empty()
+            && self.missing_orders.is_empty()
+            && self.missed_executions.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TrackedFill {
+    product_code: ProductCode,
+    recorded_size: Decimal,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FillReconciler {
+    orders: HashMap<String, TrackedFill>,
+    seen: HashSet<u64>,
+}
+
+impl FillReconciler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&mut self, product_code: ProductCode, child_order_acceptance_id: String) {
+        self.orders
+            .entry(child_order_acceptance_id)
+            .or_insert(TrackedFill {
+                product_code,
+                recorded_size: Decimal::ZERO,
+            });
+    }
+
+    pub fn forget(&mut self, child_order_acceptance_id: &str) -> bool {
+        self.orders.remove(child_order_acceptance_id).is_some()
+    }
+
+    pub fn tracked(&self) -> impl Iterator<Item = &str> {
+        self.orders.keys().map(|x| x.as_str())
+    }
+
+    pub fn recorded_size(&self, child_order_acceptance_id: &str) -> Option<Decimal> {
+        self.orders
+            .get(child_order_acceptance_id)
+            .map(|x| x.recorded_size)
+    }
+
+    pub fn record_fill(&mut self, child_order_acceptance_id: &str, size: Decimal) {
+        if let Some(order) = self.orders.get_mut(child_order_acceptance_id) {
+            order.recorded_size += size;
+        }
+    }
+
+    pub fn record_execution(&mut self, execution: &PrivateExecution) -> bool {
+        if !self.seen.insert(execution.id) {
+            return false;
+        }
+        self.record_fill(&execution.child_order_acceptance_id, execution.size);
+        true
+    }
+
+    pub async fn reconcile<A: BitflyerApi>(
+        &self,
+        api: &A,
+        product_code: &ProductCode,
+    ) -> Result<FillReconciliationReport> {
+        let mut report = FillReconciliationReport {
+            product_code: Some(product_code.clone()),
+            ..Default::default()
+        };
+        let mut exchange: HashMap<String, ChildOrder> = api
+            .send(GetChildOrders {
+                product_code: Some(product_code.clone()),
+                count: Some(RECONCILE_COUNT),
+                ..Default::default()
+            })
+            .await?
+            .into_iter()
+            .map(|x| (x.child_order_acceptance_id.clone(), x))
+            .collect();
+
+        report.orphaned_orders = exchange
+            .values()
+            .filter(|x| x.child_order_state.is_active())
+            .filter(|x| !self.orders.contains_key(&x.child_order_acceptance_id))
+            .cloned()
+            .collect();
+        report.orphaned_orders.sort_by_key(|x| x.id);
+
+        let mut tracked: Vec<_> = self
+            .orders
+            .iter()
+            .filter(|(_, x)| x.product_code == *product_code)
+            .collect();
+        tracked.sort_by(|a, b| a.0.cmp(b.0));
+        for (acceptance_id, tracked) in tracked {
+            let order = match exchange.remove(acceptance_id) {
+                Some(order) => order,
+                None => match child_order(api, product_code, acceptance_id).await? {
+                    Some(order) => order,
+                    None => {
+                        report.missing_orders.push(acceptance_id.clone());
+                        continue;
+                    }
+                },
+            };
+            if order.executed_size > Decimal::ZERO && order.executed_size < order.size {
+                report.partial_fills.push(order.clone());
+            }
+            if order.executed_size <= tracked.recorded_size {
+                continue;
+            }
+            report.missed_fills.push(MissedFill {
+                child_order_acceptance_id: acceptance_id.clone(),
+                recorded_size: tracked.recorded_size,
+                executed_size: order.executed_size,
+            });
+            let executions = api
+                .send(GetPrivateExecutions {
+                    product_code: Some(product_code.clone()),
+                    child_order_acceptance_id: Some(acceptance_id.clone()),
+                    ..Default::default()
+                })
+                .await?;
+            report.missed_executions.extend(
+                executions
+                    .into_iter()
+                    .filter(|x| !self.seen.contains(&x.id)),
+            );
+        }
+        report.missed_executions.sort_by_key(|x| x.id);
+        Ok(report)
+    }
+
+    pub fn acknowledge(&mut self, report: &FillReconciliationReport) {
+        for execution in &report.missed_executions {
+            self.seen.insert(execution.id);
+        }
+        for missed in &report.missed_fills {
+            if let Some(order) = self.orders.get_mut(&missed.child_order_acceptance_id) {
+                order.recorded_size = order.recorded_size.max(missed.executed_size);
+            }
+        }
+        if let Some(product_code) = &report.product_code {
+            for order in &report.orphaned_orders {
+                self.track(
+                    product_code.clone(),
+                    order.child_order_acceptance_id.clone(),
+                );
+                self.record_fill(&order.child_order_acceptance_id, order.executed_size);
+            }
+        }
+    }
+}
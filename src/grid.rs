@@ -0,0 +1,223 @@
+use crate::api::{BitflyerApi, Client, SendChildOrder};
+use crate::entity::{ChildOrderType, ProductCode, Side};
+use crate::order_manager::{OrderManager, OrderStatus};
+use crate::shutdown::ShutdownHandle;
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridSpec {
+    pub product_code: ProductCode,
+    pub lower_price: Decimal,
+    pub upper_price: Decimal,
+    pub step: Decimal,
+    pub size: Decimal,
+}
+
+impl GridSpec {
+    pub fn levels(&self) -> Result<Vec<Decimal>> {
+        if self.step <= Decimal::ZERO {
+            return Err(anyhow!("grid step must be positive: {}", self.step));
+        }
+        if self.lower_price > self.upper_price {
+            return Err(anyhow!(
+                "grid lower price {} is above the upper price {}",
+                self.lower_price,
+                self.upper_price
+            ));
+        }
+        let mut levels = vec![];
+        let mut price = self.lower_price;
+        while price <= self.upper_price {
+            levels.push(price);
+            price += self.step;
+        }
+        Ok(levels)
+    }
+
+    fn order(&self, side: Side, price: Decimal) -> SendChildOrder {
+        SendChildOrder {
+            child_order_type: ChildOrderType::Limit { price },
+            product_code: self.product_code.clone(),
+            side,
+            size: self.size,
+            minute_to_expire: None,
+            time_in_force: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridLevel {
+    pub price: Decimal,
+    pub side: Option<Side>,
+    pub acceptance_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GridEvent {
+    Placed {
+        price: Decimal,
+        side: Side,
+        acceptance_id: String,
+    },
+    Filled {
+        price: Decimal,
+        side: Side,
+        acceptance_id: String,
+    },
+    Removed {
+        price: Decimal,
+        side: Side,
+        acceptance_id: String,
+        status: OrderStatus,
+    },
+}
+
+#[derive(Debug)]
+pub struct Grid<A = Client> {
+    manager: Arc<OrderManager<A>>,
+    spec: GridSpec,
+    levels: Mutex<Vec<GridLevel>>,
+}
+
+impl<A: BitflyerApi + 'static> Grid<A> {
+    pub fn new(manager: Arc<OrderManager<A>>, spec: GridSpec) -> Result<Self> {
+        let levels = spec
+            .levels()?
+            .into_iter()
+            .map(|price| GridLevel {
+                price,
+                side: None,
+                acceptance_id: None,
+            })
+            .collect();
+        Ok(Self {
+            manager,
+            spec,
+            levels: Mutex::new(levels),
+        })
+    }
+
+    pub fn spec(&self) -> &GridSpec {
+        &self.spec
+    }
+
+    pub async fn levels(&self) -> Vec<GridLevel> {
+        self.levels.lock().await.clone()
+    }
+
+    pub async fn place(&self, reference_price: Decimal) -> Result<Vec<GridEvent>> {
+        let mut levels = self.levels.lock().await;
+        let mut events = vec![];
+        for index in 0..levels.len() {
+            if levels[index].acceptance_id.is_some() {
+                continue;
+            }
+            let price = levels[index].price;
+            let side = if price < reference_price {
+                Side::Buy
+            } else if price > reference_price {
+                Side::Sell
+            } else {
+                continue;
+            };
+            events.push(self.place_level(&mut levels, index, side).await?);
+        }
+        Ok(events)
+    }
+
+    async fn place_level(
+        &self,
+        levels: &mut [GridLevel],
+        index: usize,
+        side: Side,
+    ) -> Result<GridEvent> {
+        let price = levels[index].price;
+        let acceptance_id = self.manager.submit(self.spec.order(side, price)).await?;
+        levels[index].side = Some(side);
+        levels[index].acceptance_id = Some(acceptance_id.clone());
+        Ok(GridEvent::Placed {
+            price,
+            side,
+            acceptance_id,
+        })
+    }
+
+    pub async fn rebalance(&self) -> Result<Vec<GridEvent>> {
+        let mut levels = self.levels.lock().await;
+        let mut events = vec![];
+        let mut replacements = vec![];
+        for (index, level) in levels.iter_mut().enumerate() {
+            let (Some(side), Some(acceptance_id)) = (level.side, level.acceptance_id.clone())
+            else {
+                continue;
+            };
+            let Some(status) = self.manager.order_status(&acceptance_id) else {
+                continue;
+            };
+            if status.is_open() {
+                continue;
+            }
+            level.side = None;
+            level.acceptance_id = None;
+            self.manager.forget(&acceptance_id);
+            if status != OrderStatus::Completed {
+                events.push(GridEvent::Removed {
+                    price: level.price,
+                    side,
+                    acceptance_id,
+                    status,
+                });
+                continue;
+            }
+            events.push(GridEvent::Filled {
+                price: level.price,
+                side,
+                acceptance_id,
+            });
+            // A filled buy is taken profit on one level up, a filled sell is bought back one level down.
+            match side {
+                Side::Buy => replacements.push((index + 1, Side::Sell)),
+                Side::Sell if index > 0 => replacements.push((index - 1, Side::Buy)),
+                Side::Sell => {}
+            }
+        }
+        for (index, side) in replacements {
+            if levels.get(index).is_some_and(|x| x.acceptance_id.is_none()) {
+                events.push(self.place_level(&mut levels, index, side).await?);
+            }
+        }
+        Ok(events)
+    }
+
+    pub async fn cancel_all(&self) -> Result<()> {
+        let mut levels = self.levels.lock().await;
+        for level in levels.iter_mut() {
+            if let Some(acceptance_id) = level.acceptance_id.take() {
+                level.side = None;
+                self.manager.cancel(&acceptance_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let grid = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = grid.manager.poll().await {
+                    tracing::warn!("failed to poll grid orders: {e:?}");
+                    continue;
+                }
+                if let Err(e) = grid.rebalance().await {
+                    tracing::warn!("failed to rebalance grid: {e:?}");
+                }
+            }
+        })
+    }
+}
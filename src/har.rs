@@ -0,0 +1,322 @@
+use crate::api::RawRequest;
+use crate::redact::{Redactor, REDACTED};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Version};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Never written as they are: the first two authenticate, cookies may track the session.
+const SECRET_HEADERS: [&str; 4] = ["ACCESS-KEY", "ACCESS-SIGN", "cookie", "set-cookie"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub headers: Vec<NameValue>,
+    pub query_string: Vec<NameValue>,
+    pub cookies: Vec<NameValue>,
+    pub headers_size: i64,
+    pub body_size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+// Status 0 when no response arrived; the entry comment then holds the error.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub headers: Vec<NameValue>,
+    pub cookies: Vec<NameValue>,
+    pub content: Content,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+// Milliseconds. Only the total is measured, so it is all `wait`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub started_date_time: DateTime<Utc>,
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub cache: Map<String, Value>,
+    pub timings: Timings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+impl Default for Har {
+    fn default() -> Self {
+        Self {
+            log: Log {
+                version: "1.2".to_string(),
+                creator: Creator {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries: vec![],
+            },
+        }
+    }
+}
+
+impl Har {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read the capture {}", path.display()))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct CapturedResponse<'a> {
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: &'a HeaderMap,
+    pub(crate) body: &'a str,
+}
+
+// Writes every exchange of a client to a HAR 1.2 file that browser dev tools and HAR viewers
+// open. The file is rewritten after each request so a crashed session still leaves a trace.
+// Credentials, cookies and the redacted JSON fields are replaced before anything is written.
+#[derive(Debug)]
+pub struct HarCapture {
+    path: PathBuf,
+    redactor: Redactor,
+    har: Mutex<Har>,
+}
+
+impl HarCapture {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            redactor: Redactor::default(),
+            har: Mutex::new(Har::default()),
+        }
+    }
+
+    // One file per session in `dir`, named after the time it started.
+    pub fn in_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let name = format!("bitflyer-{}.har", Utc::now().format("%Y%m%d-%H%M%S%.3f"));
+        Ok(Self::new(dir.join(name)))
+    }
+
+    // JSON fields whose values are replaced in request and response bodies.
+    pub fn with_redacted_fields(
+        mut self,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.redactor.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn har(&self) -> Har {
+        self.har.lock().unwrap().clone()
+    }
+
+    fn headers(headers: &HeaderMap) -> Vec<NameValue> {
+        headers
+            .iter()
+            .map(|(name, value)| NameValue {
+                name: name.to_string(),
+                value: match SECRET_HEADERS
+                    .iter()
+                    .any(|x| name.as_str().eq_ignore_ascii_case(x))
+                {
+                    true => REDACTED.to_string(),
+                    false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                },
+            })
+            .collect()
+    }
+
+    // Returns the index of the new entry.
+    pub(crate) fn record(
+        &self,
+        request: &RawRequest,
+        request_headers: &HeaderMap,
+        started: DateTime<Utc>,
+        elapsed: Duration,
+        response: std::result::Result<CapturedResponse<'_>, String>,
+    ) -> usize {
+        let body = request.body.as_deref().map(|x| self.redactor.redact(x));
+        let http_version = match &response {
+            Ok(x) => format!("{:?}", x.version),
+            Err(_) => format!("{:?}", Version::HTTP_11),
+        };
+        let har_request = HarRequest {
+            method: request.method.to_string(),
+            url: request.url.to_string(),
+            http_version: http_version.clone(),
+            headers: Self::headers(request_headers),
+            query_string: request
+                .url
+                .query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect(),
+            cookies: vec![],
+            headers_size: -1,
+            body_size: body.as_ref().map_or(0, |x| x.len() as i64),
+            post_data: body.map(|text| PostData {
+                mime_type: "application/json".to_string(),
+                text,
+            }),
+        };
+        let (har_response, comment) = match response {
+            Ok(response) => {
+                let text = self.redactor.redact(response.body);
+                let mime_type = response
+                    .headers
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|x| x.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                let har_response = HarResponse {
+                    status: response.status.as_u16(),
+                    status_text: response
+                        .status
+                        .canonical_reason()
+                        .unwrap_or_default()
+                        .to_string(),
+                    http_version,
+                    headers: Self::headers(response.headers),
+                    cookies: vec![],
+                    content: Content {
+                        size: text.len() as i64,
+                        mime_type,
+                        text: Some(text),
+                    },
+                    redirect_url: String::new(),
+                    headers_size: -1,
+                    body_size: response.body.len() as i64,
+                };
+                (har_response, None)
+            }
+            Err(error) => {
+                let har_response = HarResponse {
+                    status: 0,
+                    status_text: String::new(),
+                    http_version,
+                    headers: vec![],
+                    cookies: vec![],
+                    content: Content {
+                        size: 0,
+                        mime_type: String::new(),
+                        text: None,
+                    },
+                    redirect_url: String::new(),
+                    headers_size: -1,
+                    body_size: -1,
+                };
+                (har_response, Some(error))
+            }
+        };
+        let time = elapsed.as_secs_f64() * 1000.0;
+        let mut har = self.har.lock().unwrap();
+        har.log.entries.push(Entry {
+            started_date_time: started,
+            time,
+            request: har_request,
+            response: har_response,
+            cache: Map::new(),
+            timings: Timings {
+                send: 0.0,
+                wait: time,
+                receive: 0.0,
+            },
+            comment,
+        });
+        self.save(&har);
+        har.log.entries.len() - 1
+    }
+
+    // Notes on an entry why its response was unusable, e.g. a deserialization error.
+    pub(crate) fn comment(&self, entry: usize, comment: String) {
+        let mut har = self.har.lock().unwrap();
+        if let Some(x) = har.log.entries.get_mut(entry) {
+            x.comment = Some(comment);
+        }
+        self.save(&har);
+    }
+
+    // A failed write never fails the request being captured.
+    fn save(&self, har: &Har) {
+        if let Err(e) = har.save(&self.path) {
+            tracing::warn!("failed to write the capture {}: {e:?}", self.path.display());
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::api::{BitflyerApi, Client, GetExecutions};
+use crate::entity::{Execution, ProductCode};
+use crate::executions::ExecutionRange;
+use crate::pager::{Pager, PAGE_INTERVAL, PAGE_SIZE};
+use crate::shutdown::CancellationToken;
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    pub product_code: ProductCode,
+    pub range: ExecutionRange,
+    // The next page is requested before this id.
+    pub before: Option<u64>,
+    pub newest_id: Option<u64>,
+    pub oldest_id: Option<u64>,
+    pub downloaded: u64,
+    // Bytes of the data file covered by this progress; anything past it is discarded on resume.
+    pub file_len: u64,
+    pub completed: bool,
+}
+
+impl DownloadProgress {
+    fn new(product_code: ProductCode, range: ExecutionRange) -> Self {
+        Self {
+            product_code,
+            before: range.before,
+            range,
+            newest_id: None,
+            oldest_id: None,
+            downloaded: 0,
+            file_len: 0,
+            completed: false,
+        }
+    }
+}
+
+// Writes executions newest first as JSON lines, with progress stored next to the data file.
+#[derive(Debug)]
+pub struct HistoryDownloader<A = Client> {
+    client: Arc<A>,
+    product_code: ProductCode,
+    range: ExecutionRange,
+    path: PathBuf,
+    page_size: u64,
+    page_interval: Duration,
+    max_retries: usize,
+    retry_interval: Duration,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<A: BitflyerApi + 'static> HistoryDownloader<A> {
+    pub fn new(
+        client: Arc<A>,
+        product_code: ProductCode,
+        range: ExecutionRange,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            client,
+            product_code,
+            range,
+            path: path.into(),
+            page_size: PAGE_SIZE,
+            page_interval: PAGE_INTERVAL,
+            max_retries: 5,
+            retry_interval: Duration::from_secs(10),
+            cancellation: None,
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: u64) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn with_page_interval(mut self, page_interval: Duration) -> Self {
+        self.page_interval = page_interval;
+        self
+    }
+
+    // Consecutive failures are retried with a linearly growing delay.
+    pub fn with_retry(mut self, max_retries: usize, retry_interval: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    // Stops the download between pages once `token` is cancelled. `run` then returns the
+    // progress so far, not completed, and the next run resumes from it.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(|x| x.is_cancelled())
+    }
+
+    async fn cancelled(&self) {
+        match &self.cancellation {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn progress_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".progress.json");
+        path.into()
+    }
+
+    pub fn progress(&self) -> Result<DownloadProgress> {
+        let path = self.progress_path();
+        if !path.exists() {
+            return Ok(DownloadProgress::new(
+                self.product_code.clone(),
+                self.range.clone(),
+            ));
+        }
+        let progress: DownloadProgress = serde_json::from_slice(&std::fs::read(&path)?)?;
+        if progress.product_code != self.product_code || progress.range != self.range {
+            return Err(anyhow!(
+                "{} belongs to another download: {} {:?}",
+                path.display(),
+                progress.product_code,
+                progress.range
+            ));
+        }
+        Ok(progress)
+    }
+
+    fn persist(&self, progress: &DownloadProgress) -> Result<()> {
+        std::fs::write(self.progress_path(), serde_json::to_vec_pretty(progress)?)?;
+        Ok(())
+    }
+
+    pub async fn run(&self) -> Result<DownloadProgress> {
+        let mut progress = self.progress()?;
+        if progress.completed {
+            return Ok(progress);
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.path)?;
+        file.set_len(progress.file_len)?;
+        std::io::Seek::seek(&mut file, std::io::SeekFrom::End(0))?;
+
+        let mut retries = 0;
+        loop {
+            match self.download(&mut file, &mut progress).await {
+                Ok(()) if self.is_cancelled() => return Ok(progress),
+                Ok(()) => {
+                    progress.completed = true;
+                    self.persist(&progress)?;
+                    return Ok(progress);
+                }
+                Err(e) if retries >= self.max_retries => return Err(e),
+                Err(e) => {
+                    retries += 1;
+                    tracing::warn!(
+                        "failed to download executions of {} before {:?} (retry {retries}): {e:?}",
+                        self.product_code,
+                        progress.before
+                    );
+                    tokio::select! {
+                        _ = self.cancelled() => return Ok(progress),
+                        _ = tokio::time::sleep(self.retry_interval * retries as u32) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn download(
+        &self,
+        file: &mut std::fs::File,
+        progress: &mut DownloadProgress,
+    ) -> Result<()> {
+        let request = GetExecutions {
+            product_code: Some(self.product_code.clone()),
+            before: progress.before,
+            after: self.range.after,
+            ..Default::default()
+        };
+        let mut pages = std::pin::pin!(Pager::new(self.client.as_ref(), request)
+            .with_page_size(self.page_size)
+            .with_page_interval(self.page_interval)
+            .pages());
+        loop {
+            let page = tokio::select! {
+                biased;
+                _ = self.cancelled() => break,
+                page = pages.next() => page,
+            };
+            let Some(page) = page else {
+                break;
+            };
+            let page = page?;
+            let mut lines = String::new();
+            let mut finished = false;
+            for execution in &page {
+                // Pages overlapping what is already written are skipped.
+                if progress.oldest_id.is_some_and(|x| execution.id >= x)
+                    && progress.newest_id.is_some_and(|x| execution.id <= x)
+                {
+                    continue;
+                }
+                if self.range.is_past(execution) {
+                    finished = true;
+                    break;
+                }
+                if !self.range.contains(execution) {
+                    continue;
+                }
+                lines.push_str(&serde_json::to_string(execution)?);
+                lines.push('\n');
+                progress.newest_id = progress.newest_id.max(Some(execution.id));
+                progress.oldest_id = Some(
+                    progress
+                        .oldest_id
+                        .map_or(execution.id, |x| x.min(execution.id)),
+                );
+                progress.downloaded += 1;
+            }
+            file.write_all(lines.as_bytes())?;
+            file.sync_data()?;
+            progress.file_len += lines.len() as u64;
+            progress.before = page.iter().map(|x| x.id).min().or(progress.before);
+            self.persist(progress)?;
+            tracing::debug!(
+                "downloaded {} executions of {} down to {:?}",
+                progress.downloaded,
+                self.product_code,
+                progress.before
+            );
+            if finished {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn read_executions(path: impl AsRef<Path>) -> Result<Vec<Execution>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut executions = vec![];
+    for line in file.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        executions.push(serde_json::from_str(&line)?);
+    }
+    Ok(executions)
+}
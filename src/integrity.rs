@@ -0,0 +1,209 @@
+use crate::entity::Execution;
+use crate::executions::ExecutionRange;
+use crate::history::read_executions;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+// Ids strictly between `after` and `before` are missing. The dates are None at a range bound
+// with no execution on that side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdGap {
+    pub after: u64,
+    pub before: u64,
+    pub after_date: Option<DateTime<Utc>>,
+    pub before_date: Option<DateTime<Utc>>,
+}
+
+impl IdGap {
+    pub fn missing(&self) -> u64 {
+        self.before.saturating_sub(self.after).saturating_sub(1)
+    }
+
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        Some(self.before_date? - self.after_date?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateId {
+    pub id: u64,
+    pub count: usize,
+    // The copies differ in something other than the id.
+    pub conflicting: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub count: usize,
+    pub unique: usize,
+    pub first_id: Option<u64>,
+    pub last_id: Option<u64>,
+    pub first_date: Option<DateTime<Utc>>,
+    pub last_date: Option<DateTime<Utc>>,
+    pub out_of_range: usize,
+    pub duplicates: Vec<DuplicateId>,
+    pub gaps: Vec<IdGap>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.duplicates.is_empty() && self.gaps.is_empty() && self.out_of_range == 0
+    }
+
+    pub fn missing(&self) -> u64 {
+        self.gaps.iter().map(|x| x.missing()).sum()
+    }
+
+    pub fn duplicated(&self) -> usize {
+        self.count - self.unique
+    }
+}
+
+// Execution ids are shared by every product, so a single product's history always skips
+// ids. Set `max_id_gap` (and optionally `max_time_gap`) to what is normal for the product;
+// only jumps exceeding both limits are reported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityChecker {
+    range: ExecutionRange,
+    max_id_gap: u64,
+    max_time_gap: Option<chrono::Duration>,
+}
+
+impl Default for IntegrityChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntegrityChecker {
+    pub fn new() -> Self {
+        Self {
+            range: ExecutionRange::default(),
+            max_id_gap: 0,
+            max_time_gap: None,
+        }
+    }
+
+    // Id bounds of the range are checked for leading and trailing gaps too.
+    pub fn with_range(mut self, range: ExecutionRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    // The number of consecutive missing ids tolerated.
+    pub fn with_max_id_gap(mut self, max_id_gap: u64) -> Self {
+        self.max_id_gap = max_id_gap;
+        self
+    }
+
+    pub fn with_max_time_gap(mut self, max_time_gap: chrono::Duration) -> Self {
+        self.max_time_gap = Some(max_time_gap);
+        self
+    }
+
+    fn is_gap(&self, gap: &IdGap) -> bool {
+        if gap.missing() <= self.max_id_gap {
+            return false;
+        }
+        match (self.max_time_gap, gap.duration()) {
+            (Some(max), Some(duration)) => duration > max,
+            _ => true,
+        }
+    }
+
+    // Accepts executions in any order.
+    pub fn check(&self, executions: &[Execution]) -> IntegrityReport {
+        let mut by_id: BTreeMap<u64, Vec<&Execution>> = BTreeMap::new();
+        let mut out_of_range = 0;
+        for execution in executions {
+            if self.range.contains(execution) {
+                by_id.entry(execution.id).or_default().push(execution);
+            } else {
+                out_of_range += 1;
+            }
+        }
+
+        let duplicates = by_id
+            .iter()
+            .filter(|(_, x)| x.len() > 1)
+            .map(|(id, x)| DuplicateId {
+                id: *id,
+                count: x.len(),
+                conflicting: x.iter().any(|y| *y != x[0]),
+            })
+            .collect();
+
+        let mut gaps = vec![];
+        let mut previous: Option<(u64, Option<DateTime<Utc>>)> =
+            self.range.after.map(|x| (x, None));
+        for (id, x) in &by_id {
+            if let Some((after, after_date)) = previous {
+                gaps.push(IdGap {
+                    after,
+                    before: *id,
+                    after_date,
+                    before_date: Some(x[0].exec_date),
+                });
+            }
+            previous = Some((*id, Some(x[0].exec_date)));
+        }
+        if let (Some((after, after_date)), Some(before)) = (previous, self.range.before) {
+            gaps.push(IdGap {
+                after,
+                before,
+                after_date,
+                before_date: None,
+            });
+        }
+        gaps.retain(|x| self.is_gap(x));
+
+        let dates = by_id.values().map(|x| x[0].exec_date);
+        IntegrityReport {
+            count: executions.len() - out_of_range,
+            unique: by_id.len(),
+            first_id: by_id.keys().next().copied(),
+            last_id: by_id.keys().next_back().copied(),
+            first_date: dates.clone().min(),
+            last_date: dates.max(),
+            out_of_range,
+            duplicates,
+            gaps,
+        }
+    }
+
+    // Checks a JSON lines file as written by `HistoryDownloader`.
+    pub fn check_file(&self, path: impl AsRef<Path>) -> Result<IntegrityReport> {
+        Ok(self.check(&read_executions(path)?))
+    }
+}
+
+// Drops every execution whose id was already seen, keeping the original order, and returns
+// how many were dropped.
+pub fn deduplicate(executions: &mut Vec<Execution>) -> usize {
+    let before = executions.len();
+    let mut seen = HashSet::new();
+    executions.retain(|x| seen.insert(x.id));
+    before - executions.len()
+}
+
+// Rewrites a JSON lines file without duplicates, returning how many were dropped. Only for
+// finished downloads: the progress of an unfinished `HistoryDownloader` refers to byte offsets.
+pub fn deduplicate_file(path: impl AsRef<Path>) -> Result<usize> {
+    let path = path.as_ref();
+    let mut executions = read_executions(path)?;
+    let dropped = deduplicate(&mut executions);
+    if dropped == 0 {
+        return Ok(0);
+    }
+    let mut lines = String::new();
+    for execution in &executions {
+        lines.push_str(&serde_json::to_string(execution)?);
+        lines.push('\n');
+    }
+    let temporary = path.with_extension("dedup.tmp");
+    std::fs::write(&temporary, lines)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(dropped)
+}
@@ -0,0 +1,189 @@
+use crate::api::{BitflyerApi, GetChildOrders, GetPrivateExecutions};
+use crate::entity::{ChildOrder, ChildOrderType, PrivateExecution, ProductCode, Side};
+use crate::export::csv::{decimal, label, timestamp, CsvRecord};
+use crate::pager::Pager;
+use crate::pnl::{CostMethod, PnlEngine};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+// One closing order and the lots it closed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TradeRecord {
+    pub product_code: ProductCode,
+    // Side of the position that was closed.
+    pub side: Side,
+    pub entry_order_ids: Vec<String>,
+    pub exit_order_id: String,
+    pub entry_order_type: Option<String>,
+    pub exit_order_type: Option<String>,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: DateTime<Utc>,
+    pub holding_seconds: i64,
+    pub size: Decimal,
+    // Size-weighted prices, commissions included.
+    pub entry_price: Decimal,
+    pub exit_price: Decimal,
+    pub pnl: Decimal,
+}
+
+impl TradeRecord {
+    pub fn holding_time(&self) -> TimeDelta {
+        self.closed_at - self.opened_at
+    }
+
+    pub fn is_win(&self) -> bool {
+        self.pnl > Decimal::ZERO
+    }
+}
+
+fn order_type(order: &ChildOrder) -> String {
+    match order.child_order_type {
+        ChildOrderType::Limit { .. } => "LIMIT".to_string(),
+        ChildOrderType::Market => "MARKET".to_string(),
+    }
+}
+
+pub fn trade_journal(
+    method: CostMethod,
+    executions: &[(ProductCode, PrivateExecution)],
+    orders: &[ChildOrder],
+) -> Vec<TradeRecord> {
+    let order_types: HashMap<&str, String> = orders
+        .iter()
+        .map(|x| (x.child_order_acceptance_id.as_str(), order_type(x)))
+        .collect();
+    let acceptance_ids: HashMap<u64, &str> = executions
+        .iter()
+        .map(|(_, x)| (x.id, x.child_order_acceptance_id.as_str()))
+        .collect();
+    let mut sorted: Vec<&(ProductCode, PrivateExecution)> = executions.iter().collect();
+    sorted.sort_by_key(|(_, x)| (x.exec_date, x.id));
+
+    let mut engine = PnlEngine::new(method);
+    let mut records: Vec<TradeRecord> = vec![];
+    let mut index: HashMap<(ProductCode, &str), usize> = HashMap::new();
+    for (product_code, execution) in sorted {
+        for realization in engine.apply(product_code, execution) {
+            let exit_order_id = execution.child_order_acceptance_id.as_str();
+            let entry_order_id = acceptance_ids
+                .get(&realization.entry_execution_id)
+                .copied()
+                .unwrap_or_default();
+            let key = (product_code.clone(), exit_order_id);
+            let i = *index.entry(key).or_insert_with(|| {
+                records.push(TradeRecord {
+                    product_code: product_code.clone(),
+                    side: realization.side,
+                    entry_order_ids: vec![],
+                    exit_order_id: exit_order_id.to_string(),
+                    entry_order_type: order_types.get(entry_order_id).cloned(),
+                    exit_order_type: order_types.get(exit_order_id).cloned(),
+                    opened_at: realization.opened_at,
+                    closed_at: realization.exec_date,
+                    holding_seconds: 0,
+                    size: Decimal::ZERO,
+                    entry_price: Decimal::ZERO,
+                    exit_price: Decimal::ZERO,
+                    pnl: Decimal::ZERO,
+                });
+                records.len() - 1
+            });
+            let record = &mut records[i];
+            let size = record.size + realization.size;
+            record.entry_price = (record.entry_price * record.size
+                + realization.entry_price * realization.size)
+                / size;
+            record.exit_price = (record.exit_price * record.size
+                + realization.exit_price * realization.size)
+                / size;
+            record.size = size;
+            record.pnl += realization.pnl;
+            record.opened_at = record.opened_at.min(realization.opened_at);
+            record.closed_at = record.closed_at.max(realization.exec_date);
+            record.holding_seconds = record.holding_time().num_seconds();
+            if !entry_order_id.is_empty()
+                && !record.entry_order_ids.iter().any(|x| x == entry_order_id)
+            {
+                record.entry_order_ids.push(entry_order_id.to_string());
+            }
+        }
+    }
+    records
+}
+
+// Fetches every order and private execution of the products and builds the journal.
+pub async fn load_trade_journal<A: BitflyerApi>(
+    api: &A,
+    method: CostMethod,
+    product_codes: &[ProductCode],
+) -> Result<Vec<TradeRecord>> {
+    let mut executions = vec![];
+    let mut orders = vec![];
+    for product_code in product_codes {
+        let request = GetPrivateExecutions {
+            product_code: Some(product_code.clone()),
+            ..Default::default()
+        };
+        let product_executions: Vec<PrivateExecution> =
+            Pager::new(api, request).items().try_collect().await?;
+        executions.extend(
+            product_executions
+                .into_iter()
+                .map(|x| (product_code.clone(), x)),
+        );
+        let request = GetChildOrders {
+            product_code: Some(product_code.clone()),
+            ..Default::default()
+        };
+        let product_orders: Vec<ChildOrder> =
+            Pager::new(api, request).items().try_collect().await?;
+        orders.extend(product_orders);
+    }
+    Ok(trade_journal(method, &executions, &orders))
+}
+
+pub fn write_json<W: Write>(writer: W, records: &[TradeRecord]) -> Result<()> {
+    serde_json::to_writer_pretty(writer, records)?;
+    Ok(())
+}
+
+impl CsvRecord for TradeRecord {
+    const HEADER: &'static [&'static str] = &[
+        "product_code",
+        "side",
+        "entry_order_ids",
+        "exit_order_id",
+        "entry_order_type",
+        "exit_order_type",
+        "opened_at",
+        "closed_at",
+        "holding_seconds",
+        "size",
+        "entry_price",
+        "exit_price",
+        "pnl",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            label(&self.product_code),
+            label(&self.side),
+            self.entry_order_ids.join(" "),
+            self.exit_order_id.clone(),
+            self.entry_order_type.clone().unwrap_or_default(),
+            self.exit_order_type.clone().unwrap_or_default(),
+            timestamp(&self.opened_at),
+            timestamp(&self.closed_at),
+            self.holding_seconds.to_string(),
+            decimal(self.size),
+            decimal(self.entry_price),
+            decimal(self.exit_price),
+            decimal(self.pnl),
+        ]
+    }
+}
@@ -0,0 +1,279 @@
+use crate::api::{
+    BitflyerApi, CancelAllChildOrders, CancelParentOrder, EmergencyOrder, GetChildOrders,
+    GetParentOrders, GetPositions, SendChildOrder,
+};
+use crate::entity::{ChildOrderType, OrderState, Position, ProductCode, Side};
+use crate::orders::{slippage_protected_order, wait_for_fill, SlippageAction, SlippageProtection};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KillSwitchOptions {
+    pub close_positions: bool,
+    pub max_slippage: Option<Decimal>,
+    pub max_attempts: usize,
+    pub retry_interval: Duration,
+    // How long to wait for the close orders of an attempt to fill before sending more.
+    pub fill_timeout: Duration,
+}
+
+impl Default for KillSwitchOptions {
+    fn default() -> Self {
+        Self {
+            close_positions: false,
+            max_slippage: None,
+            max_attempts: 10,
+            retry_interval: Duration::from_secs(1),
+            fill_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KillSwitchReport {
+    pub attempts: usize,
+    pub canceled_parent_orders: Vec<String>,
+    pub close_orders: Vec<String>,
+    pub remaining_child_orders: usize,
+    pub remaining_parent_orders: usize,
+    pub remaining_positions: Vec<(ProductCode, Decimal)>,
+    pub errors: Vec<String>,
+}
+
+impl KillSwitchReport {
+    pub fn is_confirmed(&self) -> bool {
+        self.remaining_child_orders == 0
+            && self.remaining_parent_orders == 0
+            && self.remaining_positions.is_empty()
+    }
+}
+
+// The positions to close, read once: they lag the fills, so a retry reading them again would
+// close the same size twice. What is left is whatever the close orders sent so far have not
+// filled.
+#[derive(Debug, Default)]
+struct Flatten {
+    positions: Option<Vec<(ProductCode, Decimal)>>,
+    closes: Vec<Close>,
+}
+
+#[derive(Debug)]
+struct Close {
+    product_code: ProductCode,
+    side: Side,
+    child_order_acceptance_id: String,
+    // Once the order is done.
+    executed_size: Option<Decimal>,
+}
+
+pub(crate) fn net_positions(positions: &[Position]) -> Vec<(ProductCode, Decimal)> {
+    let mut net: Vec<(ProductCode, Decimal)> = vec![];
+    for position in positions {
+        let size = match position.side {
+            Side::Buy => position.size,
+            Side::Sell => -position.size,
+        };
+        match net.iter_mut().find(|(x, _)| *x == position.product_code) {
+            Some((_, total)) => *total += size,
+            None => net.push((position.product_code.clone(), size)),
+        }
+    }
+    net.retain(|(_, size)| !size.is_zero());
+    net
+}
+
+pub async fn kill_switch<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+    options: &KillSwitchOptions,
+) -> Result<KillSwitchReport> {
+    let mut report = KillSwitchReport::default();
+    let mut flatten = Flatten::default();
+    for attempt in 1..=options.max_attempts.max(1) {
+        report.attempts = attempt;
+        if let Err(e) =
+            kill_switch_once(api, product_codes, options, &mut flatten, &mut report).await
+        {
+            tracing::warn!("kill switch attempt {attempt} failed: {e:?}");
+            report.errors.push(format!("{e:?}"));
+        }
+        tokio::time::sleep(options.retry_interval).await;
+        match kill_switch_verify(api, product_codes, options, &mut report).await {
+            Ok(()) if report.is_confirmed() => return Ok(report),
+            Ok(()) => {}
+            Err(e) => report.errors.push(format!("{e:?}")),
+        }
+    }
+    Ok(report)
+}
+
+async fn kill_switch_once<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+    options: &KillSwitchOptions,
+    flatten: &mut Flatten,
+    report: &mut KillSwitchReport,
+) -> Result<()> {
+    for product_code in product_codes {
+        api.send(CancelAllChildOrders {
+            product_code: product_code.clone(),
+        })
+        .await?;
+        let parent_orders = api
+            .send(GetParentOrders {
+                product_code: Some(product_code.clone()),
+                parent_order_state: Some(OrderState::Active),
+                ..Default::default()
+            })
+            .await?;
+        for parent_order in parent_orders {
+            api.send(CancelParentOrder {
+                product_code: product_code.clone(),
+                parent_order_acceptance_id: parent_order.parent_order_acceptance_id.clone(),
+            })
+            .await?;
+            report
+                .canceled_parent_orders
+                .push(parent_order.parent_order_acceptance_id);
+        }
+    }
+
+    if !options.close_positions || !product_codes.iter().any(|x| x.is_fx()) {
+        return Ok(());
+    }
+    let positions = match &flatten.positions {
+        Some(positions) => positions.clone(),
+        None => {
+            let positions: Vec<(ProductCode, Decimal)> =
+                net_positions(&api.send(GetPositions {}).await?)
+                    .into_iter()
+                    .filter(|(x, _)| product_codes.contains(x))
+                    .collect();
+            flatten.positions = Some(positions.clone());
+            positions
+        }
+    };
+    for (product_code, size) in positions {
+        let mut remaining = size;
+        for close in flatten
+            .closes
+            .iter_mut()
+            .filter(|x| x.product_code == product_code)
+        {
+            let executed_size = match close.executed_size {
+                Some(executed_size) => executed_size,
+                None => {
+                    let order = wait_for_fill(
+                        api,
+                        &product_code,
+                        &close.child_order_acceptance_id,
+                        options.fill_timeout,
+                    )
+                    .await?;
+                    *close.executed_size.insert(order.executed_size)
+                }
+            };
+            remaining += match close.side {
+                Side::Buy => executed_size,
+                Side::Sell => -executed_size,
+            };
+        }
+        if remaining.is_zero() {
+            continue;
+        }
+        let side = if remaining.is_sign_positive() {
+            Side::Sell
+        } else {
+            Side::Buy
+        };
+        let request = protected_market_order(
+            api,
+            product_code.clone(),
+            side,
+            remaining.abs(),
+            options.max_slippage,
+        )
+        .await?;
+        // Closing must not be held back by the limits that may have set off the kill switch.
+        let response = api.send(EmergencyOrder(request)).await?;
+        report
+            .close_orders
+            .push(response.child_order_acceptance_id.clone());
+        flatten.closes.push(Close {
+            product_code,
+            side,
+            child_order_acceptance_id: response.child_order_acceptance_id,
+            executed_size: None,
+        });
+    }
+    Ok(())
+}
+
+async fn kill_switch_verify<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+    options: &KillSwitchOptions,
+    report: &mut KillSwitchReport,
+) -> Result<()> {
+    let mut child_orders = 0;
+    let mut parent_orders = 0;
+    for product_code in product_codes {
+        child_orders += api
+            .send(GetChildOrders {
+                product_code: Some(product_code.clone()),
+                child_order_state: Some(OrderState::Active),
+                ..Default::default()
+            })
+            .await?
+            .len();
+        parent_orders += api
+            .send(GetParentOrders {
+                product_code: Some(product_code.clone()),
+                parent_order_state: Some(OrderState::Active),
+                ..Default::default()
+            })
+            .await?
+            .len();
+    }
+    report.remaining_child_orders = child_orders;
+    report.remaining_parent_orders = parent_orders;
+    report.remaining_positions =
+        if options.close_positions && product_codes.iter().any(|x| x.is_fx()) {
+            net_positions(&api.send(GetPositions {}).await?)
+                .into_iter()
+                .filter(|(x, _)| product_codes.contains(x))
+                .collect()
+        } else {
+            vec![]
+        };
+    Ok(())
+}
+
+pub(crate) async fn protected_market_order<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    side: Side,
+    size: Decimal,
+    max_slippage: Option<Decimal>,
+) -> Result<SendChildOrder> {
+    let Some(max_slippage) = max_slippage else {
+        return Ok(SendChildOrder {
+            child_order_type: ChildOrderType::Market,
+            product_code,
+            side,
+            size,
+            minute_to_expire: None,
+            time_in_force: None,
+        });
+    };
+    let protection = SlippageProtection {
+        max_slippage,
+        action: SlippageAction::Limit,
+    };
+    Ok(
+        slippage_protected_order(api, product_code, side, size, &protection)
+            .await?
+            .request,
+    )
+}
@@ -1,61 +1,71 @@
+pub mod anonymize;
 pub mod api;
-pub mod entity;
+pub mod backtest;
+pub mod balance_watcher;
+pub mod bracket;
+pub mod candle;
+#[cfg(feature = "test-util")]
+pub mod chaos;
+pub mod clock;
+pub mod collateral_watcher;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod dca;
+pub mod depth;
+pub mod equity;
+pub mod error;
+pub mod events;
+pub mod exchange;
+pub mod execution_quality;
+pub mod executions;
+pub mod export;
+pub mod exposure;
+pub mod fees;
+pub mod fill_reconciler;
+pub mod grid;
+pub mod har;
+pub mod history;
+pub mod integrity;
+pub mod journal;
+pub mod kill_switch;
+pub mod margin_monitor;
+pub mod market_state;
+pub mod markets;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod oco;
+pub mod order_manager;
+pub mod orders;
+pub mod pager;
+pub mod peg;
+pub mod pnl;
+pub mod poller;
+pub mod portfolio;
+pub mod position_tracker;
+pub mod queue;
+pub mod rate_limit;
+mod redact;
+pub mod risk;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod sfd;
+pub mod shutdown;
+pub mod signing;
+pub mod sim;
+pub mod sink;
+pub mod sizing;
+pub mod stats;
+pub mod stop_loss;
+pub mod storage;
+pub mod strategy;
+#[cfg(feature = "stub-server")]
+pub mod stub_server;
+pub mod supervisor;
+pub mod swap;
+pub mod tax;
+pub mod trail;
+#[cfg(feature = "test-util")]
+pub mod vcr;
+pub mod vwap;
 
-pub mod deserializer {
-    use chrono::{DateTime, Utc};
-    use core::fmt;
-    use serde::de;
-
-    pub(super) struct TimeStampVisitor;
-
-    impl<'de> de::Visitor<'de> for TimeStampVisitor {
-        type Value = DateTime<Utc>;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a UTC or custom UTC(2015-07-08T02:50:59.97)")
-        }
-
-        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            use std::str::FromStr;
-            match DateTime::<Utc>::from_str(value) {
-                Ok(datetime) => Ok(datetime),
-                Err(_) => {
-                    let value = format!("{value}+00:00");
-                    DateTime::<Utc>::from_str(&value).map_err(de::Error::custom)
-                }
-            }
-        }
-    }
-
-    pub mod timestamp {
-        use super::TimeStampVisitor;
-        use chrono::{DateTime, Utc};
-        use serde::de;
-
-        pub fn deserialize<'de, D>(d: D) -> Result<DateTime<Utc>, D::Error>
-        where
-            D: de::Deserializer<'de>,
-        {
-            d.deserialize_str(TimeStampVisitor)
-        }
-    }
-
-    pub mod timestamp_option {
-        use chrono::{DateTime, Utc};
-        use serde::de;
-
-        pub fn deserialize<'de, D>(d: D) -> Result<Option<DateTime<Utc>>, D::Error>
-        where
-            D: de::Deserializer<'de>,
-        {
-            use serde::Deserialize;
-            #[derive(Deserialize)]
-            struct Helper(#[serde(with = "super::timestamp")] DateTime<Utc>);
-            let helper = Option::deserialize(d)?;
-            Ok(helper.map(|Helper(x)| x))
-        }
-    }
-}
+pub use bitflyer_types::{board, deserializer, entity};
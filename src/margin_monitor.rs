@@ -0,0 +1,143 @@
+use crate::api::{BitflyerApi, Client, GetCollateral};
+use crate::entity::Collateral;
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarginEvent {
+    KeepRateBelow {
+        threshold: f64,
+        collateral: Collateral,
+    },
+    KeepRateRecovered {
+        threshold: f64,
+        collateral: Collateral,
+    },
+    MarginCall {
+        collateral: Collateral,
+    },
+    MarginCallResolved {
+        collateral: Collateral,
+    },
+}
+
+type Callback = Box<dyn Fn(&MarginEvent) + Send + Sync>;
+
+pub struct MarginMonitor<A = Client> {
+    client: Arc<A>,
+    thresholds: Vec<f64>,
+    callbacks: Mutex<Vec<Callback>>,
+    state: Mutex<MonitorState>,
+    events: broadcast::Sender<MarginEvent>,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    breached: Vec<f64>,
+    margin_call: bool,
+    last: Option<Collateral>,
+}
+
+impl<A> std::fmt::Debug for MarginMonitor<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MarginMonitor {{ thresholds: {:?} }}", self.thresholds)
+    }
+}
+
+impl<A: BitflyerApi + 'static> MarginMonitor<A> {
+    pub fn new(client: Arc<A>, mut thresholds: Vec<f64>) -> Self {
+        thresholds.sort_by(|a, b| b.total_cmp(a));
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            thresholds,
+            callbacks: Mutex::new(vec![]),
+            state: Mutex::new(MonitorState::default()),
+            events,
+        }
+    }
+
+    pub fn on_event<F>(&self, callback: F)
+    where
+        F: Fn(&MarginEvent) + Send + Sync + 'static,
+    {
+        self.callbacks.lock().unwrap().push(Box::new(callback));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MarginEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn last_collateral(&self) -> Option<Collateral> {
+        self.state.lock().unwrap().last.clone()
+    }
+
+    pub fn evaluate(&self, collateral: &Collateral) -> Vec<MarginEvent> {
+        let mut events = vec![];
+        let mut state = self.state.lock().unwrap();
+        // keep_rate is 0 when there is no open position.
+        let has_position = !collateral.require_collateral.is_zero();
+        for &threshold in &self.thresholds {
+            let below = has_position && collateral.keep_rate < threshold;
+            let breached = state.breached.contains(&threshold);
+            if below && !breached {
+                state.breached.push(threshold);
+                events.push(MarginEvent::KeepRateBelow {
+                    threshold,
+                    collateral: collateral.clone(),
+                });
+            } else if !below && breached {
+                state.breached.retain(|x| *x != threshold);
+                events.push(MarginEvent::KeepRateRecovered {
+                    threshold,
+                    collateral: collateral.clone(),
+                });
+            }
+        }
+
+        let margin_call = !collateral.margin_call_amount.is_zero();
+        if margin_call && !state.margin_call {
+            events.push(MarginEvent::MarginCall {
+                collateral: collateral.clone(),
+            });
+        } else if !margin_call && state.margin_call {
+            events.push(MarginEvent::MarginCallResolved {
+                collateral: collateral.clone(),
+            });
+        }
+        state.margin_call = margin_call;
+        state.last = Some(collateral.clone());
+        drop(state);
+
+        let callbacks = self.callbacks.lock().unwrap();
+        for event in &events {
+            for callback in callbacks.iter() {
+                callback(event);
+            }
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    pub async fn poll(&self) -> Result<Vec<MarginEvent>> {
+        let collateral = self.client.send(GetCollateral).await?;
+        Ok(self.evaluate(&collateral))
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let monitor = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = monitor.poll().await {
+                    tracing::warn!("failed to poll collateral: {e:?}");
+                }
+            }
+        })
+    }
+}
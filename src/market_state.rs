@@ -0,0 +1,153 @@
+use crate::entity::{BoardState, Health, ProductCode, State};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClosedMarketAction {
+    Refuse,
+    // Hold the order until the market is running again or the timeout elapses.
+    Queue {
+        timeout: Duration,
+        poll_interval: Duration,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketNotRunning {
+    pub product_code: ProductCode,
+    pub state: State,
+    pub health: Health,
+}
+
+impl std::fmt::Display for MarketNotRunning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "market is not accepting orders: {} state -> {:?}, health -> {:?}",
+            self.product_code, self.state, self.health
+        )
+    }
+}
+
+impl std::error::Error for MarketNotRunning {}
+
+#[derive(Debug)]
+pub struct MarketStateGuard {
+    action: ClosedMarketAction,
+    max_age: Duration,
+    states: Mutex<HashMap<ProductCode, (BoardState, Instant)>>,
+    overridden: AtomicBool,
+}
+
+impl MarketStateGuard {
+    pub fn new(action: ClosedMarketAction) -> Self {
+        Self {
+            action,
+            max_age: Duration::from_secs(5),
+            states: Mutex::new(HashMap::new()),
+            overridden: AtomicBool::new(false),
+        }
+    }
+
+    // Cached states older than this are fetched again before an order is sent.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn action(&self) -> ClosedMarketAction {
+        self.action
+    }
+
+    // While overridden, orders are sent regardless of the market state.
+    pub fn set_override(&self, overridden: bool) {
+        self.overridden.store(overridden, Ordering::Relaxed);
+    }
+
+    pub fn is_overridden(&self) -> bool {
+        self.overridden.load(Ordering::Relaxed)
+    }
+
+    // Feeds a state from polling or a realtime channel.
+    pub fn on_board_state(&self, product_code: ProductCode, state: BoardState) {
+        self.states
+            .lock()
+            .unwrap()
+            .insert(product_code, (state, Instant::now()));
+    }
+
+    pub fn board_state(&self, product_code: &ProductCode) -> Option<BoardState> {
+        self.states
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .map(|(x, _)| x.clone())
+    }
+
+    fn fresh_state(&self, product_code: &ProductCode) -> Option<BoardState> {
+        self.states
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .filter(|(_, at)| at.elapsed() < self.max_age)
+            .map(|(x, _)| x.clone())
+    }
+
+    async fn current_state<F, Fut>(
+        &self,
+        product_code: &ProductCode,
+        fetch: &F,
+    ) -> Result<BoardState>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<BoardState>>,
+    {
+        if let Some(state) = self.fresh_state(product_code) {
+            return Ok(state);
+        }
+        let state = fetch().await?;
+        self.on_board_state(product_code.clone(), state.clone());
+        Ok(state)
+    }
+
+    pub async fn admit<F, Fut>(&self, product_code: &ProductCode, fetch: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<BoardState>>,
+    {
+        let deadline = match self.action {
+            ClosedMarketAction::Refuse => None,
+            ClosedMarketAction::Queue { timeout, .. } => Some(Instant::now() + timeout),
+        };
+        loop {
+            if self.is_overridden() {
+                return Ok(());
+            }
+            let state = self.current_state(product_code, &fetch).await?;
+            if state.is_orderable() {
+                return Ok(());
+            }
+            let refused = MarketNotRunning {
+                product_code: product_code.clone(),
+                state: state.state(),
+                health: state.health(),
+            };
+            let (Some(deadline), ClosedMarketAction::Queue { poll_interval, .. }) =
+                (deadline, self.action)
+            else {
+                return Err(refused.into());
+            };
+            if Instant::now() >= deadline {
+                return Err(refused.into());
+            }
+            tracing::info!("{refused}. waiting for the market to reopen");
+            tokio::time::sleep(poll_interval.min(deadline - Instant::now())).await;
+            // Force a fresh fetch on the next round.
+            self.states.lock().unwrap().remove(product_code);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::api::{BitflyerApi, Client, GetBoardHealth, GetBoardState, GetMarkets};
+use crate::entity::{BoardHealth, BoardState, Market, ProductCode};
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T: Clone> Cached<T> {
+    fn fresh(&self, ttl: Duration) -> Option<T> {
+        (self.fetched_at.elapsed() < ttl).then(|| self.value.clone())
+    }
+}
+
+// Markets, health and board states rarely change, so lookups are served from a TTL cache.
+#[derive(Debug)]
+pub struct MarketCatalog<A = Client> {
+    client: Arc<A>,
+    ttl: Duration,
+    markets: Mutex<Option<Cached<Vec<Market>>>>,
+    health: Mutex<HashMap<ProductCode, Cached<BoardHealth>>>,
+    states: Mutex<HashMap<ProductCode, Cached<BoardState>>>,
+}
+
+impl<A: BitflyerApi + 'static> MarketCatalog<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        Self {
+            client,
+            ttl: Duration::from_secs(300),
+            markets: Mutex::new(None),
+            health: Mutex::new(HashMap::new()),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub async fn markets(&self) -> Result<Vec<Market>> {
+        let cached = self
+            .markets
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|x| x.fresh(self.ttl));
+        match cached {
+            Some(markets) => Ok(markets),
+            None => self.refresh_markets().await,
+        }
+    }
+
+    pub async fn refresh_markets(&self) -> Result<Vec<Market>> {
+        let markets = self.client.send(GetMarkets).await?;
+        *self.markets.lock().unwrap() = Some(Cached {
+            value: markets.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok(markets)
+    }
+
+    pub async fn market(&self, product_code: &ProductCode) -> Result<Option<Market>> {
+        Ok(self
+            .markets()
+            .await?
+            .into_iter()
+            .find(|x| x.product_code() == product_code))
+    }
+
+    // Accepts either a product code such as `BTC_JPY` or an alias such as `BTCJPY_MAT1WK`.
+    pub async fn resolve(&self, name: &str) -> Result<Option<Market>> {
+        Ok(self.markets().await?.into_iter().find(|x| {
+            x.alias() == Some(name)
+                || (*x.product_code() != ProductCode::Other && x.product_code().to_string() == name)
+        }))
+    }
+
+    pub async fn health(&self, product_code: &ProductCode) -> Result<BoardHealth> {
+        let cached = self
+            .health
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .and_then(|x| x.fresh(self.ttl));
+        if let Some(health) = cached {
+            return Ok(health);
+        }
+        let health = self
+            .client
+            .send(GetBoardHealth {
+                product_code: Some(product_code.clone()),
+            })
+            .await?;
+        self.health.lock().unwrap().insert(
+            product_code.clone(),
+            Cached {
+                value: health,
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(health)
+    }
+
+    pub async fn board_state(&self, product_code: &ProductCode) -> Result<BoardState> {
+        let cached = self
+            .states
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .and_then(|x| x.fresh(self.ttl));
+        if let Some(state) = cached {
+            return Ok(state);
+        }
+        let state = self
+            .client
+            .send(GetBoardState {
+                product_code: Some(product_code.clone()),
+            })
+            .await?;
+        self.states.lock().unwrap().insert(
+            product_code.clone(),
+            Cached {
+                value: state.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(state)
+    }
+
+    pub fn invalidate(&self) {
+        *self.markets.lock().unwrap() = None;
+        self.health.lock().unwrap().clear();
+        self.states.lock().unwrap().clear();
+    }
+
+    // Reloads the markets and every product whose health or state has been looked up.
+    pub async fn refresh(&self) -> Result<()> {
+        self.refresh_markets().await?;
+        let products: Vec<ProductCode> = {
+            let health = self.health.lock().unwrap();
+            let states = self.states.lock().unwrap();
+            let mut products: Vec<ProductCode> = health.keys().cloned().collect();
+            products.extend(states.keys().filter(|x| !health.contains_key(x)).cloned());
+            products
+        };
+        for product_code in products {
+            self.health.lock().unwrap().remove(&product_code);
+            self.states.lock().unwrap().remove(&product_code);
+            self.health(&product_code).await?;
+            self.board_state(&product_code).await?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn_refresh(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let catalog = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = catalog.refresh().await {
+                    tracing::warn!("failed to refresh the market catalog: {e:?}");
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,380 @@
+use crate::api::{ApiRequest, BitflyerApi, RawRequest};
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Sample bodies for every endpoint, shaped like the live API's responses.
+pub fn fixture(path: &str) -> Option<&'static str> {
+    let body = match path {
+        "/v1/markets" => include_str!("mock/fixtures/markets.json"),
+        "/v1/board" => include_str!("mock/fixtures/board.json"),
+        "/v1/ticker" => include_str!("mock/fixtures/ticker.json"),
+        "/v1/executions" => include_str!("mock/fixtures/executions.json"),
+        "/v1/getboardstate" => include_str!("mock/fixtures/getboardstate.json"),
+        "/v1/gethealth" => include_str!("mock/fixtures/gethealth.json"),
+        "/v1/me/getpermissions" => include_str!("mock/fixtures/me_getpermissions.json"),
+        "/v1/me/getbalance" => include_str!("mock/fixtures/me_getbalance.json"),
+        "/v1/me/getcollateral" => include_str!("mock/fixtures/me_getcollateral.json"),
+        "/v1/me/getcollateralaccounts" => {
+            include_str!("mock/fixtures/me_getcollateralaccounts.json")
+        }
+        "/v1/me/sendchildorder" => include_str!("mock/fixtures/me_sendchildorder.json"),
+        "/v1/me/sendparentorder" => include_str!("mock/fixtures/me_sendparentorder.json"),
+        "/v1/me/cancelchildorder" | "/v1/me/cancelparentorder" | "/v1/me/cancelallchildorders" => {
+            ""
+        }
+        "/v1/me/getchildorders" => include_str!("mock/fixtures/me_getchildorders.json"),
+        "/v1/me/getparentorders" => include_str!("mock/fixtures/me_getparentorders.json"),
+        "/v1/me/getparentorder" => include_str!("mock/fixtures/me_getparentorder.json"),
+        "/v1/me/getpositions" => include_str!("mock/fixtures/me_getpositions.json"),
+        "/v1/me/getexecutions" => include_str!("mock/fixtures/me_getexecutions.json"),
+        "/v1/me/getbalancehistory" => include_str!("mock/fixtures/me_getbalancehistory.json"),
+        "/v1/me/getcoinins" => include_str!("mock/fixtures/me_getcoinins.json"),
+        "/v1/me/getcoinouts" => include_str!("mock/fixtures/me_getcoinouts.json"),
+        _ => return None,
+    };
+    Some(body)
+}
+
+// The fixture of `T` parsed into its response type.
+pub fn load_fixture<T: ApiRequest>() -> Result<T::Response> {
+    let body = fixture(T::PATH).ok_or_else(|| anyhow!("no fixture for {}", T::PATH))?;
+    T::deserialize_response_body(body)
+}
+
+// A failure injected in place of a response. Errors carry the fault, so code under test can
+// tell them apart with `downcast_ref::<Fault>()`, and read like the client's own errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    RateLimited,
+    ServerError(StatusCode),
+    // Fails after waiting, like a request that never got an answer.
+    Timeout(Duration),
+    Maintenance,
+}
+
+impl Fault {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Fault::RateLimited => Some(StatusCode::TOO_MANY_REQUESTS),
+            Fault::ServerError(status) => Some(*status),
+            Fault::Timeout(_) => None,
+            Fault::Maintenance => Some(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::Timeout(duration) => write!(f, "operation timed out after {duration:?}"),
+            Fault::Maintenance => write!(
+                f,
+                "request is failed: status -> {}\nresponse -> The system is under maintenance.",
+                StatusCode::SERVICE_UNAVAILABLE
+            ),
+            _ => write!(
+                f,
+                "request is failed: status -> {}",
+                self.status().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+// When an injected fault fires. Calls are counted from zero per injection, among the
+// requests it applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    Always,
+    // Every `n`th call: the `n - 1`th, the `2n - 1`th and so on.
+    Every(usize),
+    Calls(Range<usize>),
+    Probability(f64),
+}
+
+#[derive(Clone, Debug)]
+struct Injection {
+    path: Option<&'static str>,
+    fault: Fault,
+    schedule: Schedule,
+    calls: usize,
+}
+
+// splitmix64, so probabilistic faults repeat for a given seed.
+pub(crate) fn next_random(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+type TypedResponse = Arc<dyn Fn() -> Box<dyn Any + Send> + Send + Sync>;
+
+#[derive(Clone)]
+enum Canned {
+    Typed(TypedResponse),
+    Json(String),
+    Error(String),
+}
+
+#[derive(Clone, Default)]
+struct Responses {
+    once: VecDeque<Canned>,
+    always: Option<Canned>,
+}
+
+// Answers requests from canned responses keyed by request type and records every call.
+// One-shot responses are used first, in order, then the persistent one, then the bundled
+// fixture when enabled; a request with none of them fails. Injected faults and the rate
+// limit are checked before any of them, in the order they were added.
+#[derive(Default)]
+pub struct MockBitflyer {
+    responses: Mutex<HashMap<&'static str, Responses>>,
+    calls: Mutex<Vec<RawRequest>>,
+    fixtures: bool,
+    injections: Mutex<Vec<Injection>>,
+    seed: Mutex<u64>,
+    rate_limit: Option<(usize, Duration)>,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+impl std::fmt::Debug for MockBitflyer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockBitflyer")
+            .field("calls", &self.calls.lock().unwrap().len())
+            .field("fixtures", &self.fixtures)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockBitflyer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Falls back to the bundled fixtures for requests without a canned response.
+    pub fn with_fixtures(mut self) -> Self {
+        self.fixtures = true;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Mutex::new(seed);
+        self
+    }
+
+    // Answers with `Fault::RateLimited` once more than `max_requests` arrive within `per`,
+    // like bitFlyer's per-IP limit. Rejected requests count too.
+    pub fn with_rate_limit(mut self, max_requests: usize, per: Duration) -> Self {
+        self.rate_limit = Some((max_requests, per));
+        self
+    }
+
+    fn add_injection(&self, path: Option<&'static str>, fault: Fault, schedule: Schedule) {
+        self.injections.lock().unwrap().push(Injection {
+            path,
+            fault,
+            schedule,
+            calls: 0,
+        });
+    }
+
+    // Applies to every request.
+    pub fn inject(&self, fault: Fault, schedule: Schedule) -> &Self {
+        self.add_injection(None, fault, schedule);
+        self
+    }
+
+    pub fn inject_for<T: ApiRequest>(&self, fault: Fault, schedule: Schedule) -> &Self {
+        self.add_injection(Some(T::PATH), fault, schedule);
+        self
+    }
+
+    pub fn clear_faults(&self) {
+        self.injections.lock().unwrap().clear();
+    }
+
+    fn rate_limited(&self) -> bool {
+        let Some((max_requests, per)) = self.rate_limit else {
+            return false;
+        };
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        while recent
+            .front()
+            .is_some_and(|x| now.duration_since(*x) >= per)
+        {
+            recent.pop_front();
+        }
+        recent.push_back(now);
+        recent.len() > max_requests
+    }
+
+    // Every matching injection counts the call, even when an earlier one already fired.
+    fn fault(&self, path: &str) -> Option<Fault> {
+        if self.rate_limited() {
+            return Some(Fault::RateLimited);
+        }
+        let mut seed = self.seed.lock().unwrap();
+        let mut fired = None;
+        for injection in self.injections.lock().unwrap().iter_mut() {
+            if injection.path.is_some_and(|x| x != path) {
+                continue;
+            }
+            let call = injection.calls;
+            injection.calls += 1;
+            let fires = match &injection.schedule {
+                Schedule::Always => true,
+                Schedule::Every(n) => *n > 0 && (call + 1) % n == 0,
+                Schedule::Calls(range) => range.contains(&call),
+                Schedule::Probability(p) => next_random(&mut seed) < *p,
+            };
+            if fires && fired.is_none() {
+                fired = Some(injection.fault.clone());
+            }
+        }
+        fired
+    }
+
+    fn push<T: ApiRequest>(&self, canned: Canned, once: bool) {
+        let mut responses = self.responses.lock().unwrap();
+        let entry = responses.entry(T::PATH).or_default();
+        if once {
+            entry.once.push_back(canned);
+        } else {
+            entry.always = Some(canned);
+        }
+    }
+
+    fn typed<T: ApiRequest>(response: T::Response) -> Canned
+    where
+        T::Response: Clone + Send + Sync + 'static,
+    {
+        Canned::Typed(Arc::new(move || Box::new(response.clone())))
+    }
+
+    pub fn respond<T: ApiRequest>(&self, response: T::Response) -> &Self
+    where
+        T::Response: Clone + Send + Sync + 'static,
+    {
+        self.push::<T>(Self::typed::<T>(response), false);
+        self
+    }
+
+    pub fn respond_once<T: ApiRequest>(&self, response: T::Response) -> &Self
+    where
+        T::Response: Clone + Send + Sync + 'static,
+    {
+        self.push::<T>(Self::typed::<T>(response), true);
+        self
+    }
+
+    // The body is parsed like a live response, including the request's own parsing rules.
+    pub fn respond_json<T: ApiRequest>(&self, body: impl Into<String>) -> &Self {
+        self.push::<T>(Canned::Json(body.into()), false);
+        self
+    }
+
+    pub fn respond_json_once<T: ApiRequest>(&self, body: impl Into<String>) -> &Self {
+        self.push::<T>(Canned::Json(body.into()), true);
+        self
+    }
+
+    pub fn fail<T: ApiRequest>(&self, message: impl Into<String>) -> &Self {
+        self.push::<T>(Canned::Error(message.into()), false);
+        self
+    }
+
+    pub fn fail_once<T: ApiRequest>(&self, message: impl Into<String>) -> &Self {
+        self.push::<T>(Canned::Error(message.into()), true);
+        self
+    }
+
+    pub fn reset<T: ApiRequest>(&self) {
+        self.responses.lock().unwrap().remove(T::PATH);
+    }
+
+    pub fn calls(&self) -> Vec<RawRequest> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn calls_to<T: ApiRequest>(&self) -> Vec<RawRequest> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|x| x.path == T::PATH)
+            .cloned()
+            .collect()
+    }
+
+    pub fn call_count<T: ApiRequest>(&self) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|x| x.path == T::PATH)
+            .count()
+    }
+
+    // Bodies of the recorded POST requests of type `T`, e.g. every `SendChildOrder` sent.
+    pub fn sent<T: ApiRequest + DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.calls_to::<T>()
+            .iter()
+            .filter_map(|x| x.body.as_deref())
+            .map(|x| Ok(serde_json::from_str(x)?))
+            .collect()
+    }
+
+    pub fn clear_calls(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    fn next(&self, path: &str) -> Option<Canned> {
+        let mut responses = self.responses.lock().unwrap();
+        let entry = responses.get_mut(path)?;
+        entry.once.pop_front().or_else(|| entry.always.clone())
+    }
+}
+
+impl BitflyerApi for MockBitflyer {
+    async fn send<T>(&self, request: T) -> Result<T::Response>
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        self.calls.lock().unwrap().push(RawRequest::new(&request)?);
+        if let Some(fault) = self.fault(T::PATH) {
+            if let Fault::Timeout(duration) = fault {
+                tokio::time::sleep(duration).await;
+            }
+            return Err(fault.into());
+        }
+        let canned = match self.next(T::PATH) {
+            Some(canned) => canned,
+            None => match fixture(T::PATH).filter(|_| self.fixtures) {
+                Some(body) => Canned::Json(body.to_string()),
+                None => return Err(anyhow!("no mock response for {request:?}")),
+            },
+        };
+        match canned {
+            Canned::Typed(response) => response()
+                .downcast::<T::Response>()
+                .map(|x| *x)
+                .map_err(|_| anyhow!("mock response for {} has the wrong type", T::PATH)),
+            Canned::Json(body) => request.parse_response(&body),
+            Canned::Error(message) => Err(anyhow!(message)),
+        }
+    }
+}
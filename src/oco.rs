@@ -0,0 +1,249 @@
+use crate::api::{BitflyerApi, CancelChildOrder, Client, SendChildOrder};
+use crate::entity::{ChildOrder, ProductCode};
+use crate::orders::child_order;
+use crate::shutdown::ShutdownHandle;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OcoLeg {
+    pub child_order_acceptance_id: String,
+    pub request: SendChildOrder,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OcoPair {
+    pub id: String,
+    pub product_code: ProductCode,
+    pub legs: [OcoLeg; 2],
+}
+
+impl OcoPair {
+    fn leg(&self, child_order_acceptance_id: &str) -> Option<usize> {
+        self.legs
+            .iter()
+            .position(|x| x.child_order_acceptance_id == child_order_acceptance_id)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OcoEvent {
+    Filled {
+        pair: OcoPair,
+        filled: ChildOrder,
+        canceled: String,
+    },
+    Closed {
+        pair: OcoPair,
+        order: ChildOrder,
+        canceled: String,
+    },
+    CancelFailed {
+        pair: OcoPair,
+        child_order_acceptance_id: String,
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct OcoManager<A = Client> {
+    client: Arc<A>,
+    pairs: Mutex<Vec<OcoPair>>,
+    path: Option<PathBuf>,
+    events: broadcast::Sender<OcoEvent>,
+}
+
+impl<A: BitflyerApi + 'static> OcoManager<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            pairs: Mutex::new(vec![]),
+            path: None,
+            events,
+        }
+    }
+
+    pub fn with_persistence(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            let pairs: Vec<OcoPair> = serde_json::from_slice(&std::fs::read(&path)?)?;
+            *self.pairs.lock().unwrap() = pairs;
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OcoEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn pairs(&self) -> Vec<OcoPair> {
+        self.pairs.lock().unwrap().clone()
+    }
+
+    fn persist(&self, pairs: &[OcoPair]) -> Result<()> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_vec_pretty(pairs)?)?;
+        }
+        Ok(())
+    }
+
+    pub async fn place(
+        &self,
+        id: impl Into<String>,
+        first: SendChildOrder,
+        second: SendChildOrder,
+    ) -> Result<OcoPair> {
+        let id = id.into();
+        if first.product_code != second.product_code {
+            return Err(anyhow!(
+                "OCO legs must share a product: {} {}",
+                first.product_code,
+                second.product_code
+            ));
+        }
+        if self.pairs.lock().unwrap().iter().any(|x| x.id == id) {
+            return Err(anyhow!("OCO is already placed: {id}"));
+        }
+        let product_code = first.product_code.clone();
+        let first_id = self
+            .client
+            .send(first.clone())
+            .await?
+            .child_order_acceptance_id;
+        let second_id = match self.client.send(second.clone()).await {
+            Ok(response) => response.child_order_acceptance_id,
+            Err(e) => {
+                self.cancel_leg(&product_code, &first_id).await?;
+                return Err(e.context("second OCO leg is rejected"));
+            }
+        };
+        let pair = OcoPair {
+            id,
+            product_code,
+            legs: [
+                OcoLeg {
+                    child_order_acceptance_id: first_id,
+                    request: first,
+                },
+                OcoLeg {
+                    child_order_acceptance_id: second_id,
+                    request: second,
+                },
+            ],
+        };
+        let mut pairs = self.pairs.lock().unwrap();
+        pairs.push(pair.clone());
+        self.persist(&pairs)?;
+        Ok(pair)
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<Option<OcoPair>> {
+        let Some(pair) = self.remove(|x| x.id == id)? else {
+            return Ok(None);
+        };
+        for leg in &pair.legs {
+            self.cancel_leg(&pair.product_code, &leg.child_order_acceptance_id)
+                .await?;
+        }
+        Ok(Some(pair))
+    }
+
+    fn remove(&self, f: impl Fn(&OcoPair) -> bool) -> Result<Option<OcoPair>> {
+        let mut pairs = self.pairs.lock().unwrap();
+        let removed = pairs.iter().position(f).map(|i| pairs.remove(i));
+        if removed.is_some() {
+            self.persist(&pairs)?;
+        }
+        Ok(removed)
+    }
+
+    async fn cancel_leg(&self, product_code: &ProductCode, acceptance_id: &str) -> Result<()> {
+        self.client
+            .send(CancelChildOrder {
+                product_code: product_code.clone(),
+                child_order_acceptance_id: acceptance_id.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Any execution on one leg cancels the other, as does the leg ending without a fill.
+    pub async fn on_child_order(&self, order: &ChildOrder) -> Result<Option<OcoEvent>> {
+        let acceptance_id = &order.child_order_acceptance_id;
+        let filled = !order.executed_size.is_zero();
+        if !filled && order.child_order_state.is_active() {
+            return Ok(None);
+        }
+        let Some(pair) = self.remove(|x| x.leg(acceptance_id).is_some())? else {
+            return Ok(None);
+        };
+        let leg = pair.leg(acceptance_id).unwrap_or_default();
+        let sibling = pair.legs[1 - leg].child_order_acceptance_id.clone();
+        let event = match self.cancel_leg(&pair.product_code, &sibling).await {
+            Ok(()) if filled => OcoEvent::Filled {
+                pair,
+                filled: order.clone(),
+                canceled: sibling,
+            },
+            Ok(()) => OcoEvent::Closed {
+                pair,
+                order: order.clone(),
+                canceled: sibling,
+            },
+            Err(e) => {
+                let mut pairs = self.pairs.lock().unwrap();
+                pairs.push(pair.clone());
+                self.persist(&pairs)?;
+                OcoEvent::CancelFailed {
+                    pair,
+                    child_order_acceptance_id: sibling,
+                    error: format!("{e:?}"),
+                }
+            }
+        };
+        let _ = self.events.send(event.clone());
+        Ok(Some(event))
+    }
+
+    pub async fn poll(&self) -> Result<Vec<OcoEvent>> {
+        let mut events = vec![];
+        for pair in self.pairs() {
+            for leg in &pair.legs {
+                let Some(order) = child_order(
+                    self.client.as_ref(),
+                    &pair.product_code,
+                    &leg.child_order_acceptance_id,
+                )
+                .await?
+                else {
+                    continue;
+                };
+                if let Some(event) = self.on_child_order(&order).await? {
+                    events.push(event);
+                    break;
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let manager = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = manager.poll().await {
+                    tracing::warn!("failed to poll OCO orders: {e:?}");
+                }
+            }
+        })
+    }
+}
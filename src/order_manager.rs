@@ -0,0 +1,408 @@
+use crate::api::{BitflyerApi, CancelChildOrder, Client, GetChildOrders, SendChildOrder};
+use crate::board::OrderBook;
+use crate::entity::{ChildOrder, ChildOrderType, Execution, OrderState, ProductCode};
+use crate::orders::{ensure_order, EnsureOutcome};
+use crate::queue::{QueueEstimator, QueuePosition};
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 1024;
+const POLL_COUNT: u64 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    Pending,
+    Active,
+    PartiallyFilled,
+    Completed,
+    Canceled,
+    Expired,
+    Rejected,
+}
+
+impl OrderStatus {
+    pub fn from_child_order(order: &ChildOrder) -> Self {
+        match order.child_order_state {
+            OrderState::Active if order.executed_size.is_zero() => OrderStatus::Active,
+            OrderState::Active => OrderStatus::PartiallyFilled,
+            OrderState::Completed => OrderStatus::Completed,
+            OrderState::Canceled => OrderStatus::Canceled,
+            OrderState::Expired => OrderStatus::Expired,
+            OrderState::Rejected => OrderStatus::Rejected,
+            _ => OrderStatus::Pending,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Pending | OrderStatus::Active | OrderStatus::PartiallyFilled
+        )
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !self.is_open()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedOrder {
+    pub acceptance_id: String,
+    pub product_code: ProductCode,
+    pub request: Option<SendChildOrder>,
+    pub submitted_at: DateTime<Utc>,
+    pub status: OrderStatus,
+    pub order: Option<ChildOrder>,
+    pub tag: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrderEvent {
+    Submitted {
+        acceptance_id: String,
+        request: SendChildOrder,
+    },
+    CancelRequested {
+        acceptance_id: String,
+    },
+    StatusChanged {
+        acceptance_id: String,
+        previous: OrderStatus,
+        current: OrderStatus,
+        order: ChildOrder,
+    },
+}
+
+impl OrderEvent {
+    pub fn acceptance_id(&self) -> &str {
+        match self {
+            OrderEvent::Submitted { acceptance_id, .. }
+            | OrderEvent::CancelRequested { acceptance_id }
+            | OrderEvent::StatusChanged { acceptance_id, .. } => acceptance_id,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OrderManager<A = Client> {
+    client: Arc<A>,
+    orders: Mutex<HashMap<String, TrackedOrder>>,
+    queue: Mutex<QueueEstimator>,
+    events: broadcast::Sender<OrderEvent>,
+}
+
+impl<A: BitflyerApi + 'static> OrderManager<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            orders: Mutex::new(HashMap::new()),
+            queue: Mutex::new(QueueEstimator::new()),
+            events,
+        }
+    }
+
+    pub fn client(&self) -> &Arc<A> {
+        &self.client
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderEvent> {
+        self.events.subscribe()
+    }
+
+    pub async fn submit(&self, request: SendChildOrder) -> Result<String> {
+        let response = self.client.send(request.clone()).await?;
+        let acceptance_id = response.child_order_acceptance_id;
+        self.track(
+            acceptance_id.clone(),
+            request.product_code.clone(),
+            Some(request.clone()),
+        );
+        let _ = self.events.send(OrderEvent::Submitted {
+            acceptance_id: acceptance_id.clone(),
+            request,
+        });
+        Ok(acceptance_id)
+    }
+
+    pub async fn cancel(&self, acceptance_id: &str) -> Result<()> {
+        let product_code = self
+            .order(acceptance_id)
+            .map(|x| x.product_code)
+            .ok_or_else(|| anyhow::anyhow!("order is not tracked: {acceptance_id}"))?;
+        self.client
+            .send(CancelChildOrder {
+                product_code,
+                child_order_acceptance_id: acceptance_id.to_string(),
+            })
+            .await?;
+        let _ = self.events.send(OrderEvent::CancelRequested {
+            acceptance_id: acceptance_id.to_string(),
+        });
+        Ok(())
+    }
+
+    pub fn track(
+        &self,
+        acceptance_id: String,
+        product_code: ProductCode,
+        request: Option<SendChildOrder>,
+    ) {
+        if let Some(request) = &request {
+            if let ChildOrderType::Limit { price } = request.child_order_type {
+                self.queue.lock().unwrap().track(
+                    acceptance_id.clone(),
+                    product_code.clone(),
+                    request.side,
+                    price,
+                    request.size,
+                );
+            }
+        }
+        let mut orders = self.orders.lock().unwrap();
+        orders
+            .entry(acceptance_id.clone())
+            .or_insert_with(|| TrackedOrder {
+                acceptance_id,
+                product_code,
+                request,
+                submitted_at: Utc::now(),
+                status: OrderStatus::Pending,
+                order: None,
+                tag: None,
+            });
+    }
+
+    pub fn tagged(&self, tag: &str) -> Option<TrackedOrder> {
+        self.orders
+            .lock()
+            .unwrap()
+            .values()
+            .find(|x| x.status.is_open() && x.tag.as_deref() == Some(tag))
+            .cloned()
+    }
+
+    pub async fn ensure_order(
+        &self,
+        request: SendChildOrder,
+        tag: Option<String>,
+    ) -> Result<EnsureOutcome> {
+        if let Some(tracked) = tag.as_deref().and_then(|x| self.tagged(x)) {
+            return Ok(EnsureOutcome::Existing {
+                child_order_acceptance_id: tracked.acceptance_id,
+                order: tracked.order,
+            });
+        }
+        let outcome = ensure_order(self.client.as_ref(), request.clone()).await?;
+        let acceptance_id = outcome.child_order_acceptance_id().to_string();
+        self.track(
+            acceptance_id.clone(),
+            request.product_code.clone(),
+            Some(request.clone()),
+        );
+        let mut orders = self.orders.lock().unwrap();
+        if let Some(tracked) = orders.get_mut(&acceptance_id) {
+            tracked.tag = tag.or(tracked.tag.take());
+        }
+        drop(orders);
+        match &outcome {
+            EnsureOutcome::Existing {
+                order: Some(order), ..
+            } => {
+                self.update(order);
+            }
+            EnsureOutcome::Submitted { .. } => {
+                let _ = self.events.send(OrderEvent::Submitted {
+                    acceptance_id,
+                    request,
+                });
+            }
+            EnsureOutcome::Existing { order: None, .. } => {}
+        }
+        Ok(outcome)
+    }
+
+    pub fn forget(&self, acceptance_id: &str) -> Option<TrackedOrder> {
+        self.queue.lock().unwrap().forget(acceptance_id);
+        self.orders.lock().unwrap().remove(acceptance_id)
+    }
+
+    pub fn queue_position(&self, acceptance_id: &str) -> Option<QueuePosition> {
+        self.queue.lock().unwrap().position(acceptance_id).cloned()
+    }
+
+    pub fn on_book(&self, product_code: &ProductCode, book: &impl OrderBook) {
+        self.queue.lock().unwrap().on_book(product_code, book);
+    }
+
+    pub fn on_execution(&self, product_code: &ProductCode, execution: &Execution) {
+        self.queue
+            .lock()
+            .unwrap()
+            .on_execution(product_code, execution);
+    }
+
+    pub fn order(&self, acceptance_id: &str) -> Option<TrackedOrder> {
+        self.orders.lock().unwrap().get(acceptance_id).cloned()
+    }
+
+    pub fn order_status(&self, acceptance_id: &str) -> Option<OrderStatus> {
+        self.orders
+            .lock()
+            .unwrap()
+            .get(acceptance_id)
+            .map(|x| x.status)
+    }
+
+    pub fn orders(&self) -> Vec<TrackedOrder> {
+        self.orders.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn open_orders(&self) -> Vec<TrackedOrder> {
+        self.orders
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|x| x.status.is_open())
+            .cloned()
+            .collect()
+    }
+
+    pub fn update(&self, order: &ChildOrder) -> Option<OrderEvent> {
+        let current = OrderStatus::from_child_order(order);
+        let mut orders = self.orders.lock().unwrap();
+        let tracked = orders.get_mut(&order.child_order_acceptance_id)?;
+        let previous = tracked.status;
+        let changed = previous != current || tracked.order.as_ref() != Some(order);
+        tracked.status = current;
+        tracked.order = Some(order.clone());
+        drop(orders);
+        let mut queue = self.queue.lock().unwrap();
+        if current.is_terminal() {
+            queue.forget(&order.child_order_acceptance_id);
+        } else {
+            queue.set_remaining(&order.child_order_acceptance_id, order.outstanding_size);
+        }
+        drop(queue);
+        if !changed {
+            return None;
+        }
+        let event = OrderEvent::StatusChanged {
+            acceptance_id: order.child_order_acceptance_id.clone(),
+            previous,
+            current,
+            order: order.clone(),
+        };
+        let _ = self.events.send(event.clone());
+        Some(event)
+    }
+
+    pub async fn poll(&self) -> Result<()> {
+        let open = self.open_orders();
+        let mut products: Vec<ProductCode> = vec![];
+        for order in &open {
+            if !products.contains(&order.product_code) {
+                products.push(order.product_code.clone());
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for product_code in products {
+            let orders = self
+                .client
+                .send(GetChildOrders {
+                    product_code: Some(product_code),
+                    count: Some(POLL_COUNT),
+                    ..Default::default()
+                })
+                .await?;
+            for order in &orders {
+                seen.insert(order.child_order_acceptance_id.clone());
+                self.update(order);
+            }
+        }
+
+        for tracked in open {
+            if seen.contains(&tracked.acceptance_id) {
+                continue;
+            }
+            let orders = self
+                .client
+                .send(GetChildOrders {
+                    product_code: Some(tracked.product_code.clone()),
+                    child_order_acceptance_id: Some(tracked.acceptance_id.clone()),
+                    ..Default::default()
+                })
+                .await?;
+            for order in &orders {
+                self.update(order);
+            }
+        }
+        Ok(())
+    }
+
+    // Yields the order each time its status changes and ends once it is terminal.
+    pub fn watch(&self, acceptance_id: &str) -> impl Stream<Item = TrackedOrder> + Send + '_ {
+        let receiver = self.events.subscribe();
+        let initial = self.order(acceptance_id);
+        let acceptance_id = acceptance_id.to_string();
+        let finished = initial.is_none();
+        futures::stream::unfold(
+            (receiver, initial, None, finished),
+            move |(mut receiver, mut pending, mut last, finished)| {
+                let acceptance_id = acceptance_id.clone();
+                async move {
+                    if finished {
+                        return None;
+                    }
+                    loop {
+                        if let Some(order) = pending.take() {
+                            if last != Some(order.status) {
+                                last = Some(order.status);
+                                let finished = order.status.is_terminal();
+                                return Some((order, (receiver, None, last, finished)));
+                            }
+                        }
+                        match receiver.recv().await {
+                            Ok(OrderEvent::StatusChanged {
+                                acceptance_id: x,
+                                current,
+                                order,
+                                ..
+                            }) if x == acceptance_id => {
+                                pending = self.order(&acceptance_id).map(|mut x| {
+                                    x.status = current;
+                                    x.order = Some(order);
+                                    x
+                                });
+                            }
+                            Ok(_) => {}
+                            Err(broadcast::error::RecvError::Lagged(_)) => {
+                                pending = self.order(&acceptance_id);
+                            }
+                            Err(broadcast::error::RecvError::Closed) => return None,
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let manager = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = manager.poll().await {
+                    tracing::warn!("failed to poll child orders: {e:?}");
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,598 @@
+use crate::api::{
+    BitflyerApi, CancelChildOrder, GetBoard, GetChildOrders, GetParentOrder, GetParentOrders,
+    GetParentOrdersResponse, GetParentOrdersResponseParameter, GetPositions, SendChildOrder,
+};
+use crate::board::{ImpactEstimate, OrderBook};
+use crate::entity::{
+    ChildOrder, ChildOrderAcceptanceId, ChildOrderType, MinuteToExpire, OrderState,
+    ParentOrderConditionType, ParentOrderMethod, ProductCode, Side, TimeInForce,
+};
+use crate::kill_switch::{net_positions, protected_market_order};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::time::Duration;
+
+const CANCEL_CONFIRM_ATTEMPTS: usize = 20;
+const CANCEL_CONFIRM_INTERVAL: Duration = Duration::from_millis(500);
+const FILL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlippageAction {
+    Limit,
+    Refuse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlippageProtection {
+    pub max_slippage: Decimal,
+    pub action: SlippageAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtectedOrder {
+    pub request: SendChildOrder,
+    pub estimate: ImpactEstimate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlippageExceeded {
+    pub estimate: ImpactEstimate,
+    pub max_slippage: Decimal,
+}
+
+impl std::fmt::Display for SlippageExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let estimate = &self.estimate;
+        match (estimate.slippage(), estimate.best_price) {
+            (Some(slippage), Some(best)) if estimate.is_fully_filled() => write!(
+                f,
+                "estimated slippage {slippage} exceeds {} of the best price {best}",
+                self.max_slippage
+            ),
+            _ => write!(
+                f,
+                "board can't fill {} of {}",
+                estimate.unfilled_size(),
+                estimate.requested_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SlippageExceeded {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpenOrders {
+    pub child_orders: Vec<ChildOrder>,
+    pub parent_orders: Vec<GetParentOrdersResponseParameter>,
+}
+
+impl OpenOrders {
+    pub fn is_empty(&self) -> bool {
+        self.child_orders.is_empty() && self.parent_orders.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CloseOptions {
+    pub size: Option<Decimal>,
+    pub max_slippage: Option<Decimal>,
+    pub time_in_force: Option<TimeInForce>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClosePositionReport {
+    pub product_code: ProductCode,
+    pub position_size: Decimal,
+    pub orders: Vec<(SendChildOrder, String)>,
+}
+
+impl ClosePositionReport {
+    pub fn closed_size(&self) -> Decimal {
+        self.orders.iter().map(|(x, _)| x.size).sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnsureOutcome {
+    Existing {
+        child_order_acceptance_id: String,
+        order: Option<ChildOrder>,
+    },
+    Submitted {
+        child_order_acceptance_id: String,
+    },
+}
+
+impl EnsureOutcome {
+    pub fn child_order_acceptance_id(&self) -> &str {
+        match self {
+            EnsureOutcome::Existing {
+                child_order_acceptance_id,
+                ..
+            }
+            | EnsureOutcome::Submitted {
+                child_order_acceptance_id,
+            } => child_order_acceptance_id,
+        }
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        matches!(self, EnsureOutcome::Submitted { .. })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AmendOutcome {
+    Replaced {
+        canceled: ChildOrder,
+        child_order_acceptance_id: String,
+        size: Decimal,
+    },
+    Filled(ChildOrder),
+    NotActive(ChildOrder),
+}
+
+pub async fn child_order<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    child_order_acceptance_id: &str,
+) -> Result<Option<ChildOrder>> {
+    let orders = api
+        .send(GetChildOrders {
+            product_code: Some(product_code.clone()),
+            child_order_acceptance_id: Some(child_order_acceptance_id.to_string()),
+            ..Default::default()
+        })
+        .await?;
+    Ok(orders
+        .into_iter()
+        .find(|x| x.child_order_acceptance_id == child_order_acceptance_id))
+}
+
+// One parameter of a parent order and the child orders it placed, oldest first. Empty until
+// the parameter triggers, and for an OCO leg canceled because the other one filled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParentOrderLeg {
+    pub parameter: ParentOrderConditionType,
+    pub child_orders: Vec<ChildOrder>,
+}
+
+impl ParentOrderLeg {
+    pub fn is_triggered(&self) -> bool {
+        !self.child_orders.is_empty()
+    }
+
+    // The latest child order, whose state is the leg's.
+    pub fn child_order(&self) -> Option<&ChildOrder> {
+        self.child_orders.last()
+    }
+}
+
+// A parent order with its legs in the order of its parameters. Child orders are linked to the
+// first parameter they could have come from that has none yet, by product, side, size and
+// price; children that match no parameter are kept in `unmatched`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParentOrderDetail {
+    pub parent: GetParentOrdersResponse,
+    pub legs: Vec<ParentOrderLeg>,
+    pub unmatched: Vec<ChildOrder>,
+}
+
+fn parameters(method: &ParentOrderMethod) -> &[ParentOrderConditionType] {
+    match method {
+        ParentOrderMethod::Simple { parameters } => parameters,
+        ParentOrderMethod::Ifd { parameters } | ParentOrderMethod::Oco { parameters } => parameters,
+        ParentOrderMethod::Ifdoco { parameters } => parameters,
+    }
+}
+
+// Stops and trails place market orders, stop limits place limit orders at their price.
+fn spawns(parameter: &ParentOrderConditionType, order: &ChildOrder) -> bool {
+    use ParentOrderConditionType::*;
+    let (product_code, side, size, price) = match parameter {
+        Limit {
+            product_code,
+            side,
+            size,
+            price,
+        }
+        | StopLimit {
+            product_code,
+            side,
+            size,
+            price,
+            ..
+        } => (product_code, side, size, Some(*price)),
+        Market {
+            product_code,
+            side,
+            size,
+        }
+        | Stop {
+            product_code,
+            side,
+            size,
+            ..
+        }
+        | Trail {
+            product_code,
+            side,
+            size,
+            ..
+        } => (product_code, side, size, None),
+    };
+    let order_price = match order.child_order_type {
+        ChildOrderType::Limit { price } => Some(price),
+        ChildOrderType::Market => None,
+    };
+    *product_code == order.product_code
+        && *side == order.side
+        && *size == order.size
+        && price == order_price
+}
+
+pub async fn parent_order_detail<A: BitflyerApi>(
+    api: &A,
+    parent_order_acceptance_id: &str,
+) -> Result<ParentOrderDetail> {
+    let parent = api
+        .send(GetParentOrder {
+            parent_order_acceptance_id: Some(parent_order_acceptance_id.to_string()),
+            ..Default::default()
+        })
+        .await?;
+    let mut legs = parameters(&parent.order_method)
+        .iter()
+        .map(|x| ParentOrderLeg {
+            parameter: x.clone(),
+            child_orders: vec![],
+        })
+        .collect::<Vec<_>>();
+    // Without a product code the API only lists BTC_JPY orders.
+    let mut product_codes: Vec<ProductCode> = vec![];
+    for leg in &legs {
+        let product_code = match &leg.parameter {
+            ParentOrderConditionType::Limit { product_code, .. }
+            | ParentOrderConditionType::Market { product_code, .. }
+            | ParentOrderConditionType::Stop { product_code, .. }
+            | ParentOrderConditionType::StopLimit { product_code, .. }
+            | ParentOrderConditionType::Trail { product_code, .. } => product_code,
+        };
+        if !product_codes.contains(product_code) {
+            product_codes.push(product_code.clone());
+        }
+    }
+    let mut child_orders = vec![];
+    for product_code in product_codes {
+        child_orders.extend(
+            api.send(GetChildOrders {
+                product_code: Some(product_code),
+                parent_order_id: Some(parent.parent_order_id.clone()),
+                ..Default::default()
+            })
+            .await?,
+        );
+    }
+    child_orders.sort_by_key(|x| x.id);
+    let mut unmatched = vec![];
+    for order in child_orders {
+        let matching = legs
+            .iter()
+            .enumerate()
+            .filter(|(_, leg)| spawns(&leg.parameter, &order))
+            .map(|(i, leg)| (i, leg.is_triggered()))
+            .collect::<Vec<_>>();
+        let leg = matching
+            .iter()
+            .find(|(_, is_triggered)| !is_triggered)
+            .or(matching.first())
+            .map(|(i, _)| *i);
+        match leg {
+            Some(i) => legs[i].child_orders.push(order),
+            None => unmatched.push(order),
+        }
+    }
+    Ok(ParentOrderDetail {
+        parent,
+        legs,
+        unmatched,
+    })
+}
+
+async fn wait_until_inactive<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    child_order_acceptance_id: &str,
+) -> Result<ChildOrder> {
+    for _ in 0..CANCEL_CONFIRM_ATTEMPTS {
+        if let Some(order) = child_order(api, product_code, child_order_acceptance_id).await? {
+            if !order.child_order_state.is_active() {
+                return Ok(order);
+            }
+        }
+        tokio::time::sleep(CANCEL_CONFIRM_INTERVAL).await;
+    }
+    Err(anyhow!(
+        "order is still active after cancel: {child_order_acceptance_id}"
+    ))
+}
+
+pub async fn wait_for_fill<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    child_order_acceptance_id: &str,
+    timeout: Duration,
+) -> Result<ChildOrder> {
+    let wait = async {
+        loop {
+            if let Some(order) = child_order(api, product_code, child_order_acceptance_id).await? {
+                if order.child_order_state.is_terminal() {
+                    return Ok(order);
+                }
+            }
+            tokio::time::sleep(FILL_POLL_INTERVAL).await;
+        }
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .map_err(|_| anyhow!("timed out waiting for order: {child_order_acceptance_id}"))?
+}
+
+pub async fn submit_child_order<A: BitflyerApi>(
+    api: &A,
+    request: SendChildOrder,
+) -> Result<ChildOrderAcceptanceId> {
+    request.validate()?;
+    let response = api.send(request).await?;
+    Ok(response.child_order_acceptance_id.into())
+}
+
+pub async fn slippage_protected_order<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    side: Side,
+    size: Decimal,
+    protection: &SlippageProtection,
+) -> Result<ProtectedOrder> {
+    let board = api.send(GetBoard::new(product_code.clone())).await?;
+    let estimate = board.estimate_impact(side, size);
+    let max_slippage = protection.max_slippage;
+    if protection.action == SlippageAction::Refuse {
+        let exceeded = match (estimate.slippage(), estimate.best_price) {
+            (Some(slippage), Some(best)) => slippage > best * max_slippage,
+            _ => true,
+        };
+        if exceeded || !estimate.is_fully_filled() {
+            return Err(SlippageExceeded {
+                estimate,
+                max_slippage,
+            }
+            .into());
+        }
+        return Ok(ProtectedOrder {
+            request: SendChildOrder::market(product_code, side, size),
+            estimate,
+        });
+    }
+
+    // An empty book or a zero price would put the limit at zero.
+    let best = estimate
+        .best_price
+        .or_else(|| board.mid())
+        .filter(|x| x.is_sign_positive() && !x.is_zero())
+        .ok_or_else(|| anyhow!("{product_code} has no price to limit the order at"))?;
+    // Rounded toward the best price, so the limit stays within `max_slippage`.
+    let price = match side {
+        Side::Buy => (best * (Decimal::ONE + max_slippage))
+            .round_dp_with_strategy(best.scale(), RoundingStrategy::ToZero),
+        Side::Sell => (best * (Decimal::ONE - max_slippage))
+            .round_dp_with_strategy(best.scale(), RoundingStrategy::AwayFromZero),
+    };
+    Ok(ProtectedOrder {
+        request: SendChildOrder {
+            time_in_force: Some(TimeInForce::Ioc),
+            ..SendChildOrder::limit(product_code, side, size, price)
+        },
+        estimate,
+    })
+}
+
+pub async fn send_protected_market_order<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    side: Side,
+    size: Decimal,
+    protection: &SlippageProtection,
+) -> Result<ChildOrderAcceptanceId> {
+    let order = slippage_protected_order(api, product_code, side, size, protection).await?;
+    submit_child_order(api, order.request).await
+}
+
+pub async fn open_orders<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+) -> Result<Vec<ChildOrder>> {
+    api.send(GetChildOrders {
+        product_code: Some(product_code.clone()),
+        child_order_state: Some(OrderState::Active),
+        ..Default::default()
+    })
+    .await
+}
+
+pub async fn open_orders_with_parents<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+) -> Result<OpenOrders> {
+    let child_orders = open_orders(api, product_code).await?;
+    let parent_orders = api
+        .send(GetParentOrders {
+            product_code: Some(product_code.clone()),
+            parent_order_state: Some(OrderState::Active),
+            ..Default::default()
+        })
+        .await?;
+    Ok(OpenOrders {
+        child_orders,
+        parent_orders,
+    })
+}
+
+pub fn is_equivalent_order(order: &ChildOrder, request: &SendChildOrder) -> bool {
+    order.product_code == request.product_code
+        && order.side == request.side
+        && order.child_order_type == request.child_order_type
+        && order.size == request.size
+}
+
+pub async fn ensure_order<A: BitflyerApi>(
+    api: &A,
+    request: SendChildOrder,
+) -> Result<EnsureOutcome> {
+    // Market orders never rest on the book, so there is nothing to match against.
+    if request.child_order_type != ChildOrderType::Market {
+        let active = api
+            .send(GetChildOrders {
+                product_code: Some(request.product_code.clone()),
+                child_order_state: Some(OrderState::Active),
+                ..Default::default()
+            })
+            .await?;
+        if let Some(order) = active
+            .into_iter()
+            .find(|x| is_equivalent_order(x, &request))
+        {
+            return Ok(EnsureOutcome::Existing {
+                child_order_acceptance_id: order.child_order_acceptance_id.clone(),
+                order: Some(order),
+            });
+        }
+    }
+    let response = api.send(request).await?;
+    Ok(EnsureOutcome::Submitted {
+        child_order_acceptance_id: response.child_order_acceptance_id,
+    })
+}
+
+pub async fn close_position<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    options: &CloseOptions,
+) -> Result<ClosePositionReport> {
+    if !product_code.is_fx() {
+        return Err(anyhow!("{product_code} has no positions to close"));
+    }
+    let positions = api.send(GetPositions {}).await?;
+    let position_size = net_positions(&positions)
+        .into_iter()
+        .find(|(x, _)| x == product_code)
+        .map(|(_, size)| size)
+        .unwrap_or_default();
+    let mut report = ClosePositionReport {
+        product_code: product_code.clone(),
+        position_size,
+        orders: vec![],
+    };
+    let size = match options.size {
+        Some(size) => size.min(position_size.abs()),
+        None => position_size.abs(),
+    };
+    if size <= Decimal::ZERO {
+        return Ok(report);
+    }
+    let side = if position_size.is_sign_positive() {
+        Side::Sell
+    } else {
+        Side::Buy
+    };
+    let mut request =
+        protected_market_order(api, product_code.clone(), side, size, options.max_slippage).await?;
+    if options.time_in_force.is_some() {
+        request.time_in_force = options.time_in_force;
+    }
+    let response = api.send(request.clone()).await?;
+    report
+        .orders
+        .push((request, response.child_order_acceptance_id));
+    Ok(report)
+}
+
+// `new_size` is the total size of the amended order; size already executed on the
+// original order is subtracted from the replacement.
+pub async fn amend_child_order<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    child_order_acceptance_id: &str,
+    new_price: Option<Decimal>,
+    new_size: Option<Decimal>,
+) -> Result<AmendOutcome> {
+    let order = child_order(api, &product_code, child_order_acceptance_id)
+        .await?
+        .ok_or_else(|| anyhow!("order is not found: {child_order_acceptance_id}"))?;
+    if !order.child_order_state.is_active() {
+        return Ok(inactive_outcome(order));
+    }
+    let price = match (&order.child_order_type, new_price) {
+        (ChildOrderType::Limit { .. }, Some(price)) => price,
+        (ChildOrderType::Limit { price }, None) => *price,
+        (ChildOrderType::Market, _) => return Err(anyhow!("market orders can't be amended")),
+    };
+    // The replacement expires with the original, not 30 days after it is sent.
+    let minute_to_expire = remaining_minutes(&order, Utc::now())?;
+
+    let cancel = api
+        .send(CancelChildOrder {
+            product_code: product_code.clone(),
+            child_order_acceptance_id: child_order_acceptance_id.to_string(),
+        })
+        .await;
+    let canceled = match cancel {
+        Ok(_) => wait_until_inactive(api, &product_code, child_order_acceptance_id).await?,
+        Err(e) => match child_order(api, &product_code, child_order_acceptance_id).await? {
+            Some(order) if !order.child_order_state.is_active() => order,
+            _ => return Err(e.context("cancel is rejected")),
+        },
+    };
+    if canceled.child_order_state != OrderState::Canceled {
+        return Ok(inactive_outcome(canceled));
+    }
+
+    let size = new_size.unwrap_or(order.size) - canceled.executed_size;
+    if size <= Decimal::ZERO {
+        return Ok(AmendOutcome::Filled(canceled));
+    }
+    let response = api
+        .send(
+            SendChildOrder::from_child_order(&order)
+                .with_price(price)
+                .with_size(size)
+                .with_minute_to_expire(minute_to_expire),
+        )
+        .await?;
+    Ok(AmendOutcome::Replaced {
+        canceled,
+        child_order_acceptance_id: response.child_order_acceptance_id,
+        size,
+    })
+}
+
+// Whole minutes, so the replacement never outlives the original.
+fn remaining_minutes(order: &ChildOrder, now: DateTime<Utc>) -> Result<MinuteToExpire> {
+    let minutes = (order.expire_date - now).num_minutes().max(0) as u64;
+    MinuteToExpire::from_minutes(minutes.min(MinuteToExpire::MAX.minutes())).map_err(|_| {
+        anyhow!(
+            "order expires within a minute: {}",
+            order.child_order_acceptance_id
+        )
+    })
+}
+
+fn inactive_outcome(order: ChildOrder) -> AmendOutcome {
+    if order.child_order_state == OrderState::Completed {
+        AmendOutcome::Filled(order)
+    } else {
+        AmendOutcome::NotActive(order)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::api::{
+    ApiRequest, BitflyerApi, GetBalanceHistory, GetChildOrders, GetCoinIns, GetCoinOuts,
+    GetExecutions, GetParentOrders, GetParentOrdersResponseParameter, GetPrivateExecutions,
+};
+use crate::entity::{BalanceHistory, ChildOrder, CoinIn, CoinOut, Execution, PrivateExecution};
+use anyhow::Result;
+use futures::{Stream, TryStreamExt};
+use std::fmt::Debug;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+pub const PAGE_SIZE: u64 = 500;
+// Public endpoints allow roughly 500 requests per 5 minutes per IP.
+pub const PAGE_INTERVAL: Duration = Duration::from_millis(600);
+
+// A list endpoint returning items newest first, paged backwards with `before`.
+pub trait Paginated: ApiRequest<Response = Vec<Self::Item>> + Clone + Debug + Send + Sync {
+    type Item: Send + 'static;
+
+    fn before(&self) -> Option<u64>;
+    fn set_page(&mut self, count: u64, before: Option<u64>);
+    fn item_id(item: &Self::Item) -> u64;
+}
+
+macro_rules! paginated {
+    ($request:ty, $item:ty) => {
+        impl Paginated for $request {
+            type Item = $item;
+
+            fn before(&self) -> Option<u64> {
+                self.before
+            }
+
+            fn set_page(&mut self, count: u64, before: Option<u64>) {
+                self.count = Some(count);
+                self.before = before;
+            }
+
+            fn item_id(item: &Self::Item) -> u64 {
+                item.id
+            }
+        }
+    };
+}
+
+paginated!(GetExecutions, Execution);
+paginated!(GetChildOrders, ChildOrder);
+paginated!(GetParentOrders, GetParentOrdersResponseParameter);
+paginated!(GetPrivateExecutions, PrivateExecution);
+paginated!(GetBalanceHistory, BalanceHistory);
+paginated!(GetCoinIns, CoinIn);
+paginated!(GetCoinOuts, CoinOut);
+
+#[derive(Debug)]
+pub struct Pager<'a, A, T> {
+    api: &'a A,
+    request: T,
+    page_size: u64,
+    page_interval: Duration,
+}
+
+impl<'a, A: BitflyerApi, T: Paginated + 'a> Pager<'a, A, T> {
+    // `before` and `after` on the request bound the range; `count` is replaced per page.
+    pub fn new(api: &'a A, request: T) -> Self {
+        Self {
+            api,
+            request,
+            page_size: PAGE_SIZE,
+            page_interval: PAGE_INTERVAL,
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: u64) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn with_page_interval(mut self, page_interval: Duration) -> Self {
+        self.page_interval = page_interval;
+        self
+    }
+
+    pub fn pages(self) -> impl Stream<Item = Result<Vec<T::Item>>> + Send + 'a {
+        let mut interval = tokio::time::interval(self.page_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let before = self.request.before();
+        futures::stream::unfold(
+            (self, interval, before, false),
+            |(pager, mut interval, before, exhausted)| async move {
+                if exhausted {
+                    return None;
+                }
+                interval.tick().await;
+                let mut request = pager.request.clone();
+                request.set_page(pager.page_size, before);
+                match pager.api.send(request).await {
+                    Ok(page) => {
+                        let oldest = page.iter().map(T::item_id).min()?;
+                        let exhausted = (page.len() as u64) < pager.page_size;
+                        Some((Ok(page), (pager, interval, Some(oldest), exhausted)))
+                    }
+                    Err(e) => Some((Err(e), (pager, interval, before, true))),
+                }
+            },
+        )
+    }
+
+    pub fn items(self) -> impl Stream<Item = Result<T::Item>> + Send + 'a {
+        self.pages()
+            .map_ok(|x| futures::stream::iter(x.into_iter().map(Ok)))
+            .try_flatten()
+    }
+}
@@ -0,0 +1,241 @@
+use crate::api::{BitflyerApi, CancelChildOrder, Client, GetBoard, SendChildOrder};
+use crate::board::OrderBook;
+use crate::entity::{ChildOrder, ChildOrderType, ProductCode, Side};
+use crate::orders::{amend_child_order, AmendOutcome};
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PegReference {
+    BestBid,
+    BestAsk,
+    Mid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PegOptions {
+    pub reference: PegReference,
+    pub offset: Decimal,
+    pub tick_size: Decimal,
+    pub tolerance: Decimal,
+    pub min_requote_interval: Duration,
+}
+
+impl PegOptions {
+    pub fn join(side: Side) -> Self {
+        Self {
+            reference: match side {
+                Side::Buy => PegReference::BestBid,
+                Side::Sell => PegReference::BestAsk,
+            },
+            offset: Decimal::ZERO,
+            tick_size: Decimal::ONE,
+            tolerance: Decimal::ZERO,
+            min_requote_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PegAction {
+    Placed {
+        child_order_acceptance_id: String,
+        price: Decimal,
+    },
+    Requoted {
+        child_order_acceptance_id: String,
+        price: Decimal,
+    },
+    Unchanged,
+    Throttled,
+    Filled(ChildOrder),
+    Stopped(Option<ChildOrder>),
+}
+
+#[derive(Debug, Default)]
+struct PegState {
+    child_order_acceptance_id: Option<String>,
+    price: Option<Decimal>,
+    remaining: Decimal,
+    last_quote: Option<Instant>,
+    done: bool,
+}
+
+#[derive(Debug)]
+pub struct PeggedOrder<A = Client> {
+    api: Arc<A>,
+    product_code: ProductCode,
+    side: Side,
+    options: PegOptions,
+    state: Mutex<PegState>,
+}
+
+impl<A: BitflyerApi + 'static> PeggedOrder<A> {
+    pub fn new(
+        api: Arc<A>,
+        product_code: ProductCode,
+        side: Side,
+        size: Decimal,
+        options: PegOptions,
+    ) -> Self {
+        Self {
+            api,
+            product_code,
+            side,
+            options,
+            state: Mutex::new(PegState {
+                remaining: size,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn options(&self) -> &PegOptions {
+        &self.options
+    }
+
+    pub async fn child_order_acceptance_id(&self) -> Option<String> {
+        self.state.lock().await.child_order_acceptance_id.clone()
+    }
+
+    pub async fn price(&self) -> Option<Decimal> {
+        self.state.lock().await.price
+    }
+
+    pub async fn is_done(&self) -> bool {
+        self.state.lock().await.done
+    }
+
+    pub fn target_price(&self, book: &impl OrderBook) -> Option<Decimal> {
+        let reference = match self.options.reference {
+            PegReference::BestBid => book.bid_levels().first()?.price,
+            PegReference::BestAsk => book.ask_levels().first()?.price,
+            PegReference::Mid => book.mid()?,
+        };
+        let price = reference + self.options.offset;
+        let tick_size = self.options.tick_size;
+        if tick_size <= Decimal::ZERO {
+            return Some(price);
+        }
+        // Round away from the opposite side so the peg never becomes more aggressive.
+        let ticks = price / tick_size;
+        let ticks = match self.side {
+            Side::Buy => ticks.floor(),
+            Side::Sell => ticks.ceil(),
+        };
+        Some(ticks * tick_size)
+    }
+
+    pub async fn on_book(&self, book: &impl OrderBook) -> Result<PegAction> {
+        let mut state = self.state.lock().await;
+        if state.done {
+            return Ok(PegAction::Stopped(None));
+        }
+        let Some(price) = self.target_price(book) else {
+            return Ok(PegAction::Unchanged);
+        };
+        if let Some(current) = state.price {
+            if (price - current).abs() <= self.options.tolerance {
+                return Ok(PegAction::Unchanged);
+            }
+        }
+        if let Some(last_quote) = state.last_quote {
+            if last_quote.elapsed() < self.options.min_requote_interval {
+                return Ok(PegAction::Throttled);
+            }
+        }
+        state.last_quote = Some(Instant::now());
+
+        let Some(acceptance_id) = state.child_order_acceptance_id.clone() else {
+            let response = self
+                .api
+                .send(SendChildOrder {
+                    child_order_type: ChildOrderType::Limit { price },
+                    product_code: self.product_code.clone(),
+                    side: self.side,
+                    size: state.remaining,
+                    minute_to_expire: None,
+                    time_in_force: None,
+                })
+                .await?;
+            state.child_order_acceptance_id = Some(response.child_order_acceptance_id.clone());
+            state.price = Some(price);
+            return Ok(PegAction::Placed {
+                child_order_acceptance_id: response.child_order_acceptance_id,
+                price,
+            });
+        };
+
+        let outcome = amend_child_order(
+            self.api.as_ref(),
+            self.product_code.clone(),
+            &acceptance_id,
+            Some(price),
+            Some(state.remaining),
+        )
+        .await?;
+        match outcome {
+            AmendOutcome::Replaced {
+                child_order_acceptance_id,
+                size,
+                ..
+            } => {
+                state.child_order_acceptance_id = Some(child_order_acceptance_id.clone());
+                state.price = Some(price);
+                state.remaining = size;
+                Ok(PegAction::Requoted {
+                    child_order_acceptance_id,
+                    price,
+                })
+            }
+            AmendOutcome::Filled(order) => {
+                state.done = true;
+                Ok(PegAction::Filled(order))
+            }
+            AmendOutcome::NotActive(order) => {
+                state.done = true;
+                Ok(PegAction::Stopped(Some(order)))
+            }
+        }
+    }
+
+    pub async fn cancel(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.done = true;
+        if let Some(acceptance_id) = state.child_order_acceptance_id.clone() {
+            self.api
+                .send(CancelChildOrder {
+                    product_code: self.product_code.clone(),
+                    child_order_acceptance_id: acceptance_id,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let peg = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                let board = peg
+                    .api
+                    .send(GetBoard::new(peg.product_code.clone()).with_depth(1))
+                    .await;
+                let result = match board {
+                    Ok(board) => peg.on_book(&board).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(PegAction::Filled(_) | PegAction::Stopped(_)) => break,
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("failed to re-quote pegged order: {e:?}"),
+                }
+            }
+        })
+    }
+}
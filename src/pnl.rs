@@ -0,0 +1,338 @@
+use crate::api::{BitflyerApi, GetPrivateExecutions};
+use crate::entity::{PrivateExecution, ProductCode, Side};
+use crate::pager::Pager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CostMethod {
+    #[default]
+    AverageCost,
+    Fifo,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lot {
+    pub execution_id: u64,
+    pub opened_at: DateTime<Utc>,
+    pub size: Decimal,
+    // Price per unit including the opening commission.
+    pub unit_cost: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Realization {
+    pub product_code: ProductCode,
+    pub execution_id: u64,
+    pub exec_date: DateTime<Utc>,
+    // Execution that opened the lot; the first one when lots are averaged.
+    pub entry_execution_id: u64,
+    pub opened_at: DateTime<Utc>,
+    // Side of the position being closed.
+    pub side: Side,
+    // On spot a sell also closes the commission it pays in the base currency.
+    pub size: Decimal,
+    pub entry_price: Decimal,
+    // Price per unit net of the closing commission.
+    pub exit_price: Decimal,
+    pub pnl: Decimal,
+}
+
+fn sign(side: Side) -> Decimal {
+    match side {
+        Side::Buy => Decimal::ONE,
+        Side::Sell => Decimal::NEGATIVE_ONE,
+    }
+}
+
+// The change in the position and its price per unit in the quote currency, commission
+// included. Spot commissions are taken from the base currency: a buy receives
+// `size - commission` for the price of `size`, and a sell gives up `size + commission` for the
+// proceeds of `size`. FX commissions leave the size alone and are valued at the execution
+// price.
+pub(crate) fn position_change(
+    side: Side,
+    price: Decimal,
+    size: Decimal,
+    commission: Decimal,
+    spot: bool,
+) -> (Decimal, Decimal) {
+    let quantity = match (spot, side) {
+        (false, _) => return (size, price + sign(side) * commission * price / size),
+        (true, Side::Buy) => size - commission,
+        (true, Side::Sell) => size + commission,
+    };
+    let price = if quantity > Decimal::ZERO {
+        price * size / quantity
+    } else {
+        price
+    };
+    (quantity, price)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProductPnl {
+    pub product_code: ProductCode,
+    pub side: Option<Side>,
+    pub lots: VecDeque<Lot>,
+    pub realized_pnl: Decimal,
+    // Commissions are charged in the base currency and valued at the execution price.
+    pub commission: Decimal,
+    pub bought: Decimal,
+    pub sold: Decimal,
+}
+
+impl ProductPnl {
+    pub(crate) fn new(product_code: ProductCode) -> Self {
+        Self {
+            product_code,
+            side: None,
+            lots: VecDeque::new(),
+            realized_pnl: Decimal::ZERO,
+            commission: Decimal::ZERO,
+            bought: Decimal::ZERO,
+            sold: Decimal::ZERO,
+        }
+    }
+
+    pub fn size(&self) -> Decimal {
+        self.lots.iter().map(|x| x.size).sum()
+    }
+
+    // Positive when long, negative when short.
+    pub fn net_size(&self) -> Decimal {
+        self.side.map(sign).unwrap_or_default() * self.size()
+    }
+
+    pub fn cost_basis(&self) -> Decimal {
+        self.lots.iter().map(|x| x.size * x.unit_cost).sum()
+    }
+
+    pub fn average_cost(&self) -> Option<Decimal> {
+        let size = self.size();
+        (!size.is_zero()).then(|| self.cost_basis() / size)
+    }
+
+    pub fn unrealized_pnl(&self, mark_price: Decimal) -> Decimal {
+        let Some(side) = self.side else {
+            return Decimal::ZERO;
+        };
+        sign(side) * (mark_price * self.size() - self.cost_basis())
+    }
+
+    fn open(&mut self, method: CostMethod, side: Side, lot: Lot) {
+        self.side = Some(side);
+        match (method, self.lots.front_mut()) {
+            (CostMethod::AverageCost, Some(merged)) => {
+                let size = merged.size + lot.size;
+                merged.unit_cost =
+                    (merged.unit_cost * merged.size + lot.unit_cost * lot.size) / size;
+                merged.size = size;
+            }
+            _ => self.lots.push_back(lot),
+        }
+    }
+
+    // Replaces the lots with one of `net_size`, negative when short, e.g. a position read from
+    // the exchange.
+    pub(crate) fn set(&mut self, net_size: Decimal, unit_cost: Decimal, opened_at: DateTime<Utc>) {
+        self.lots.clear();
+        self.side = None;
+        if net_size.is_zero() {
+            return;
+        }
+        self.side = Some(if net_size.is_sign_positive() {
+            Side::Buy
+        } else {
+            Side::Sell
+        });
+        self.lots.push_back(Lot {
+            execution_id: 0,
+            opened_at,
+            size: net_size.abs(),
+            unit_cost,
+        });
+    }
+
+    fn apply(&mut self, method: CostMethod, execution: &PrivateExecution) -> Vec<Realization> {
+        self.fill(
+            method,
+            execution.id,
+            execution.exec_date,
+            execution.side,
+            execution.price,
+            execution.size,
+            execution.commission,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn fill(
+        &mut self,
+        method: CostMethod,
+        execution_id: u64,
+        exec_date: DateTime<Utc>,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+        commission: Decimal,
+    ) -> Vec<Realization> {
+        if size <= Decimal::ZERO {
+            return vec![];
+        }
+        self.commission += commission * price;
+        match side {
+            Side::Buy => self.bought += size,
+            Side::Sell => self.sold += size,
+        }
+        let spot = !self.product_code.is_fx();
+        let (mut remaining, fill_price) = position_change(side, price, size, commission, spot);
+
+        let mut realizations = vec![];
+        if let Some(position) = self.side.filter(|x| *x != side) {
+            let direction = sign(position);
+            while remaining > Decimal::ZERO {
+                let Some(lot) = self.lots.front_mut() else {
+                    break;
+                };
+                let size = remaining.min(lot.size);
+                let pnl = direction * size * (fill_price - lot.unit_cost);
+                self.realized_pnl += pnl;
+                realizations.push(Realization {
+                    product_code: self.product_code.clone(),
+                    execution_id,
+                    exec_date,
+                    entry_execution_id: lot.execution_id,
+                    opened_at: lot.opened_at,
+                    side: position,
+                    size,
+                    entry_price: lot.unit_cost,
+                    exit_price: fill_price,
+                    pnl,
+                });
+                lot.size -= size;
+                remaining -= size;
+                if lot.size.is_zero() {
+                    self.lots.pop_front();
+                }
+            }
+            if self.lots.is_empty() {
+                self.side = None;
+            }
+        }
+        if remaining <= Decimal::ZERO {
+            return realizations;
+        }
+        // Spot cannot be short, so selling more than the tracked holdings means the rest was
+        // bought before the executions at hand, e.g. deposited. It has no known cost to realize
+        // against.
+        if spot && side == Side::Sell {
+            tracing::warn!(
+                "{} sold {remaining} more than its tracked holdings; left unrealized",
+                self.product_code
+            );
+            return realizations;
+        }
+        self.open(
+            method,
+            side,
+            Lot {
+                execution_id,
+                opened_at: exec_date,
+                size: remaining,
+                unit_cost: fill_price,
+            },
+        );
+        realizations
+    }
+}
+
+// Executions must be applied in chronological order; `process` sorts a batch first.
+#[derive(Clone, Debug, Default)]
+pub struct PnlEngine {
+    method: CostMethod,
+    products: HashMap<ProductCode, ProductPnl>,
+    realizations: Vec<Realization>,
+    seen: HashSet<u64>,
+}
+
+impl PnlEngine {
+    pub fn new(method: CostMethod) -> Self {
+        Self {
+            method,
+            ..Default::default()
+        }
+    }
+
+    pub fn method(&self) -> CostMethod {
+        self.method
+    }
+
+    pub fn apply(
+        &mut self,
+        product_code: &ProductCode,
+        execution: &PrivateExecution,
+    ) -> Vec<Realization> {
+        if !self.seen.insert(execution.id) {
+            return vec![];
+        }
+        let realizations = self
+            .products
+            .entry(product_code.clone())
+            .or_insert_with(|| ProductPnl::new(product_code.clone()))
+            .apply(self.method, execution);
+        self.realizations.extend(realizations.iter().cloned());
+        realizations
+    }
+
+    pub fn process<'a>(
+        &mut self,
+        product_code: &ProductCode,
+        executions: impl IntoIterator<Item = &'a PrivateExecution>,
+    ) -> Vec<Realization> {
+        let mut executions: Vec<&PrivateExecution> = executions.into_iter().collect();
+        executions.sort_by_key(|x| (x.exec_date, x.id));
+        executions
+            .into_iter()
+            .flat_map(|x| self.apply(product_code, x))
+            .collect()
+    }
+
+    // Fetches every private execution of the product and processes it.
+    pub async fn load<A: BitflyerApi>(
+        &mut self,
+        api: &A,
+        product_code: &ProductCode,
+    ) -> Result<Vec<Realization>> {
+        let executions: Vec<PrivateExecution> = Pager::new(
+            api,
+            GetPrivateExecutions {
+                product_code: Some(product_code.clone()),
+                ..Default::default()
+            },
+        )
+        .items()
+        .try_collect()
+        .await?;
+        Ok(self.process(product_code, &executions))
+    }
+
+    pub fn product(&self, product_code: &ProductCode) -> Option<&ProductPnl> {
+        self.products.get(product_code)
+    }
+
+    pub fn products(&self) -> impl Iterator<Item = &ProductPnl> {
+        self.products.values()
+    }
+
+    pub fn realizations(&self) -> &[Realization] {
+        &self.realizations
+    }
+
+    pub fn realized_pnl(&self) -> Decimal {
+        self.products.values().map(|x| x.realized_pnl).sum()
+    }
+}
@@ -0,0 +1,102 @@
+use crate::api::{BitflyerApi, GetBoard, GetTicker};
+use crate::entity::{Board, ProductCode, Ticker};
+use futures::Stream;
+use rust_decimal::Decimal;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn backoff(interval: Duration, failures: u32) -> Duration {
+    interval
+        .saturating_mul(2u32.saturating_pow(failures.min(16)))
+        .min(MAX_BACKOFF)
+}
+
+fn interval(period: Duration) -> tokio::time::Interval {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+// Failed polls are logged and retried with exponential backoff; the stream never ends on its own.
+pub fn ticker_poller<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+) -> impl Stream<Item = Ticker> + Send + '_ {
+    futures::stream::unfold(
+        (interval(period), None::<Decimal>, 0),
+        move |(mut interval, mut last_tick_id, mut failures)| {
+            let product_code = product_code.clone();
+            async move {
+                loop {
+                    interval.tick().await;
+                    let ticker = match api
+                        .send(GetTicker {
+                            product_code: Some(product_code.clone()),
+                        })
+                        .await
+                    {
+                        Ok(ticker) => ticker,
+                        Err(e) => {
+                            failures += 1;
+                            let delay = backoff(period, failures);
+                            tracing::warn!("failed to poll the {product_code} ticker, retrying in {delay:?}: {e:?}");
+                            tokio::time::sleep(delay).await;
+                            continue;
+                        }
+                    };
+                    failures = 0;
+                    if last_tick_id == Some(ticker.tick_id) {
+                        continue;
+                    }
+                    last_tick_id = Some(ticker.tick_id);
+                    return Some((ticker, (interval, last_tick_id, failures)));
+                }
+            }
+        },
+    )
+}
+
+// Yields the book truncated to `depth` levels per side, only when it differs from the last one.
+pub fn board_poller<A: BitflyerApi>(
+    api: &A,
+    product_code: ProductCode,
+    period: Duration,
+    depth: Option<usize>,
+) -> impl Stream<Item = Board> + Send + '_ {
+    futures::stream::unfold(
+        (interval(period), None::<Board>, 0),
+        move |(mut interval, mut last, mut failures)| {
+            let product_code = product_code.clone();
+            async move {
+                loop {
+                    interval.tick().await;
+                    let board = match api
+                        .send(GetBoard {
+                            product_code: Some(product_code.clone()),
+                            depth,
+                        })
+                        .await
+                    {
+                        Ok(board) => board,
+                        Err(e) => {
+                            failures += 1;
+                            let delay = backoff(period, failures);
+                            tracing::warn!("failed to poll the {product_code} board, retrying in {delay:?}: {e:?}");
+                            tokio::time::sleep(delay).await;
+                            continue;
+                        }
+                    };
+                    failures = 0;
+                    if last.as_ref() == Some(&board) {
+                        continue;
+                    }
+                    last = Some(board.clone());
+                    return Some((board, (interval, last, failures)));
+                }
+            }
+        },
+    )
+}
@@ -0,0 +1,153 @@
+use crate::api::{
+    BitflyerApi, GetBalance, GetChildOrders, GetCollateral, GetCollateralAccounts, GetPositions,
+    GetTicker,
+};
+use crate::entity::{
+    Balance, ChildOrder, Collateral, CollateralAccount, OrderState, Position, ProductCode,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+const JPY: &str = "JPY";
+const BTC: &str = "BTC";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Portfolio {
+    pub timestamp: DateTime<Utc>,
+    pub balances: Vec<Balance>,
+    pub collateral: Collateral,
+    pub collateral_accounts: Vec<CollateralAccount>,
+    pub positions: Vec<Position>,
+    pub open_orders: Vec<ChildOrder>,
+    pub jpy_prices: HashMap<String, Decimal>,
+}
+
+impl Portfolio {
+    pub fn jpy_price(&self, currency_code: &str) -> Option<Decimal> {
+        if currency_code == JPY {
+            Some(Decimal::ONE)
+        } else {
+            self.jpy_prices.get(currency_code).copied()
+        }
+    }
+
+    pub fn jpy_value(&self, currency_code: &str, amount: Decimal) -> Option<Decimal> {
+        self.jpy_price(currency_code).map(|x| x * amount)
+    }
+
+    pub fn balance_jpy(&self) -> Decimal {
+        self.balances
+            .iter()
+            .filter_map(|x| self.jpy_value(&x.currency_code, x.amount))
+            .sum()
+    }
+
+    pub fn collateral_jpy(&self) -> Decimal {
+        self.collateral.collateral + self.collateral.open_position_pnl
+    }
+
+    pub fn total_jpy(&self) -> Decimal {
+        self.balance_jpy() + self.collateral_jpy()
+    }
+
+    pub fn unpriced_currencies(&self) -> Vec<String> {
+        self.balances
+            .iter()
+            .filter(|x| !x.amount.is_zero() && self.jpy_price(&x.currency_code).is_none())
+            .map(|x| x.currency_code.clone())
+            .collect()
+    }
+}
+
+pub async fn portfolio<A: BitflyerApi>(
+    api: &A,
+    product_codes: &[ProductCode],
+) -> Result<Portfolio> {
+    let open_orders = futures::future::try_join_all(product_codes.iter().map(|x| {
+        api.send(GetChildOrders {
+            product_code: Some(x.clone()),
+            child_order_state: Some(OrderState::Active),
+            ..Default::default()
+        })
+    }));
+    let (balances, collateral, collateral_accounts, positions, open_orders) = tokio::try_join!(
+        api.send(GetBalance),
+        api.send(GetCollateral),
+        api.send(GetCollateralAccounts),
+        api.send(GetPositions {}),
+        open_orders,
+    )?;
+
+    let jpy_prices = jpy_prices(
+        api,
+        balances
+            .iter()
+            .map(|x| x.currency_code.as_str())
+            .chain(collateral_accounts.iter().map(|x| x.currency_code.as_str())),
+    )
+    .await?;
+
+    Ok(Portfolio {
+        timestamp: Utc::now(),
+        balances,
+        collateral,
+        collateral_accounts,
+        positions,
+        open_orders: open_orders.into_iter().flatten().collect(),
+        jpy_prices,
+    })
+}
+
+pub async fn jpy_prices<'a, A: BitflyerApi>(
+    api: &A,
+    currency_codes: impl IntoIterator<Item = &'a str>,
+) -> Result<HashMap<String, Decimal>> {
+    let mut products = vec![];
+    for currency_code in currency_codes {
+        if currency_code == JPY {
+            continue;
+        }
+        let product_code =
+            ProductCode::spot(currency_code, JPY).or_else(|| ProductCode::spot(currency_code, BTC));
+        if let Some(product_code) = product_code {
+            if product_code.quote_currency() == Some(BTC)
+                && !products.contains(&ProductCode::BtcJpy)
+            {
+                products.push(ProductCode::BtcJpy);
+            }
+            if !products.contains(&product_code) {
+                products.push(product_code);
+            }
+        }
+    }
+
+    let tickers = futures::future::try_join_all(products.into_iter().map(|x| {
+        api.send(GetTicker {
+            product_code: Some(x),
+        })
+    }))
+    .await?;
+
+    let mut prices = HashMap::new();
+    for ticker in tickers
+        .iter()
+        .filter(|x| x.product_code.quote_currency() == Some(JPY))
+    {
+        if let Some(base) = ticker.product_code.base_currency() {
+            prices.insert(base.to_string(), ticker.ltp);
+        }
+    }
+    if let Some(btc) = prices.get(BTC).copied() {
+        for ticker in tickers
+            .iter()
+            .filter(|x| x.product_code.quote_currency() == Some(BTC))
+        {
+            if let Some(base) = ticker.product_code.base_currency() {
+                prices.entry(base.to_string()).or_insert(ticker.ltp * btc);
+            }
+        }
+    }
+    Ok(prices)
+}
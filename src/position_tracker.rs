@@ -0,0 +1,333 @@
+use crate::api::{BitflyerApi, GetBalance, GetPositions, GetPrivateExecutions};
+use crate::entity::{Balance, Position, PrivateExecution, ProductCode, Side};
+use crate::pnl::{position_change, CostMethod, ProductPnl};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+
+const BACKFILL_COUNT: u64 = 500;
+
+// Kept on the lots of `ProductPnl`, so spot commissions come out of `net_size` like they do
+// out of the balance, and `average_price` includes them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProductPosition {
+    pub net_size: Decimal,
+    pub average_price: Decimal,
+    pub realized_pnl: Decimal,
+    // In the base currency.
+    pub commission: Decimal,
+    pub last_execution_id: Option<u64>,
+    lots: ProductPnl,
+    // What the fills applied moved the base and the quote currency by, for spot products.
+    // Unlike `net_size`, it also counts selling holdings the tracker never saw bought.
+    base_change: Decimal,
+    quote_change: Decimal,
+}
+
+impl ProductPosition {
+    fn new(product_code: ProductCode) -> Self {
+        Self {
+            net_size: Decimal::ZERO,
+            average_price: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+            commission: Decimal::ZERO,
+            last_execution_id: None,
+            lots: ProductPnl::new(product_code),
+            base_change: Decimal::ZERO,
+            quote_change: Decimal::ZERO,
+        }
+    }
+
+    pub fn side(&self) -> Option<Side> {
+        self.lots.side
+    }
+
+    pub fn unrealized_pnl(&self, mark_price: Decimal) -> Decimal {
+        self.lots.unrealized_pnl(mark_price)
+    }
+
+    fn apply(
+        &mut self,
+        execution_id: u64,
+        exec_date: DateTime<Utc>,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+        commission: Decimal,
+    ) {
+        self.commission += commission;
+        let (quantity, _) = position_change(side, price, size, commission, true);
+        match side {
+            Side::Buy => {
+                self.base_change += quantity;
+                self.quote_change -= price * size;
+            }
+            Side::Sell => {
+                self.base_change -= quantity;
+                self.quote_change += price * size;
+            }
+        }
+        self.lots.fill(
+            CostMethod::AverageCost,
+            execution_id,
+            exec_date,
+            side,
+            price,
+            size,
+            commission,
+        );
+        self.update();
+    }
+
+    fn set(&mut self, net_size: Decimal, average_price: Decimal) {
+        self.lots.set(net_size, average_price, Utc::now());
+        self.update();
+    }
+
+    fn update(&mut self) {
+        self.net_size = self.lots.net_size();
+        self.average_price = self.lots.average_cost().unwrap_or_default();
+        self.realized_pnl = self.lots.realized_pnl;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionDiscrepancy {
+    pub product_code: ProductCode,
+    pub tracked_size: Decimal,
+    pub exchange_size: Decimal,
+}
+
+// A currency's balance moved differently since the baseline than the tracked spot fills of
+// every product trading it account for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceDiscrepancy {
+    pub currency_code: String,
+    pub tracked_change: Decimal,
+    pub exchange_change: Decimal,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub positions: Vec<PositionDiscrepancy>,
+    pub balances: Vec<BalanceDiscrepancy>,
+}
+
+impl Reconciliation {
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty() && self.balances.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PositionTracker {
+    positions: HashMap<ProductCode, ProductPosition>,
+    seen: HashSet<u64>,
+    // The exchange's balances and the tracked changes when the baseline was taken.
+    balance_baseline: Option<(HashMap<String, Decimal>, HashMap<String, Decimal>)>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(&self, product_code: &ProductCode) -> Option<&ProductPosition> {
+        self.positions.get(product_code)
+    }
+
+    pub fn positions(&self) -> &HashMap<ProductCode, ProductPosition> {
+        &self.positions
+    }
+
+    fn position_mut(&mut self, product_code: &ProductCode) -> &mut ProductPosition {
+        self.positions
+            .entry(product_code.clone())
+            .or_insert_with(|| ProductPosition::new(product_code.clone()))
+    }
+
+    pub fn set_position(
+        &mut self,
+        product_code: ProductCode,
+        net_size: Decimal,
+        average_price: Decimal,
+    ) {
+        self.position_mut(&product_code)
+            .set(net_size, average_price);
+    }
+
+    pub fn apply_fill(
+        &mut self,
+        product_code: &ProductCode,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+        commission: Decimal,
+    ) {
+        self.position_mut(product_code)
+            .apply(0, Utc::now(), side, price, size, commission);
+    }
+
+    pub fn apply_execution(
+        &mut self,
+        product_code: &ProductCode,
+        execution: &PrivateExecution,
+    ) -> bool {
+        if !self.seen.insert(execution.id) {
+            return false;
+        }
+        let position = self.position_mut(product_code);
+        position.apply(
+            execution.id,
+            execution.exec_date,
+            execution.side,
+            execution.price,
+            execution.size,
+            execution.commission,
+        );
+        position.last_execution_id = position.last_execution_id.max(Some(execution.id));
+        true
+    }
+
+    pub async fn backfill<A: BitflyerApi>(
+        &mut self,
+        client: &A,
+        product_code: &ProductCode,
+    ) -> Result<usize> {
+        let after = self
+            .positions
+            .get(product_code)
+            .and_then(|x| x.last_execution_id);
+        let mut executions = vec![];
+        let mut before = None;
+        loop {
+            let page = client
+                .send(GetPrivateExecutions {
+                    product_code: Some(product_code.clone()),
+                    count: Some(BACKFILL_COUNT),
+                    before,
+                    after,
+                    ..Default::default()
+                })
+                .await?;
+            let len = page.len();
+            before = page.iter().map(|x| x.id).min();
+            executions.extend(page);
+            if len < BACKFILL_COUNT as usize || before.is_none() {
+                break;
+            }
+        }
+        executions.sort_by_key(|x| x.id);
+        Ok(executions
+            .iter()
+            .filter(|x| self.apply_execution(product_code, x))
+            .count())
+    }
+
+    pub fn reconcile_positions(&self, positions: &[Position]) -> Vec<PositionDiscrepancy> {
+        let mut exchange: HashMap<ProductCode, Decimal> = HashMap::new();
+        for position in positions {
+            let signed = match position.side {
+                Side::Buy => position.size,
+                Side::Sell => -position.size,
+            };
+            *exchange.entry(position.product_code.clone()).or_default() += signed;
+        }
+        let mut products: Vec<_> = exchange.keys().cloned().collect();
+        for product_code in self.positions.keys().filter(|x| x.is_fx()) {
+            if !products.contains(product_code) {
+                products.push(product_code.clone());
+            }
+        }
+        products
+            .into_iter()
+            .filter_map(|product_code| {
+                let tracked_size = self
+                    .positions
+                    .get(&product_code)
+                    .map(|x| x.net_size)
+                    .unwrap_or_default();
+                let exchange_size = exchange.get(&product_code).copied().unwrap_or_default();
+                (tracked_size != exchange_size).then_some(PositionDiscrepancy {
+                    product_code,
+                    tracked_size,
+                    exchange_size,
+                })
+            })
+            .collect()
+    }
+
+    // Net change of each currency from the spot fills tracked so far, summed over every product
+    // trading it as the base or the quote currency.
+    fn currency_changes(&self) -> HashMap<String, Decimal> {
+        let mut changes: HashMap<String, Decimal> = HashMap::new();
+        for (product_code, position) in &self.positions {
+            if product_code.is_fx() {
+                continue;
+            }
+            if let Some(base) = product_code.base_currency() {
+                *changes.entry(base.to_string()).or_default() += position.base_change;
+            }
+            if let Some(quote) = product_code.quote_currency() {
+                *changes.entry(quote.to_string()).or_default() += position.quote_change;
+            }
+        }
+        changes
+    }
+
+    // Balances are shared by every product trading the currency and move with deposits and
+    // withdrawals too, so only their change from here on is reconciled. Take it again after a
+    // deposit or a withdrawal.
+    pub fn set_balance_baseline(&mut self, balances: &[Balance]) {
+        let amounts = balances
+            .iter()
+            .map(|x| (x.currency_code.clone(), x.amount))
+            .collect();
+        self.balance_baseline = Some((amounts, self.currency_changes()));
+    }
+
+    // Empty until `set_balance_baseline`.
+    pub fn reconcile_balances(&self, balances: &[Balance]) -> Vec<BalanceDiscrepancy> {
+        let Some((baseline, tracked_baseline)) = &self.balance_baseline else {
+            return vec![];
+        };
+        let changes = self.currency_changes();
+        let mut currencies: Vec<&String> = changes.keys().chain(tracked_baseline.keys()).collect();
+        currencies.sort();
+        currencies.dedup();
+        currencies
+            .into_iter()
+            .filter_map(|currency_code| {
+                let amount = |balances: &[Balance]| {
+                    balances
+                        .iter()
+                        .find(|x| x.currency_code == *currency_code)
+                        .map(|x| x.amount)
+                        .unwrap_or_default()
+                };
+                let tracked_change = changes.get(currency_code).copied().unwrap_or_default()
+                    - tracked_baseline
+                        .get(currency_code)
+                        .copied()
+                        .unwrap_or_default();
+                let exchange_change =
+                    amount(balances) - baseline.get(currency_code).copied().unwrap_or_default();
+                (tracked_change != exchange_change).then(|| BalanceDiscrepancy {
+                    currency_code: currency_code.clone(),
+                    tracked_change,
+                    exchange_change,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn reconcile<A: BitflyerApi>(&self, client: &A) -> Result<Reconciliation> {
+        let positions = client.send(GetPositions {}).await?;
+        let balances = client.send(GetBalance).await?;
+        Ok(Reconciliation {
+            positions: self.reconcile_positions(&positions),
+            balances: self.reconcile_balances(&balances),
+        })
+    }
+}
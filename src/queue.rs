@@ -0,0 +1,123 @@
+use crate::board::OrderBook;
+use crate::entity::{Execution, ExecutionSide, ProductCode, Side};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuePosition {
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub price: Decimal,
+    pub remaining: Decimal,
+    // None until a book snapshot has been seen after the order was placed.
+    pub size_ahead: Option<Decimal>,
+    pub level_size: Option<Decimal>,
+}
+
+impl QueuePosition {
+    pub fn is_front(&self) -> bool {
+        self.size_ahead.is_some_and(|x| x.is_zero())
+    }
+
+    fn resting_side_hit_by(&self, side: &ExecutionSide) -> bool {
+        matches!(
+            (self.side, side),
+            (Side::Buy, ExecutionSide::Sell) | (Side::Sell, ExecutionSide::Buy)
+        )
+    }
+
+    fn traded_through(&self, price: Decimal) -> bool {
+        match self.side {
+            Side::Buy => price < self.price,
+            Side::Sell => price > self.price,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueueEstimator {
+    orders: HashMap<String, QueuePosition>,
+}
+
+impl QueueEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(
+        &mut self,
+        acceptance_id: String,
+        product_code: ProductCode,
+        side: Side,
+        price: Decimal,
+        remaining: Decimal,
+    ) {
+        self.orders.insert(
+            acceptance_id,
+            QueuePosition {
+                product_code,
+                side,
+                price,
+                remaining,
+                size_ahead: None,
+                level_size: None,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, acceptance_id: &str) -> Option<QueuePosition> {
+        self.orders.remove(acceptance_id)
+    }
+
+    pub fn position(&self, acceptance_id: &str) -> Option<&QueuePosition> {
+        self.orders.get(acceptance_id)
+    }
+
+    pub fn set_remaining(&mut self, acceptance_id: &str, remaining: Decimal) {
+        if let Some(position) = self.orders.get_mut(acceptance_id) {
+            position.remaining = remaining;
+        }
+    }
+
+    pub fn on_book(&mut self, product_code: &ProductCode, book: &impl OrderBook) {
+        for position in self.orders.values_mut() {
+            if position.product_code != *product_code {
+                continue;
+            }
+            let levels = match position.side {
+                Side::Buy => book.bid_levels(),
+                Side::Sell => book.ask_levels(),
+            };
+            let level_size = levels
+                .iter()
+                .find(|x| x.price == position.price)
+                .map(|x| x.size)
+                .unwrap_or_default();
+            // The level includes our own order; anything beyond it that disappeared
+            // is assumed to have been ahead of us.
+            let others = (level_size - position.remaining).max(Decimal::ZERO);
+            position.size_ahead = Some(match position.size_ahead {
+                Some(ahead) => ahead.min(others),
+                None => others,
+            });
+            position.level_size = Some(level_size);
+        }
+    }
+
+    pub fn on_execution(&mut self, product_code: &ProductCode, execution: &Execution) {
+        for position in self.orders.values_mut() {
+            if position.product_code != *product_code {
+                continue;
+            }
+            if position.traded_through(execution.price) {
+                position.size_ahead = Some(Decimal::ZERO);
+            } else if execution.price == position.price
+                && position.resting_side_hit_by(&execution.side)
+            {
+                if let Some(ahead) = position.size_ahead.as_mut() {
+                    *ahead = (*ahead - execution.size).max(Decimal::ZERO);
+                }
+            }
+        }
+    }
+}
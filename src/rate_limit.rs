@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use std::time::Duration;
+
+// bitFlyer's counters from the headers of a response: X-RateLimit-* for every request, and
+// X-OrderRequest-RateLimit-* for the stricter limit on order requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    // Length of the window in seconds.
+    pub period: Option<u64>,
+    // Requests left in the window.
+    pub remaining: Option<u64>,
+    // When the window starts over.
+    pub reset: Option<DateTime<Utc>>,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl RateLimitStatus {
+    pub fn from_headers(headers: &HeaderMap, observed_at: DateTime<Utc>) -> Option<Self> {
+        Self::parse(headers, "x-ratelimit", observed_at)
+    }
+
+    pub fn order_from_headers(headers: &HeaderMap, observed_at: DateTime<Utc>) -> Option<Self> {
+        Self::parse(headers, "x-orderrequest-ratelimit", observed_at)
+    }
+
+    // None when none of the headers is there.
+    fn parse(headers: &HeaderMap, prefix: &str, observed_at: DateTime<Utc>) -> Option<Self> {
+        let value = |name: &str| -> Option<u64> {
+            headers
+                .get(format!("{prefix}-{name}"))?
+                .to_str()
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        let status = Self {
+            period: value("period"),
+            remaining: value("remaining"),
+            reset: value("reset")
+                .and_then(|x| i64::try_from(x).ok())
+                .and_then(|x| DateTime::from_timestamp(x, 0)),
+            observed_at,
+        };
+        (status.period.is_some() || status.remaining.is_some() || status.reset.is_some())
+            .then_some(status)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    // How long to hold off before the next request: until the reset once no request is left,
+    // otherwise not at all.
+    pub fn wait_time(&self, now: DateTime<Utc>) -> Duration {
+        match self.reset {
+            Some(reset) if self.is_exhausted() => (reset - now).to_std().unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
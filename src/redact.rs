@@ -0,0 +1,48 @@
+use serde_json::Value;
+
+pub(crate) const REDACTED: &str = "REDACTED";
+
+// Wallet details show up in coin transfers.
+pub const DEFAULT_REDACTED_FIELDS: [&str; 2] = ["address", "tx_hash"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Redactor {
+    pub(crate) fields: Vec<String>,
+}
+
+impl Redactor {
+    pub(crate) fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.fields.iter().any(|x| x == key) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|x| self.redact_value(x)),
+            _ => {}
+        }
+    }
+
+    // Bodies that are not JSON are kept as they are.
+    pub(crate) fn redact(&self, body: &str) -> String {
+        match serde_json::from_str::<Value>(body) {
+            Ok(mut value) if !self.fields.is_empty() => {
+                self.redact_value(&mut value);
+                value.to_string()
+            }
+            _ => body.to_string(),
+        }
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            fields: DEFAULT_REDACTED_FIELDS.map(String::from).to_vec(),
+        }
+    }
+}
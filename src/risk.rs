@@ -0,0 +1,369 @@
+use crate::api::OrderIntent;
+use crate::entity::{ChildOrder, Position, ProductCode, Side, Ticker};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Asia::Tokyo;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskLimits {
+    pub max_order_size: Option<Decimal>,
+    pub max_notional: Option<Decimal>,
+    pub max_open_orders: Option<usize>,
+    pub price_collar: Option<Decimal>,
+    pub allowed_products: Option<Vec<ProductCode>>,
+    pub daily_loss_limit: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RiskViolation {
+    ProductNotAllowed(ProductCode),
+    OrderSizeExceeded {
+        size: Decimal,
+        max: Decimal,
+    },
+    NotionalExceeded {
+        notional: Decimal,
+        max: Decimal,
+    },
+    TooManyOpenOrders {
+        open: usize,
+        max: usize,
+    },
+    PriceOutsideCollar {
+        price: Decimal,
+        reference: Decimal,
+        collar: Decimal,
+    },
+    MissingReferencePrice(ProductCode),
+    DailyLossLimitReached {
+        pnl: Decimal,
+        limit: Decimal,
+    },
+}
+
+impl std::fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use RiskViolation::*;
+        match self {
+            ProductNotAllowed(product_code) => write!(f, "product is not allowed: {product_code}"),
+            OrderSizeExceeded { size, max } => write!(f, "order size {size} exceeds {max}"),
+            NotionalExceeded { notional, max } => write!(f, "notional {notional} exceeds {max}"),
+            TooManyOpenOrders { open, max } => {
+                write!(f, "{open} open orders reach the limit of {max}")
+            }
+            PriceOutsideCollar {
+                price,
+                reference,
+                collar,
+            } => write!(
+                f,
+                "price {price} is outside the collar {collar} around {reference}"
+            ),
+            MissingReferencePrice(product_code) => {
+                write!(f, "no reference price for {product_code}")
+            }
+            DailyLossLimitReached { pnl, limit } => {
+                write!(f, "daily PnL {pnl} reached the loss limit of {limit}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RiskViolation {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RiskEvent {
+    DailyLossLimitBreached {
+        date: NaiveDate,
+        pnl: Decimal,
+        limit: Decimal,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DailyPnl {
+    pub date: NaiveDate,
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    // Unrealized PnL carried over from the previous day is not part of today's PnL.
+    pub unrealized_baseline: Decimal,
+}
+
+impl DailyPnl {
+    pub fn total(&self) -> Decimal {
+        self.realized + self.unrealized - self.unrealized_baseline
+    }
+}
+
+#[derive(Debug)]
+pub struct RiskChecker {
+    limits: RiskLimits,
+    reference_prices: Mutex<HashMap<ProductCode, Decimal>>,
+    open_orders: Mutex<usize>,
+    // Net size per product, positive when long, for letting closing orders through once the
+    // daily loss limit is reached.
+    positions: Mutex<HashMap<ProductCode, Decimal>>,
+    daily_pnl: Mutex<DailyPnl>,
+    events: broadcast::Sender<RiskEvent>,
+}
+
+impl Default for RiskChecker {
+    fn default() -> Self {
+        Self::new(RiskLimits::default())
+    }
+}
+
+fn jst_today() -> NaiveDate {
+    Utc::now().with_timezone(&Tokyo).date_naive()
+}
+
+impl RiskChecker {
+    pub fn new(limits: RiskLimits) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            limits,
+            reference_prices: Mutex::new(HashMap::new()),
+            open_orders: Mutex::new(0),
+            positions: Mutex::new(HashMap::new()),
+            daily_pnl: Mutex::new(DailyPnl {
+                date: jst_today(),
+                ..Default::default()
+            }),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RiskEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn daily_pnl(&self) -> DailyPnl {
+        let mut daily_pnl = self.daily_pnl.lock().unwrap();
+        Self::roll_over(&mut daily_pnl);
+        daily_pnl.clone()
+    }
+
+    pub fn record_realized_pnl(&self, pnl: Decimal) {
+        self.update_daily_pnl(|x| x.realized += pnl);
+    }
+
+    pub fn set_unrealized_pnl(&self, pnl: Decimal) {
+        self.update_daily_pnl(|x| x.unrealized = pnl);
+    }
+
+    fn roll_over(daily_pnl: &mut DailyPnl) -> bool {
+        let today = jst_today();
+        if daily_pnl.date == today {
+            return false;
+        }
+        *daily_pnl = DailyPnl {
+            date: today,
+            realized: Decimal::ZERO,
+            unrealized: daily_pnl.unrealized,
+            unrealized_baseline: daily_pnl.unrealized,
+        };
+        true
+    }
+
+    fn update_daily_pnl(&self, f: impl FnOnce(&mut DailyPnl)) {
+        let mut daily_pnl = self.daily_pnl.lock().unwrap();
+        Self::roll_over(&mut daily_pnl);
+        let before = daily_pnl.total();
+        f(&mut daily_pnl);
+        let Some(limit) = self.limits.daily_loss_limit else {
+            return;
+        };
+        let pnl = daily_pnl.total();
+        if before > -limit && pnl <= -limit {
+            let _ = self.events.send(RiskEvent::DailyLossLimitBreached {
+                date: daily_pnl.date,
+                pnl,
+                limit,
+            });
+        }
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    pub fn update_ticker(&self, ticker: &Ticker) {
+        self.set_reference_price(ticker.product_code.clone(), ticker.ltp);
+    }
+
+    pub fn set_reference_price(&self, product_code: ProductCode, price: Decimal) {
+        self.reference_prices
+            .lock()
+            .unwrap()
+            .insert(product_code, price);
+    }
+
+    pub fn reference_price(&self, product_code: &ProductCode) -> Option<Decimal> {
+        self.reference_prices
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .copied()
+    }
+
+    // The open order count is only as fresh as its last update. `Client` and `SimClient` count
+    // the orders they get accepted, but cancels, fills and expiries are only seen by refreshing
+    // it from the exchange, e.g. with `update_open_orders` on `GetChildOrders`, or from the
+    // order events like `Context` does.
+    pub fn open_orders(&self) -> usize {
+        *self.open_orders.lock().unwrap()
+    }
+
+    pub fn set_open_orders(&self, open_orders: usize) {
+        *self.open_orders.lock().unwrap() = open_orders;
+    }
+
+    pub fn update_open_orders(&self, orders: &[ChildOrder]) {
+        let open = orders
+            .iter()
+            .filter(|x| x.child_order_state.is_active())
+            .count();
+        self.set_open_orders(open);
+    }
+
+    // Like the open order count, positions are only as fresh as their last update: `Context`
+    // sets them from its fills and `SimClient` from its own book, anything else has to set them
+    // itself, e.g. with `update_positions` on `GetPositions`. Spot holdings are the net size.
+    pub fn position(&self, product_code: &ProductCode) -> Decimal {
+        self.positions
+            .lock()
+            .unwrap()
+            .get(product_code)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_position(&self, product_code: ProductCode, net_size: Decimal) {
+        self.positions
+            .lock()
+            .unwrap()
+            .insert(product_code, net_size);
+    }
+
+    // Replaces the FX positions; a product missing from `positions` has none.
+    pub fn update_positions(&self, positions: &[Position]) {
+        let mut net_sizes: HashMap<ProductCode, Decimal> = HashMap::new();
+        for position in positions {
+            let signed = match position.side {
+                Side::Buy => position.size,
+                Side::Sell => -position.size,
+            };
+            *net_sizes.entry(position.product_code.clone()).or_default() += signed;
+        }
+        let mut tracked = self.positions.lock().unwrap();
+        tracked.retain(|product_code, _| !product_code.is_fx());
+        tracked.extend(net_sizes);
+    }
+
+    // Whether the order only takes off some or all of the position, without reversing it.
+    pub fn reduces_position(&self, intent: &OrderIntent) -> bool {
+        let net_size = self.position(&intent.product_code);
+        match intent.side {
+            Side::Buy => -net_size >= intent.size,
+            Side::Sell => net_size >= intent.size,
+        }
+    }
+
+    pub fn record_accepted_order(&self) {
+        *self.open_orders.lock().unwrap() += 1;
+    }
+
+    // Takes back an order `check_and_record` counted that was not accepted after all.
+    pub fn release_order(&self) {
+        let mut open_orders = self.open_orders.lock().unwrap();
+        *open_orders = open_orders.saturating_sub(1);
+    }
+
+    pub fn check(&self, intent: &OrderIntent) -> Result<(), RiskViolation> {
+        let open = *self.open_orders.lock().unwrap();
+        self.check_with(intent, open)
+    }
+
+    // Checks the intents of one request and counts it as an open order in one step, so
+    // concurrent requests can't all pass the open order limit before any of them is counted.
+    // Emergency intents are counted but not checked. Returns whether the request was counted;
+    // call `release_order` if it then fails.
+    pub fn check_and_record(&self, intents: &[OrderIntent]) -> Result<bool, RiskViolation> {
+        let mut open_orders = self.open_orders.lock().unwrap();
+        for intent in intents.iter().filter(|x| !x.emergency) {
+            self.check_with(intent, *open_orders)?;
+        }
+        if intents.is_empty() {
+            return Ok(false);
+        }
+        *open_orders += 1;
+        Ok(true)
+    }
+
+    fn check_with(&self, intent: &OrderIntent, open: usize) -> Result<(), RiskViolation> {
+        let limits = &self.limits;
+        if let Some(limit) = limits.daily_loss_limit {
+            let pnl = self.daily_pnl().total();
+            // Closing what is open only limits the loss further.
+            if pnl <= -limit && !self.reduces_position(intent) {
+                return Err(RiskViolation::DailyLossLimitReached { pnl, limit });
+            }
+        }
+        if let Some(allowed) = &limits.allowed_products {
+            if !allowed.contains(&intent.product_code) {
+                return Err(RiskViolation::ProductNotAllowed(
+                    intent.product_code.clone(),
+                ));
+            }
+        }
+
+        if let Some(max) = limits.max_order_size {
+            if intent.size > max {
+                return Err(RiskViolation::OrderSizeExceeded {
+                    size: intent.size,
+                    max,
+                });
+            }
+        }
+
+        if let Some(max) = limits.max_open_orders {
+            if open >= max {
+                return Err(RiskViolation::TooManyOpenOrders { open, max });
+            }
+        }
+
+        let reference = self.reference_price(&intent.product_code);
+        if let Some(collar) = limits.price_collar {
+            // Without a reference the collar cannot hold, so it refuses like `max_notional`.
+            let reference = reference
+                .ok_or_else(|| RiskViolation::MissingReferencePrice(intent.product_code.clone()))?;
+            if let Some(price) = intent.price {
+                if (price - reference).abs() > reference * collar {
+                    return Err(RiskViolation::PriceOutsideCollar {
+                        price,
+                        reference,
+                        collar,
+                    });
+                }
+            }
+        }
+
+        if let Some(max) = limits.max_notional {
+            let price = intent
+                .price
+                .or(reference)
+                .ok_or_else(|| RiskViolation::MissingReferencePrice(intent.product_code.clone()))?;
+            let notional = price * intent.size;
+            if notional > max {
+                return Err(RiskViolation::NotionalExceeded { notional, max });
+            }
+        }
+        Ok(())
+    }
+}
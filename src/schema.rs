@@ -0,0 +1,281 @@
+use crate::api::*;
+use reqwest::Method;
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::transform::transform_subschemas;
+use schemars::{JsonSchema, Schema};
+use serde_json::{json, Map, Value};
+
+const SERVER: &str = "https://api.bitflyer.com";
+const SECURITY_HEADERS: [&str; 3] = ["ACCESS-KEY", "ACCESS-TIMESTAMP", "ACCESS-SIGN"];
+
+// Request and response schemas of one `ApiRequest` impl.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndpointSchema {
+    pub name: String,
+    pub path: &'static str,
+    pub method: Method,
+    pub is_private: bool,
+    pub request: Value,
+    pub response: Value,
+}
+
+fn endpoint<T>(generator: &mut SchemaGenerator) -> EndpointSchema
+where
+    T: ApiRequest + JsonSchema,
+    T::Response: JsonSchema,
+{
+    EndpointSchema {
+        name: T::schema_name().into_owned(),
+        path: T::PATH,
+        method: T::METHOD,
+        is_private: T::IS_PRIVATE,
+        request: generator.subschema_for::<T>().to_value(),
+        response: generator.subschema_for::<T::Response>().to_value(),
+    }
+}
+
+macro_rules! endpoints {
+    ($generator:expr, $($request:ty),* $(,)?) => {
+        vec![$(endpoint::<$request>($generator)),*]
+    };
+}
+
+// Every request type of the crate. New `ApiRequest` impls have to be added here.
+fn endpoints(generator: &mut SchemaGenerator) -> Vec<EndpointSchema> {
+    endpoints!(
+        generator,
+        GetMarkets,
+        GetBoard,
+        GetTicker,
+        GetExecutions,
+        GetBoardState,
+        GetBoardHealth,
+        GetPermissions,
+        GetBalance,
+        GetCollateral,
+        GetCollateralAccounts,
+        SendChildOrder,
+        CancelChildOrder,
+        SendParentOrder,
+        CancelParentOrder,
+        CancelAllChildOrders,
+        GetChildOrders,
+        GetParentOrders,
+        GetParentOrder,
+        GetPositions,
+        GetPrivateExecutions,
+        GetBalanceHistory,
+        GetCoinIns,
+        GetCoinOuts,
+    )
+}
+
+// OpenAPI 3.0 allows a single `type`, so decimals (a string or a number) become an `anyOf`.
+fn split_type_arrays(schema: &mut Schema) {
+    transform_subschemas(&mut split_type_arrays, schema);
+    if !schema.get("type").is_some_and(Value::is_array) {
+        return;
+    }
+    let Some(Value::Array(types)) = schema.remove("type") else {
+        return;
+    };
+    let rest = schema
+        .as_object_mut()
+        .map(std::mem::take)
+        .unwrap_or_default();
+    let variants = types
+        .into_iter()
+        .map(|x| {
+            let mut variant = rest.clone();
+            variant.insert("type".to_string(), x);
+            Value::Object(variant)
+        })
+        .collect::<Vec<_>>();
+    schema.insert("anyOf".to_string(), Value::Array(variants));
+}
+
+// Query parameters are left out rather than sent as null.
+fn non_null(schema: &Value) -> Value {
+    let mut schema = schema.clone();
+    let Some(map) = schema.as_object_mut() else {
+        return schema;
+    };
+    map.remove("nullable");
+    if let Some(Value::Array(variants)) = map.get("anyOf") {
+        let mut variants = variants
+            .iter()
+            .filter(|x| x.get("enum") != Some(&json!([null])))
+            .cloned()
+            .collect::<Vec<_>>();
+        if variants.len() == 1 {
+            return variants.remove(0);
+        }
+        map.insert("anyOf".to_string(), Value::Array(variants));
+    }
+    schema
+}
+
+fn definition<'a>(definitions: &'a Map<String, Value>, schema: &Value) -> Option<&'a Value> {
+    let name = schema.get("$ref")?.as_str()?.rsplit('/').next()?;
+    definitions.get(name)
+}
+
+// GET requests send their fields in the query string.
+fn query_parameters(request: Option<&Value>) -> Vec<Value> {
+    let Some(request) = request else {
+        return vec![];
+    };
+    let required = request
+        .get("required")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    request
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&json!(name)),
+                "schema": non_null(schema),
+            })
+        })
+        .collect()
+}
+
+fn operation(endpoint: &EndpointSchema, definitions: &Map<String, Value>) -> Value {
+    let mut operation = json!({
+        "operationId": endpoint.name,
+        "responses": {
+            "200": {
+                "description": "OK",
+                "content": { "application/json": { "schema": endpoint.response } },
+            },
+        },
+    });
+    if endpoint.method == Method::GET {
+        let parameters = query_parameters(definition(definitions, &endpoint.request));
+        if !parameters.is_empty() {
+            operation["parameters"] = Value::Array(parameters);
+        }
+    } else {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": endpoint.request } },
+        });
+    }
+    if endpoint.is_private {
+        operation["security"] = json!([SECURITY_HEADERS
+            .iter()
+            .map(|x| (x.to_string(), json!([])))
+            .collect::<Map<_, _>>()]);
+    }
+    operation
+}
+
+// An OpenAPI 3.0 description of every endpoint, with shared types under
+// `components/schemas`.
+pub fn openapi() -> Value {
+    let mut generator = SchemaSettings::openapi3()
+        .with_transform(split_type_arrays)
+        .into_generator();
+    let endpoints = endpoints(&mut generator);
+    let mut definitions = generator.take_definitions(true);
+
+    let mut paths = Map::new();
+    for endpoint in &endpoints {
+        let method = endpoint.method.as_str().to_lowercase();
+        paths.insert(
+            endpoint.path.to_string(),
+            json!({ method: operation(endpoint, &definitions) }),
+        );
+    }
+    // GET requests only live on as query parameters.
+    for endpoint in endpoints.iter().filter(|x| x.method == Method::GET) {
+        definitions.remove(&endpoint.name);
+    }
+
+    let security_schemes = SECURITY_HEADERS
+        .iter()
+        .map(|x| {
+            (
+                x.to_string(),
+                json!({ "type": "apiKey", "in": "header", "name": x }),
+            )
+        })
+        .collect::<Map<_, _>>();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "bitFlyer Lightning API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": SERVER }],
+        "paths": paths,
+        "components": {
+            "schemas": definitions,
+            "securitySchemes": security_schemes,
+        },
+    })
+}
+
+// Names of the definitions `schema` refers to, directly or through other definitions.
+fn referenced(schema: &Value, definitions: &Map<String, Value>, names: &mut Vec<String>) {
+    match schema {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value.as_str()) {
+                    ("$ref", Some(reference)) => {
+                        let name = reference.rsplit('/').next().unwrap_or_default();
+                        if !names.iter().any(|x| x == name) {
+                            names.push(name.to_string());
+                            if let Some(definition) = definitions.get(name) {
+                                referenced(definition, definitions, names);
+                            }
+                        }
+                    }
+                    _ => referenced(value, definitions, names),
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|x| referenced(x, definitions, names)),
+        _ => {}
+    }
+}
+
+// Standalone JSON Schemas (draft 2020-12) of every request and response, each carrying the
+// definitions it needs.
+pub fn json_schemas() -> Vec<EndpointSchema> {
+    let mut generator = SchemaSettings::draft2020_12().into_generator();
+    let mut endpoints = endpoints(&mut generator);
+    let definitions = generator.take_definitions(true);
+    for endpoint in &mut endpoints {
+        for schema in [&mut endpoint.request, &mut endpoint.response] {
+            let mut names = vec![];
+            referenced(schema, &definitions, &mut names);
+            let mut root = Map::new();
+            root.insert(
+                "$schema".to_string(),
+                json!("https://json-schema.org/draft/2020-12/schema"),
+            );
+            if let Value::Object(map) = schema {
+                root.extend(std::mem::take(map));
+            }
+            if !names.is_empty() {
+                names.sort();
+                let defs = names
+                    .into_iter()
+                    .filter_map(|x| Some((x.clone(), definitions.get(&x)?.clone())))
+                    .collect::<Map<_, _>>();
+                root.insert("$defs".to_string(), Value::Object(defs));
+            }
+            *schema = Value::Object(root);
+        }
+    }
+    endpoints
+}
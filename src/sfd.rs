@@ -0,0 +1,199 @@
+use crate::api::{BitflyerApi, GetTicker};
+use crate::entity::{ProductCode, Side, Ticker};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SfdTier {
+    pub disparity: Decimal,
+    pub rate: Decimal,
+}
+
+pub const DEFAULT_SFD_TIERS: [SfdTier; 4] = [
+    SfdTier {
+        disparity: dec!(5),
+        rate: dec!(0.25),
+    },
+    SfdTier {
+        disparity: dec!(10),
+        rate: dec!(0.5),
+    },
+    SfdTier {
+        disparity: dec!(15),
+        rate: dec!(1),
+    },
+    SfdTier {
+        disparity: dec!(20),
+        rate: dec!(2),
+    },
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SfdCalculator {
+    pub spot_price: Decimal,
+    pub fx_price: Decimal,
+    tiers: Vec<SfdTier>,
+}
+
+impl SfdCalculator {
+    pub fn new(spot_price: Decimal, fx_price: Decimal) -> Self {
+        Self {
+            spot_price,
+            fx_price,
+            tiers: DEFAULT_SFD_TIERS.to_vec(),
+        }
+    }
+
+    pub fn from_tickers(spot: &Ticker, fx: &Ticker) -> Result<Self> {
+        if spot.product_code != ProductCode::BtcJpy || fx.product_code != ProductCode::FxBtcJpy {
+            return Err(anyhow!(
+                "expected BTC_JPY and FX_BTC_JPY tickers: {} {}",
+                spot.product_code,
+                fx.product_code
+            ));
+        }
+        Ok(Self::new(spot.ltp, fx.ltp))
+    }
+
+    pub fn with_tiers(mut self, mut tiers: Vec<SfdTier>) -> Self {
+        tiers.sort_by_key(|x| x.disparity);
+        self.tiers = tiers;
+        self
+    }
+
+    pub fn tiers(&self) -> &[SfdTier] {
+        &self.tiers
+    }
+
+    pub fn disparity(&self) -> Decimal {
+        self.disparity_at(self.fx_price)
+    }
+
+    pub fn disparity_at(&self, fx_price: Decimal) -> Decimal {
+        if self.spot_price.is_zero() {
+            return Decimal::ZERO;
+        }
+        (fx_price / self.spot_price - Decimal::ONE) * dec!(100)
+    }
+
+    pub fn rate(&self) -> Decimal {
+        self.rate_at(self.fx_price)
+    }
+
+    pub fn rate_at(&self, fx_price: Decimal) -> Decimal {
+        let disparity = self.disparity_at(fx_price).abs();
+        self.tiers
+            .iter()
+            .rev()
+            .find(|x| disparity >= x.disparity)
+            .map(|x| x.rate)
+            .unwrap_or_default()
+    }
+
+    pub fn would_incur_sfd(&self, side: Side, price: Decimal) -> bool {
+        let disparity = self.disparity_at(price);
+        let widens = match side {
+            Side::Buy => disparity.is_sign_positive(),
+            Side::Sell => disparity.is_sign_negative(),
+        };
+        widens && !self.rate_at(price).is_zero()
+    }
+
+    pub fn sfd_amount(&self, side: Side, price: Decimal, size: Decimal) -> Decimal {
+        if self.would_incur_sfd(side, price) {
+            price * size * self.rate_at(price) / dec!(100)
+        } else {
+            Decimal::ZERO
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disparity {
+    pub timestamp: DateTime<Utc>,
+    pub spot_price: Decimal,
+    pub fx_price: Decimal,
+    pub disparity: Decimal,
+    pub rate: Decimal,
+}
+
+impl From<&SfdCalculator> for Disparity {
+    fn from(calculator: &SfdCalculator) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            spot_price: calculator.spot_price,
+            fx_price: calculator.fx_price,
+            disparity: calculator.disparity(),
+            rate: calculator.rate(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DisparityTracker {
+    spot: Option<Ticker>,
+    fx: Option<Ticker>,
+    tiers: Option<Vec<SfdTier>>,
+}
+
+impl DisparityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tiers(mut self, tiers: Vec<SfdTier>) -> Self {
+        self.tiers = Some(tiers);
+        self
+    }
+
+    pub fn on_ticker(&mut self, ticker: &Ticker) -> Option<Disparity> {
+        match ticker.product_code {
+            ProductCode::BtcJpy => self.spot = Some(ticker.clone()),
+            ProductCode::FxBtcJpy => self.fx = Some(ticker.clone()),
+            _ => return None,
+        }
+        let (spot, fx) = (self.spot.as_ref()?, self.fx.as_ref()?);
+        let mut calculator = SfdCalculator::from_tickers(spot, fx).ok()?;
+        if let Some(tiers) = &self.tiers {
+            calculator = calculator.with_tiers(tiers.clone());
+        }
+        Some(Disparity {
+            timestamp: spot.timestamp.max(fx.timestamp),
+            ..Disparity::from(&calculator)
+        })
+    }
+}
+
+pub fn disparity_stream<A: BitflyerApi + 'static>(
+    api: Arc<A>,
+    interval: Duration,
+) -> impl Stream<Item = Result<Disparity>> + Send {
+    let interval = tokio::time::interval(interval);
+    futures::stream::unfold(
+        (api, interval, DisparityTracker::new()),
+        |(api, mut interval, mut tracker)| async move {
+            interval.tick().await;
+            let tickers = futures::future::try_join(
+                api.send(GetTicker {
+                    product_code: Some(ProductCode::BtcJpy),
+                }),
+                api.send(GetTicker {
+                    product_code: Some(ProductCode::FxBtcJpy),
+                }),
+            )
+            .await;
+            let item = tickers.and_then(|(spot, fx)| {
+                tracker.on_ticker(&spot);
+                tracker
+                    .on_ticker(&fx)
+                    .ok_or_else(|| anyhow!("failed to compute the disparity"))
+            });
+            Some((item, (api, interval, tracker)))
+        },
+    )
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use futures::{Stream, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Interval;
+pub use tokio_util::sync::CancellationToken;
+
+// What a background task watches to know when to stop. Tasks check it between units of work,
+// so a poll or the cancels it is sending always complete.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(CancellationToken);
+
+impl ShutdownSignal {
+    pub fn new(token: CancellationToken) -> Self {
+        Self(token)
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.0
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    // Resolves once a shutdown is requested. Dropping the handle does not request one, which
+    // leaves the task running like a dropped `JoinHandle`.
+    pub async fn wait(&mut self) {
+        self.0.cancelled().await
+    }
+
+    // Waits for the next tick of `interval`; false once a shutdown is requested.
+    pub async fn tick(&mut self, interval: &mut Interval) -> bool {
+        tokio::select! {
+            biased;
+            _ = self.wait() => false,
+            _ = interval.tick() => true,
+        }
+    }
+
+    // Sleeps for `duration`; false once a shutdown is requested.
+    pub async fn sleep(&mut self, duration: Duration) -> bool {
+        tokio::select! {
+            biased;
+            _ = self.wait() => false,
+            _ = tokio::time::sleep(duration) => true,
+        }
+    }
+}
+
+impl From<CancellationToken> for ShutdownSignal {
+    fn from(token: CancellationToken) -> Self {
+        Self::new(token)
+    }
+}
+
+// A spawned background task. `shutdown().await` asks it to stop, lets it finish the work in
+// flight and returns once it has ended.
+#[derive(Debug)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+impl ShutdownHandle {
+    pub fn spawn<F, Fut>(task: F) -> Self
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::spawn_with_token(&CancellationToken::new(), task)
+    }
+
+    // The task also stops once `parent` is cancelled, e.g. the application's token. Shutting
+    // down the handle leaves `parent` alone.
+    pub fn spawn_with_token<F, Fut>(parent: &CancellationToken, task: F) -> Self
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let token = parent.child_token();
+        // Cancelled when the task ends, which also ends the links of `with_cancellation`.
+        let guard = token.clone().drop_guard();
+        let task = task(ShutdownSignal(token.clone()));
+        Self {
+            token,
+            task: tokio::spawn(async move {
+                let _guard = guard;
+                task.await
+            }),
+        }
+    }
+
+    // Also stops the task once `token` is cancelled, for the tasks the crate's components
+    // spawn, e.g. `order_manager.spawn_polling(interval).with_cancellation(&token)`.
+    pub fn with_cancellation(self, token: &CancellationToken) -> Self {
+        let (parent, child) = (token.clone(), self.token.clone());
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = parent.cancelled() => child.cancel(),
+                _ = child.cancelled() => {}
+            }
+        });
+        self
+    }
+
+    // Cancelled once a shutdown is requested or the task has ended.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    // Whether the task has ended, on its own or after a shutdown.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    // Asks the task to stop without waiting for it.
+    pub fn request_shutdown(&self) {
+        self.token.cancel();
+    }
+
+    // Fails when the task panicked or was aborted.
+    pub async fn shutdown(self) -> Result<()> {
+        self.request_shutdown();
+        self.join().await
+    }
+
+    // Waits for the task to end without asking it to.
+    pub async fn join(self) -> Result<()> {
+        self.task
+            .await
+            .map_err(|e| anyhow!("the background task did not end cleanly: {e}"))
+    }
+
+    // Stops the task at its next await point, possibly in the middle of a request.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+}
+
+// Shuts down all the handles at once and waits for every one of them. Returns the first
+// failure.
+pub async fn shutdown_all(handles: impl IntoIterator<Item = ShutdownHandle>) -> Result<()> {
+    let handles: Vec<ShutdownHandle> = handles.into_iter().collect();
+    for handle in &handles {
+        handle.request_shutdown();
+    }
+    let mut result = Ok(());
+    for handle in handles {
+        if let Err(e) = handle.shutdown().await {
+            result = result.and(Err(e));
+        }
+    }
+    result
+}
+
+// Ends `stream` once `token` is cancelled, e.g. a poller or `Pager::pages`. Stays `Unpin` when
+// `stream` is.
+pub fn take_until_cancelled<'a, S>(
+    stream: S,
+    token: CancellationToken,
+) -> impl Stream<Item = S::Item> + Send + 'a
+where
+    S: Stream + Send + 'a,
+{
+    stream.take_until(Box::pin(token.cancelled_owned()))
+}
@@ -0,0 +1,1252 @@
+use crate::api::{ApiRequest, BitflyerApi, Client, GetBoard, SendChildOrder};
+use crate::board::{BookSide, OrderBook};
+use crate::clock::{Clock, SystemClock};
+use crate::entity::{
+    Board, ChildOrderType, Execution, ExecutionSide, MinuteToExpire, OrderState, ProductCode, Side,
+    Ticker, TimeInForce,
+};
+use crate::pnl::{CostMethod, ProductPnl};
+use crate::risk::RiskChecker;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const EXECUTION_HISTORY: usize = 1000;
+const DEFAULT_COUNT: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillModel {
+    // Resting orders fill as soon as the market trades at their price.
+    #[default]
+    Touch,
+    // Resting orders fill only when the market trades through their price.
+    TradeThrough,
+    // Resting orders join the back of the queue at their price, behind the size the board
+    // shows there. Trades at the price work through the queue first; trades through it fill.
+    QueueAware,
+    // Orders reach the market `latency` after they are sent, so they meet whatever the book
+    // is by then, and taker fills are `slippage` (a fraction of the price) worse.
+    Latency {
+        latency: Duration,
+        slippage: Decimal,
+    },
+}
+
+impl FillModel {
+    fn latency(&self) -> Option<Duration> {
+        match self {
+            FillModel::Latency { latency, .. } => Some(*latency),
+            _ => None,
+        }
+    }
+
+    fn slippage(&self) -> Decimal {
+        match self {
+            FillModel::Latency { slippage, .. } => *slippage,
+            _ => Decimal::ZERO,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+// Commission rates by liquidity. A product's own rates take precedence over the tiers, which
+// are chosen by the size of the product the simulator has executed so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    maker_rate: Decimal,
+    taker_rate: Decimal,
+    products: HashMap<ProductCode, (Decimal, Decimal)>,
+    // Sorted by the minimum volume.
+    tiers: Vec<(Decimal, Decimal, Decimal)>,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_rate: Decimal, taker_rate: Decimal) -> Self {
+        Self {
+            maker_rate,
+            taker_rate,
+            ..Default::default()
+        }
+    }
+
+    pub fn flat(rate: Decimal) -> Self {
+        Self::new(rate, rate)
+    }
+
+    pub fn with_product(
+        mut self,
+        product_code: ProductCode,
+        maker_rate: Decimal,
+        taker_rate: Decimal,
+    ) -> Self {
+        self.products.insert(product_code, (maker_rate, taker_rate));
+        self
+    }
+
+    pub fn with_tier(
+        mut self,
+        min_volume: Decimal,
+        maker_rate: Decimal,
+        taker_rate: Decimal,
+    ) -> Self {
+        self.tiers.retain(|x| x.0 != min_volume);
+        self.tiers.push((min_volume, maker_rate, taker_rate));
+        self.tiers.sort_by_key(|x| x.0);
+        self
+    }
+
+    pub fn rate(
+        &self,
+        product_code: &ProductCode,
+        liquidity: Liquidity,
+        volume: Decimal,
+    ) -> Decimal {
+        let (maker_rate, taker_rate) = self
+            .products
+            .get(product_code)
+            .copied()
+            .or_else(|| {
+                self.tiers
+                    .iter()
+                    .rev()
+                    .find(|x| x.0 <= volume)
+                    .map(|x| (x.1, x.2))
+            })
+            .unwrap_or((self.maker_rate, self.taker_rate));
+        match liquidity {
+            Liquidity::Maker => maker_rate,
+            Liquidity::Taker => taker_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SimOrder {
+    id: u64,
+    child_order_id: String,
+    child_order_acceptance_id: String,
+    product_code: ProductCode,
+    side: Side,
+    price: Option<Decimal>,
+    size: Decimal,
+    executed_size: Decimal,
+    executed_value: Decimal,
+    commission: Decimal,
+    state: OrderState,
+    time_in_force: TimeInForce,
+    child_order_date: DateTime<Utc>,
+    expire_date: DateTime<Utc>,
+    // Set while the order is on its way to the market.
+    arrives_at: Option<DateTime<Utc>>,
+    // Size queued before the order at its price, for `FillModel::QueueAware`.
+    queue_ahead: Decimal,
+    // The higher of the maker and taker rates when it was sent, so a spot sell keeps back the
+    // commission it will take from the base currency.
+    fee_rate: Decimal,
+}
+
+impl SimOrder {
+    fn remaining_size(&self) -> Decimal {
+        self.size - self.executed_size
+    }
+
+    fn is_active(&self) -> bool {
+        self.state == OrderState::Active
+    }
+
+    fn is_resting(&self) -> bool {
+        self.is_active() && self.arrives_at.is_none() && self.price.is_some()
+    }
+
+    fn to_json(&self) -> Value {
+        let (outstanding_size, cancel_size) = match self.state {
+            OrderState::Active => (self.remaining_size(), Decimal::ZERO),
+            OrderState::Completed => (Decimal::ZERO, Decimal::ZERO),
+            _ => (Decimal::ZERO, self.remaining_size()),
+        };
+        let average_price = if self.executed_size.is_zero() {
+            Decimal::ZERO
+        } else {
+            self.executed_value / self.executed_size
+        };
+        json!({
+            "id": self.id,
+            "child_order_id": self.child_order_id,
+            "product_code": self.product_code,
+            "side": self.side,
+            "child_order_type": if self.price.is_some() { "LIMIT" } else { "MARKET" },
+            "price": self.price.unwrap_or_default(),
+            "average_price": average_price,
+            "size": self.size,
+            "child_order_state": self.state,
+            "expire_date": self.expire_date,
+            "child_order_date": self.child_order_date,
+            "child_order_acceptance_id": self.child_order_acceptance_id,
+            "outstanding_size": outstanding_size,
+            "cancel_size": cancel_size,
+            "executed_size": self.executed_size,
+            "total_commission": self.commission,
+            "time_in_force": self.time_in_force,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SimFill {
+    id: u64,
+    child_order_id: String,
+    child_order_acceptance_id: String,
+    product_code: ProductCode,
+    side: Side,
+    price: Decimal,
+    size: Decimal,
+    commission: Decimal,
+    exec_date: DateTime<Utc>,
+}
+
+impl SimFill {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "child_order_id": self.child_order_id,
+            "side": self.side,
+            "price": self.price,
+            "size": self.size,
+            "commission": self.commission,
+            "exec_date": self.exec_date,
+            "child_order_acceptance_id": self.child_order_acceptance_id,
+        })
+    }
+}
+
+// On the lots of `ProductPnl`, like `PnlEngine` and `PositionTracker` keep them.
+#[derive(Clone, Debug)]
+struct SimPosition {
+    lots: ProductPnl,
+    // Of the open position, in the base currency.
+    commission: Decimal,
+}
+
+impl SimPosition {
+    // Positive for long, negative for short.
+    fn size(&self) -> Decimal {
+        self.lots.net_size()
+    }
+
+    // Includes the opening commissions.
+    fn price(&self) -> Decimal {
+        self.lots.average_cost().unwrap_or_default()
+    }
+
+    fn open_date(&self) -> Option<DateTime<Utc>> {
+        self.lots.lots.front().map(|x| x.opened_at)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    next_id: u64,
+    orders: Vec<SimOrder>,
+    fills: Vec<SimFill>,
+    balances: HashMap<String, Decimal>,
+    collateral: Decimal,
+    positions: HashMap<ProductCode, SimPosition>,
+    boards: HashMap<ProductCode, Board>,
+    last_prices: HashMap<ProductCode, Decimal>,
+    executions: HashMap<ProductCode, VecDeque<Execution>>,
+    // Size executed per product, for the fee tiers.
+    volumes: HashMap<ProductCode, Decimal>,
+}
+
+impl SimState {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn best_price(&self, product_code: &ProductCode, side: Side) -> Option<Decimal> {
+        let board = self.boards.get(product_code);
+        let best = match side {
+            Side::Buy => board.and_then(|x| x.best_ask()),
+            Side::Sell => board.and_then(|x| x.best_bid()),
+        };
+        best.map(|x| x.price)
+            .or_else(|| self.last_prices.get(product_code).copied())
+    }
+
+    fn mark_price(&self, product_code: &ProductCode) -> Option<Decimal> {
+        self.last_prices
+            .get(product_code)
+            .copied()
+            .or_else(|| self.boards.get(product_code).and_then(|x| x.mid()))
+    }
+
+    fn available(&self, currency_code: &str) -> Decimal {
+        let amount = self
+            .balances
+            .get(currency_code)
+            .copied()
+            .unwrap_or_default();
+        let locked: Decimal = self
+            .orders
+            .iter()
+            .filter(|x| x.is_active() && !x.product_code.is_fx())
+            .map(|x| match x.side {
+                Side::Buy if x.product_code.quote_currency() == Some(currency_code) => {
+                    x.remaining_size() * x.price.unwrap_or_default()
+                }
+                Side::Sell if x.product_code.base_currency() == Some(currency_code) => {
+                    x.remaining_size() * (Decimal::ONE + x.fee_rate)
+                }
+                _ => Decimal::ZERO,
+            })
+            .sum();
+        amount - locked
+    }
+
+    fn expire_orders(&mut self, now: DateTime<Utc>) {
+        for order in self.orders.iter_mut() {
+            if order.is_active() && order.expire_date <= now {
+                order.state = OrderState::Expired;
+            }
+        }
+    }
+
+    fn fill(
+        &mut self,
+        index: usize,
+        price: Decimal,
+        size: Decimal,
+        fees: &FeeSchedule,
+        liquidity: Liquidity,
+        now: DateTime<Utc>,
+    ) {
+        let id = self.next_id();
+        let order = &mut self.orders[index];
+        let volume = self.volumes.entry(order.product_code.clone()).or_default();
+        let commission = size * fees.rate(&order.product_code, liquidity, *volume);
+        *volume += size;
+        order.executed_size += size;
+        order.executed_value += price * size;
+        order.commission += commission;
+        if order.remaining_size() <= Decimal::ZERO {
+            order.state = OrderState::Completed;
+        }
+        let fill = SimFill {
+            id,
+            child_order_id: order.child_order_id.clone(),
+            child_order_acceptance_id: order.child_order_acceptance_id.clone(),
+            product_code: order.product_code.clone(),
+            side: order.side,
+            price,
+            size,
+            commission,
+            exec_date: now,
+        };
+        self.settle(&fill);
+        self.fills.push(fill);
+    }
+
+    fn settle(&mut self, fill: &SimFill) {
+        if fill.product_code.is_fx() {
+            let position = self
+                .positions
+                .entry(fill.product_code.clone())
+                .or_insert_with(|| SimPosition {
+                    lots: ProductPnl::new(fill.product_code.clone()),
+                    commission: Decimal::ZERO,
+                });
+            let realizations = position.lots.fill(
+                CostMethod::AverageCost,
+                fill.id,
+                fill.exec_date,
+                fill.side,
+                fill.price,
+                fill.size,
+                fill.commission,
+            );
+            position.commission += fill.commission;
+            if position.size().is_zero() {
+                position.commission = Decimal::ZERO;
+            }
+            // Commissions are part of the lots, so they reach the collateral with the realized
+            // PnL and count in the open position's PnL until then.
+            self.collateral += realizations.iter().map(|x| x.pnl).sum::<Decimal>();
+        } else if let (Some(base), Some(quote)) = (
+            fill.product_code.base_currency(),
+            fill.product_code.quote_currency(),
+        ) {
+            let signed_size = match fill.side {
+                Side::Buy => fill.size,
+                Side::Sell => -fill.size,
+            };
+            *self.balances.entry(base.to_string()).or_default() += signed_size - fill.commission;
+            *self.balances.entry(quote.to_string()).or_default() -= signed_size * fill.price;
+        }
+    }
+
+    // The size the board shows on the order's own side at its price.
+    fn displayed_size(&self, index: usize) -> Decimal {
+        let order = &self.orders[index];
+        let Some(board) = self.boards.get(&order.product_code) else {
+            return Decimal::ZERO;
+        };
+        let levels = match order.side {
+            Side::Buy => &board.bids,
+            Side::Sell => &board.asks,
+        };
+        levels
+            .iter()
+            .filter(|x| Some(x.price) == order.price)
+            .map(|x| x.size)
+            .sum()
+    }
+
+    // Fills an order reaching the market against the book, leaving what does not cross
+    // resting unless its time in force says otherwise.
+    fn take(&mut self, index: usize, fees: &FeeSchedule, slippage: Decimal, now: DateTime<Utc>) {
+        let order = &self.orders[index];
+        let (side, price, size) = (order.side, order.price, order.remaining_size());
+        let best = self.best_price(&order.product_code, side);
+        let crosses = |best| match (price, side) {
+            (None, _) => true,
+            (Some(price), Side::Buy) => best <= price,
+            (Some(price), Side::Sell) => best >= price,
+        };
+        let (fill_price, fill_size) = match best {
+            // Only as deep as the book goes, and a limit order only down to its price; without
+            // a book, all of it at the last price.
+            Some(best) if crosses(best) => match self.boards.get(&order.product_code) {
+                Some(board) => {
+                    let depth = match price {
+                        Some(price) => {
+                            board.cumulative_size_to_price(BookSide::taken_by(side), price)
+                        }
+                        None => size,
+                    };
+                    let estimate = board.estimate_impact(side, size.min(depth));
+                    (estimate.average_price, estimate.filled_size)
+                }
+                None => (Some(best), size),
+            },
+            _ => (None, size),
+        };
+        // A fill-or-kill order that can't fill in full doesn't fill at all.
+        let fill_price = fill_price
+            .filter(|_| self.orders[index].time_in_force != TimeInForce::Fok || fill_size >= size);
+        match fill_price {
+            Some(fill_price) => {
+                let slipped = match side {
+                    Side::Buy => fill_price * (Decimal::ONE + slippage),
+                    Side::Sell => fill_price * (Decimal::ONE - slippage),
+                };
+                // A limit order never fills beyond its price.
+                let fill_price = match (price, side) {
+                    (Some(price), Side::Buy) => slipped.min(price),
+                    (Some(price), Side::Sell) => slipped.max(price),
+                    (None, _) => slipped,
+                };
+                self.fill(index, fill_price, fill_size, fees, Liquidity::Taker, now);
+                // The exchange cancels what a market or IOC order could not fill; the rest of
+                // a GTC limit order rests.
+                if !self.orders[index].is_active() {
+                    return;
+                }
+                if price.is_none() || self.orders[index].time_in_force != TimeInForce::Gtc {
+                    self.orders[index].state = OrderState::Canceled;
+                } else {
+                    self.orders[index].queue_ahead = self.displayed_size(index);
+                }
+            }
+            None if price.is_none() || self.orders[index].time_in_force != TimeInForce::Gtc => {
+                self.orders[index].state = OrderState::Canceled;
+            }
+            None => self.orders[index].queue_ahead = self.displayed_size(index),
+        }
+    }
+
+    fn arrive_orders(&mut self, now: DateTime<Utc>, fees: &FeeSchedule, slippage: Decimal) {
+        for index in 0..self.orders.len() {
+            let order = &mut self.orders[index];
+            if order.is_active() && order.arrives_at.is_some_and(|x| x <= now) {
+                order.arrives_at = None;
+                self.take(index, fees, slippage, now);
+            }
+        }
+    }
+
+    // Size leaving the board ahead of an order moves it up the queue.
+    fn shrink_queues(&mut self, product_code: &ProductCode) {
+        for index in 0..self.orders.len() {
+            let order = &self.orders[index];
+            if order.is_resting() && order.product_code == *product_code {
+                let displayed = self.displayed_size(index);
+                let order = &mut self.orders[index];
+                order.queue_ahead = order.queue_ahead.min(displayed);
+            }
+        }
+    }
+
+    fn match_resting(
+        &mut self,
+        product_code: &ProductCode,
+        best_bid: Option<Decimal>,
+        best_ask: Option<Decimal>,
+        fill_model: FillModel,
+        fees: &FeeSchedule,
+        now: DateTime<Utc>,
+    ) {
+        for index in 0..self.orders.len() {
+            let order = &self.orders[index];
+            if !order.is_resting() || order.product_code != *product_code {
+                continue;
+            }
+            let Some(price) = order.price else {
+                continue;
+            };
+            let crossed = match (fill_model, order.side) {
+                (FillModel::QueueAware, Side::Buy) => best_ask.is_some_and(|x| x < price),
+                (FillModel::QueueAware, Side::Sell) => best_bid.is_some_and(|x| x > price),
+                (_, Side::Buy) => best_ask.is_some_and(|x| x <= price),
+                (_, Side::Sell) => best_bid.is_some_and(|x| x >= price),
+            };
+            if crossed {
+                let size = order.remaining_size();
+                self.fill(index, price, size, fees, Liquidity::Maker, now);
+            }
+        }
+    }
+}
+
+pub struct SimClient<M = Client> {
+    market: Option<M>,
+    fill_model: FillModel,
+    fees: FeeSchedule,
+    leverage: Decimal,
+    risk_checker: Option<Arc<RiskChecker>>,
+    clock: Arc<dyn Clock>,
+    state: Mutex<SimState>,
+}
+
+impl<M: std::fmt::Debug> std::fmt::Debug for SimClient<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimClient")
+            .field("market", &self.market)
+            .field("fill_model", &self.fill_model)
+            .field("fees", &self.fees)
+            .field("leverage", &self.leverage)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SimClient {
+    pub fn new() -> Self {
+        Self::from_market(None)
+    }
+}
+
+impl Default for SimClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> SimClient<M> {
+    pub fn with_market(market: M) -> Self {
+        Self::from_market(Some(market))
+    }
+
+    fn from_market(market: Option<M>) -> Self {
+        Self {
+            market,
+            fill_model: FillModel::default(),
+            fees: FeeSchedule::default(),
+            leverage: dec!(2),
+            risk_checker: None,
+            clock: Arc::new(SystemClock),
+            state: Mutex::new(SimState::default()),
+        }
+    }
+
+    pub fn with_fill_model(mut self, fill_model: FillModel) -> Self {
+        self.fill_model = fill_model;
+        self
+    }
+
+    // The same rate for makers and takers on every product.
+    pub fn with_commission_rate(mut self, commission_rate: Decimal) -> Self {
+        self.fees = FeeSchedule::flat(commission_rate);
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_leverage(mut self, leverage: Decimal) -> Self {
+        self.leverage = leverage;
+        self
+    }
+
+    pub fn with_risk_checker(mut self, risk_checker: Arc<RiskChecker>) -> Self {
+        self.risk_checker = Some(risk_checker);
+        self
+    }
+
+    // Dates orders, fills and expiries, e.g. a `ManualClock` following replayed data.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_balance(self, currency_code: impl Into<String>, amount: Decimal) -> Self {
+        self.set_balance(currency_code, amount);
+        self
+    }
+
+    pub fn with_collateral(self, amount: Decimal) -> Self {
+        self.state.lock().unwrap().collateral = amount;
+        self
+    }
+
+    pub fn market(&self) -> Option<&M> {
+        self.market.as_ref()
+    }
+
+    pub fn fill_model(&self) -> FillModel {
+        self.fill_model
+    }
+
+    pub fn fee_schedule(&self) -> &FeeSchedule {
+        &self.fees
+    }
+
+    pub fn set_balance(&self, currency_code: impl Into<String>, amount: Decimal) {
+        self.state
+            .lock()
+            .unwrap()
+            .balances
+            .insert(currency_code.into(), amount);
+    }
+
+    pub fn balance(&self, currency_code: &str) -> Decimal {
+        self.state
+            .lock()
+            .unwrap()
+            .balances
+            .get(currency_code)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn collateral(&self) -> Decimal {
+        self.state.lock().unwrap().collateral
+    }
+
+    // Orders in flight under `FillModel::Latency` reach the market on the first update after
+    // they arrive, meeting the book as it is then.
+    pub fn on_board(&self, product_code: &ProductCode, board: Board) {
+        let mut state = self.state.lock().unwrap();
+        let now = self.clock.now();
+        state.expire_orders(now);
+        let best_bid = board.best_bid().map(|x| x.price);
+        let best_ask = board.best_ask().map(|x| x.price);
+        state.boards.insert(product_code.clone(), board);
+        state.arrive_orders(now, &self.fees, self.fill_model.slippage());
+        if self.fill_model == FillModel::QueueAware {
+            state.shrink_queues(product_code);
+        }
+        state.match_resting(
+            product_code,
+            best_bid,
+            best_ask,
+            self.fill_model,
+            &self.fees,
+            now,
+        );
+    }
+
+    pub fn on_ticker(&self, ticker: &Ticker) {
+        let mut state = self.state.lock().unwrap();
+        let now = self.clock.now();
+        state.expire_orders(now);
+        state
+            .last_prices
+            .insert(ticker.product_code.clone(), ticker.ltp);
+        state.arrive_orders(now, &self.fees, self.fill_model.slippage());
+        state.match_resting(
+            &ticker.product_code,
+            Some(ticker.best_bid),
+            Some(ticker.best_ask),
+            self.fill_model,
+            &self.fees,
+            now,
+        );
+    }
+
+    pub fn on_execution(&self, product_code: &ProductCode, execution: &Execution) {
+        let mut state = self.state.lock().unwrap();
+        let now = self.clock.now();
+        state.expire_orders(now);
+        state
+            .last_prices
+            .insert(product_code.clone(), execution.price);
+        let history = state.executions.entry(product_code.clone()).or_default();
+        history.push_front(execution.clone());
+        history.truncate(EXECUTION_HISTORY);
+        state.arrive_orders(now, &self.fees, self.fill_model.slippage());
+
+        let mut available = execution.size;
+        for index in 0..state.orders.len() {
+            if available <= Decimal::ZERO {
+                break;
+            }
+            let order = &state.orders[index];
+            if !order.is_resting() || order.product_code != *product_code {
+                continue;
+            }
+            let Some(price) = order.price else {
+                continue;
+            };
+            let through = match order.side {
+                Side::Buy => execution.price < price,
+                Side::Sell => execution.price > price,
+            };
+            let fills = match self.fill_model {
+                FillModel::TradeThrough => through,
+                FillModel::QueueAware if !through => {
+                    // Only the other side's takers trade against the queue.
+                    let hits = match order.side {
+                        Side::Buy => execution.side != ExecutionSide::Buy,
+                        Side::Sell => execution.side != ExecutionSide::Sell,
+                    };
+                    if execution.price != price || !hits {
+                        continue;
+                    }
+                    let consumed = order.queue_ahead.min(available);
+                    state.orders[index].queue_ahead -= consumed;
+                    available -= consumed;
+                    available > Decimal::ZERO
+                }
+                _ => through || execution.price == price,
+            };
+            if fills {
+                let size = state.orders[index].remaining_size().min(available);
+                available -= size;
+                state.fill(index, price, size, &self.fees, Liquidity::Maker, now);
+            }
+        }
+    }
+
+    fn submit(&self, request: SendChildOrder) -> Result<Value> {
+        let mut state = self.state.lock().unwrap();
+        let now = self.clock.now();
+        state.expire_orders(now);
+        if request.size <= Decimal::ZERO {
+            return Err(anyhow!("order size must be positive: {}", request.size));
+        }
+        let price = match request.child_order_type {
+            ChildOrderType::Limit { price } => Some(price),
+            ChildOrderType::Market => None,
+        };
+        let best = state.best_price(&request.product_code, request.side);
+        // Only an estimate under `FillModel::Latency`, where the order fills on arrival.
+        let fill_price = match (price, best) {
+            (None, None) => {
+                return Err(anyhow!("no market price for {}", request.product_code));
+            }
+            (None, Some(_)) => state
+                .boards
+                .get(&request.product_code)
+                .and_then(|x| x.estimate_impact(request.side, request.size).average_price)
+                .or(best),
+            (Some(_), _) => None,
+        };
+
+        let volume = state
+            .volumes
+            .get(&request.product_code)
+            .copied()
+            .unwrap_or_default();
+        let fee_rate = self
+            .fees
+            .rate(&request.product_code, Liquidity::Maker, volume)
+            .max(
+                self.fees
+                    .rate(&request.product_code, Liquidity::Taker, volume),
+            );
+        if request.product_code.is_fx() {
+            let price = price.or(fill_price).unwrap_or_default();
+            let required = self.opening_size(&state, &request) * price / self.leverage;
+            let available = self.available_collateral(&state);
+            if available < required {
+                return Err(anyhow!(
+                    "insufficient collateral: required {required}, available {available}"
+                ));
+            }
+        } else {
+            let (currency_code, required) = match request.side {
+                Side::Buy => (
+                    request.product_code.quote_currency(),
+                    request.size * price.or(fill_price).unwrap_or_default(),
+                ),
+                // The commission comes out of the base currency on top of the size.
+                Side::Sell => (
+                    request.product_code.base_currency(),
+                    request.size * (Decimal::ONE + fee_rate),
+                ),
+            };
+            let currency_code = currency_code
+                .ok_or_else(|| anyhow!("unknown product: {}", request.product_code))?;
+            let available = state.available(currency_code);
+            if available < required {
+                return Err(anyhow!(
+                    "insufficient {currency_code}: required {required}, available {available}"
+                ));
+            }
+        }
+
+        let id = state.next_id();
+        let minute_to_expire = request.minute_to_expire.unwrap_or_default();
+        let time_in_force = request.time_in_force.unwrap_or(TimeInForce::Gtc);
+        let order = SimOrder {
+            id,
+            child_order_id: format!("JOR{}-{id:06}", now.format("%Y%m%d-%H%M%S")),
+            child_order_acceptance_id: format!("JRF{}-{id:06}", now.format("%Y%m%d-%H%M%S")),
+            product_code: request.product_code,
+            side: request.side,
+            price,
+            size: request.size,
+            executed_size: Decimal::ZERO,
+            executed_value: Decimal::ZERO,
+            commission: Decimal::ZERO,
+            state: OrderState::Active,
+            time_in_force,
+            child_order_date: now,
+            expire_date: now + expiry(minute_to_expire),
+            arrives_at: self
+                .fill_model
+                .latency()
+                .map(|x| now + chrono::Duration::from_std(x).unwrap_or_default()),
+            queue_ahead: Decimal::ZERO,
+            fee_rate,
+        };
+        let child_order_acceptance_id = order.child_order_acceptance_id.clone();
+        let in_flight = order.arrives_at.is_some();
+        state.orders.push(order);
+        if !in_flight {
+            let index = state.orders.len() - 1;
+            state.take(index, &self.fees, self.fill_model.slippage(), now);
+        }
+        Ok(json!({ "child_order_acceptance_id": child_order_acceptance_id }))
+    }
+
+    fn respond(&self, path: &str, params: &HashMap<String, String>, body: Value) -> Result<Value> {
+        match path {
+            "/v1/board" => self.board(&param_product_code(params, "product_code")?),
+            "/v1/ticker" => self.ticker(&param_product_code(params, "product_code")?),
+            "/v1/executions" => self.executions(params),
+            "/v1/me/getpermissions" => Ok(json!(SUPPORTED_PRIVATE_PATHS)),
+            "/v1/me/getbalance" => Ok(self.balances()),
+            "/v1/me/getcollateral" => Ok(self.collateral_json()),
+            "/v1/me/getcollateralaccounts" => {
+                Ok(json!([{ "currency_code": "JPY", "amount": self.collateral() }]))
+            }
+            "/v1/me/getpositions" => Ok(self.positions()),
+            "/v1/me/sendchildorder" => self.submit(serde_json::from_value(body)?),
+            "/v1/me/cancelchildorder" => self.cancel(&body),
+            "/v1/me/cancelallchildorders" => self.cancel_all(&body),
+            "/v1/me/getchildorders" => self.child_orders(params),
+            "/v1/me/getexecutions" => self.private_executions(params),
+            "/v1/me/getparentorders" => Ok(json!([])),
+            path => Err(anyhow!("not supported by the simulator: {path}")),
+        }
+    }
+
+    fn board(&self, product_code: &ProductCode) -> Result<Value> {
+        let state = self.state.lock().unwrap();
+        let board = state
+            .boards
+            .get(product_code)
+            .ok_or_else(|| anyhow!("no board for {product_code}"))?;
+        let levels = |levels: &[crate::entity::BoardElement]| -> Vec<Value> {
+            levels
+                .iter()
+                .map(|x| json!({ "price": x.price, "size": x.size }))
+                .collect()
+        };
+        Ok(json!({
+            "mid_price": board.mid_price,
+            "bids": levels(&board.bids),
+            "asks": levels(&board.asks),
+        }))
+    }
+
+    fn ticker(&self, product_code: &ProductCode) -> Result<Value> {
+        let state = self.state.lock().unwrap();
+        let ltp = state
+            .mark_price(product_code)
+            .ok_or_else(|| anyhow!("no market price for {product_code}"))?;
+        let board = state.boards.get(product_code);
+        let best = |level: Option<&crate::entity::BoardElement>| {
+            level.map_or((ltp, Decimal::ZERO), |x| (x.price, x.size))
+        };
+        let (best_bid, best_bid_size) = best(board.and_then(|x| x.best_bid()));
+        let (best_ask, best_ask_size) = best(board.and_then(|x| x.best_ask()));
+        let depth = |levels: Option<&Vec<crate::entity::BoardElement>>| -> Decimal {
+            levels.map_or(Decimal::ZERO, |x| x.iter().map(|x| x.size).sum())
+        };
+        Ok(json!({
+            "product_code": product_code,
+            "state": "RUNNING",
+            "timestamp": self.clock.now(),
+            "tick_id": state.next_id,
+            "best_bid": best_bid,
+            "best_ask": best_ask,
+            "best_bid_size": best_bid_size,
+            "best_ask_size": best_ask_size,
+            "total_bid_depth": depth(board.map(|x| &x.bids)),
+            "total_ask_depth": depth(board.map(|x| &x.asks)),
+            "market_bid_size": 0,
+            "market_ask_size": 0,
+            "ltp": ltp,
+            "volume": 0,
+            "volume_by_product": 0,
+        }))
+    }
+
+    fn executions(&self, params: &HashMap<String, String>) -> Result<Value> {
+        let product_code = param_product_code(params, "product_code")?;
+        let state = self.state.lock().unwrap();
+        let executions = state
+            .executions
+            .get(&product_code)
+            .into_iter()
+            .flatten()
+            .filter(|x| in_range(params, x.id))
+            .take(param_count(params)?)
+            .map(|x| {
+                json!({
+                    "id": x.id,
+                    "side": x.side,
+                    "price": x.price,
+                    "size": x.size,
+                    "exec_date": x.exec_date,
+                    "buy_child_order_acceptance_id": x.buy_child_order_acceptance_id,
+                    "sell_child_order_acceptance_id": x.sell_child_order_acceptance_id,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json!(executions))
+    }
+
+    fn balances(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        let mut currency_codes = state.balances.keys().collect::<Vec<_>>();
+        currency_codes.sort();
+        let balances = currency_codes
+            .into_iter()
+            .map(|x| {
+                json!({
+                    "currency_code": x,
+                    "amount": state.balances[x],
+                    "available": state.available(x),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!(balances)
+    }
+
+    // The FX position, or the holdings of the base currency on spot.
+    fn net_position(&self, product_code: &ProductCode) -> Decimal {
+        let state = self.state.lock().unwrap();
+        if product_code.is_fx() {
+            state
+                .positions
+                .get(product_code)
+                .map(|x| x.size())
+                .unwrap_or_default()
+        } else {
+            product_code
+                .base_currency()
+                .and_then(|x| state.balances.get(x))
+                .copied()
+                .unwrap_or_default()
+        }
+    }
+
+    fn open_order_count(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.expire_orders(self.clock.now());
+        state.orders.iter().filter(|x| x.is_active()).count()
+    }
+
+    // The part of an FX order that adds to the position instead of closing it.
+    fn opening_size(&self, state: &SimState, request: &SendChildOrder) -> Decimal {
+        let position = state
+            .positions
+            .get(&request.product_code)
+            .map(|x| x.size())
+            .unwrap_or_default();
+        let closing = match request.side {
+            Side::Buy => (-position).max(Decimal::ZERO),
+            Side::Sell => position.max(Decimal::ZERO),
+        };
+        (request.size - closing).max(Decimal::ZERO)
+    }
+
+    // Collateral not yet taken by the open positions or the active FX orders.
+    fn available_collateral(&self, state: &SimState) -> Decimal {
+        let (pnl, require_collateral) = self.open_position_pnl(state);
+        let ordered: Decimal = state
+            .orders
+            .iter()
+            .filter(|x| x.is_active() && x.product_code.is_fx())
+            .map(|x| {
+                let price = x.price.or_else(|| state.mark_price(&x.product_code));
+                x.remaining_size() * price.unwrap_or_default() / self.leverage
+            })
+            .sum();
+        state.collateral + pnl - require_collateral - ordered
+    }
+
+    fn open_position_pnl(&self, state: &SimState) -> (Decimal, Decimal) {
+        let mut pnl = Decimal::ZERO;
+        let mut require_collateral = Decimal::ZERO;
+        for (product_code, position) in &state.positions {
+            let mark = state.mark_price(product_code).unwrap_or(position.price());
+            pnl += position.lots.unrealized_pnl(mark);
+            require_collateral += position.price() * position.size().abs() / self.leverage;
+        }
+        (pnl, require_collateral)
+    }
+
+    fn collateral_json(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        let (pnl, require_collateral) = self.open_position_pnl(&state);
+        let keep_rate = if require_collateral.is_zero() {
+            0.0
+        } else {
+            ((state.collateral + pnl) / require_collateral)
+                .to_f64()
+                .unwrap_or_default()
+        };
+        json!({
+            "collateral": state.collateral,
+            "open_position_pnl": pnl,
+            "require_collateral": require_collateral,
+            "keep_rate": keep_rate,
+            "margin_call_amount": 0,
+            "margin_call_due_date": null,
+        })
+    }
+
+    fn positions(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        let positions = state
+            .positions
+            .iter()
+            .filter(|(_, x)| !x.size().is_zero())
+            .map(|(product_code, x)| {
+                let mark = state.mark_price(product_code).unwrap_or(x.price());
+                json!({
+                    "product_code": product_code,
+                    "side": if x.size().is_sign_positive() { Side::Buy } else { Side::Sell },
+                    "price": x.price(),
+                    "size": x.size().abs(),
+                    "commission": x.commission,
+                    "swap_point_accumulate": 0,
+                    "require_collateral": x.price() * x.size().abs() / self.leverage,
+                    "open_date": x.open_date().unwrap_or_else(|| self.clock.now()),
+                    "leverage": self.leverage,
+                    "pnl": x.lots.unrealized_pnl(mark),
+                    "sfd": 0,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!(positions)
+    }
+
+    fn cancel(&self, body: &Value) -> Result<Value> {
+        let product_code: ProductCode = serde_json::from_value(body["product_code"].clone())?;
+        let mut state = self.state.lock().unwrap();
+        let order = state
+            .orders
+            .iter_mut()
+            .find(|x| {
+                x.product_code == product_code
+                    && (body["child_order_acceptance_id"] == x.child_order_acceptance_id.as_str()
+                        || body["child_order_id"] == x.child_order_id.as_str())
+            })
+            .ok_or_else(|| anyhow!("order is not found: {body}"))?;
+        if order.is_active() {
+            order.state = OrderState::Canceled;
+        }
+        Ok(Value::Null)
+    }
+
+    fn cancel_all(&self, body: &Value) -> Result<Value> {
+        let product_code: ProductCode = serde_json::from_value(body["product_code"].clone())?;
+        let mut state = self.state.lock().unwrap();
+        for order in state.orders.iter_mut() {
+            if order.is_active() && order.product_code == product_code {
+                order.state = OrderState::Canceled;
+            }
+        }
+        Ok(Value::Null)
+    }
+
+    fn child_orders(&self, params: &HashMap<String, String>) -> Result<Value> {
+        let product_code = param_product_code(params, "product_code")?;
+        let child_order_state = params
+            .get("child_order_state")
+            .map(|x| serde_json::from_value::<OrderState>(json!(x)))
+            .transpose()?;
+        let mut state = self.state.lock().unwrap();
+        state.expire_orders(self.clock.now());
+        let orders = state
+            .orders
+            .iter()
+            .rev()
+            .filter(|x| x.product_code == product_code)
+            .filter(|x| child_order_state.as_ref().is_none_or(|s| x.state == *s))
+            .filter(|x| param_matches(params, "child_order_id", &x.child_order_id))
+            .filter(|x| {
+                param_matches(
+                    params,
+                    "child_order_acceptance_id",
+                    &x.child_order_acceptance_id,
+                )
+            })
+            .filter(|x| in_range(params, x.id))
+            .take(param_count(params)?)
+            .map(SimOrder::to_json)
+            .collect::<Vec<_>>();
+        Ok(json!(orders))
+    }
+
+    fn private_executions(&self, params: &HashMap<String, String>) -> Result<Value> {
+        let product_code = param_product_code(params, "product_code")?;
+        let state = self.state.lock().unwrap();
+        let fills = state
+            .fills
+            .iter()
+            .rev()
+            .filter(|x| x.product_code == product_code)
+            .filter(|x| param_matches(params, "child_order_id", &x.child_order_id))
+            .filter(|x| {
+                param_matches(
+                    params,
+                    "child_order_acceptance_id",
+                    &x.child_order_acceptance_id,
+                )
+            })
+            .filter(|x| in_range(params, x.id))
+            .take(param_count(params)?)
+            .map(SimFill::to_json)
+            .collect::<Vec<_>>();
+        Ok(json!(fills))
+    }
+}
+
+impl<M: BitflyerApi> BitflyerApi for SimClient<M> {
+    async fn send<T>(&self, request: T) -> Result<T::Response>
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        let body = request
+            .body()?
+            .map(|x| serde_json::from_str::<Value>(&x))
+            .transpose()?
+            .unwrap_or_default();
+        if let Some(market) = &self.market {
+            if !T::IS_PRIVATE {
+                return market.send(request).await;
+            }
+            if T::PATH == SendChildOrder::PATH {
+                let product_code: ProductCode =
+                    serde_json::from_value(body["product_code"].clone())?;
+                let board = market.send(GetBoard::new(product_code.clone())).await?;
+                self.on_board(&product_code, board);
+            }
+        }
+        let intents = request.order_intents();
+        let mut recorded = false;
+        if let Some(risk_checker) = &self.risk_checker {
+            // The simulator knows its open orders and positions, so they are always current here.
+            risk_checker.set_open_orders(self.open_order_count());
+            for intent in intents.iter().filter(|x| !x.emergency) {
+                risk_checker.set_position(
+                    intent.product_code.clone(),
+                    self.net_position(&intent.product_code),
+                );
+            }
+            recorded = risk_checker.check_and_record(&intents)?;
+        }
+        let params = request
+            .url()?
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<HashMap<_, _>>();
+        let response = self.respond(T::PATH, &params, body);
+        if let (Some(risk_checker), true, Err(_)) = (&self.risk_checker, recorded, &response) {
+            risk_checker.release_order();
+        }
+        request.parse_response(&response?.to_string())
+    }
+}
+
+const SUPPORTED_PRIVATE_PATHS: [&str; 10] = [
+    "/v1/me/getbalance",
+    "/v1/me/getcollateral",
+    "/v1/me/getcollateralaccounts",
+    "/v1/me/getpositions",
+    "/v1/me/sendchildorder",
+    "/v1/me/cancelchildorder",
+    "/v1/me/cancelallchildorders",
+    "/v1/me/getchildorders",
+    "/v1/me/getexecutions",
+    "/v1/me/getparentorders",
+];
+
+fn expiry(minute_to_expire: MinuteToExpire) -> chrono::Duration {
+    chrono::Duration::minutes(minute_to_expire.minutes() as i64)
+}
+
+fn param_product_code(params: &HashMap<String, String>, key: &str) -> Result<ProductCode> {
+    match params.get(key) {
+        Some(x) => Ok(serde_json::from_value(json!(x))?),
+        None => Ok(ProductCode::BtcJpy),
+    }
+}
+
+fn param_count(params: &HashMap<String, String>) -> Result<usize> {
+    match params.get("count") {
+        Some(x) => Ok(x.parse()?),
+        None => Ok(DEFAULT_COUNT),
+    }
+}
+
+fn param_matches(params: &HashMap<String, String>, key: &str, value: &str) -> bool {
+    params.get(key).is_none_or(|x| x == value)
+}
+
+fn in_range(params: &HashMap<String, String>, id: u64) -> bool {
+    let before = params.get("before").and_then(|x| x.parse::<u64>().ok());
+    let after = params.get("after").and_then(|x| x.parse::<u64>().ok());
+    before.is_none_or(|x| id < x) && after.is_none_or(|x| id > x)
+}
@@ -0,0 +1,274 @@
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+
+use crate::entity::{Board, ChildOrderType, Execution, ProductCode, Ticker};
+use crate::export::csv::{decimal, label};
+use crate::order_manager::OrderEvent;
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{Future, Stream, StreamExt};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+// Bumped whenever a field is renamed or removed; new fields may be added within a version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Ticker,
+    Board,
+    Execution,
+    Order,
+}
+
+impl MessageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Ticker => "ticker",
+            MessageKind::Board => "board",
+            MessageKind::Execution => "execution",
+            MessageKind::Order => "order",
+        }
+    }
+}
+
+// Decimals are encoded as strings so consumers never see floating point rounding.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SinkMessage {
+    pub schema: u32,
+    pub kind: MessageKind,
+    pub product_code: Option<String>,
+    pub published_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+fn levels(levels: &[crate::entity::BoardElement]) -> Value {
+    levels
+        .iter()
+        .map(|x| json!([decimal(x.price), decimal(x.size)]))
+        .collect()
+}
+
+fn optional(value: Option<Decimal>) -> Value {
+    value
+        .map(|x| Value::String(decimal(x)))
+        .unwrap_or(Value::Null)
+}
+
+impl SinkMessage {
+    fn new(kind: MessageKind, product_code: Option<&ProductCode>, data: Value) -> Self {
+        Self {
+            schema: SCHEMA_VERSION,
+            kind,
+            product_code: product_code.map(label),
+            published_at: Utc::now(),
+            data,
+        }
+    }
+
+    pub fn ticker(ticker: &Ticker) -> Self {
+        Self::new(
+            MessageKind::Ticker,
+            Some(&ticker.product_code),
+            json!({
+                "timestamp": ticker.timestamp,
+                "tick_id": decimal(ticker.tick_id),
+                "best_bid": decimal(ticker.best_bid),
+                "best_ask": decimal(ticker.best_ask),
+                "best_bid_size": decimal(ticker.best_bid_size),
+                "best_ask_size": decimal(ticker.best_ask_size),
+                "ltp": decimal(ticker.ltp),
+                "volume": decimal(ticker.volume),
+            }),
+        )
+    }
+
+    pub fn board(product_code: &ProductCode, board: &Board) -> Self {
+        Self::new(
+            MessageKind::Board,
+            Some(product_code),
+            json!({
+                "mid_price": decimal(board.mid_price),
+                "bids": levels(&board.bids),
+                "asks": levels(&board.asks),
+            }),
+        )
+    }
+
+    pub fn execution(product_code: &ProductCode, execution: &Execution) -> Self {
+        Self::new(
+            MessageKind::Execution,
+            Some(product_code),
+            json!({
+                "id": execution.id,
+                "side": label(&execution.side),
+                "price": decimal(execution.price),
+                "size": decimal(execution.size),
+                "exec_date": execution.exec_date,
+                "buy_child_order_acceptance_id": execution.buy_child_order_acceptance_id,
+                "sell_child_order_acceptance_id": execution.sell_child_order_acceptance_id,
+            }),
+        )
+    }
+
+    pub fn order_event(event: &OrderEvent) -> Self {
+        match event {
+            OrderEvent::Submitted {
+                acceptance_id,
+                request,
+            } => {
+                let price = match request.child_order_type {
+                    ChildOrderType::Limit { price } => Some(price),
+                    ChildOrderType::Market => None,
+                };
+                Self::new(
+                    MessageKind::Order,
+                    Some(&request.product_code),
+                    json!({
+                        "event": "submitted",
+                        "acceptance_id": acceptance_id,
+                        "side": label(&request.side),
+                        "price": optional(price),
+                        "size": decimal(request.size),
+                    }),
+                )
+            }
+            OrderEvent::CancelRequested { acceptance_id } => Self::new(
+                MessageKind::Order,
+                None,
+                json!({
+                    "event": "cancel_requested",
+                    "acceptance_id": acceptance_id,
+                }),
+            ),
+            OrderEvent::StatusChanged {
+                acceptance_id,
+                previous,
+                current,
+                order,
+            } => Self::new(
+                MessageKind::Order,
+                Some(&order.product_code),
+                json!({
+                    "event": "status_changed",
+                    "acceptance_id": acceptance_id,
+                    "previous": format!("{previous:?}"),
+                    "current": format!("{current:?}"),
+                    "side": label(&order.side),
+                    "size": decimal(order.size),
+                    "executed_size": decimal(order.executed_size),
+                    "outstanding_size": decimal(order.outstanding_size),
+                    "average_price": decimal(order.average_price),
+                }),
+            ),
+        }
+    }
+
+    // `<prefix>.<kind>.<product_code>`, or without the product when there is none.
+    pub fn subject(&self, prefix: &str) -> String {
+        match &self.product_code {
+            Some(product_code) => format!("{prefix}.{}.{product_code}", self.kind.as_str()),
+            None => format!("{prefix}.{}", self.kind.as_str()),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+pub trait MessageSink: Send + Sync {
+    fn publish(&self, message: &SinkMessage) -> impl Future<Output = Result<()>> + Send;
+}
+
+// Forwards streams and order events to a sink; failed publishes are logged and dropped.
+#[derive(Debug)]
+pub struct Publisher<S> {
+    sink: S,
+}
+
+impl<S: MessageSink + 'static> Publisher<S> {
+    pub fn new(sink: S) -> Self {
+        Self { sink }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub async fn publish(&self, message: &SinkMessage) -> Result<()> {
+        self.sink.publish(message).await
+    }
+
+    // Stops after the message being published on shutdown.
+    pub fn spawn_forward<St>(self: &Arc<Self>, messages: St) -> ShutdownHandle
+    where
+        St: Stream<Item = SinkMessage> + Send + 'static,
+    {
+        let publisher = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut messages = std::pin::pin!(messages);
+            loop {
+                let message = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => break,
+                    message = messages.next() => message,
+                };
+                let Some(message) = message else {
+                    break;
+                };
+                if let Err(e) = publisher.publish(&message).await {
+                    tracing::warn!(
+                        "failed to publish a {} message: {e:?}",
+                        message.kind.as_str()
+                    );
+                }
+            }
+        })
+    }
+
+    // Pass `OrderManager::subscribe()`; the task ends when the manager is dropped. On shutdown
+    // the events already sent are still published.
+    pub fn spawn_order_events(
+        self: &Arc<Self>,
+        mut events: broadcast::Receiver<OrderEvent>,
+    ) -> ShutdownHandle {
+        let publisher = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            loop {
+                let event = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => break,
+                    event = events.recv() => event,
+                };
+                match event {
+                    Ok(event) => publisher.publish_order_event(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("publisher missed {n} order events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+            loop {
+                match events.try_recv() {
+                    Ok(event) => publisher.publish_order_event(&event).await,
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                    Err(_) => return,
+                }
+            }
+        })
+    }
+
+    async fn publish_order_event(&self, event: &OrderEvent) {
+        if let Err(e) = self.publish(&SinkMessage::order_event(event)).await {
+            tracing::warn!("failed to publish an order event: {e:?}");
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::{MessageSink, SinkMessage};
+use anyhow::Result;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use std::time::Duration;
+
+// Publishes each message to the `<prefix>.<kind>` topic keyed by product code,
+// so a product's messages stay ordered within one partition.
+#[derive(Clone)]
+pub struct KafkaSink {
+    producer: FutureProducer,
+    prefix: String,
+    queue_timeout: Duration,
+}
+
+impl std::fmt::Debug for KafkaSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaSink")
+            .field("prefix", &self.prefix)
+            .field("queue_timeout", &self.queue_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KafkaSink {
+    pub fn new(producer: FutureProducer, prefix: impl Into<String>) -> Self {
+        Self {
+            producer,
+            prefix: prefix.into(),
+            queue_timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn connect(brokers: &str, prefix: impl Into<String>) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()?;
+        Ok(Self::new(producer, prefix))
+    }
+
+    pub fn with_queue_timeout(mut self, queue_timeout: Duration) -> Self {
+        self.queue_timeout = queue_timeout;
+        self
+    }
+}
+
+impl MessageSink for KafkaSink {
+    async fn publish(&self, message: &SinkMessage) -> Result<()> {
+        let topic = format!("{}.{}", self.prefix, message.kind.as_str());
+        let payload = message.to_json()?;
+        let key = message.product_code.clone().unwrap_or_default();
+        self.producer
+            .send(
+                FutureRecord::to(&topic).key(&key).payload(&payload),
+                self.queue_timeout,
+            )
+            .await
+            .map_err(|(e, _)| e)?;
+        Ok(())
+    }
+}
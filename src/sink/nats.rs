@@ -0,0 +1,35 @@
+use super::{MessageSink, SinkMessage};
+use anyhow::Result;
+
+// Publishes each message on `<prefix>.<kind>.<product_code>`.
+#[derive(Clone, Debug)]
+pub struct NatsSink {
+    client: async_nats::Client,
+    prefix: String,
+}
+
+impl NatsSink {
+    pub fn new(client: async_nats::Client, prefix: impl Into<String>) -> Self {
+        Self {
+            client,
+            prefix: prefix.into(),
+        }
+    }
+
+    pub async fn connect(url: &str, prefix: impl Into<String>) -> Result<Self> {
+        Ok(Self::new(async_nats::connect(url).await?, prefix))
+    }
+
+    pub fn client(&self) -> &async_nats::Client {
+        &self.client
+    }
+}
+
+impl MessageSink for NatsSink {
+    async fn publish(&self, message: &SinkMessage) -> Result<()> {
+        self.client
+            .publish(message.subject(&self.prefix), message.to_json()?.into())
+            .await?;
+        Ok(())
+    }
+}
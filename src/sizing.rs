@@ -0,0 +1,127 @@
+use crate::api::{BitflyerApi, GetBoard, GetTicker};
+use crate::board::OrderBook;
+use crate::entity::{ProductCode, Side, Ticker};
+use anyhow::{anyhow, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeRounding {
+    Down,
+    Up,
+    Nearest,
+}
+
+pub fn round_size(size: Decimal, increment: Decimal, rounding: SizeRounding) -> Decimal {
+    if increment <= Decimal::ZERO {
+        return size;
+    }
+    let steps = size / increment;
+    let steps = match rounding {
+        SizeRounding::Down => steps.floor(),
+        SizeRounding::Up => steps.ceil(),
+        SizeRounding::Nearest => {
+            steps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+        }
+    };
+    (steps * increment).normalize()
+}
+
+pub fn size_from_notional(
+    product_code: &ProductCode,
+    notional: Decimal,
+    price: Decimal,
+    rounding: SizeRounding,
+) -> Result<Decimal> {
+    if price <= Decimal::ZERO {
+        return Err(anyhow!("price must be positive: {price}"));
+    }
+    if notional <= Decimal::ZERO {
+        return Err(anyhow!("notional must be positive: {notional}"));
+    }
+    let increment = product_code
+        .size_increment()
+        .ok_or_else(|| anyhow!("unknown size increment for {product_code}"))?;
+    let size = round_size(notional / price, increment, rounding);
+    if let Some(min) = product_code.min_order_size() {
+        if size < min {
+            return Err(anyhow!(
+                "size {size} for notional {notional} is below the minimum {min} of {product_code}"
+            ));
+        }
+    }
+    Ok(size)
+}
+
+pub fn size_from_ticker(
+    ticker: &Ticker,
+    side: Side,
+    notional: Decimal,
+    rounding: SizeRounding,
+) -> Result<Decimal> {
+    let price = match side {
+        Side::Buy => ticker.best_ask,
+        Side::Sell => ticker.best_bid,
+    };
+    size_from_notional(&ticker.product_code, notional, price, rounding)
+}
+
+// Walks the book so that the size accounts for the price impact of spending the whole notional.
+pub fn size_from_book(
+    book: &impl OrderBook,
+    product_code: &ProductCode,
+    side: Side,
+    notional: Decimal,
+    rounding: SizeRounding,
+) -> Result<Decimal> {
+    let levels = match side {
+        Side::Buy => book.ask_levels(),
+        Side::Sell => book.bid_levels(),
+    };
+    let mut remaining = notional;
+    let mut size = Decimal::ZERO;
+    for level in levels {
+        if remaining <= Decimal::ZERO {
+            break;
+        }
+        let value = level.price * level.size;
+        if value >= remaining {
+            size += remaining / level.price;
+            remaining = Decimal::ZERO;
+        } else {
+            size += level.size;
+            remaining -= value;
+        }
+    }
+    if remaining > Decimal::ZERO {
+        return Err(anyhow!(
+            "book is too thin to fill {notional} of {product_code}"
+        ));
+    }
+    size_from_notional(product_code, notional, notional / size, rounding)
+}
+
+pub async fn size_for_notional<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    side: Side,
+    notional: Decimal,
+    rounding: SizeRounding,
+) -> Result<Decimal> {
+    let ticker = api
+        .send(GetTicker {
+            product_code: Some(product_code.clone()),
+        })
+        .await?;
+    size_from_ticker(&ticker, side, notional, rounding)
+}
+
+pub async fn size_for_notional_from_book<A: BitflyerApi>(
+    api: &A,
+    product_code: &ProductCode,
+    side: Side,
+    notional: Decimal,
+    rounding: SizeRounding,
+) -> Result<Decimal> {
+    let board = api.send(GetBoard::new(product_code.clone())).await?;
+    size_from_book(&board, product_code, side, notional, rounding)
+}
@@ -0,0 +1,473 @@
+use crate::entity::{Execution, ExecutionSide};
+use crate::export::csv::{decimal, timestamp, CsvRecord};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{Stream, StreamExt};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+fn time_delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VolumeStats {
+    pub volume: Decimal,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    pub notional: Decimal,
+    pub trade_count: usize,
+    pub buy_count: usize,
+    pub sell_count: usize,
+}
+
+impl VolumeStats {
+    fn apply(&mut self, side: &ExecutionSide, price: Decimal, size: Decimal, add: bool) {
+        let (size, count) = if add { (size, 1) } else { (-size, -1) };
+        let add_count = |x: &mut usize| *x = x.saturating_add_signed(count);
+        self.volume += size;
+        self.notional += price * size;
+        add_count(&mut self.trade_count);
+        match side {
+            ExecutionSide::Buy => {
+                self.buy_volume += size;
+                add_count(&mut self.buy_count);
+            }
+            ExecutionSide::Sell => {
+                self.sell_volume += size;
+                add_count(&mut self.sell_count);
+            }
+            ExecutionSide::Empty => {}
+        }
+    }
+
+    // Share of the volume taken by buyers, in [0, 1].
+    pub fn buy_ratio(&self) -> Option<Decimal> {
+        let volume = self.buy_volume + self.sell_volume;
+        (!volume.is_zero()).then(|| self.buy_volume / volume)
+    }
+
+    pub fn buy_sell_ratio(&self) -> Option<Decimal> {
+        (!self.sell_volume.is_zero()).then(|| self.buy_volume / self.sell_volume)
+    }
+}
+
+// Traded volume and trade counts over a rolling time window.
+#[derive(Clone, Debug)]
+pub struct RollingVolume {
+    window: TimeDelta,
+    trades: VecDeque<(DateTime<Utc>, ExecutionSide, Decimal, Decimal)>,
+    stats: VolumeStats,
+}
+
+impl RollingVolume {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: time_delta(window),
+            trades: VecDeque::new(),
+            stats: VolumeStats::default(),
+        }
+    }
+
+    pub fn on_execution(&mut self, execution: &Execution) -> &VolumeStats {
+        self.trades.push_back((
+            execution.exec_date,
+            execution.side.clone(),
+            execution.price,
+            execution.size,
+        ));
+        self.stats
+            .apply(&execution.side, execution.price, execution.size, true);
+        self.expire(execution.exec_date);
+        &self.stats
+    }
+
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        while let Some((exec_date, side, price, size)) = self.trades.front().cloned() {
+            if now.signed_duration_since(exec_date) <= self.window {
+                break;
+            }
+            self.trades.pop_front();
+            self.stats.apply(&side, price, size, false);
+        }
+    }
+
+    pub fn stats(&self) -> &VolumeStats {
+        &self.stats
+    }
+}
+
+// Realized volatility from log returns of the last price sampled every `sample_interval`.
+#[derive(Clone, Debug)]
+pub struct RealizedVolatility {
+    window: TimeDelta,
+    sample_interval: TimeDelta,
+    returns: VecDeque<(DateTime<Utc>, f64)>,
+    sum_squares: f64,
+    last_sample: Option<(DateTime<Utc>, f64)>,
+    last_price: Option<f64>,
+}
+
+impl RealizedVolatility {
+    pub fn new(window: Duration, sample_interval: Duration) -> Self {
+        Self {
+            window: time_delta(window),
+            sample_interval: time_delta(sample_interval).max(TimeDelta::milliseconds(1)),
+            returns: VecDeque::new(),
+            sum_squares: 0.0,
+            last_sample: None,
+            last_price: None,
+        }
+    }
+
+    pub fn on_execution(&mut self, execution: &Execution) -> Option<f64> {
+        let price = execution.price.to_f64().filter(|x| *x > 0.0)?;
+        self.on_price(execution.exec_date, price);
+        self.volatility()
+    }
+
+    pub fn on_price(&mut self, time: DateTime<Utc>, price: f64) {
+        match self.last_sample {
+            None => self.last_sample = Some((time, price)),
+            Some((sampled_at, sampled_price)) if time - sampled_at >= self.sample_interval => {
+                // The return is taken against the last price seen before this sample boundary.
+                let close = self.last_price.unwrap_or(sampled_price);
+                let r = (close / sampled_price).ln();
+                self.returns.push_back((time, r));
+                self.sum_squares += r * r;
+                self.last_sample = Some((time, close));
+            }
+            Some(_) => {}
+        }
+        self.last_price = Some(price);
+        self.expire(time);
+    }
+
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        while self
+            .returns
+            .front()
+            .is_some_and(|(x, _)| now.signed_duration_since(*x) > self.window)
+        {
+            if let Some((_, r)) = self.returns.pop_front() {
+                self.sum_squares -= r * r;
+            }
+        }
+        if self.returns.is_empty() {
+            self.sum_squares = 0.0;
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.returns.len()
+    }
+
+    // Square root of the summed squared returns over the window.
+    pub fn volatility(&self) -> Option<f64> {
+        if self.returns.is_empty() {
+            return None;
+        }
+        Some(self.sum_squares.max(0.0).sqrt())
+    }
+
+    pub fn annualized(&self) -> Option<f64> {
+        let seconds = self.window.num_milliseconds() as f64 / 1000.0;
+        if seconds <= 0.0 {
+            return None;
+        }
+        Some(self.volatility()? * (SECONDS_PER_YEAR / seconds).sqrt())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsSample {
+    pub timestamp: DateTime<Utc>,
+    pub last_price: Decimal,
+    pub volume: VolumeStats,
+    pub volatility: Option<f64>,
+    pub annualized_volatility: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecutionStats {
+    volume: RollingVolume,
+    volatility: RealizedVolatility,
+}
+
+impl ExecutionStats {
+    pub fn new(window: Duration, sample_interval: Duration) -> Self {
+        Self {
+            volume: RollingVolume::new(window),
+            volatility: RealizedVolatility::new(window, sample_interval),
+        }
+    }
+
+    pub fn on_execution(&mut self, execution: &Execution) -> StatsSample {
+        self.volume.on_execution(execution);
+        self.volatility.on_execution(execution);
+        StatsSample {
+            timestamp: execution.exec_date,
+            last_price: execution.price,
+            volume: self.volume.stats().clone(),
+            volatility: self.volatility.volatility(),
+            annualized_volatility: self.volatility.annualized(),
+        }
+    }
+
+    pub fn volume(&self) -> &RollingVolume {
+        &self.volume
+    }
+
+    pub fn volatility(&self) -> &RealizedVolatility {
+        &self.volatility
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImbalanceWindow {
+    pub window: Duration,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    // Buy volume minus sell volume.
+    pub imbalance: Decimal,
+}
+
+impl ImbalanceWindow {
+    // Imbalance normalized by the traded volume, in [-1, 1].
+    pub fn ratio(&self) -> Option<Decimal> {
+        let volume = self.buy_volume + self.sell_volume;
+        (!volume.is_zero()).then(|| self.imbalance / volume)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImbalanceSample {
+    pub timestamp: DateTime<Utc>,
+    pub execution_id: u64,
+    pub price: Decimal,
+    // In the order the windows were given.
+    pub windows: Vec<ImbalanceWindow>,
+}
+
+// Signed order-flow imbalance over several rolling windows at once.
+#[derive(Clone, Debug)]
+pub struct OrderFlowImbalance {
+    windows: Vec<(Duration, RollingVolume)>,
+}
+
+impl OrderFlowImbalance {
+    pub fn new(windows: impl IntoIterator<Item = Duration>) -> Self {
+        Self {
+            windows: windows
+                .into_iter()
+                .map(|x| (x, RollingVolume::new(x)))
+                .collect(),
+        }
+    }
+
+    pub fn on_execution(&mut self, execution: &Execution) -> ImbalanceSample {
+        for (_, volume) in &mut self.windows {
+            volume.on_execution(execution);
+        }
+        ImbalanceSample {
+            timestamp: execution.exec_date,
+            execution_id: execution.id,
+            price: execution.price,
+            windows: self.windows(),
+        }
+    }
+
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        for (_, volume) in &mut self.windows {
+            volume.expire(now);
+        }
+    }
+
+    pub fn windows(&self) -> Vec<ImbalanceWindow> {
+        self.windows
+            .iter()
+            .map(|(window, volume)| {
+                let stats = volume.stats();
+                ImbalanceWindow {
+                    window: *window,
+                    buy_volume: stats.buy_volume,
+                    sell_volume: stats.sell_volume,
+                    imbalance: stats.buy_volume - stats.sell_volume,
+                }
+            })
+            .collect()
+    }
+}
+
+// One sample per execution; errors from the feed are passed through.
+pub fn order_flow_imbalance<S>(
+    executions: S,
+    windows: impl IntoIterator<Item = Duration>,
+) -> impl Stream<Item = Result<ImbalanceSample>>
+where
+    S: Stream<Item = Result<Execution>>,
+{
+    let mut imbalance = OrderFlowImbalance::new(windows);
+    executions.map(move |x| x.map(|x| imbalance.on_execution(&x)))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VolumeBin {
+    pub open_time: DateTime<Utc>,
+    pub close_time: DateTime<Utc>,
+    pub stats: VolumeStats,
+}
+
+// Bins are aligned to the Unix epoch; empty intervals produce empty bins.
+#[derive(Clone, Debug)]
+pub struct VolumeBinner {
+    interval: TimeDelta,
+    forming: Option<VolumeBin>,
+    last_id: Option<u64>,
+}
+
+impl VolumeBinner {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: time_delta(interval).max(TimeDelta::milliseconds(1)),
+            forming: None,
+            last_id: None,
+        }
+    }
+
+    pub fn interval(&self) -> TimeDelta {
+        self.interval
+    }
+
+    pub fn forming(&self) -> Option<&VolumeBin> {
+        self.forming.as_ref()
+    }
+
+    fn open_time(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.interval.num_milliseconds();
+        let millis = time.timestamp_millis();
+        DateTime::from_timestamp_millis(millis - millis.rem_euclid(interval)).unwrap_or(time)
+    }
+
+    fn bin(&self, open_time: DateTime<Utc>) -> VolumeBin {
+        VolumeBin {
+            open_time,
+            close_time: open_time + self.interval,
+            stats: VolumeStats::default(),
+        }
+    }
+
+    // Returns the bins closed by this execution.
+    pub fn on_execution(&mut self, execution: &Execution) -> Vec<VolumeBin> {
+        if self.last_id.is_some_and(|x| execution.id <= x) {
+            return vec![];
+        }
+        let open_time = self.open_time(execution.exec_date);
+        if self
+            .forming
+            .as_ref()
+            .is_some_and(|x| open_time < x.open_time)
+        {
+            return vec![];
+        }
+        self.last_id = Some(execution.id);
+        let closed = self.close_until(open_time);
+        let bin = self.bin(open_time);
+        self.forming.get_or_insert(bin).stats.apply(
+            &execution.side,
+            execution.price,
+            execution.size,
+            true,
+        );
+        closed
+    }
+
+    pub fn on_time(&mut self, now: DateTime<Utc>) -> Vec<VolumeBin> {
+        let open_time = self.open_time(now);
+        let closed = self.close_until(open_time);
+        if !closed.is_empty() && self.forming.is_none() {
+            self.forming = Some(self.bin(open_time));
+        }
+        closed
+    }
+
+    fn close_until(&mut self, open_time: DateTime<Utc>) -> Vec<VolumeBin> {
+        let mut closed = vec![];
+        while let Some(bin) = self.forming.take_if(|x| x.open_time < open_time) {
+            let close_time = bin.close_time;
+            closed.push(bin);
+            if close_time < open_time {
+                self.forming = Some(self.bin(close_time));
+            }
+        }
+        closed
+    }
+
+    pub fn finish(&mut self) -> Option<VolumeBin> {
+        self.forming.take()
+    }
+
+    // Bins a batch such as downloaded history, including the last forming bin.
+    pub fn build<'a>(
+        interval: Duration,
+        executions: impl IntoIterator<Item = &'a Execution>,
+    ) -> Vec<VolumeBin> {
+        let mut executions: Vec<&Execution> = executions.into_iter().collect();
+        executions.sort_by_key(|x| x.id);
+        let mut binner = Self::new(interval);
+        let mut bins = vec![];
+        for execution in executions {
+            bins.extend(binner.on_execution(execution));
+        }
+        bins.extend(binner.finish());
+        bins
+    }
+}
+
+// Emits each bin once it is closed by a later execution; errors from the feed are passed through.
+pub fn volume_bins<S>(executions: S, interval: Duration) -> impl Stream<Item = Result<VolumeBin>>
+where
+    S: Stream<Item = Result<Execution>>,
+{
+    let mut binner = VolumeBinner::new(interval);
+    executions
+        .map(move |x| match x {
+            Ok(x) => binner.on_execution(&x).into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        })
+        .flat_map(futures::stream::iter)
+}
+
+impl CsvRecord for VolumeBin {
+    const HEADER: &'static [&'static str] = &[
+        "open_time",
+        "close_time",
+        "volume",
+        "buy_volume",
+        "sell_volume",
+        "notional",
+        "trade_count",
+        "buy_count",
+        "sell_count",
+        "buy_ratio",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            timestamp(&self.open_time),
+            timestamp(&self.close_time),
+            decimal(self.stats.volume),
+            decimal(self.stats.buy_volume),
+            decimal(self.stats.sell_volume),
+            decimal(self.stats.notional),
+            self.stats.trade_count.to_string(),
+            self.stats.buy_count.to_string(),
+            self.stats.sell_count.to_string(),
+            self.stats.buy_ratio().map(decimal).unwrap_or_default(),
+        ]
+    }
+}
@@ -0,0 +1,200 @@
+use crate::api::{BitflyerApi, Client, GetTicker, SendChildOrder};
+use crate::entity::{ChildOrderType, Execution, ProductCode, Side, Ticker};
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopLoss {
+    pub id: String,
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub size: Decimal,
+    pub trigger_price: Decimal,
+    pub limit_price: Option<Decimal>,
+}
+
+impl StopLoss {
+    pub fn is_triggered(&self, price: Decimal) -> bool {
+        match self.side {
+            Side::Sell => price <= self.trigger_price,
+            Side::Buy => price >= self.trigger_price,
+        }
+    }
+
+    pub fn to_order(&self) -> SendChildOrder {
+        SendChildOrder {
+            child_order_type: match self.limit_price {
+                Some(price) => ChildOrderType::Limit { price },
+                None => ChildOrderType::Market,
+            },
+            product_code: self.product_code.clone(),
+            side: self.side,
+            size: self.size,
+            minute_to_expire: None,
+            time_in_force: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopLossEvent {
+    Triggered {
+        stop: StopLoss,
+        price: Decimal,
+        child_order_acceptance_id: String,
+    },
+    Failed {
+        stop: StopLoss,
+        price: Decimal,
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct StopLossManager<A = Client> {
+    client: Arc<A>,
+    stops: Mutex<Vec<StopLoss>>,
+    path: Option<PathBuf>,
+    events: broadcast::Sender<StopLossEvent>,
+}
+
+impl<A: BitflyerApi + 'static> StopLossManager<A> {
+    pub fn new(client: Arc<A>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            client,
+            stops: Mutex::new(vec![]),
+            path: None,
+            events,
+        }
+    }
+
+    pub fn with_persistence(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            let stops: Vec<StopLoss> = serde_json::from_slice(&std::fs::read(&path)?)?;
+            *self.stops.lock().unwrap() = stops;
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StopLossEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn armed(&self) -> Vec<StopLoss> {
+        self.stops.lock().unwrap().clone()
+    }
+
+    pub fn arm(&self, stop: StopLoss) -> Result<()> {
+        let mut stops = self.stops.lock().unwrap();
+        stops.retain(|x| x.id != stop.id);
+        stops.push(stop);
+        self.persist(&stops)
+    }
+
+    pub fn disarm(&self, id: &str) -> Result<Option<StopLoss>> {
+        let mut stops = self.stops.lock().unwrap();
+        let position = stops.iter().position(|x| x.id == id);
+        let removed = position.map(|i| stops.remove(i));
+        self.persist(&stops)?;
+        Ok(removed)
+    }
+
+    fn persist(&self, stops: &[StopLoss]) -> Result<()> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_vec_pretty(stops)?)?;
+        }
+        Ok(())
+    }
+
+    pub async fn on_price(
+        &self,
+        product_code: &ProductCode,
+        price: Decimal,
+    ) -> Result<Vec<StopLossEvent>> {
+        let triggered = {
+            let mut stops = self.stops.lock().unwrap();
+            let (triggered, armed): (Vec<_>, Vec<_>) = stops
+                .drain(..)
+                .partition(|x| x.product_code == *product_code && x.is_triggered(price));
+            *stops = armed;
+            self.persist(&stops)?;
+            triggered
+        };
+
+        let mut events = vec![];
+        for stop in triggered {
+            let event = match self.client.send(stop.to_order()).await {
+                Ok(response) => StopLossEvent::Triggered {
+                    stop,
+                    price,
+                    child_order_acceptance_id: response.child_order_acceptance_id,
+                },
+                Err(e) => {
+                    self.arm(stop.clone())?;
+                    StopLossEvent::Failed {
+                        stop,
+                        price,
+                        error: format!("{e:?}"),
+                    }
+                }
+            };
+            let _ = self.events.send(event.clone());
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    pub async fn on_ticker(&self, ticker: &Ticker) -> Result<Vec<StopLossEvent>> {
+        self.on_price(&ticker.product_code, ticker.ltp).await
+    }
+
+    pub async fn on_execution(
+        &self,
+        product_code: &ProductCode,
+        execution: &Execution,
+    ) -> Result<Vec<StopLossEvent>> {
+        self.on_price(product_code, execution.price).await
+    }
+
+    pub fn spawn_polling(self: &Arc<Self>, interval: Duration) -> ShutdownHandle {
+        let manager = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                let mut products: Vec<ProductCode> = vec![];
+                for stop in manager.armed() {
+                    if !products.contains(&stop.product_code) {
+                        products.push(stop.product_code);
+                    }
+                }
+                for product_code in products {
+                    let result = manager
+                        .client
+                        .send(GetTicker {
+                            product_code: Some(product_code),
+                        })
+                        .await;
+                    match result {
+                        Ok(ticker) => {
+                            if let Err(e) = manager.on_ticker(&ticker).await {
+                                tracing::warn!("failed to evaluate stop losses: {e:?}");
+                            }
+                        }
+                        Err(e) => tracing::warn!("failed to fetch ticker: {e:?}"),
+                    }
+                }
+            }
+        })
+    }
+}
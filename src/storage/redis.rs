@@ -0,0 +1,196 @@
+use crate::api::{BitflyerApi, GetChildOrders, GetPositions, GetTicker};
+use crate::entity::{ChildOrder, OrderState, Position, ProductCode, Ticker};
+use crate::export::csv::label;
+use crate::shutdown::ShutdownHandle;
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Hot state shared between processes: one instance polls the API and writes, the others
+// only read. Keys are `<prefix>:ticker:<product>`, `<prefix>:orders:<product>` (a hash
+// keyed by acceptance id) and `<prefix>:positions`, all holding JSON.
+#[derive(Clone)]
+pub struct RedisState {
+    connection: MultiplexedConnection,
+    prefix: String,
+    ttl: Option<Duration>,
+}
+
+impl std::fmt::Debug for RedisState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisState")
+            .field("prefix", &self.prefix)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisState {
+    pub fn new(connection: MultiplexedConnection, prefix: impl Into<String>) -> Self {
+        Self {
+            connection,
+            prefix: prefix.into(),
+            ttl: None,
+        }
+    }
+
+    pub async fn connect(url: &str, prefix: impl Into<String>) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self::new(
+            client.get_multiplexed_async_connection().await?,
+            prefix,
+        ))
+    }
+
+    // Entries expire unless rewritten in time, so readers never act on a dead writer's state.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn key(&self, kind: &str, product_code: Option<&ProductCode>) -> String {
+        match product_code {
+            Some(product_code) => format!("{}:{kind}:{}", self.prefix, label(product_code)),
+            None => format!("{}:{kind}", self.prefix),
+        }
+    }
+
+    fn ttl_seconds(&self) -> Option<i64> {
+        self.ttl
+            .map(|x| i64::try_from(x.as_secs().max(1)).unwrap_or(i64::MAX))
+    }
+
+    async fn set_json(&self, key: String, value: String) -> Result<()> {
+        let mut connection = self.connection.clone();
+        match self.ttl {
+            Some(ttl) => {
+                connection
+                    .set_ex::<_, _, ()>(key, value, ttl.as_secs().max(1))
+                    .await?
+            }
+            None => connection.set::<_, _, ()>(key, value).await?,
+        }
+        Ok(())
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, key: String) -> Result<Option<T>> {
+        let mut connection = self.connection.clone();
+        let value: Option<String> = connection.get(key).await?;
+        Ok(value.map(|x| serde_json::from_str(&x)).transpose()?)
+    }
+
+    pub async fn set_ticker(&self, ticker: &Ticker) -> Result<()> {
+        self.set_json(
+            self.key("ticker", Some(&ticker.product_code)),
+            serde_json::to_string(ticker)?,
+        )
+        .await
+    }
+
+    pub async fn ticker(&self, product_code: &ProductCode) -> Result<Option<Ticker>> {
+        self.get_json(self.key("ticker", Some(product_code))).await
+    }
+
+    // Replaces the product's open orders in one transaction.
+    pub async fn set_open_orders(
+        &self,
+        product_code: &ProductCode,
+        orders: &[ChildOrder],
+    ) -> Result<()> {
+        let key = self.key("orders", Some(product_code));
+        let items = orders
+            .iter()
+            .map(|x| {
+                Ok((
+                    x.child_order_acceptance_id.clone(),
+                    serde_json::to_string(x)?,
+                ))
+            })
+            .collect::<Result<Vec<(String, String)>>>()?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&key).ignore();
+        if !items.is_empty() {
+            pipe.hset_multiple(&key, &items).ignore();
+            if let Some(ttl) = self.ttl_seconds() {
+                pipe.expire(&key, ttl).ignore();
+            }
+        }
+        let mut connection = self.connection.clone();
+        pipe.query_async::<()>(&mut connection).await?;
+        Ok(())
+    }
+
+    pub async fn open_orders(&self, product_code: &ProductCode) -> Result<Vec<ChildOrder>> {
+        let mut connection = self.connection.clone();
+        let values: Vec<String> = connection
+            .hvals(self.key("orders", Some(product_code)))
+            .await?;
+        let mut orders = values
+            .iter()
+            .map(|x| serde_json::from_str(x))
+            .collect::<serde_json::Result<Vec<ChildOrder>>>()?;
+        orders.sort_by_key(|x| (x.child_order_date, x.id));
+        Ok(orders)
+    }
+
+    pub async fn set_positions(&self, positions: &[Position]) -> Result<()> {
+        self.set_json(
+            self.key("positions", None),
+            serde_json::to_string(positions)?,
+        )
+        .await
+    }
+
+    // None when no writer has stored positions yet (or they expired).
+    pub async fn positions(&self) -> Result<Option<Vec<Position>>> {
+        self.get_json(self.key("positions", None)).await
+    }
+
+    // Fetches tickers, open orders and positions from the API and stores them.
+    pub async fn refresh<A: BitflyerApi>(
+        &self,
+        api: &A,
+        product_codes: &[ProductCode],
+    ) -> Result<()> {
+        for product_code in product_codes {
+            let ticker = api
+                .send(GetTicker {
+                    product_code: Some(product_code.clone()),
+                })
+                .await?;
+            self.set_ticker(&ticker).await?;
+            let orders = api
+                .send(GetChildOrders {
+                    product_code: Some(product_code.clone()),
+                    child_order_state: Some(OrderState::Active),
+                    ..Default::default()
+                })
+                .await?;
+            self.set_open_orders(product_code, &orders).await?;
+        }
+        if product_codes.iter().any(|x| x.is_fx()) {
+            self.set_positions(&api.send(GetPositions {}).await?)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn_refresh<A: BitflyerApi + 'static>(
+        self: &Arc<Self>,
+        api: Arc<A>,
+        product_codes: Vec<ProductCode>,
+        interval: Duration,
+    ) -> ShutdownHandle {
+        let state = Arc::clone(self);
+        ShutdownHandle::spawn(|mut shutdown| async move {
+            let mut interval = tokio::time::interval(interval);
+            while shutdown.tick(&mut interval).await {
+                if let Err(e) = state.refresh(api.as_ref(), &product_codes).await {
+                    tracing::warn!("failed to refresh the redis state: {e:?}");
+                }
+            }
+        })
+    }
+}
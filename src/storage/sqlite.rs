@@ -0,0 +1,557 @@
+use crate::candle::Candle;
+use crate::entity::{Balance, ChildOrder, ChildOrderType, Execution, ProductCode};
+use crate::equity::{EquityPoint, EquitySnapshot, SnapshotSink};
+use crate::export::csv::{decimal, label, timestamp};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Each entry upgrades the schema by one version; `PRAGMA user_version` records how many ran.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE executions (
+        product_code TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        side TEXT NOT NULL,
+        price TEXT NOT NULL,
+        size TEXT NOT NULL,
+        exec_date TEXT NOT NULL,
+        buy_child_order_acceptance_id TEXT NOT NULL,
+        sell_child_order_acceptance_id TEXT NOT NULL,
+        PRIMARY KEY (product_code, id)
+    );
+    CREATE INDEX executions_exec_date ON executions (product_code, exec_date);
+    CREATE TABLE candles (
+        product_code TEXT NOT NULL,
+        interval_ms INTEGER NOT NULL,
+        open_time TEXT NOT NULL,
+        close_time TEXT NOT NULL,
+        open TEXT NOT NULL,
+        high TEXT NOT NULL,
+        low TEXT NOT NULL,
+        close TEXT NOT NULL,
+        volume TEXT NOT NULL,
+        buy_volume TEXT NOT NULL,
+        sell_volume TEXT NOT NULL,
+        trade_count INTEGER NOT NULL,
+        PRIMARY KEY (product_code, interval_ms, open_time)
+    );
+    CREATE TABLE child_orders (
+        child_order_acceptance_id TEXT PRIMARY KEY,
+        id INTEGER NOT NULL,
+        child_order_id TEXT NOT NULL,
+        product_code TEXT NOT NULL,
+        side TEXT NOT NULL,
+        child_order_type TEXT NOT NULL,
+        price TEXT,
+        average_price TEXT NOT NULL,
+        size TEXT NOT NULL,
+        child_order_state TEXT NOT NULL,
+        expire_date TEXT NOT NULL,
+        child_order_date TEXT NOT NULL,
+        outstanding_size TEXT NOT NULL,
+        cancel_size TEXT NOT NULL,
+        executed_size TEXT NOT NULL,
+        total_commission TEXT NOT NULL,
+        time_in_force TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX child_orders_product_code ON child_orders (product_code, child_order_date);
+    CREATE TABLE balance_snapshots (
+        taken_at TEXT NOT NULL,
+        currency_code TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        available TEXT NOT NULL,
+        PRIMARY KEY (taken_at, currency_code)
+    );
+",
+    "
+    CREATE TABLE equity_snapshots (
+        taken_at TEXT PRIMARY KEY,
+        balance_jpy TEXT NOT NULL,
+        collateral_jpy TEXT NOT NULL,
+        total_jpy TEXT NOT NULL,
+        snapshot TEXT NOT NULL
+    );
+",
+];
+
+// Decimals are stored as text so nothing is lost to floating point, and timestamps as
+// RFC 3339 in UTC so they sort lexically.
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+fn parse_decimal(value: &str) -> Result<Decimal> {
+    Decimal::from_str(value).with_context(|| format!("invalid decimal: {value}"))
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("invalid timestamp: {value}"))?
+        .with_timezone(&Utc))
+}
+
+// Entities are non-exhaustive, so rows are turned back into them through serde.
+fn number(value: &str) -> Result<Value> {
+    serde_json::from_str(value).with_context(|| format!("invalid number: {value}"))
+}
+
+fn interval_ms(interval: Duration) -> i64 {
+    i64::try_from(interval.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        let version: usize = connection.pragma_query_value(None, "user_version", |x| x.get(0))?;
+        if version > MIGRATIONS.len() {
+            anyhow::bail!(
+                "database schema version {version} is newer than supported {}",
+                MIGRATIONS.len()
+            );
+        }
+        let tx = connection.transaction()?;
+        for migration in &MIGRATIONS[version..] {
+            tx.execute_batch(migration)?;
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        tx.commit()?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn schema_version(&self) -> Result<usize> {
+        Ok(self
+            .connection
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |x| x.get(0))?)
+    }
+
+    // Executions already stored are skipped; returns the number inserted.
+    pub fn insert_executions<'a>(
+        &self,
+        product_code: &ProductCode,
+        executions: impl IntoIterator<Item = &'a Execution>,
+    ) -> Result<usize> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT OR IGNORE INTO executions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for execution in executions {
+                inserted += statement.execute(params![
+                    label(product_code),
+                    execution.id,
+                    label(&execution.side),
+                    decimal(execution.price),
+                    decimal(execution.size),
+                    timestamp(&execution.exec_date),
+                    execution.buy_child_order_acceptance_id,
+                    execution.sell_child_order_acceptance_id,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    // Executions with an id greater than `after`, oldest first.
+    pub fn executions(
+        &self,
+        product_code: &ProductCode,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<Execution>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT id, side, price, size, exec_date, buy_child_order_acceptance_id,
+                sell_child_order_acceptance_id
+            FROM executions WHERE product_code = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+        )?;
+        let rows = statement.query_map(
+            params![
+                label(product_code),
+                after.unwrap_or_default(),
+                i64::try_from(limit).unwrap_or(i64::MAX),
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            },
+        )?;
+        rows.map(|row| {
+            let (id, side, price, size, exec_date, buy, sell) = row?;
+            Ok(serde_json::from_value(json!({
+                "id": id,
+                "side": side,
+                "price": number(&price)?,
+                "size": number(&size)?,
+                "exec_date": exec_date,
+                "buy_child_order_acceptance_id": buy,
+                "sell_child_order_acceptance_id": sell,
+            }))?)
+        })
+        .collect()
+    }
+
+    pub fn last_execution_id(&self, product_code: &ProductCode) -> Result<Option<u64>> {
+        Ok(self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT MAX(id) FROM executions WHERE product_code = ?1",
+                params![label(product_code)],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten())
+    }
+
+    // Replaces candles with the same open time, so a forming candle can be saved repeatedly.
+    pub fn upsert_candles<'a>(
+        &self,
+        product_code: &ProductCode,
+        interval: Duration,
+        candles: impl IntoIterator<Item = &'a Candle>,
+    ) -> Result<usize> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        let mut written = 0;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT OR REPLACE INTO candles
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for candle in candles {
+                written += statement.execute(params![
+                    label(product_code),
+                    interval_ms(interval),
+                    timestamp(&candle.open_time),
+                    timestamp(&candle.close_time),
+                    decimal(candle.open),
+                    decimal(candle.high),
+                    decimal(candle.low),
+                    decimal(candle.close),
+                    decimal(candle.volume),
+                    decimal(candle.buy_volume),
+                    decimal(candle.sell_volume),
+                    candle.trade_count,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    // Candles opened within [since, until).
+    pub fn candles(
+        &self,
+        product_code: &ProductCode,
+        interval: Duration,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Candle>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT open_time, close_time, open, high, low, close, volume, buy_volume,
+                sell_volume, trade_count
+            FROM candles
+            WHERE product_code = ?1 AND interval_ms = ?2 AND open_time >= ?3 AND open_time < ?4
+            ORDER BY open_time",
+        )?;
+        let rows = statement.query_map(
+            params![
+                label(product_code),
+                interval_ms(interval),
+                timestamp(&since),
+                timestamp(&until),
+            ],
+            |row| {
+                let text = |i| row.get::<_, String>(i);
+                Ok((
+                    [
+                        text(0)?,
+                        text(1)?,
+                        text(2)?,
+                        text(3)?,
+                        text(4)?,
+                        text(5)?,
+                        text(6)?,
+                        text(7)?,
+                        text(8)?,
+                    ],
+                    row.get::<_, u64>(9)?,
+                ))
+            },
+        )?;
+        rows.map(|row| {
+            let ([open_time, close_time, open, high, low, close, volume, buy, sell], count) = row?;
+            Ok(Candle {
+                open_time: parse_timestamp(&open_time)?,
+                close_time: parse_timestamp(&close_time)?,
+                open: parse_decimal(&open)?,
+                high: parse_decimal(&high)?,
+                low: parse_decimal(&low)?,
+                close: parse_decimal(&close)?,
+                volume: parse_decimal(&volume)?,
+                buy_volume: parse_decimal(&buy)?,
+                sell_volume: parse_decimal(&sell)?,
+                trade_count: count,
+            })
+        })
+        .collect()
+    }
+
+    // Orders are keyed by acceptance id and overwritten with the latest state.
+    pub fn upsert_orders<'a>(
+        &self,
+        orders: impl IntoIterator<Item = &'a ChildOrder>,
+    ) -> Result<usize> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        let mut written = 0;
+        let updated_at = timestamp(&Utc::now());
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT OR REPLACE INTO child_orders VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            )?;
+            for order in orders {
+                let (child_order_type, price) = match &order.child_order_type {
+                    ChildOrderType::Limit { price } => ("LIMIT", Some(decimal(*price))),
+                    ChildOrderType::Market => ("MARKET", None),
+                };
+                written += statement.execute(params![
+                    order.child_order_acceptance_id,
+                    order.id,
+                    order.child_order_id,
+                    label(&order.product_code),
+                    label(&order.side),
+                    child_order_type,
+                    price,
+                    decimal(order.average_price),
+                    decimal(order.size),
+                    label(&order.child_order_state),
+                    timestamp(&order.expire_date),
+                    timestamp(&order.child_order_date),
+                    decimal(order.outstanding_size),
+                    decimal(order.cancel_size),
+                    decimal(order.executed_size),
+                    decimal(order.total_commission),
+                    label(&order.time_in_force),
+                    updated_at,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    pub fn orders(&self, product_code: &ProductCode) -> Result<Vec<ChildOrder>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT child_order_acceptance_id, id, child_order_id, product_code, side,
+                child_order_type, price, average_price, size, child_order_state, expire_date,
+                child_order_date, outstanding_size, cancel_size, executed_size,
+                total_commission, time_in_force
+            FROM child_orders WHERE product_code = ?1 ORDER BY child_order_date, id",
+        )?;
+        let rows = statement.query_map(params![label(product_code)], order_row)?;
+        rows.map(|row| row?).collect()
+    }
+
+    pub fn insert_balance_snapshot<'a>(
+        &self,
+        taken_at: DateTime<Utc>,
+        balances: impl IntoIterator<Item = &'a Balance>,
+    ) -> Result<usize> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT OR REPLACE INTO balance_snapshots VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for balance in balances {
+                inserted += statement.execute(params![
+                    timestamp(&taken_at),
+                    balance.currency_code,
+                    decimal(balance.amount),
+                    decimal(balance.available),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    // Snapshots taken within [since, until), oldest first.
+    pub fn balance_snapshots(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, Vec<Balance>)>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT taken_at, currency_code, amount, available FROM balance_snapshots
+            WHERE taken_at >= ?1 AND taken_at < ?2 ORDER BY taken_at, currency_code",
+        )?;
+        let rows = statement.query_map(params![timestamp(&since), timestamp(&until)], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut snapshots: Vec<(DateTime<Utc>, Vec<Balance>)> = vec![];
+        for row in rows {
+            let (taken_at, currency_code, amount, available) = row?;
+            let taken_at = parse_timestamp(&taken_at)?;
+            let balance = serde_json::from_value(json!({
+                "currency_code": currency_code,
+                "amount": number(&amount)?,
+                "available": number(&available)?,
+            }))?;
+            match snapshots.last_mut() {
+                Some((x, balances)) if *x == taken_at => balances.push(balance),
+                _ => snapshots.push((taken_at, vec![balance])),
+            }
+        }
+        Ok(snapshots)
+    }
+
+    // Stores the totals alongside the full snapshot as JSON, and the balances in
+    // `balance_snapshots`.
+    pub fn insert_equity_snapshot(&self, snapshot: &EquitySnapshot) -> Result<()> {
+        self.insert_balance_snapshot(snapshot.taken_at, &snapshot.balances)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT OR REPLACE INTO equity_snapshots VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                timestamp(&snapshot.taken_at),
+                decimal(snapshot.point.balance_jpy),
+                decimal(snapshot.point.collateral_jpy),
+                decimal(snapshot.point.total_jpy),
+                snapshot.to_json().to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Equity history within [since, until), oldest first.
+    pub fn equity_history(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<EquityPoint>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT taken_at, balance_jpy, collateral_jpy, total_jpy FROM equity_snapshots
+            WHERE taken_at >= ?1 AND taken_at < ?2 ORDER BY taken_at",
+        )?;
+        let rows = statement.query_map(params![timestamp(&since), timestamp(&until)], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut points = vec![];
+        for row in rows {
+            let (taken_at, balance_jpy, collateral_jpy, total_jpy) = row?;
+            points.push(EquityPoint {
+                taken_at: parse_timestamp(&taken_at)?,
+                balance_jpy: parse_decimal(&balance_jpy)?,
+                collateral_jpy: parse_decimal(&collateral_jpy)?,
+                total_jpy: parse_decimal(&total_jpy)?,
+            });
+        }
+        Ok(points)
+    }
+}
+
+impl SnapshotSink for SqliteStore {
+    fn append(&self, snapshot: &EquitySnapshot) -> Result<()> {
+        self.insert_equity_snapshot(snapshot)
+    }
+}
+
+fn order_row(row: &Row) -> rusqlite::Result<Result<ChildOrder>> {
+    let text = |i| row.get::<_, String>(i);
+    let price: Option<String> = row.get(6)?;
+    let id: u64 = row.get(1)?;
+    let fields = [
+        text(0)?,
+        text(2)?,
+        text(3)?,
+        text(4)?,
+        text(5)?,
+        text(7)?,
+        text(8)?,
+        text(9)?,
+        text(10)?,
+        text(11)?,
+        text(12)?,
+        text(13)?,
+        text(14)?,
+        text(15)?,
+        text(16)?,
+    ];
+    let order = || -> Result<ChildOrder> {
+        let [acceptance_id, child_order_id, product_code, side, child_order_type, average_price, size, state, expire_date, child_order_date, outstanding_size, cancel_size, executed_size, total_commission, time_in_force] =
+            &fields;
+        let mut value = json!({
+            "id": id,
+            "child_order_id": child_order_id,
+            "product_code": product_code,
+            "side": side,
+            "child_order_type": child_order_type,
+            "average_price": number(average_price)?,
+            "size": number(size)?,
+            "child_order_state": state,
+            "expire_date": expire_date,
+            "child_order_date": child_order_date,
+            "child_order_acceptance_id": acceptance_id,
+            "outstanding_size": number(outstanding_size)?,
+            "cancel_size": number(cancel_size)?,
+            "executed_size": number(executed_size)?,
+            "total_commission": number(total_commission)?,
+            "time_in_force": time_in_force,
+        });
+        if let Some(price) = &price {
+            value["price"] = number(price)?;
+        }
+        Ok(serde_json::from_value(value)?)
+    };
+    Ok(order())
+}
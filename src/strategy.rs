@@ -0,0 +1,378 @@
+use crate::api::{BitflyerApi, Client};
+use crate::clock::{Clock, SystemClock};
+use crate::entity::{PrivateExecution, ProductCode};
+use crate::events::{Event, EventBus};
+use crate::order_manager::OrderManager;
+use crate::pnl::{CostMethod, PnlEngine, ProductPnl, Realization};
+use crate::risk::{RiskChecker, RiskEvent};
+use crate::shutdown::CancellationToken;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+
+// Trading logic written once against `StrategyContext` and `Event`, so the same strategy runs
+// live under a `Runner` and on a `SimClient` under a `Backtest`. Only `on_event` is required.
+pub trait Strategy: Send {
+    // Before the first event, e.g. to load state or cancel leftovers. An error aborts the run.
+    fn on_start<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = context;
+        async { Ok(()) }
+    }
+
+    // Every event but fills, which go to `on_fill`.
+    fn on_event<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+        event: &Event,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    // After the fill is in the context's PnL.
+    fn on_fill<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+        product_code: &ProductCode,
+        execution: &PrivateExecution,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (context, product_code, execution);
+        async { Ok(()) }
+    }
+
+    // Every timer interval, with the context's time.
+    fn on_timer<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = (context, now);
+        async { Ok(()) }
+    }
+
+    // After the last event, before open orders are canceled.
+    fn on_stop<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let _ = context;
+        async { Ok(()) }
+    }
+}
+
+// What a strategy trades with. Orders sent through `orders()` are tracked from the events, the
+// risk checker is kept up to date with prices, open orders and realized PnL, and fills are
+// booked into a `PnlEngine`. Strategies should take the time from `now()`, which follows the
+// replayed data in a backtest.
+pub struct StrategyContext<A = Client> {
+    orders: Arc<OrderManager<A>>,
+    risk_checker: Option<Arc<RiskChecker>>,
+    clock: Arc<dyn Clock>,
+    pnl: Mutex<PnlEngine>,
+    // Last traded prices from the tickers and trades, for marking the open lots to market.
+    marks: Mutex<HashMap<ProductCode, Decimal>>,
+}
+
+impl<A: std::fmt::Debug> std::fmt::Debug for StrategyContext<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StrategyContext")
+            .field("orders", &self.orders)
+            .field("risk_checker", &self.risk_checker)
+            .field("pnl", &self.pnl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<A: BitflyerApi + 'static> StrategyContext<A> {
+    pub fn new(api: Arc<A>) -> Self {
+        Self::with_order_manager(Arc::new(OrderManager::new(api)))
+    }
+
+    // Shares an order manager that is also polled or watched elsewhere.
+    pub fn with_order_manager(orders: Arc<OrderManager<A>>) -> Self {
+        Self {
+            orders,
+            risk_checker: None,
+            clock: Arc::new(SystemClock),
+            pnl: Mutex::new(PnlEngine::default()),
+            marks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Usually the one the client checks orders against.
+    pub fn with_risk_checker(mut self, risk_checker: Arc<RiskChecker>) -> Self {
+        self.risk_checker = Some(risk_checker);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_cost_method(self, cost_method: CostMethod) -> Self {
+        *self.pnl.lock().unwrap() = PnlEngine::new(cost_method);
+        self
+    }
+
+    pub fn api(&self) -> &A {
+        self.orders.client()
+    }
+
+    pub fn orders(&self) -> &OrderManager<A> {
+        &self.orders
+    }
+
+    pub fn risk_checker(&self) -> Option<&Arc<RiskChecker>> {
+        self.risk_checker.as_ref()
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn product_pnl(&self, product_code: &ProductCode) -> Option<ProductPnl> {
+        self.pnl.lock().unwrap().product(product_code).cloned()
+    }
+
+    // Positive when long, negative when short.
+    pub fn position(&self, product_code: &ProductCode) -> Decimal {
+        self.product_pnl(product_code)
+            .map(|x| x.net_size())
+            .unwrap_or_default()
+    }
+
+    pub fn realized_pnl(&self) -> Decimal {
+        self.pnl.lock().unwrap().realized_pnl()
+    }
+
+    // Of the open lots at the last traded prices; products without a ticker yet count as zero.
+    pub fn unrealized_pnl(&self) -> Decimal {
+        let marks = self.marks.lock().unwrap();
+        self.pnl
+            .lock()
+            .unwrap()
+            .products()
+            .filter_map(|x| Some(x.unrealized_pnl(*marks.get(&x.product_code)?)))
+            .sum()
+    }
+
+    pub fn realizations(&self) -> Vec<Realization> {
+        self.pnl.lock().unwrap().realizations().to_vec()
+    }
+
+    // Feeds the order manager, the risk checker and the PnL, then hands the event to the
+    // strategy. The `Runner` and the `Backtest` both go through here.
+    pub(crate) async fn dispatch<S: Strategy>(
+        &self,
+        strategy: &mut S,
+        event: &Event,
+    ) -> Result<()> {
+        match event {
+            Event::TickerUpdate(ticker) => {
+                if let Some(risk_checker) = &self.risk_checker {
+                    risk_checker.update_ticker(ticker);
+                }
+                self.mark(&ticker.product_code, ticker.ltp);
+            }
+            Event::Trade {
+                product_code,
+                execution,
+            } => {
+                self.orders.on_execution(product_code, execution);
+                self.mark(product_code, execution.price);
+            }
+            Event::BookUpdate {
+                product_code,
+                board,
+            } => self.orders.on_book(product_code, board),
+            Event::OrderStateChange { order, .. } => {
+                self.orders.update(order);
+                if let Some(risk_checker) = &self.risk_checker {
+                    risk_checker.set_open_orders(self.orders.open_orders().len());
+                }
+            }
+            Event::MyFill {
+                product_code,
+                execution,
+            } => {
+                let (realizations, net_size) = {
+                    let mut pnl = self.pnl.lock().unwrap();
+                    let realizations = pnl.apply(product_code, execution);
+                    let net_size = pnl.product(product_code).map(|x| x.net_size());
+                    (realizations, net_size.unwrap_or_default())
+                };
+                if let Some(risk_checker) = &self.risk_checker {
+                    for realization in realizations {
+                        risk_checker.record_realized_pnl(realization.pnl);
+                    }
+                    risk_checker.set_position(product_code.clone(), net_size);
+                    risk_checker.set_unrealized_pnl(self.unrealized_pnl());
+                }
+                return strategy.on_fill(self, product_code, execution).await;
+            }
+            _ => {}
+        }
+        strategy.on_event(self, event).await
+    }
+
+    // Marks the open lots to market so the risk checker's loss limit sees open losses too.
+    fn mark(&self, product_code: &ProductCode, price: Decimal) {
+        self.marks
+            .lock()
+            .unwrap()
+            .insert(product_code.clone(), price);
+        if let Some(risk_checker) = &self.risk_checker {
+            risk_checker.set_unrealized_pnl(self.unrealized_pnl());
+        }
+    }
+
+    // Cancels the open orders sent through `orders()`, logging failures.
+    pub(crate) async fn cancel_open_orders(&self) {
+        for order in self.orders.open_orders() {
+            if let Err(e) = self.orders.cancel(&order.acceptance_id).await {
+                tracing::warn!("failed to cancel {}: {e:?}", order.acceptance_id);
+            }
+        }
+    }
+}
+
+async fn tick(timer: &mut Option<tokio::time::Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn risk_event(receiver: &mut Option<broadcast::Receiver<RiskEvent>>) -> RiskEvent {
+    loop {
+        match receiver {
+            Some(x) => match x.recv().await {
+                Ok(event) => return event,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => *receiver = None,
+            },
+            None => std::future::pending().await,
+        }
+    }
+}
+
+// Runs a strategy on live events until shut down: `on_start`, then events and timer ticks one
+// at a time, then `on_stop` and the cancellation of the strategy's open orders. Errors of the
+// strategy's handlers are logged and the run goes on. A breached daily loss limit of the risk
+// checker stops the run.
+pub struct Runner<'a, A = Client> {
+    context: StrategyContext<A>,
+    bus: EventBus<'a>,
+    timer_interval: Option<Duration>,
+    cancel_on_stop: bool,
+}
+
+impl<A: std::fmt::Debug> std::fmt::Debug for Runner<'_, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Runner")
+            .field("context", &self.context)
+            .field("bus", &self.bus)
+            .field("timer_interval", &self.timer_interval)
+            .field("cancel_on_stop", &self.cancel_on_stop)
+            .finish()
+    }
+}
+
+impl<'a, A: BitflyerApi + 'static> Runner<'a, A> {
+    pub fn new(context: StrategyContext<A>, bus: EventBus<'a>) -> Self {
+        Self {
+            context,
+            bus,
+            timer_interval: None,
+            cancel_on_stop: true,
+        }
+    }
+
+    pub fn with_timer(mut self, interval: Duration) -> Self {
+        self.timer_interval = Some(interval);
+        self
+    }
+
+    // Leaves the strategy's open orders in place on shutdown.
+    pub fn with_cancel_on_stop(mut self, cancel_on_stop: bool) -> Self {
+        self.cancel_on_stop = cancel_on_stop;
+        self
+    }
+
+    pub fn context(&self) -> &StrategyContext<A> {
+        &self.context
+    }
+
+    // Until Ctrl-C.
+    pub async fn run<S: Strategy>(self, strategy: &mut S) -> Result<StrategyContext<A>> {
+        self.run_until(strategy, async {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                tracing::warn!("failed to listen for Ctrl-C: {e:?}");
+                std::future::pending::<()>().await;
+            }
+        })
+        .await
+    }
+
+    // Until `token` is cancelled, e.g. the application's shutdown token.
+    pub async fn run_until_cancelled<S: Strategy>(
+        self,
+        strategy: &mut S,
+        token: CancellationToken,
+    ) -> Result<StrategyContext<A>> {
+        self.run_until(strategy, token.cancelled_owned()).await
+    }
+
+    // Until `shutdown` completes or every event source has ended. Returns the context for a
+    // look at the final orders and PnL.
+    pub async fn run_until<S: Strategy>(
+        self,
+        strategy: &mut S,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<StrategyContext<A>> {
+        let context = self.context;
+        strategy.on_start(&context).await?;
+        let mut events = self.bus.into_stream();
+        let mut timer = self.timer_interval.map(|x| {
+            let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + x, x);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            timer
+        });
+        let mut risk_events = context.risk_checker.as_ref().map(|x| x.subscribe());
+        tokio::pin!(shutdown);
+        loop {
+            let result = tokio::select! {
+                _ = &mut shutdown => break,
+                RiskEvent::DailyLossLimitBreached { pnl, limit, .. } = risk_event(&mut risk_events) => {
+                    tracing::warn!("stopping the strategy: daily PnL {pnl} reached the loss limit of {limit}");
+                    break;
+                }
+                event = events.next() => match event {
+                    Some(event) => context.dispatch(strategy, &event).await,
+                    None => break,
+                },
+                _ = tick(&mut timer) => strategy.on_timer(&context, context.now()).await,
+            };
+            if let Err(e) = result {
+                tracing::warn!("strategy failed to handle an event: {e:?}");
+            }
+        }
+        if let Err(e) = strategy.on_stop(&context).await {
+            tracing::warn!("strategy failed to stop: {e:?}");
+        }
+        if self.cancel_on_stop {
+            context.cancel_open_orders().await;
+        }
+        Ok(context)
+    }
+}
@@ -0,0 +1,391 @@
+use crate::api::{
+    CancelAllChildOrders, CancelChildOrder, Client, GetBoardHealth, GetBoardState, GetTicker,
+    SendChildOrder, SendChildOrderResponse,
+};
+use crate::entity::{
+    ChildOrder, ChildOrderType, OrderState, ProductCode, Side, Ticker, TimeInForce,
+};
+use crate::signing;
+use anyhow::{Context, Result};
+use axum::body::{to_bytes, Body};
+use axum::extract::{Query, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use reqwest::Url;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+pub const STUB_API_KEY: &str = "stub-api-key";
+pub const STUB_API_SECRET: &str = "stub-api-secret";
+
+// How far ACCESS-TIMESTAMP may be from the server's clock, in seconds.
+const TIMESTAMP_TOLERANCE: i64 = 300;
+
+#[derive(Debug)]
+struct Exchange {
+    api_key: String,
+    api_secret: Vec<u8>,
+    // Best bid and ask of each product.
+    quotes: HashMap<ProductCode, (Decimal, Decimal)>,
+    orders: Vec<ChildOrder>,
+}
+
+impl Exchange {
+    fn quote(&self, product_code: &ProductCode) -> Option<(Decimal, Decimal)> {
+        self.quotes.get(product_code).copied()
+    }
+
+    // Fills what the quote crosses at the order's own price, or at the quote for market orders.
+    fn fill(order: &mut ChildOrder, bid: Decimal, ask: Decimal) {
+        let price = match (&order.child_order_type, order.side) {
+            (ChildOrderType::Market, Side::Buy) => ask,
+            (ChildOrderType::Market, Side::Sell) => bid,
+            (ChildOrderType::Limit { price }, Side::Buy) if *price >= ask => *price,
+            (ChildOrderType::Limit { price }, Side::Sell) if *price <= bid => *price,
+            _ => return,
+        };
+        *order = order.clone().with_fill(order.size, price);
+    }
+
+    fn expire(&mut self) {
+        let now = Utc::now();
+        for order in &mut self.orders {
+            if order.child_order_state == OrderState::Active && order.expire_date <= now {
+                order.child_order_state = OrderState::Expired;
+            }
+        }
+    }
+
+    fn match_orders(&mut self) {
+        for order in &mut self.orders {
+            if order.child_order_state != OrderState::Active {
+                continue;
+            }
+            if let Some((bid, ask)) = self.quotes.get(&order.product_code) {
+                Self::fill(order, *bid, *ask);
+            }
+        }
+    }
+}
+
+type Shared = Arc<Mutex<Exchange>>;
+
+// The body bitFlyer answers rejected requests with.
+fn error(status: StatusCode, code: i64, message: impl Into<String>) -> Response {
+    let body = json!({
+        "status": code,
+        "error_message": message.into(),
+        "data": null,
+    });
+    (status, Json(body)).into_response()
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers().get(name).and_then(|x| x.to_str().ok())
+}
+
+// Checks ACCESS-KEY, ACCESS-TIMESTAMP and ACCESS-SIGN like the private API does.
+async fn authenticate(State(exchange): State<Shared>, request: Request, next: Next) -> Response {
+    let (api_key, api_secret) = {
+        let exchange = exchange.lock().unwrap();
+        (exchange.api_key.clone(), exchange.api_secret.clone())
+    };
+    if header(&request, "ACCESS-KEY") != Some(api_key.as_str()) {
+        return error(StatusCode::UNAUTHORIZED, -500, "Key not found");
+    }
+    let Some(timestamp) = header(&request, "ACCESS-TIMESTAMP").and_then(|x| x.parse::<i64>().ok())
+    else {
+        return error(StatusCode::UNAUTHORIZED, -500, "Invalid timestamp");
+    };
+    if (Utc::now().timestamp() - timestamp).abs() > TIMESTAMP_TOLERANCE {
+        return error(StatusCode::UNAUTHORIZED, -500, "Timestamp expired");
+    }
+    let signature = header(&request, "ACCESS-SIGN")
+        .unwrap_or_default()
+        .to_string();
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return error(StatusCode::BAD_REQUEST, -100, "Unreadable body");
+    };
+    let text = String::from_utf8_lossy(&body);
+    let is_valid = signing::verify(
+        &api_secret,
+        timestamp,
+        parts.method.as_str(),
+        parts.uri.path(),
+        parts.uri.query(),
+        Some(&*text).filter(|x| !x.is_empty()),
+        &signature,
+    );
+    if !is_valid {
+        return error(StatusCode::UNAUTHORIZED, -500, "Invalid signature");
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+fn product_code_or_default(product_code: Option<ProductCode>) -> ProductCode {
+    product_code.unwrap_or(ProductCode::BtcJpy)
+}
+
+async fn ticker(State(exchange): State<Shared>, Query(query): Query<GetTicker>) -> Response {
+    let product_code = product_code_or_default(query.product_code);
+    match exchange.lock().unwrap().quote(&product_code) {
+        Some((bid, ask)) => Json(Ticker::new(product_code, bid, ask)).into_response(),
+        None => error(StatusCode::BAD_REQUEST, -156, "Invalid product"),
+    }
+}
+
+async fn board_state(Query(_): Query<GetBoardState>) -> Response {
+    Json(json!({ "health": "NORMAL", "state": "RUNNING" })).into_response()
+}
+
+async fn health(Query(_): Query<GetBoardHealth>) -> Response {
+    Json(json!({ "status": "NORMAL" })).into_response()
+}
+
+async fn send_child_order(
+    State(exchange): State<Shared>,
+    Json(request): Json<SendChildOrder>,
+) -> Response {
+    let mut exchange = exchange.lock().unwrap();
+    let Some((bid, ask)) = exchange.quote(&request.product_code) else {
+        return error(StatusCode::BAD_REQUEST, -156, "Invalid product");
+    };
+    let min_order_size = request
+        .product_code
+        .min_order_size()
+        .unwrap_or(Decimal::ZERO);
+    if request.size <= Decimal::ZERO || request.size < min_order_size {
+        return error(
+            StatusCode::BAD_REQUEST,
+            -110,
+            format!("The minimum order size is {min_order_size}"),
+        );
+    }
+    if let ChildOrderType::Limit { price } = request.child_order_type {
+        if price <= Decimal::ZERO {
+            return error(StatusCode::BAD_REQUEST, -100, "Invalid price");
+        }
+    }
+    let time_in_force = request.time_in_force.unwrap_or(TimeInForce::Gtc);
+    let mut order = ChildOrder::new(
+        request.product_code,
+        request.side,
+        request.child_order_type,
+        request.size,
+    )
+    .with_time_in_force(time_in_force);
+    if let Some(minute_to_expire) = request.minute_to_expire {
+        let expire_date =
+            order.child_order_date + chrono::Duration::minutes(minute_to_expire.minutes() as i64);
+        order = order.with_expire_date(expire_date);
+    }
+    Exchange::fill(&mut order, bid, ask);
+    // Whatever an IOC or FOK order cannot take at once is canceled.
+    if order.child_order_state == OrderState::Active && time_in_force != TimeInForce::Gtc {
+        order = order.canceled();
+    }
+    let response = SendChildOrderResponse::new(order.child_order_acceptance_id.clone());
+    exchange.orders.push(order);
+    Json(response).into_response()
+}
+
+fn cancel(order: &mut ChildOrder) {
+    if order.child_order_state == OrderState::Active {
+        *order = order.clone().canceled();
+    }
+}
+
+// Like bitFlyer, unknown and already closed orders are not an error.
+async fn cancel_child_order(
+    State(exchange): State<Shared>,
+    Json(request): Json<CancelChildOrder>,
+) -> Response {
+    let mut exchange = exchange.lock().unwrap();
+    exchange.expire();
+    exchange
+        .orders
+        .iter_mut()
+        .filter(|x| {
+            x.product_code == request.product_code
+                && x.child_order_acceptance_id == request.child_order_acceptance_id
+        })
+        .for_each(cancel);
+    StatusCode::OK.into_response()
+}
+
+async fn cancel_all_child_orders(
+    State(exchange): State<Shared>,
+    Json(request): Json<CancelAllChildOrders>,
+) -> Response {
+    let mut exchange = exchange.lock().unwrap();
+    exchange.expire();
+    exchange
+        .orders
+        .iter_mut()
+        .filter(|x| x.product_code == request.product_code)
+        .for_each(cancel);
+    StatusCode::OK.into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct ChildOrdersQuery {
+    product_code: Option<ProductCode>,
+    count: Option<usize>,
+    before: Option<u64>,
+    after: Option<u64>,
+    child_order_state: Option<OrderState>,
+    child_order_acceptance_id: Option<String>,
+    child_order_id: Option<String>,
+}
+
+async fn child_orders(
+    State(exchange): State<Shared>,
+    Query(query): Query<ChildOrdersQuery>,
+) -> Response {
+    let mut exchange = exchange.lock().unwrap();
+    exchange.expire();
+    let product_code = product_code_or_default(query.product_code);
+    let orders = exchange
+        .orders
+        .iter()
+        .rev()
+        .filter(|x| x.product_code == product_code)
+        .filter(|x| query.before.is_none_or(|before| x.id < before))
+        .filter(|x| query.after.is_none_or(|after| x.id > after))
+        .filter(|x| {
+            query
+                .child_order_state
+                .as_ref()
+                .is_none_or(|state| &x.child_order_state == state)
+        })
+        .filter(|x| {
+            query
+                .child_order_acceptance_id
+                .as_ref()
+                .is_none_or(|id| &x.child_order_acceptance_id == id)
+        })
+        .filter(|x| {
+            query
+                .child_order_id
+                .as_ref()
+                .is_none_or(|id| &x.child_order_id == id)
+        })
+        .take(query.count.unwrap_or(100))
+        .cloned()
+        .collect::<Vec<_>>();
+    Json(orders).into_response()
+}
+
+// An in-process stand-in for the REST API on a local port, so `Client` can be tested end to
+// end. It checks signatures like the private API, accepts child orders and moves them through
+// ACTIVE, COMPLETED, CANCELED and EXPIRED. Orders fill in full against the quote set with
+// `set_quote`; there is no book depth. Other endpoints answer 404. The server stops when
+// dropped.
+#[derive(Debug)]
+pub struct StubServer {
+    addr: SocketAddr,
+    exchange: Shared,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl StubServer {
+    // Quotes BTC_JPY and FX_BTC_JPY and accepts `STUB_API_KEY` and `STUB_API_SECRET`.
+    pub async fn start() -> Result<Self> {
+        let exchange = Arc::new(Mutex::new(Exchange {
+            api_key: STUB_API_KEY.to_string(),
+            api_secret: STUB_API_SECRET.as_bytes().to_vec(),
+            quotes: HashMap::from([
+                (ProductCode::BtcJpy, (dec!(10000000), dec!(10001000))),
+                (ProductCode::FxBtcJpy, (dec!(10010000), dec!(10011000))),
+            ]),
+            orders: vec![],
+        }));
+        let private = Router::new()
+            .route("/v1/me/sendchildorder", post(send_child_order))
+            .route("/v1/me/cancelchildorder", post(cancel_child_order))
+            .route("/v1/me/cancelallchildorders", post(cancel_all_child_orders))
+            .route("/v1/me/getchildorders", get(child_orders))
+            .route_layer(middleware::from_fn_with_state(
+                exchange.clone(),
+                authenticate,
+            ));
+        let router = Router::new()
+            .route("/v1/ticker", get(ticker))
+            .route("/v1/getboardstate", get(board_state))
+            .route("/v1/gethealth", get(health))
+            .merge(private)
+            .with_state(exchange.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::warn!("the stub server stopped: {e:?}");
+            }
+        });
+        Ok(Self {
+            addr,
+            exchange,
+            task,
+        })
+    }
+
+    // Replaces the accepted credentials.
+    pub fn with_credentials(
+        self,
+        api_key: impl Into<String>,
+        api_secret: impl AsRef<[u8]>,
+    ) -> Self {
+        {
+            let mut exchange = self.exchange.lock().unwrap();
+            exchange.api_key = api_key.into();
+            exchange.api_secret = api_secret.as_ref().to_vec();
+        }
+        self
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).expect("a socket address is a valid host")
+    }
+
+    // A client signing with the stub's default credentials.
+    pub fn client(&self) -> Result<Client> {
+        Client::new()?
+            .with_base_url(self.url())
+            .with_credentials(STUB_API_KEY, STUB_API_SECRET)
+            .context("failed to create a client of the stub server")
+    }
+
+    // Lists the product from now on, and fills the resting orders the new quote crosses.
+    pub fn set_quote(&self, product_code: ProductCode, best_bid: Decimal, best_ask: Decimal) {
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.quotes.insert(product_code, (best_bid, best_ask));
+        exchange.match_orders();
+    }
+
+    // Every order accepted so far, oldest first.
+    pub fn orders(&self) -> Vec<ChildOrder> {
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.expire();
+        exchange.orders.clone()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
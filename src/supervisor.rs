@@ -0,0 +1,282 @@
+use crate::shutdown::{shutdown_all, CancellationToken, ShutdownHandle, ShutdownSignal};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restart {
+    Never,
+    // After an error or a panic, not after the task ended on its own.
+    OnFailure,
+    Always,
+}
+
+// When and how soon a supervised task is started again. The delay doubles with each failure
+// in a row up to `max_backoff`, and starts over once a run lasted `reset_after`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestartPolicy {
+    pub restart: Restart,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // None restarts forever.
+    pub max_restarts: Option<u32>,
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            restart: Restart::OnFailure,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: None,
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn never() -> Self {
+        Self {
+            restart: Restart::Never,
+            ..Default::default()
+        }
+    }
+
+    pub fn on_failure() -> Self {
+        Self::default()
+    }
+
+    pub fn always() -> Self {
+        Self {
+            restart: Restart::Always,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    pub fn with_reset_after(mut self, reset_after: Duration) -> Self {
+        self.reset_after = reset_after;
+        self
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(failures.min(16)))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    // Waiting out the backoff before the next start.
+    Restarting { at: DateTime<Utc> },
+    // Ended on its own and not restarted.
+    Finished,
+    // Ended with an error and not restarted, by policy or after `max_restarts`.
+    Failed,
+    // Shut down by the supervisor.
+    Stopped,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+    pub restarts: u32,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+impl TaskHealth {
+    pub fn is_healthy(&self) -> bool {
+        matches!(
+            self.state,
+            TaskState::Running | TaskState::Finished | TaskState::Stopped
+        )
+    }
+}
+
+type Health = Arc<Mutex<BTreeMap<String, TaskHealth>>>;
+
+fn update(health: &Health, name: &str, f: impl FnOnce(&mut TaskHealth)) {
+    if let Some(task) = health.lock().unwrap().get_mut(name) {
+        f(task);
+    }
+}
+
+// Owns long-running tasks, e.g. the polling of the crate's components, and starts them again
+// after an error or a panic with a growing delay. Each task gets a `ShutdownSignal` it must
+// watch, so `shutdown` can stop them all.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    token: CancellationToken,
+    tasks: Mutex<Vec<ShutdownHandle>>,
+    health: Health,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Stops the tasks once `token` is cancelled, e.g. the application's token.
+    pub fn with_cancellation(mut self, token: &CancellationToken) -> Self {
+        self.token = token.child_token();
+        self
+    }
+
+    // `task` is called for every start. Fails when a task of the same name is supervised.
+    pub fn supervise<F, Fut>(
+        &self,
+        name: impl Into<String>,
+        policy: RestartPolicy,
+        task: F,
+    ) -> Result<()>
+    where
+        F: Fn(ShutdownSignal) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        {
+            let mut health = self.health.lock().unwrap();
+            if health.contains_key(&name) {
+                return Err(anyhow!("a task named {name} is already supervised"));
+            }
+            health.insert(
+                name.clone(),
+                TaskHealth {
+                    name: name.clone(),
+                    state: TaskState::Running,
+                    restarts: 0,
+                    consecutive_failures: 0,
+                    last_error: None,
+                    started_at: Utc::now(),
+                    failed_at: None,
+                },
+            );
+        }
+        let health = self.health.clone();
+        let handle = ShutdownHandle::spawn_with_token(&self.token, |mut signal| async move {
+            loop {
+                let started = Instant::now();
+                // In a task of its own, so a panic ends the run and not the supervisor.
+                let result = match tokio::spawn(task(signal.clone())).await {
+                    Ok(result) => result,
+                    Err(e) => Err(anyhow!("the task panicked: {e}")),
+                };
+                let failed = result.is_err();
+                update(&health, &name, |x| {
+                    if started.elapsed() >= policy.reset_after {
+                        x.consecutive_failures = 0;
+                    }
+                    if let Err(e) = &result {
+                        x.consecutive_failures += 1;
+                        x.last_error = Some(format!("{e:#}"));
+                        x.failed_at = Some(Utc::now());
+                    }
+                });
+                if signal.is_shutdown() {
+                    update(&health, &name, |x| x.state = TaskState::Stopped);
+                    return;
+                }
+                if let Err(e) = &result {
+                    tracing::warn!("supervised task {name} failed: {e:?}");
+                }
+
+                let restart = match policy.restart {
+                    Restart::Never => false,
+                    Restart::OnFailure => failed,
+                    Restart::Always => true,
+                };
+                let (restarts, failures) = health
+                    .lock()
+                    .unwrap()
+                    .get(&name)
+                    .map(|x| (x.restarts, x.consecutive_failures))
+                    .unwrap_or_default();
+                if !restart || policy.max_restarts.is_some_and(|x| restarts >= x) {
+                    let state = if failed {
+                        TaskState::Failed
+                    } else {
+                        TaskState::Finished
+                    };
+                    update(&health, &name, |x| x.state = state);
+                    return;
+                }
+                let delay = policy.backoff(failures.saturating_sub(1));
+                let at = Utc::now() + chrono::TimeDelta::from_std(delay).unwrap_or_default();
+                update(&health, &name, |x| x.state = TaskState::Restarting { at });
+                if !signal.sleep(delay).await {
+                    update(&health, &name, |x| x.state = TaskState::Stopped);
+                    return;
+                }
+                update(&health, &name, |x| {
+                    x.state = TaskState::Running;
+                    x.restarts += 1;
+                    x.started_at = Utc::now();
+                });
+            }
+        });
+        self.tasks.lock().unwrap().push(handle);
+        Ok(())
+    }
+
+    // Supervises a task spawned by one of the crate's components, e.g.
+    // `supervise_handle("orders", policy, move || manager.spawn_polling(interval))`. Those tasks
+    // log their errors and go on, so only a panic restarts them.
+    pub fn supervise_handle<F>(
+        &self,
+        name: impl Into<String>,
+        policy: RestartPolicy,
+        spawn: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> ShutdownHandle + Send + Sync + 'static,
+    {
+        self.supervise(name, policy, move |signal| {
+            let handle = spawn().with_cancellation(signal.token());
+            async move { handle.join().await }
+        })
+    }
+
+    // By name.
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.health.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn task_health(&self, name: &str) -> Option<TaskHealth> {
+        self.health.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health
+            .lock()
+            .unwrap()
+            .values()
+            .all(TaskHealth::is_healthy)
+    }
+
+    // Stops every task and waits for them to end. Tasks supervised afterwards stop at once.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.token.cancel();
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        shutdown_all(tasks).await
+    }
+}
@@ -0,0 +1,190 @@
+use crate::api::{BitflyerApi, GetPositions, GetTicker};
+use crate::entity::{Position, ProductCode, Side};
+use anyhow::Result;
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use chrono_tz::Asia::Tokyo;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+// Charged per rollover on the position value, for longs and shorts alike.
+pub const DEFAULT_SWAP_RATE: Decimal = dec!(0.0004);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapProjection {
+    pub product_code: ProductCode,
+    pub side: Side,
+    pub size: Decimal,
+    pub open_date: DateTime<Utc>,
+    pub position_value: Decimal,
+    // Rollovers the position has been held through so far.
+    pub rollovers: u32,
+    // Reported by the exchange, taken as a positive cost.
+    pub accumulated: Decimal,
+    pub daily_cost: Decimal,
+    pub projected: Decimal,
+}
+
+impl SwapProjection {
+    pub fn total(&self) -> Decimal {
+        self.accumulated + self.projected
+    }
+
+    // What the accumulated cost would be at today's position value.
+    pub fn estimated_accumulated(&self) -> Decimal {
+        self.daily_cost * Decimal::from(self.rollovers)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapSummary {
+    pub as_of: DateTime<Utc>,
+    pub days: u32,
+    pub positions: Vec<SwapProjection>,
+}
+
+impl SwapSummary {
+    pub fn accumulated(&self) -> Decimal {
+        self.positions.iter().map(|x| x.accumulated).sum()
+    }
+
+    pub fn daily_cost(&self) -> Decimal {
+        self.positions.iter().map(|x| x.daily_cost).sum()
+    }
+
+    pub fn projected(&self) -> Decimal {
+        self.positions.iter().map(|x| x.projected).sum()
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.accumulated() + self.projected()
+    }
+}
+
+// Costs are positive amounts paid in the quote currency. A position is charged at every
+// rollover (00:00 JST by default) it is held through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapProjector {
+    rate: Decimal,
+    rollover_time: NaiveTime,
+}
+
+impl Default for SwapProjector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SwapProjector {
+    pub fn new() -> Self {
+        Self {
+            rate: DEFAULT_SWAP_RATE,
+            rollover_time: NaiveTime::MIN,
+        }
+    }
+
+    pub fn with_rate(mut self, rate: Decimal) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    // Rollover time of day in JST.
+    pub fn with_rollover_time(mut self, rollover_time: NaiveTime) -> Self {
+        self.rollover_time = rollover_time;
+        self
+    }
+
+    pub fn rate(&self) -> Decimal {
+        self.rate
+    }
+
+    // Rollovers in (from, to].
+    pub fn rollovers_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> u32 {
+        if to <= from {
+            return 0;
+        }
+        let mut date = from.with_timezone(&Tokyo).date_naive();
+        let mut count = 0;
+        while let Some(rollover) = date
+            .and_time(self.rollover_time)
+            .and_local_timezone(Tokyo)
+            .earliest()
+        {
+            let rollover = rollover.with_timezone(&Utc);
+            if rollover > to {
+                break;
+            }
+            if rollover > from {
+                count += 1;
+            }
+            match date.checked_add_days(Days::new(1)) {
+                Some(next) => date = next,
+                None => break,
+            }
+        }
+        count
+    }
+
+    // Values the position at `mark_price`, or at its entry price when None.
+    pub fn project(
+        &self,
+        position: &Position,
+        mark_price: Option<Decimal>,
+        as_of: DateTime<Utc>,
+        days: u32,
+    ) -> SwapProjection {
+        let position_value = mark_price.unwrap_or(position.price) * position.size;
+        let daily_cost = position_value * self.rate;
+        let upcoming = as_of
+            .checked_add_days(Days::new(days.into()))
+            .map(|x| self.rollovers_between(as_of, x))
+            .unwrap_or(days);
+        SwapProjection {
+            product_code: position.product_code.clone(),
+            side: position.side,
+            size: position.size,
+            open_date: position.open_date,
+            position_value,
+            rollovers: self.rollovers_between(position.open_date, as_of),
+            accumulated: position.swap_point_accumulate.abs(),
+            daily_cost,
+            projected: daily_cost * Decimal::from(upcoming),
+        }
+    }
+
+    pub fn project_all(
+        &self,
+        positions: &[Position],
+        mark_price: Option<Decimal>,
+        as_of: DateTime<Utc>,
+        days: u32,
+    ) -> SwapSummary {
+        SwapSummary {
+            as_of,
+            days,
+            positions: positions
+                .iter()
+                .map(|x| self.project(x, mark_price, as_of, days))
+                .collect(),
+        }
+    }
+
+    // Projects the account's FX_BTC_JPY positions at the current last traded price.
+    pub async fn load<A: BitflyerApi>(&self, api: &A, days: u32) -> Result<SwapSummary> {
+        let positions = api.send(GetPositions {}).await?;
+        let positions: Vec<Position> = positions
+            .into_iter()
+            .filter(|x| x.product_code == ProductCode::FxBtcJpy)
+            .collect();
+        let mark_price = if positions.is_empty() {
+            None
+        } else {
+            let ticker = api
+                .send(GetTicker {
+                    product_code: Some(ProductCode::FxBtcJpy),
+                })
+                .await?;
+            Some(ticker.ltp)
+        };
+        Ok(self.project_all(&positions, mark_price, Utc::now(), days))
+    }
+}
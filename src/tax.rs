@@ -0,0 +1,397 @@
+use crate::entity::{PrivateExecution, ProductCode, Side};
+use crate::export::csv::{decimal, label, CsvRecord};
+use crate::pnl::{position_change, CostMethod, PnlEngine};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Asia::Tokyo;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TaxMethod {
+    // 移動平均法
+    #[default]
+    MovingAverage,
+    // 総平均法
+    TotalAverage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaxEntryKind {
+    Acquisition,
+    Disposal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaxEntry {
+    pub execution_id: u64,
+    pub exec_date: DateTime<Utc>,
+    pub product_code: ProductCode,
+    pub currency_code: String,
+    pub kind: TaxEntryKind,
+    // The change in the holdings. The commission is charged in the traded currency, so an
+    // acquisition receives the executed size less the commission and a disposal gives up the
+    // executed size plus it.
+    pub size: Decimal,
+    pub price: Decimal,
+    // JPY value of the commission, already in `size`.
+    pub commission: Decimal,
+    // JPY paid for an acquisition or received for a disposal.
+    pub amount: Decimal,
+    // Cost basis of the disposed size; zero for acquisitions.
+    pub cost: Decimal,
+    pub gain: Decimal,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CurrencyTaxSummary {
+    pub currency_code: String,
+    pub opening_size: Decimal,
+    pub opening_cost: Decimal,
+    pub acquired_size: Decimal,
+    pub acquisition_cost: Decimal,
+    pub disposed_size: Decimal,
+    pub proceeds: Decimal,
+    pub cost_of_disposals: Decimal,
+    pub gain: Decimal,
+    pub closing_size: Decimal,
+    pub closing_cost: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaxReport {
+    pub year: i32,
+    pub method: TaxMethod,
+    pub entries: Vec<TaxEntry>,
+    pub currencies: Vec<CurrencyTaxSummary>,
+    // Realized PnL of margin (FX) trading, reported separately from spot disposals.
+    pub margin_pnl: Decimal,
+    // Products without a JPY quote cannot be valued and are left out.
+    pub skipped_products: Vec<ProductCode>,
+}
+
+impl TaxReport {
+    pub fn spot_gain(&self) -> Decimal {
+        self.currencies.iter().map(|x| x.gain).sum()
+    }
+
+    pub fn taxable_gain(&self) -> Decimal {
+        self.spot_gain() + self.margin_pnl
+    }
+}
+
+// The tax year runs from January 1st to December 31st in JST.
+pub fn tax_year_bounds(year: i32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let bound = |year| {
+        let date = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        Some(
+            Tokyo
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+                .earliest()?
+                .with_timezone(&Utc),
+        )
+    };
+    Some((bound(year)?, bound(year + 1)?))
+}
+
+fn tax_year(time: DateTime<Utc>) -> i32 {
+    use chrono::Datelike;
+    time.with_timezone(&Tokyo).year()
+}
+
+#[derive(Clone, Debug, Default)]
+struct Holding {
+    size: Decimal,
+    cost: Decimal,
+}
+
+fn entry(
+    product_code: &ProductCode,
+    currency_code: &str,
+    execution: &PrivateExecution,
+) -> TaxEntry {
+    let kind = match execution.side {
+        Side::Buy => TaxEntryKind::Acquisition,
+        Side::Sell => TaxEntryKind::Disposal,
+    };
+    let (size, _) = position_change(
+        execution.side,
+        execution.price,
+        execution.size,
+        execution.commission,
+        true,
+    );
+    TaxEntry {
+        execution_id: execution.id,
+        exec_date: execution.exec_date,
+        product_code: product_code.clone(),
+        currency_code: currency_code.to_string(),
+        kind,
+        size,
+        price: execution.price,
+        commission: execution.commission * execution.price,
+        amount: execution.price * execution.size,
+        cost: Decimal::ZERO,
+        gain: Decimal::ZERO,
+    }
+}
+
+fn moving_average(
+    product_code: &ProductCode,
+    currency_code: &str,
+    executions: &[&PrivateExecution],
+    start: DateTime<Utc>,
+) -> (Holding, Vec<TaxEntry>, Holding) {
+    let mut engine = PnlEngine::new(CostMethod::AverageCost);
+    let holding = |engine: &PnlEngine| {
+        engine
+            .product(product_code)
+            .map(|x| Holding {
+                size: x.net_size(),
+                cost: x.cost_basis(),
+            })
+            .unwrap_or_default()
+    };
+    let (before, during): (Vec<&PrivateExecution>, Vec<&PrivateExecution>) =
+        executions.iter().partition(|x| x.exec_date < start);
+    engine.process(product_code, before);
+    let opening = holding(&engine);
+    let mut entries = vec![];
+    for execution in during {
+        let realizations = engine.apply(product_code, execution);
+        let mut entry = entry(product_code, currency_code, execution);
+        if entry.kind == TaxEntryKind::Disposal {
+            entry.cost = realizations.iter().map(|x| x.entry_price * x.size).sum();
+            // Selling more than the executions at hand bought leaves no cost for the rest,
+            // which is then all gain.
+            entry.gain = entry.amount - entry.cost;
+        }
+        entries.push(entry);
+    }
+    (opening, entries, holding(&engine))
+}
+
+fn total_average(
+    product_code: &ProductCode,
+    currency_code: &str,
+    executions: &[&PrivateExecution],
+    year: i32,
+) -> (Holding, Vec<TaxEntry>, Holding) {
+    let mut by_year: BTreeMap<i32, Vec<&PrivateExecution>> = BTreeMap::new();
+    for execution in executions {
+        by_year
+            .entry(tax_year(execution.exec_date))
+            .or_default()
+            .push(execution);
+    }
+    let mut holding = Holding::default();
+    let mut opening = Holding::default();
+    let mut entries = vec![];
+    for (y, executions) in by_year.range(..=year) {
+        opening = holding.clone();
+        let year_entries: Vec<TaxEntry> = executions
+            .iter()
+            .map(|x| entry(product_code, currency_code, x))
+            .collect();
+        // One unit cost for the whole year from the opening balance and the year's acquisitions.
+        let (acquired_size, acquired_cost) = year_entries
+            .iter()
+            .filter(|x| x.kind == TaxEntryKind::Acquisition)
+            .fold((Decimal::ZERO, Decimal::ZERO), |(size, cost), x| {
+                (size + x.size, cost + x.amount)
+            });
+        let total_size = holding.size + acquired_size;
+        let unit_cost = if total_size.is_zero() {
+            Decimal::ZERO
+        } else {
+            (holding.cost + acquired_cost) / total_size
+        };
+        let mut size = total_size;
+        for mut entry in year_entries {
+            if entry.kind == TaxEntryKind::Disposal {
+                // Like `moving_average`, what was not bought has no cost.
+                let disposed = entry.size.min(size.max(Decimal::ZERO));
+                size -= disposed;
+                entry.cost = unit_cost * disposed;
+                entry.gain = entry.amount - entry.cost;
+            }
+            if *y == year {
+                entries.push(entry);
+            }
+        }
+        holding = Holding {
+            size,
+            cost: unit_cost * size,
+        };
+    }
+    if by_year.range(year..=year).next().is_none() {
+        opening = holding.clone();
+    }
+    (opening, entries, holding)
+}
+
+pub fn tax_report(
+    year: i32,
+    method: TaxMethod,
+    executions: &[(ProductCode, PrivateExecution)],
+) -> TaxReport {
+    let (start, end) =
+        tax_year_bounds(year).unwrap_or((DateTime::UNIX_EPOCH, DateTime::UNIX_EPOCH));
+    let mut seen = HashSet::new();
+    let mut sorted: Vec<&(ProductCode, PrivateExecution)> = executions
+        .iter()
+        .filter(|(_, x)| x.exec_date < end && seen.insert(x.id))
+        .collect();
+    sorted.sort_by_key(|(_, x)| (x.exec_date, x.id));
+
+    let mut products: BTreeMap<String, Vec<&PrivateExecution>> = BTreeMap::new();
+    let mut margin = PnlEngine::new(CostMethod::AverageCost);
+    let mut margin_pnl = Decimal::ZERO;
+    let mut skipped_products = vec![];
+    for (product_code, execution) in sorted {
+        if product_code.is_fx() {
+            let pnl: Decimal = margin
+                .apply(product_code, execution)
+                .iter()
+                .map(|x| x.pnl)
+                .sum();
+            if execution.exec_date >= start {
+                margin_pnl += pnl;
+            }
+            continue;
+        }
+        match (product_code.base_currency(), product_code.quote_currency()) {
+            (Some(base), Some("JPY")) => products
+                .entry(base.to_string())
+                .or_default()
+                .push(execution),
+            _ => {
+                if !skipped_products.contains(product_code) {
+                    skipped_products.push(product_code.clone());
+                }
+            }
+        }
+    }
+
+    let mut entries = vec![];
+    let mut currencies = vec![];
+    for (currency_code, executions) in products {
+        let Some(product_code) = ProductCode::spot(&currency_code, "JPY") else {
+            continue;
+        };
+        let (opening, year_entries, closing) = match method {
+            TaxMethod::MovingAverage => {
+                moving_average(&product_code, &currency_code, &executions, start)
+            }
+            TaxMethod::TotalAverage => {
+                total_average(&product_code, &currency_code, &executions, year)
+            }
+        };
+        let mut summary = CurrencyTaxSummary {
+            currency_code,
+            opening_size: opening.size,
+            opening_cost: opening.cost,
+            closing_size: closing.size,
+            closing_cost: closing.cost,
+            ..Default::default()
+        };
+        for entry in &year_entries {
+            match entry.kind {
+                TaxEntryKind::Acquisition => {
+                    summary.acquired_size += entry.size;
+                    summary.acquisition_cost += entry.amount;
+                }
+                TaxEntryKind::Disposal => {
+                    summary.disposed_size += entry.size;
+                    summary.proceeds += entry.amount;
+                    summary.cost_of_disposals += entry.cost;
+                    summary.gain += entry.gain;
+                }
+            }
+        }
+        entries.extend(year_entries);
+        currencies.push(summary);
+    }
+    entries.sort_by_key(|x| (x.exec_date, x.execution_id));
+    TaxReport {
+        year,
+        method,
+        entries,
+        currencies,
+        margin_pnl,
+        skipped_products,
+    }
+}
+
+fn jst(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Tokyo)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+impl CsvRecord for TaxEntry {
+    const HEADER: &'static [&'static str] = &[
+        "date_jst",
+        "execution_id",
+        "product_code",
+        "currency_code",
+        "kind",
+        "size",
+        "price",
+        "commission_jpy",
+        "amount_jpy",
+        "cost_jpy",
+        "gain_jpy",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        let kind = match self.kind {
+            TaxEntryKind::Acquisition => "ACQUISITION",
+            TaxEntryKind::Disposal => "DISPOSAL",
+        };
+        vec![
+            jst(&self.exec_date),
+            self.execution_id.to_string(),
+            label(&self.product_code),
+            self.currency_code.clone(),
+            kind.to_string(),
+            decimal(self.size),
+            decimal(self.price),
+            decimal(self.commission.round_dp(0)),
+            decimal(self.amount.round_dp(0)),
+            decimal(self.cost.round_dp(0)),
+            decimal(self.gain.round_dp(0)),
+        ]
+    }
+}
+
+impl CsvRecord for CurrencyTaxSummary {
+    const HEADER: &'static [&'static str] = &[
+        "currency_code",
+        "opening_size",
+        "opening_cost_jpy",
+        "acquired_size",
+        "acquisition_cost_jpy",
+        "disposed_size",
+        "proceeds_jpy",
+        "cost_of_disposals_jpy",
+        "gain_jpy",
+        "closing_size",
+        "closing_cost_jpy",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.currency_code.clone(),
+            decimal(self.opening_size),
+            decimal(self.opening_cost.round_dp(0)),
+            decimal(self.acquired_size),
+            decimal(self.acquisition_cost.round_dp(0)),
+            decimal(self.disposed_size),
+            decimal(self.proceeds.round_dp(0)),
+            decimal(self.cost_of_disposals.round_dp(0)),
+            decimal(self.gain.round_dp(0)),
+            decimal(self.closing_size),
+            decimal(self.closing_cost.round_dp(0)),
+        ]
+    }
+}
@@ -0,0 +1,124 @@
+use crate::api::SendParentOrder;
+use crate::entity::{
+    MinuteToExpire, ParentOrderConditionType, ParentOrderMethod, ProductCode, Side, TimeInForce,
+};
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+// The offset of `percent` percent of `price`, e.g. 150000 for 1.5 percent of 10000000.
+pub fn offset_from_percent(price: Decimal, percent: Decimal) -> Decimal {
+    price * percent / Decimal::ONE_HUNDRED
+}
+
+// A TRAIL order: a market order triggered once the price moves `offset` against the best price
+// seen since it was placed. bitFlyer takes the offset as a whole number of yen; here it is a
+// price distance rounded to the nearest tick, so it can be derived from prices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrailingStop {
+    product_code: ProductCode,
+    side: Side,
+    size: Decimal,
+    offset: Option<Decimal>,
+    tick_size: Decimal,
+    minute_to_expire: Option<MinuteToExpire>,
+    time_in_force: Option<TimeInForce>,
+}
+
+impl TrailingStop {
+    // `side` is the side of the stop order, e.g. `Sell` to protect a long position.
+    pub fn new(product_code: ProductCode, side: Side, size: Decimal) -> Self {
+        Self {
+            product_code,
+            side,
+            size,
+            offset: None,
+            tick_size: Decimal::ONE,
+            minute_to_expire: None,
+            time_in_force: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Decimal) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    // An offset of `percent` percent of `price`, usually the current price.
+    pub fn with_offset_percent(self, price: Decimal, percent: Decimal) -> Self {
+        self.with_offset(offset_from_percent(price, percent))
+    }
+
+    // 1 by default.
+    pub fn with_tick_size(mut self, tick_size: Decimal) -> Self {
+        self.tick_size = tick_size;
+        self
+    }
+
+    pub fn with_minute_to_expire(mut self, minute_to_expire: MinuteToExpire) -> Self {
+        self.minute_to_expire = Some(minute_to_expire);
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    // The offset as sent: rounded to the nearest tick, at least one tick, and a whole number.
+    pub fn offset(&self) -> Result<u64> {
+        let offset = self
+            .offset
+            .ok_or_else(|| anyhow!("the trailing stop has no offset"))?;
+        if offset <= Decimal::ZERO {
+            return Err(anyhow!("trail offset must be positive: {offset}"));
+        }
+        if self.tick_size <= Decimal::ZERO {
+            return Err(anyhow!("tick size must be positive: {}", self.tick_size));
+        }
+        let rounded = (offset / self.tick_size).round() * self.tick_size;
+        if rounded.is_zero() {
+            return Err(anyhow!(
+                "trail offset {offset} is less than half a tick of {}",
+                self.tick_size
+            ));
+        }
+        if !rounded.fract().is_zero() {
+            return Err(anyhow!("trail offset must be a whole number: {rounded}"));
+        }
+        rounded
+            .to_u64()
+            .ok_or_else(|| anyhow!("trail offset is out of range: {rounded}"))
+    }
+
+    // The condition alone, e.g. for a leg of an IFD order.
+    pub fn condition(&self) -> Result<ParentOrderConditionType> {
+        if self.size <= Decimal::ZERO {
+            return Err(anyhow!("order size must be positive: {}", self.size));
+        }
+        Ok(ParentOrderConditionType::Trail {
+            product_code: self.product_code.clone(),
+            side: self.side,
+            size: self.size,
+            offset: self.offset()?,
+        })
+    }
+
+    pub fn build(&self) -> Result<SendParentOrder> {
+        Ok(SendParentOrder {
+            order_method: ParentOrderMethod::Simple {
+                parameters: [self.condition()?],
+            },
+            minute_to_expire: self.minute_to_expire,
+            time_in_force: self.time_in_force,
+        })
+    }
+}
+
+impl TryFrom<TrailingStop> for SendParentOrder {
+    type Error = anyhow::Error;
+
+    fn try_from(trailing_stop: TrailingStop) -> Result<Self> {
+        trailing_stop.build()
+    }
+}
@@ -0,0 +1,213 @@
+use crate::api::{ApiRequest, BitflyerApi, BoxFuture, Client, DynBitflyerApi, RawRequest};
+use crate::redact::Redactor;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Credentials never reach a cassette because requests are captured before they are signed.
+pub use crate::redact::DEFAULT_REDACTED_FIELDS;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: Option<String>,
+    pub response: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Interaction {
+    fn matches(&self, request: &Interaction) -> bool {
+        self.method == request.method
+            && self.path == request.path
+            && self.query == request.query
+            && self.body == request.body
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read the cassette {}", path.display()))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl Redactor {
+    fn request(&self, request: &RawRequest) -> Interaction {
+        Interaction {
+            method: request.method.to_string(),
+            path: request.path.to_string(),
+            query: request.query().map(|x| x.to_string()),
+            body: request.body.as_deref().map(|x| self.redact(x)),
+            response: None,
+            error: None,
+        }
+    }
+}
+
+// Forwards requests to a live client and appends every exchange to the cassette file,
+// which is rewritten after each request so a crashed run still leaves a usable cassette.
+#[derive(Debug)]
+pub struct Recorder<A = Client> {
+    inner: A,
+    path: PathBuf,
+    redactor: Redactor,
+    cassette: Mutex<Cassette>,
+}
+
+impl<A: DynBitflyerApi> Recorder<A> {
+    pub fn new(inner: A, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            redactor: Redactor::default(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    // JSON fields whose values are replaced in recorded bodies and responses.
+    pub fn with_redacted_fields(
+        mut self,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.redactor.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    async fn record(&self, request: RawRequest) -> Result<String> {
+        let mut interaction = self.redactor.request(&request);
+        let result = self.inner.send_raw(request).await;
+        match &result {
+            Ok(body) => interaction.response = Some(self.redactor.redact(body)),
+            Err(e) => interaction.error = Some(format!("{e:#}")),
+        }
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(interaction);
+        cassette.save(&self.path)?;
+        result
+    }
+}
+
+impl<A: DynBitflyerApi> DynBitflyerApi for Recorder<A> {
+    fn send_raw(&self, request: RawRequest) -> BoxFuture<'_, Result<String>> {
+        Box::pin(self.record(request))
+    }
+}
+
+impl<A: DynBitflyerApi> BitflyerApi for Recorder<A> {
+    async fn send<T>(&self, request: T) -> Result<T::Response>
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        let body = self.record(RawRequest::new(&request)?).await?;
+        request.parse_response(&body)
+    }
+}
+
+// Answers requests from a cassette without touching the network. Each recorded interaction
+// is used once, the earliest unused match first, so repeated identical requests replay
+// their recorded responses in order.
+#[derive(Debug)]
+pub struct Replayer {
+    redactor: Redactor,
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl Replayer {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            redactor: Redactor::default(),
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    // Must match the fields the cassette was recorded with.
+    pub fn with_redacted_fields(
+        mut self,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.redactor.fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn unused(&self) -> Vec<Interaction> {
+        let used = self.used.lock().unwrap();
+        self.interactions
+            .iter()
+            .zip(used.iter())
+            .filter(|(_, used)| !**used)
+            .map(|(x, _)| x.clone())
+            .collect()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.used.lock().unwrap().iter().all(|x| *x)
+    }
+
+    fn replay(&self, request: &RawRequest) -> Result<String> {
+        let request = self.redactor.request(request);
+        let mut used = self.used.lock().unwrap();
+        let index = self
+            .interactions
+            .iter()
+            .zip(used.iter())
+            .position(|(x, used)| !*used && x.matches(&request))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no recorded interaction for {} {}{}",
+                    request.method,
+                    request.path,
+                    request.query.map(|x| format!("?{x}")).unwrap_or_default()
+                )
+            })?;
+        used[index] = true;
+        let interaction = &self.interactions[index];
+        match (&interaction.response, &interaction.error) {
+            (_, Some(error)) => Err(anyhow!("{error}")),
+            (Some(response), None) => Ok(response.clone()),
+            (None, None) => Ok(String::new()),
+        }
+    }
+}
+
+impl DynBitflyerApi for Replayer {
+    fn send_raw(&self, request: RawRequest) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move { self.replay(&request) })
+    }
+}
+
+impl BitflyerApi for Replayer {
+    async fn send<T>(&self, request: T) -> Result<T::Response>
+    where
+        T: ApiRequest + std::fmt::Debug + Send + Sync,
+        T::Response: Send + 'static,
+    {
+        let body = self.replay(&RawRequest::new(&request)?)?;
+        request.parse_response(&body)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::entity::Execution;
+use chrono::{DateTime, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VwapWindow {
+    // Executions within this duration of the latest one.
+    Time(Duration),
+    // The most recent executions adding up to this size; the oldest one is counted partially.
+    Volume(Decimal),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Trade {
+    exec_date: DateTime<Utc>,
+    price: Decimal,
+    size: Decimal,
+}
+
+// Timestamps come from the executions themselves so downloaded history replays the same way as live data.
+#[derive(Clone, Debug)]
+pub struct RollingVwap {
+    window: VwapWindow,
+    trades: VecDeque<Trade>,
+    volume: Decimal,
+    notional: Decimal,
+    price_sum: Decimal,
+}
+
+impl RollingVwap {
+    pub fn new(window: VwapWindow) -> Self {
+        Self {
+            window,
+            trades: VecDeque::new(),
+            volume: Decimal::ZERO,
+            notional: Decimal::ZERO,
+            price_sum: Decimal::ZERO,
+        }
+    }
+
+    pub fn window(&self) -> VwapWindow {
+        self.window
+    }
+
+    pub fn on_execution(&mut self, execution: &Execution) -> Option<Decimal> {
+        if execution.size <= Decimal::ZERO {
+            return self.vwap();
+        }
+        self.trades.push_back(Trade {
+            exec_date: execution.exec_date,
+            price: execution.price,
+            size: execution.size,
+        });
+        self.volume += execution.size;
+        self.notional += execution.price * execution.size;
+        self.price_sum += execution.price;
+        match self.window {
+            VwapWindow::Time(_) => self.expire(execution.exec_date),
+            VwapWindow::Volume(window) => self.trim_volume(window),
+        }
+        self.vwap()
+    }
+
+    // Drops executions that fell out of a time window while no new ones arrived.
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let VwapWindow::Time(window) = self.window else {
+            return;
+        };
+        let window = TimeDelta::from_std(window).unwrap_or(TimeDelta::MAX);
+        while self
+            .trades
+            .front()
+            .is_some_and(|x| now.signed_duration_since(x.exec_date) > window)
+        {
+            self.pop_front();
+        }
+    }
+
+    fn trim_volume(&mut self, window: Decimal) {
+        while let Some(front) = self.trades.front() {
+            if self.volume - front.size < window {
+                break;
+            }
+            self.pop_front();
+        }
+        let excess = self.volume - window;
+        if let Some(front) = self.trades.front_mut().filter(|_| excess > Decimal::ZERO) {
+            front.size -= excess;
+            self.volume -= excess;
+            self.notional -= front.price * excess;
+        }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(trade) = self.trades.pop_front() {
+            self.volume -= trade.size;
+            self.notional -= trade.price * trade.size;
+            self.price_sum -= trade.price;
+        }
+        if self.trades.is_empty() {
+            self.volume = Decimal::ZERO;
+            self.notional = Decimal::ZERO;
+            self.price_sum = Decimal::ZERO;
+        }
+    }
+
+    pub fn vwap(&self) -> Option<Decimal> {
+        if self.volume.is_zero() {
+            return None;
+        }
+        Some(self.notional / self.volume)
+    }
+
+    // Unweighted mean of the execution prices in the window.
+    pub fn average_price(&self) -> Option<Decimal> {
+        if self.trades.is_empty() {
+            return None;
+        }
+        Some(self.price_sum / Decimal::from(self.trades.len()))
+    }
+
+    pub fn volume(&self) -> Decimal {
+        self.volume
+    }
+
+    pub fn notional(&self) -> Decimal {
+        self.notional
+    }
+
+    pub fn trade_count(&self) -> usize {
+        self.trades.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.trades.clear();
+        self.volume = Decimal::ZERO;
+        self.notional = Decimal::ZERO;
+        self.price_sum = Decimal::ZERO;
+    }
+}
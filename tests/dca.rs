@@ -0,0 +1,101 @@
+// DCA runs against `MockBitflyer`, sending the order again only when the last one can't have
+// been placed.
+
+use bitflyer::api::{GetChildOrders, GetTicker, SendChildOrder, SendChildOrderResponse};
+use bitflyer::dca::{DcaOutcome, DcaPlan, DcaRun, DcaScheduler};
+use bitflyer::entity::{ChildOrder, ChildOrderType, ProductCode, Side, Ticker};
+use bitflyer::mock::{Fault, MockBitflyer, Schedule};
+use chrono::{NaiveTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn mock() -> MockBitflyer {
+    let mock = MockBitflyer::new();
+    mock.respond::<GetTicker>(Ticker::new(
+        ProductCode::BtcJpy,
+        dec!(9990000),
+        dec!(10000000),
+    ))
+    .respond::<SendChildOrder>(SendChildOrderResponse::new("JRF-SENT"));
+    mock
+}
+
+async fn run(mock: &Arc<MockBitflyer>, min_size: Decimal) -> DcaRun {
+    let scheduler = DcaScheduler::new(mock.clone()).with_retry(3, Duration::from_millis(1));
+    let plan = DcaPlan {
+        id: "daily".to_string(),
+        product_code: ProductCode::BtcJpy,
+        notional: dec!(10000),
+        time: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        min_size,
+    };
+    scheduler.execute(&plan, Utc::now()).await
+}
+
+fn acceptance_id(run: &DcaRun) -> &str {
+    match &run.outcome {
+        DcaOutcome::Submitted {
+            child_order_acceptance_id,
+            ..
+        } => child_order_acceptance_id,
+        DcaOutcome::Failed { error } => panic!("{error}"),
+    }
+}
+
+#[tokio::test]
+async fn finds_the_order_after_a_timeout_instead_of_sending_another() {
+    let mock = Arc::new(mock());
+    mock.inject_for::<SendChildOrder>(
+        Fault::Timeout(Duration::from_millis(1)),
+        Schedule::Calls(0..1),
+    )
+    .respond::<GetChildOrders>(vec![ChildOrder::new(
+        ProductCode::BtcJpy,
+        Side::Buy,
+        ChildOrderType::Market,
+        dec!(0.001),
+    )
+    .with_acceptance_id("JRF-PLACED")]);
+
+    let run = run(&mock, dec!(0.001)).await;
+    assert_eq!(acceptance_id(&run), "JRF-PLACED");
+    assert_eq!(run.attempts, 1);
+    assert_eq!(mock.call_count::<SendChildOrder>(), 1);
+}
+
+#[tokio::test]
+async fn sends_again_when_the_order_is_not_found() {
+    let mock = Arc::new(mock());
+    mock.inject_for::<SendChildOrder>(
+        Fault::ServerError(reqwest::StatusCode::BAD_GATEWAY),
+        Schedule::Calls(0..1),
+    )
+    .respond::<GetChildOrders>(vec![]);
+
+    let run = run(&mock, dec!(0.001)).await;
+    assert_eq!(acceptance_id(&run), "JRF-SENT");
+    assert_eq!(run.attempts, 2);
+    assert_eq!(mock.call_count::<GetChildOrders>(), 1);
+}
+
+#[tokio::test]
+async fn sends_again_without_looking_when_rate_limited() {
+    let mock = Arc::new(mock());
+    mock.inject_for::<SendChildOrder>(Fault::RateLimited, Schedule::Calls(0..1));
+
+    let run = run(&mock, dec!(0.001)).await;
+    assert_eq!(acceptance_id(&run), "JRF-SENT");
+    assert_eq!(run.attempts, 2);
+    assert_eq!(mock.call_count::<GetChildOrders>(), 0);
+}
+
+#[tokio::test]
+async fn fails_at_once_below_the_minimum_size() {
+    let mock = Arc::new(mock());
+    let run = run(&mock, dec!(0.01)).await;
+    assert!(!run.is_success());
+    assert_eq!(run.attempts, 1);
+    assert_eq!(mock.call_count::<SendChildOrder>(), 0);
+}
@@ -0,0 +1,60 @@
+// Polled event sources against `MockBitflyer`.
+
+use bitflyer::api::GetPrivateExecutions;
+use bitflyer::entity::{PrivateExecution, ProductCode};
+use bitflyer::events::{fill_events, Event};
+use bitflyer::mock::MockBitflyer;
+use futures::StreamExt;
+use serde_json::json;
+use std::time::Duration;
+
+// Newest first, as the exchange pages them.
+fn executions(ids: impl DoubleEndedIterator<Item = u64>) -> Vec<PrivateExecution> {
+    let executions: Vec<_> = ids
+        .rev()
+        .map(|id| {
+            json!({
+                "id": id,
+                "child_order_id": "JOR-1",
+                "side": "BUY",
+                "price": 10000000,
+                "size": 0.01,
+                "commission": 0,
+                "exec_date": "2024-01-01T00:00:00",
+                "child_order_acceptance_id": "JRF-1",
+            })
+        })
+        .collect();
+    serde_json::from_value(json!(executions)).unwrap()
+}
+
+#[tokio::test]
+async fn fill_bursts_are_paged_back_to_the_last_seen_fill() {
+    let mock = MockBitflyer::new();
+    mock.respond::<GetPrivateExecutions>(vec![])
+        .respond_once::<GetPrivateExecutions>(executions(100..=100))
+        // More than a page between two polls.
+        .respond_once::<GetPrivateExecutions>(executions(251..=750))
+        .respond_once::<GetPrivateExecutions>(executions(101..=250));
+
+    let fills: Vec<_> = fill_events(&mock, ProductCode::BtcJpy, Duration::from_millis(10))
+        .take(650)
+        .map(|event| match event {
+            Event::MyFill { execution, .. } => execution.id,
+            event => panic!("unexpected {event:?}"),
+        })
+        .collect()
+        .await;
+    assert_eq!(fills, (101..=750).collect::<Vec<_>>());
+
+    let queries: Vec<String> = mock
+        .calls_to::<GetPrivateExecutions>()
+        .iter()
+        .map(|x| x.url.query().unwrap_or_default().to_string())
+        .collect();
+    assert!(queries[0].contains("count=1"), "{queries:?}");
+    assert!(queries[1].contains("after=100"), "{queries:?}");
+    assert!(!queries[1].contains("before"), "{queries:?}");
+    assert!(queries[2].contains("before=251"), "{queries:?}");
+    assert!(queries[2].contains("after=100"), "{queries:?}");
+}
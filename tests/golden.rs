@@ -0,0 +1,131 @@
+// Runs every response in `tests/golden/<endpoint>/*.json` through the response type of its
+// endpoint, and checks that the types which implement `Serialize` read back what they write.
+// Add a file whenever the API returns something new; the directory names follow the endpoint
+// paths without `/v1/`, with `/` replaced by `_`.
+
+use bitflyer::api::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+fn corpus() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn directory<T: ApiRequest>() -> String {
+    T::PATH.trim_start_matches("/v1/").replace('/', "_")
+}
+
+fn cases<T: ApiRequest>() -> Vec<(PathBuf, String)> {
+    let dir = corpus().join(directory::<T>());
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("no golden responses for {}: {e}", T::PATH))
+        .map(|x| x.unwrap().path())
+        .filter(|x| x.extension().is_some_and(|x| x == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no golden responses for {}", T::PATH);
+    paths
+        .into_iter()
+        .map(|x| {
+            let body = std::fs::read_to_string(&x).unwrap();
+            (x, body)
+        })
+        .collect()
+}
+
+fn deserializes<T: ApiRequest>() -> Vec<T::Response> {
+    cases::<T>()
+        .into_iter()
+        .map(|(path, body)| {
+            T::deserialize_response_body(&body)
+                .unwrap_or_else(|e| panic!("{}: {e:?}", path.display()))
+        })
+        .collect()
+}
+
+fn round_trips<T>()
+where
+    T: ApiRequest,
+    T::Response: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    for (response, (path, _)) in deserializes::<T>().into_iter().zip(cases::<T>()) {
+        let json = serde_json::to_string(&response).unwrap();
+        let read: T::Response = serde_json::from_str(&json)
+            .unwrap_or_else(|e| panic!("{}: {e:?}\nserialized: {json}", path.display()));
+        assert_eq!(read, response, "{}", path.display());
+    }
+}
+
+macro_rules! endpoints {
+    ($($request:ty),* $(,)?) => {
+        fn directories() -> BTreeSet<String> {
+            [$(directory::<$request>()),*].into_iter().collect()
+        }
+
+        #[test]
+        fn every_response_deserializes() {
+            $(deserializes::<$request>();)*
+        }
+    };
+}
+
+endpoints!(
+    GetMarkets,
+    GetBoard,
+    GetTicker,
+    GetExecutions,
+    GetBoardState,
+    GetBoardHealth,
+    GetPermissions,
+    GetBalance,
+    GetCollateral,
+    GetCollateralAccounts,
+    SendChildOrder,
+    SendParentOrder,
+    GetChildOrders,
+    GetParentOrders,
+    GetParentOrder,
+    GetPositions,
+    GetPrivateExecutions,
+    GetBalanceHistory,
+    GetCoinIns,
+    GetCoinOuts,
+);
+
+#[test]
+fn serializable_responses_round_trip() {
+    round_trips::<GetTicker>();
+    round_trips::<GetExecutions>();
+    round_trips::<GetPermissions>();
+    round_trips::<SendChildOrder>();
+    round_trips::<SendParentOrder>();
+    round_trips::<GetChildOrders>();
+    round_trips::<GetPositions>();
+}
+
+// A directory the tests do not know about would silently go unchecked.
+#[test]
+fn corpus_covers_only_known_endpoints() {
+    let found: BTreeSet<String> = std::fs::read_dir(corpus())
+        .unwrap()
+        .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(found, directories());
+}
+
+// Only the endpoints that return nothing accept an empty body.
+#[test]
+fn empty_bodies_read_only_as_empty_responses() {
+    assert_eq!(
+        CancelChildOrder::deserialize_response_body("").unwrap(),
+        EmptyResponse
+    );
+    assert_eq!(
+        CancelAllChildOrders::deserialize_response_body(" \n").unwrap(),
+        EmptyResponse
+    );
+    assert!(GetTicker::deserialize_response_body("").is_err());
+    assert!(GetPositions::deserialize_response_body("").is_err());
+}
@@ -0,0 +1,104 @@
+// The kill switch flattening positions through limits that would refuse the close, and without
+// closing twice while positions lag the fills.
+
+use bitflyer::api::{
+    BitflyerApi, CancelAllChildOrders, GetChildOrders, GetParentOrders, GetPositions,
+    SendChildOrder, SendChildOrderResponse,
+};
+use bitflyer::entity::{Board, ChildOrder, ChildOrderType, Position, ProductCode, Side};
+use bitflyer::kill_switch::{kill_switch, KillSwitchOptions};
+use bitflyer::mock::MockBitflyer;
+use bitflyer::risk::{RiskChecker, RiskLimits};
+use bitflyer::sim::SimClient;
+use rust_decimal_macros::dec;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn options() -> KillSwitchOptions {
+    KillSwitchOptions {
+        close_positions: true,
+        max_attempts: 3,
+        retry_interval: Duration::from_millis(10),
+        fill_timeout: Duration::from_secs(1),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn closes_positions_past_the_loss_limit() {
+    let checker = Arc::new(RiskChecker::new(RiskLimits {
+        max_order_size: Some(dec!(0.2)),
+        daily_loss_limit: Some(dec!(10000)),
+        ..Default::default()
+    }));
+    let sim = SimClient::new()
+        .with_collateral(dec!(5000000))
+        .with_risk_checker(checker.clone());
+    let board: Board = serde_json::from_value(json!({
+        "mid_price": 10000000,
+        "bids": [{ "price": 9990000, "size": 1 }],
+        "asks": [{ "price": 10010000, "size": 1 }],
+    }))
+    .unwrap();
+    sim.on_board(&ProductCode::FxBtcJpy, board);
+    for _ in 0..2 {
+        sim.send(SendChildOrder::market(
+            ProductCode::FxBtcJpy,
+            Side::Buy,
+            dec!(0.2),
+        ))
+        .await
+        .unwrap();
+    }
+    checker.record_realized_pnl(dec!(-50000));
+    // Too large for the risk checker even though it only closes.
+    assert!(sim
+        .send(SendChildOrder::market(
+            ProductCode::FxBtcJpy,
+            Side::Sell,
+            dec!(0.4)
+        ))
+        .await
+        .is_err());
+
+    let report = kill_switch(&sim, &[ProductCode::FxBtcJpy], &options())
+        .await
+        .unwrap();
+    assert!(report.is_confirmed(), "{report:?}");
+    assert_eq!(report.close_orders.len(), 1);
+    assert!(sim.send(GetPositions {}).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn does_not_close_again_while_positions_lag() {
+    let mock = MockBitflyer::new();
+    mock.respond_json::<CancelAllChildOrders>("")
+        .respond::<GetParentOrders>(vec![])
+        // Still shows the position after the close has filled.
+        .respond::<GetPositions>(vec![Position::new(
+            ProductCode::FxBtcJpy,
+            Side::Buy,
+            dec!(10000000),
+            dec!(0.3),
+        )])
+        .respond::<SendChildOrder>(SendChildOrderResponse::new("JRF-close"));
+    let close = ChildOrder::new(
+        ProductCode::FxBtcJpy,
+        Side::Sell,
+        ChildOrderType::Market,
+        dec!(0.3),
+    )
+    .with_acceptance_id("JRF-close")
+    .with_fill(dec!(0.3), dec!(10000000));
+    mock.respond::<GetChildOrders>(vec![close]);
+
+    let report = kill_switch(&mock, &[ProductCode::FxBtcJpy], &options())
+        .await
+        .unwrap();
+    assert_eq!(report.attempts, 3);
+    assert_eq!(report.close_orders, vec!["JRF-close".to_string()]);
+    let sent = mock.sent::<SendChildOrder>().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!((sent[0].side, sent[0].size), (Side::Sell, dec!(0.3)));
+}
@@ -0,0 +1,147 @@
+// Slippage-protected and amended orders against `MockBitflyer`.
+
+use bitflyer::api::{
+    CancelChildOrder, GetBoard, GetChildOrders, SendChildOrder, SendChildOrderResponse,
+};
+use bitflyer::entity::{
+    Board, ChildOrder, ChildOrderType, MinuteToExpire, ProductCode, Side, TimeInForce,
+};
+use bitflyer::mock::MockBitflyer;
+use bitflyer::orders::{
+    amend_child_order, slippage_protected_order, AmendOutcome, SlippageAction, SlippageProtection,
+};
+use chrono::{TimeDelta, Utc};
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+const LIMIT: SlippageProtection = SlippageProtection {
+    max_slippage: dec!(0.01),
+    action: SlippageAction::Limit,
+};
+
+fn board(value: serde_json::Value) -> Board {
+    serde_json::from_value(value).unwrap()
+}
+
+#[tokio::test]
+async fn limits_the_price_from_the_best_one() {
+    let mock = MockBitflyer::new();
+    mock.respond::<GetBoard>(board(json!({
+        "mid_price": 10000000,
+        "bids": [{ "price": 9990000, "size": 1 }],
+        "asks": [{ "price": 10010000, "size": 1 }],
+    })));
+    let order = slippage_protected_order(&mock, ProductCode::BtcJpy, Side::Buy, dec!(0.1), &LIMIT)
+        .await
+        .unwrap();
+    assert_eq!(
+        order.request.child_order_type,
+        ChildOrderType::Limit {
+            price: dec!(10110100)
+        }
+    );
+    assert_eq!(order.request.time_in_force, Some(TimeInForce::Ioc));
+}
+
+#[tokio::test]
+async fn rounds_the_limit_toward_the_best_price() {
+    let mock = MockBitflyer::new();
+    mock.respond::<GetBoard>(board(json!({
+        "mid_price": 2000,
+        "bids": [{ "price": 2000, "size": 1 }],
+        "asks": [{ "price": 2000, "size": 1 }],
+    })));
+    let protection = SlippageProtection {
+        max_slippage: dec!(0.00075),
+        ..LIMIT
+    };
+    // 2,001.5 and 1,998.5 unrounded, halfway between the ticks.
+    for (side, price) in [(Side::Buy, dec!(2001)), (Side::Sell, dec!(1999))] {
+        let order =
+            slippage_protected_order(&mock, ProductCode::BtcJpy, side, dec!(0.1), &protection)
+                .await
+                .unwrap();
+        assert_eq!(
+            order.request.child_order_type,
+            ChildOrderType::Limit { price },
+            "{side:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn refuses_to_limit_without_a_price() {
+    for value in [
+        json!({ "mid_price": 10000000, "bids": [], "asks": [] }),
+        json!({
+            "mid_price": 0,
+            "bids": [{ "price": 0, "size": 1 }],
+            "asks": [{ "price": 0, "size": 1 }],
+        }),
+    ] {
+        let mock = MockBitflyer::new();
+        mock.respond::<GetBoard>(board(value));
+        let result =
+            slippage_protected_order(&mock, ProductCode::BtcJpy, Side::Buy, dec!(0.1), &LIMIT)
+                .await;
+        assert!(result.is_err(), "{result:?}");
+    }
+}
+
+fn resting_order(expires_in: TimeDelta) -> ChildOrder {
+    ChildOrder::new(
+        ProductCode::BtcJpy,
+        Side::Buy,
+        ChildOrderType::Limit {
+            price: dec!(9000000),
+        },
+        dec!(0.1),
+    )
+    .with_acceptance_id("JRF-RESTING")
+    .with_expire_date(Utc::now() + expires_in)
+}
+
+#[tokio::test]
+async fn amended_orders_keep_the_expiry() {
+    let order = resting_order(TimeDelta::minutes(120) + TimeDelta::seconds(30));
+    let mock = MockBitflyer::new();
+    mock.respond_once::<GetChildOrders>(vec![order.clone()])
+        .respond::<GetChildOrders>(vec![order.canceled()])
+        .respond_json::<CancelChildOrder>("")
+        .respond::<SendChildOrder>(SendChildOrderResponse::new("JRF-AMENDED"));
+
+    let outcome = amend_child_order(
+        &mock,
+        ProductCode::BtcJpy,
+        "JRF-RESTING",
+        Some(dec!(9100000)),
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(
+        matches!(outcome, AmendOutcome::Replaced { .. }),
+        "{outcome:?}"
+    );
+    let sent = mock.sent::<SendChildOrder>().unwrap();
+    assert_eq!(
+        sent[0].minute_to_expire,
+        Some(MinuteToExpire::from_minutes(120).unwrap())
+    );
+}
+
+#[tokio::test]
+async fn orders_about_to_expire_are_not_amended() {
+    let mock = MockBitflyer::new();
+    mock.respond::<GetChildOrders>(vec![resting_order(TimeDelta::seconds(30))]);
+    let result = amend_child_order(
+        &mock,
+        ProductCode::BtcJpy,
+        "JRF-RESTING",
+        Some(dec!(9100000)),
+        None,
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(mock.call_count::<CancelChildOrder>(), 0);
+}
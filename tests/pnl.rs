@@ -0,0 +1,207 @@
+// Realized PnL and lots of `PnlEngine`, with commissions on spot and FX.
+
+use bitflyer::entity::{PrivateExecution, ProductCode, Side};
+use bitflyer::pnl::{CostMethod, PnlEngine};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+fn execution(
+    id: u64,
+    side: &str,
+    price: Decimal,
+    size: Decimal,
+    commission: Decimal,
+) -> PrivateExecution {
+    serde_json::from_value(json!({
+        "id": id,
+        "child_order_id": format!("JOR{id}"),
+        "side": side,
+        "price": price,
+        "size": size,
+        "commission": commission,
+        "exec_date": format!("2026-01-01T00:00:{id:02}.000"),
+        "child_order_acceptance_id": format!("JRF{id}"),
+    }))
+    .unwrap()
+}
+
+fn engine(
+    method: CostMethod,
+    product_code: &ProductCode,
+    executions: &[PrivateExecution],
+) -> PnlEngine {
+    let mut engine = PnlEngine::new(method);
+    engine.process(product_code, executions);
+    engine
+}
+
+#[test]
+fn spot_buy_commission_leaves_nothing_after_selling_the_holdings() {
+    let product_code = ProductCode::BtcJpy;
+    for method in [CostMethod::AverageCost, CostMethod::Fifo] {
+        let engine = engine(
+            method,
+            &product_code,
+            &[
+                execution(1, "BUY", dec!(1000000), dec!(1), dec!(0.0015)),
+                execution(2, "SELL", dec!(1100000), dec!(0.9985), dec!(0)),
+            ],
+        );
+        let product = engine.product(&product_code).unwrap();
+        assert_eq!(product.net_size(), Decimal::ZERO, "{method:?}");
+        assert_eq!(product.side, None, "{method:?}");
+        // 1,098,350 of proceeds for the 1,000,000 paid.
+        assert_eq!(engine.realized_pnl().round_dp(8), dec!(98350), "{method:?}");
+        assert_eq!(product.commission, dec!(1500));
+    }
+}
+
+#[test]
+fn spot_buy_commission_raises_the_unit_cost() {
+    let product_code = ProductCode::BtcJpy;
+    let engine = engine(
+        CostMethod::AverageCost,
+        &product_code,
+        &[execution(1, "BUY", dec!(1000000), dec!(1), dec!(0.0015))],
+    );
+    let product = engine.product(&product_code).unwrap();
+    assert_eq!(product.net_size(), dec!(0.9985));
+    assert_eq!(product.cost_basis().round_dp(8), dec!(1000000));
+    assert_eq!(
+        product.unrealized_pnl(dec!(1000000)).round_dp(8),
+        dec!(-1500)
+    );
+}
+
+#[test]
+fn spot_sell_commission_closes_more_than_the_executed_size() {
+    let product_code = ProductCode::BtcJpy;
+    let engine = engine(
+        CostMethod::Fifo,
+        &product_code,
+        &[
+            execution(1, "BUY", dec!(1000000), dec!(1.001), dec!(0.001)),
+            execution(2, "SELL", dec!(1200000), dec!(0.5), dec!(0.0005)),
+        ],
+    );
+    let product = engine.product(&product_code).unwrap();
+    assert_eq!(product.net_size(), dec!(0.4995));
+    let realization = &engine.realizations()[0];
+    assert_eq!(realization.size, dec!(0.5005));
+    // 600,000 of proceeds for 0.5005 bought at 1,001,000 / 1.
+    assert_eq!(realization.pnl.round_dp(8), dec!(98999.5));
+}
+
+#[test]
+fn fifo_closes_the_oldest_lot_first() {
+    let product_code = ProductCode::BtcJpy;
+    let engine = engine(
+        CostMethod::Fifo,
+        &product_code,
+        &[
+            execution(1, "BUY", dec!(1000000), dec!(0.5), dec!(0.00075)),
+            execution(2, "BUY", dec!(1200000), dec!(0.5), dec!(0.00075)),
+            execution(3, "SELL", dec!(1100000), dec!(0.6), dec!(0.0009)),
+        ],
+    );
+    let product = engine.product(&product_code).unwrap();
+    assert_eq!(product.lots.len(), 1);
+    assert_eq!(product.lots[0].execution_id, 2);
+    assert_eq!(product.net_size(), dec!(0.3976));
+
+    let realizations = engine.realizations();
+    assert_eq!(realizations.len(), 2);
+    assert_eq!(realizations[0].entry_execution_id, 1);
+    assert_eq!(realizations[0].size, dec!(0.49925));
+    assert_eq!(realizations[1].entry_execution_id, 2);
+    assert_eq!(realizations[1].size, dec!(0.10165));
+    // 660,000 of proceeds for all of the first lot (500,000) and 0.10165 / 0.49925 of the
+    // second (600,000).
+    assert_eq!(engine.realized_pnl().round_dp(2), dec!(37836.76));
+    assert_eq!(product.cost_basis().round_dp(2), dec!(477836.76));
+}
+
+#[test]
+fn average_cost_merges_the_lots() {
+    let product_code = ProductCode::BtcJpy;
+    let engine = engine(
+        CostMethod::AverageCost,
+        &product_code,
+        &[
+            execution(1, "BUY", dec!(1000000), dec!(0.5), dec!(0.00075)),
+            execution(2, "BUY", dec!(1200000), dec!(0.5), dec!(0.00075)),
+            execution(3, "SELL", dec!(1100000), dec!(0.6), dec!(0.0009)),
+        ],
+    );
+    let product = engine.product(&product_code).unwrap();
+    assert_eq!(product.lots.len(), 1);
+    assert_eq!(product.net_size(), dec!(0.3976));
+    // 1,100,000 paid for 0.9985, of which 0.6009 is sold for 660,000.
+    assert_eq!(engine.realized_pnl().round_dp(2), dec!(-1982.97));
+    assert_eq!(product.cost_basis().round_dp(2), dec!(438017.03));
+}
+
+#[test]
+fn spot_oversell_does_not_open_a_short() {
+    let product_code = ProductCode::BtcJpy;
+    let engine = engine(
+        CostMethod::AverageCost,
+        &product_code,
+        &[
+            execution(1, "BUY", dec!(1000000), dec!(0.1), dec!(0)),
+            execution(2, "SELL", dec!(1100000), dec!(0.3), dec!(0)),
+        ],
+    );
+    let product = engine.product(&product_code).unwrap();
+    assert_eq!(product.net_size(), Decimal::ZERO);
+    assert_eq!(product.side, None);
+    assert_eq!(engine.realized_pnl(), dec!(10000));
+}
+
+#[test]
+fn fx_commission_is_valued_at_the_price_and_keeps_the_size() {
+    let product_code = ProductCode::FxBtcJpy;
+    let engine = engine(
+        CostMethod::AverageCost,
+        &product_code,
+        &[
+            execution(1, "SELL", dec!(1000000), dec!(1), dec!(0.001)),
+            execution(2, "BUY", dec!(900000), dec!(1.5), dec!(0.0015)),
+        ],
+    );
+    let product = engine.product(&product_code).unwrap();
+    // The short is closed and the rest opens a long.
+    assert_eq!(product.side, Some(Side::Buy));
+    assert_eq!(product.net_size(), dec!(0.5));
+    assert_eq!(product.average_cost(), Some(dec!(900900)));
+    // 100,000 on the price less 1,000 and 900 of commission.
+    assert_eq!(engine.realized_pnl(), dec!(98100));
+    assert_eq!(product.commission, dec!(2350));
+}
+
+#[test]
+fn futures_are_traded_on_margin() {
+    let product_code: ProductCode = serde_json::from_value(json!("BTCJPY27DEC2026")).unwrap();
+    assert!(product_code.is_fx());
+    let engine = engine(
+        CostMethod::AverageCost,
+        &product_code,
+        &[execution(1, "SELL", dec!(1000000), dec!(0.3), dec!(0))],
+    );
+    // Unlike on spot, selling with nothing held opens a short.
+    assert_eq!(
+        engine.product(&product_code).unwrap().side,
+        Some(Side::Sell)
+    );
+}
+
+#[test]
+fn executions_are_applied_once() {
+    let product_code = ProductCode::FxBtcJpy;
+    let buy = execution(1, "BUY", dec!(1000000), dec!(1), dec!(0));
+    let mut engine = PnlEngine::new(CostMethod::Fifo);
+    engine.apply(&product_code, &buy);
+    engine.apply(&product_code, &buy);
+    assert_eq!(engine.product(&product_code).unwrap().net_size(), dec!(1));
+}
@@ -0,0 +1,112 @@
+// Positions of `PositionTracker` against the balances and positions the exchange reports.
+
+use bitflyer::entity::{Balance, ProductCode, Side};
+use bitflyer::position_tracker::{BalanceDiscrepancy, PositionTracker};
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+fn balances(amounts: &[(&str, &str)]) -> Vec<Balance> {
+    let balances: Vec<_> = amounts
+        .iter()
+        .map(|(currency_code, amount)| {
+            json!({ "currency_code": currency_code, "amount": amount, "available": amount })
+        })
+        .collect();
+    serde_json::from_value(json!(balances)).unwrap()
+}
+
+#[test]
+fn spot_commission_comes_out_of_the_size() {
+    let product_code = ProductCode::BtcJpy;
+    let mut tracker = PositionTracker::new();
+    tracker.set_balance_baseline(&balances(&[("JPY", "2000000"), ("BTC", "0")]));
+    tracker.apply_fill(
+        &product_code,
+        Side::Buy,
+        dec!(1000000),
+        dec!(1),
+        dec!(0.0015),
+    );
+    tracker.apply_fill(
+        &product_code,
+        Side::Sell,
+        dec!(1100000),
+        dec!(0.5),
+        dec!(0.00075),
+    );
+
+    let position = tracker.position(&product_code).unwrap();
+    // 1 - 0.0015 bought, 0.5 + 0.00075 sold.
+    assert_eq!(position.net_size, dec!(0.49775));
+    assert_eq!(position.commission, dec!(0.00225));
+    // 1,000,000 paid and 550,000 received.
+    let after = balances(&[("JPY", "1550000"), ("BTC", "0.49775")]);
+    assert!(tracker.reconcile_balances(&after).is_empty());
+
+    let discrepancies =
+        tracker.reconcile_balances(&balances(&[("JPY", "1550000"), ("BTC", "0.5")]));
+    assert_eq!(
+        discrepancies,
+        vec![BalanceDiscrepancy {
+            currency_code: "BTC".to_string(),
+            tracked_change: dec!(0.49775),
+            exchange_change: dec!(0.5),
+        }]
+    );
+}
+
+#[test]
+fn shared_currencies_sum_every_product_since_the_baseline() {
+    let mut tracker = PositionTracker::new();
+    // Held before tracking started, e.g. deposited.
+    let baseline = balances(&[("JPY", "1000000"), ("BTC", "1"), ("ETH", "5")]);
+    tracker.set_balance_baseline(&baseline);
+    assert!(tracker.reconcile_balances(&baseline).is_empty());
+
+    tracker.apply_fill(
+        &ProductCode::EthJpy,
+        Side::Buy,
+        dec!(500000),
+        dec!(1),
+        dec!(0),
+    );
+    // ETH bought with BTC, the quote currency.
+    tracker.apply_fill(
+        &ProductCode::EthBtc,
+        Side::Buy,
+        dec!(0.05),
+        dec!(2),
+        dec!(0),
+    );
+    tracker.apply_fill(
+        &ProductCode::BtcJpy,
+        Side::Sell,
+        dec!(10000000),
+        dec!(0.1),
+        dec!(0),
+    );
+
+    let after = balances(&[("JPY", "1500000"), ("BTC", "0.8"), ("ETH", "8")]);
+    assert!(tracker.reconcile_balances(&after).is_empty());
+}
+
+#[test]
+fn fx_commission_keeps_the_size() {
+    let product_code = ProductCode::FxBtcJpy;
+    let mut tracker = PositionTracker::new();
+    tracker.set_balance_baseline(&balances(&[("BTC", "0")]));
+    tracker.apply_fill(
+        &product_code,
+        Side::Sell,
+        dec!(1000000),
+        dec!(0.3),
+        dec!(0.0003),
+    );
+    let position = tracker.position(&product_code).unwrap();
+    assert_eq!(position.net_size, dec!(-0.3));
+    assert_eq!(position.side(), Some(Side::Sell));
+    assert_eq!(position.average_price, dec!(999000));
+    assert!(tracker
+        .reconcile_balances(&balances(&[("BTC", "0")]))
+        .is_empty());
+}
@@ -0,0 +1,224 @@
+// Limits of `RiskChecker`, on their own and enforced by `SimClient`.
+
+use anyhow::Result;
+use bitflyer::api::{BitflyerApi, CancelChildOrder, OrderIntent, SendChildOrder};
+use bitflyer::backtest::Backtest;
+use bitflyer::entity::{Board, Execution, ProductCode, Side};
+use bitflyer::events::Event;
+use bitflyer::risk::{RiskChecker, RiskLimits, RiskViolation};
+use bitflyer::sim::SimClient;
+use bitflyer::strategy::{Strategy, StrategyContext};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::json;
+use std::sync::Arc;
+
+fn intent(side: Side, size: Decimal, price: Option<Decimal>) -> OrderIntent {
+    OrderIntent::new(ProductCode::BtcJpy, side, size, price)
+}
+
+fn violation(api_result: anyhow::Result<impl std::fmt::Debug>) -> RiskViolation {
+    api_result
+        .unwrap_err()
+        .downcast::<RiskViolation>()
+        .expect("a risk violation")
+}
+
+fn board() -> Board {
+    serde_json::from_value(json!({
+        "mid_price": 10000000,
+        "bids": [{ "price": 9990000, "size": 1 }],
+        "asks": [{ "price": 10010000, "size": 1 }],
+    }))
+    .unwrap()
+}
+
+#[test]
+fn the_collar_refuses_without_a_reference_price() {
+    let checker = RiskChecker::new(RiskLimits {
+        price_collar: Some(dec!(0.05)),
+        ..Default::default()
+    });
+    for price in [Some(dec!(10000000)), None] {
+        assert_eq!(
+            checker.check(&intent(Side::Buy, dec!(0.01), price)),
+            Err(RiskViolation::MissingReferencePrice(ProductCode::BtcJpy))
+        );
+    }
+
+    checker.set_reference_price(ProductCode::BtcJpy, dec!(10000000));
+    assert_eq!(checker.check(&intent(Side::Buy, dec!(0.01), None)), Ok(()));
+    assert_eq!(
+        checker.check(&intent(Side::Buy, dec!(0.01), Some(dec!(10400000)))),
+        Ok(())
+    );
+    assert!(matches!(
+        checker.check(&intent(Side::Buy, dec!(0.01), Some(dec!(10600000)))),
+        Err(RiskViolation::PriceOutsideCollar { .. })
+    ));
+}
+
+#[tokio::test]
+async fn the_simulator_keeps_the_open_order_count() {
+    let checker = Arc::new(RiskChecker::new(RiskLimits {
+        max_open_orders: Some(2),
+        ..Default::default()
+    }));
+    let sim = SimClient::new()
+        .with_balance("JPY", dec!(1000000))
+        .with_risk_checker(checker.clone());
+    sim.on_board(&ProductCode::BtcJpy, board());
+
+    let price = dec!(9000000);
+    let order = || SendChildOrder::limit(ProductCode::BtcJpy, Side::Buy, dec!(0.01), price);
+    let first = sim.send(order()).await.unwrap();
+    sim.send(order()).await.unwrap();
+    assert_eq!(
+        violation(sim.send(order()).await),
+        RiskViolation::TooManyOpenOrders { open: 2, max: 2 }
+    );
+
+    sim.send(CancelChildOrder {
+        product_code: ProductCode::BtcJpy,
+        child_order_acceptance_id: first.child_order_acceptance_id,
+    })
+    .await
+    .unwrap();
+    sim.send(order()).await.unwrap();
+    assert_eq!(checker.open_orders(), 2);
+}
+
+#[test]
+fn the_loss_limit_lets_closing_orders_through() {
+    let checker = RiskChecker::new(RiskLimits {
+        daily_loss_limit: Some(dec!(10000)),
+        ..Default::default()
+    });
+    checker.set_position(ProductCode::BtcJpy, dec!(0.5));
+    checker.record_realized_pnl(dec!(-10000));
+
+    let refused = |side, size| {
+        matches!(
+            checker.check(&intent(side, size, None)),
+            Err(RiskViolation::DailyLossLimitReached { .. })
+        )
+    };
+    assert!(refused(Side::Buy, dec!(0.1)));
+    // Reversing the position opens a new one.
+    assert!(refused(Side::Sell, dec!(0.6)));
+    assert!(!refused(Side::Sell, dec!(0.5)));
+    assert!(!refused(Side::Sell, dec!(0.2)));
+}
+
+#[tokio::test]
+async fn the_simulator_closes_positions_after_the_loss_limit() {
+    let checker = Arc::new(RiskChecker::new(RiskLimits {
+        daily_loss_limit: Some(dec!(10000)),
+        ..Default::default()
+    }));
+    let sim = SimClient::new()
+        .with_collateral(dec!(1000000))
+        .with_risk_checker(checker.clone());
+    sim.on_board(&ProductCode::FxBtcJpy, board());
+    let market = |side, size| SendChildOrder::market(ProductCode::FxBtcJpy, side, size);
+
+    sim.send(market(Side::Buy, dec!(0.1))).await.unwrap();
+    checker.record_realized_pnl(dec!(-20000));
+    assert!(matches!(
+        violation(sim.send(market(Side::Buy, dec!(0.01))).await),
+        RiskViolation::DailyLossLimitReached { .. }
+    ));
+    sim.send(market(Side::Sell, dec!(0.1))).await.unwrap();
+    // Flat now, so selling again would open a short.
+    assert!(matches!(
+        violation(sim.send(market(Side::Sell, dec!(0.1))).await),
+        RiskViolation::DailyLossLimitReached { .. }
+    ));
+}
+
+#[test]
+fn concurrent_orders_are_counted_as_they_are_checked() {
+    let checker = Arc::new(RiskChecker::new(RiskLimits {
+        max_open_orders: Some(3),
+        ..Default::default()
+    }));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let checker = checker.clone();
+            std::thread::spawn(move || {
+                checker
+                    .check_and_record(&[intent(Side::Buy, dec!(0.01), None)])
+                    .is_ok()
+            })
+        })
+        .collect();
+    let passed = threads
+        .into_iter()
+        .map(|x| x.join().unwrap())
+        .filter(|x| *x)
+        .count();
+    assert_eq!(passed, 3);
+    assert_eq!(checker.open_orders(), 3);
+
+    checker.release_order();
+    assert_eq!(
+        checker.check_and_record(&[intent(Side::Buy, dec!(0.01), None)]),
+        Ok(true)
+    );
+}
+
+// Buys on every trade and keeps what the simulator answered.
+#[derive(Default)]
+struct BuyEveryTrade {
+    results: Vec<Result<(), String>>,
+}
+
+impl Strategy for BuyEveryTrade {
+    async fn on_event<A: BitflyerApi + 'static>(
+        &mut self,
+        context: &StrategyContext<A>,
+        event: &Event,
+    ) -> Result<()> {
+        if let Event::Trade { product_code, .. } = event {
+            let result = context
+                .api()
+                .send(SendChildOrder::market(
+                    product_code.clone(),
+                    Side::Buy,
+                    dec!(0.1),
+                ))
+                .await;
+            self.results
+                .push(result.map(|_| ()).map_err(|e| e.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn open_losses_count_towards_the_loss_limit() {
+    let checker = Arc::new(RiskChecker::new(RiskLimits {
+        daily_loss_limit: Some(dec!(10000)),
+        ..Default::default()
+    }));
+    let executions = [dec!(10000000), dec!(9850000), dec!(9800000)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, price)| Execution::new(i as u64 + 1, Side::Sell, price, dec!(1)))
+        .collect();
+    let sim = SimClient::new().with_balance("JPY", dec!(10000000));
+    let mut strategy = BuyEveryTrade::default();
+    Backtest::new(sim, ProductCode::BtcJpy, executions)
+        .with_risk_checker(checker.clone())
+        .run(&mut strategy)
+        .await
+        .unwrap();
+
+    // The first buy is 15,000 down once its fill is in at 9,850,000, with nothing realized.
+    assert_eq!(checker.daily_pnl().realized, dec!(0));
+    assert!(checker.daily_pnl().total() <= dec!(-10000));
+    assert!(strategy.results[0].is_ok());
+    assert!(strategy.results[1].is_ok());
+    let error = strategy.results[2].as_ref().unwrap_err();
+    assert!(error.contains("loss limit"), "{error}");
+}
@@ -0,0 +1,202 @@
+// Fills and margin of `SimClient` against the book it is given.
+
+use bitflyer::api::{BitflyerApi, GetChildOrders, GetPositions, SendChildOrder};
+use bitflyer::entity::{Board, Execution, OrderState, ProductCode, Side, TimeInForce};
+use bitflyer::sim::{FeeSchedule, SimClient};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+fn board() -> Board {
+    board_at(dec!(10000000))
+}
+
+// Two levels of 0.1 on each side, 10,000 apart from `mid_price`.
+fn board_at(mid_price: Decimal) -> Board {
+    let step = dec!(10000);
+    serde_json::from_value(json!({
+        "mid_price": mid_price,
+        "bids": [
+            { "price": mid_price - step, "size": 0.1 },
+            { "price": mid_price - step * dec!(2), "size": 0.1 },
+        ],
+        "asks": [
+            { "price": mid_price + step, "size": 0.1 },
+            { "price": mid_price + step * dec!(2), "size": 0.1 },
+        ],
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn market_orders_fill_as_deep_as_the_book_and_cancel_the_rest() {
+    let product_code = ProductCode::BtcJpy;
+    let sim = SimClient::new().with_balance("JPY", dec!(10000000));
+    sim.on_board(&product_code, board());
+
+    let response = sim
+        .send(SendChildOrder::market(
+            product_code.clone(),
+            Side::Buy,
+            dec!(0.5),
+        ))
+        .await
+        .unwrap();
+    let orders = sim
+        .send(GetChildOrders {
+            child_order_acceptance_id: Some(response.child_order_acceptance_id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let order = &orders[0];
+    assert_eq!(order.child_order_state, OrderState::Canceled);
+    assert_eq!(order.executed_size, dec!(0.2));
+    assert_eq!(order.cancel_size, dec!(0.3));
+    assert_eq!(order.average_price, dec!(10015000));
+    assert_eq!(sim.balance("BTC"), dec!(0.2));
+    assert_eq!(sim.balance("JPY"), dec!(7997000));
+}
+
+#[tokio::test]
+async fn fx_orders_need_the_collateral_at_the_leverage() {
+    let product_code = ProductCode::FxBtcJpy;
+    let sim = SimClient::new().with_collateral(dec!(1000000));
+    sim.on_board(&product_code, board());
+    let market = |side, size| SendChildOrder::market(product_code.clone(), side, size);
+    let limit = |side, size| SendChildOrder::limit(product_code.clone(), side, size, dec!(9000000));
+
+    // 0.1 at 10,010,000 takes 500,500 of it at 2x.
+    sim.send(market(Side::Buy, dec!(0.1))).await.unwrap();
+    // Another 450,000 for a resting order leaves less than 450,000.
+    sim.send(limit(Side::Buy, dec!(0.1))).await.unwrap();
+    let error = sim.send(limit(Side::Buy, dec!(0.1))).await.unwrap_err();
+    assert!(
+        error.to_string().contains("insufficient collateral"),
+        "{error}"
+    );
+
+    // Closing needs none, and the part that reverses the position is charged on its own.
+    sim.send(market(Side::Sell, dec!(0.1))).await.unwrap();
+    assert!(sim.send(GetPositions {}).await.unwrap().is_empty());
+    assert!(sim.send(market(Side::Sell, dec!(0.3))).await.is_err());
+    sim.send(market(Side::Sell, dec!(0.1))).await.unwrap();
+}
+
+#[tokio::test]
+async fn resting_spot_orders_fill_as_makers_with_the_commission_in_btc() {
+    let product_code = ProductCode::BtcJpy;
+    let sim = SimClient::new()
+        .with_balance("JPY", dec!(10000000))
+        .with_fee_schedule(FeeSchedule::new(dec!(0.001), dec!(0.002)));
+    sim.on_board(&product_code, board());
+
+    let response = sim
+        .send(SendChildOrder::limit(
+            product_code.clone(),
+            Side::Buy,
+            dec!(0.2),
+            dec!(9900000),
+        ))
+        .await
+        .unwrap();
+    sim.on_execution(
+        &product_code,
+        &Execution::new(1, Side::Sell, dec!(9900000), dec!(0.1)),
+    );
+
+    let orders = sim
+        .send(GetChildOrders {
+            child_order_acceptance_id: Some(response.child_order_acceptance_id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let order = &orders[0];
+    assert_eq!(order.child_order_state, OrderState::Active);
+    assert_eq!(order.executed_size, dec!(0.1));
+    assert_eq!(order.total_commission, dec!(0.0001));
+    // The commission comes out of the BTC, the JPY pays for all of the executed size.
+    assert_eq!(sim.balance("BTC"), dec!(0.0999));
+    assert_eq!(sim.balance("JPY"), dec!(9010000));
+}
+
+#[tokio::test]
+async fn fx_round_trips_settle_into_the_collateral() {
+    let product_code = ProductCode::FxBtcJpy;
+    let sim = SimClient::new()
+        .with_collateral(dec!(1000000))
+        .with_fee_schedule(FeeSchedule::flat(dec!(0.001)));
+    sim.on_board(&product_code, board());
+    let market = |side| SendChildOrder::market(product_code.clone(), side, dec!(0.1));
+
+    sim.send(market(Side::Buy)).await.unwrap();
+    let positions = sim.send(GetPositions {}).await.unwrap();
+    // The unit cost carries the 1,001 of commission.
+    assert_eq!(positions[0].price, dec!(10020010));
+    assert_eq!(sim.collateral(), dec!(1000000));
+
+    sim.on_board(&product_code, board_at(dec!(10120000)));
+    sim.send(market(Side::Sell)).await.unwrap();
+    assert!(sim.send(GetPositions {}).await.unwrap().is_empty());
+    // 100,000 a BTC less 1,001 and 1,011 of commission.
+    assert_eq!(sim.collateral(), dec!(1007988));
+}
+
+// A buy for 0.3 up to 10,015,000, where only the first ask is within the price.
+async fn marketable_limit(time_in_force: TimeInForce) -> (OrderState, Decimal, Decimal) {
+    let product_code = ProductCode::BtcJpy;
+    let sim = SimClient::new().with_balance("JPY", dec!(10000000));
+    sim.on_board(&product_code, board());
+    let response = sim
+        .send(SendChildOrder {
+            time_in_force: Some(time_in_force),
+            ..SendChildOrder::limit(product_code, Side::Buy, dec!(0.3), dec!(10015000))
+        })
+        .await
+        .unwrap();
+    let orders = sim
+        .send(GetChildOrders {
+            child_order_acceptance_id: Some(response.child_order_acceptance_id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let order = &orders[0];
+    (
+        order.child_order_state.clone(),
+        order.executed_size,
+        sim.balance("BTC"),
+    )
+}
+
+#[tokio::test]
+async fn marketable_limit_orders_fill_down_to_their_price() {
+    assert_eq!(
+        marketable_limit(TimeInForce::Gtc).await,
+        (OrderState::Active, dec!(0.1), dec!(0.1))
+    );
+    assert_eq!(
+        marketable_limit(TimeInForce::Ioc).await,
+        (OrderState::Canceled, dec!(0.1), dec!(0.1))
+    );
+    assert_eq!(
+        marketable_limit(TimeInForce::Fok).await,
+        (OrderState::Canceled, dec!(0), dec!(0))
+    );
+}
+
+#[tokio::test]
+async fn spot_sells_keep_back_the_commission() {
+    let product_code = ProductCode::BtcJpy;
+    let sim = SimClient::new()
+        .with_balance("BTC", dec!(0.1))
+        .with_fee_schedule(FeeSchedule::flat(dec!(0.001)));
+    sim.on_board(&product_code, board());
+    let sell = |size| SendChildOrder::market(product_code.clone(), Side::Sell, size);
+
+    let error = sim.send(sell(dec!(0.1))).await.unwrap_err();
+    assert!(error.to_string().contains("insufficient BTC"), "{error}");
+    sim.send(sell(dec!(0.0999))).await.unwrap();
+    assert!(sim.balance("BTC") >= dec!(0));
+}
@@ -0,0 +1,150 @@
+// Yearly tax reports on a worked example with commissions charged in BTC:
+//
+//   2024-06-01  buy  0.1 @ 3,000,000, commission 0.0001  -> +0.0999, 300,000 paid
+//   2025-01-01  buy  1   @ 4,000,000, commission 0.001   -> +0.999, 4,000,000 paid
+//   2025-05-01  sell 0.5 @ 5,000,000, commission 0.0005  -> -0.5005, 2,500,000 received
+//   2025-08-01  buy  0.5 @ 6,000,000, commission 0.0005  -> +0.4995, 3,000,000 paid
+//   2025-11-01  sell 0.4 @ 7,000,000, commission 0.0004  -> -0.4004, 2,800,000 received
+//
+// The second one is at 00:30 JST on New Year's Day, still 2024 in UTC.
+
+use bitflyer::entity::{PrivateExecution, ProductCode};
+use bitflyer::tax::{tax_report, TaxEntryKind, TaxMethod, TaxReport};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+// Execution date, side, price, size and commission.
+type Row = [&'static str; 5];
+
+fn executions_of(product_code: ProductCode, rows: &[Row]) -> Vec<(ProductCode, PrivateExecution)> {
+    rows.iter()
+        .enumerate()
+        .map(|(i, [exec_date, side, price, size, commission])| {
+            let id = i + 1;
+            let execution = serde_json::from_value(json!({
+                "id": id,
+                "child_order_id": format!("JOR{id}"),
+                "side": side,
+                "price": price,
+                "size": size,
+                "commission": commission,
+                "exec_date": exec_date,
+                "child_order_acceptance_id": format!("JRF{id}"),
+            }))
+            .unwrap();
+            (product_code.clone(), execution)
+        })
+        .collect()
+}
+
+fn executions() -> Vec<(ProductCode, PrivateExecution)> {
+    executions_of(
+        ProductCode::BtcJpy,
+        &[
+            ["2024-06-01T00:00:00", "BUY", "3000000", "0.1", "0.0001"],
+            ["2024-12-31T15:30:00", "BUY", "4000000", "1", "0.001"],
+            ["2025-05-01T00:00:00", "SELL", "5000000", "0.5", "0.0005"],
+            ["2025-08-01T00:00:00", "BUY", "6000000", "0.5", "0.0005"],
+            ["2025-11-01T00:00:00", "SELL", "7000000", "0.4", "0.0004"],
+        ],
+    )
+}
+
+fn rounded(report: &TaxReport) -> Vec<(TaxEntryKind, Decimal, Decimal, Decimal)> {
+    report
+        .entries
+        .iter()
+        .map(|x| (x.kind, x.size, x.cost.round_dp(2), x.gain.round_dp(2)))
+        .collect()
+}
+
+#[test]
+fn moving_average_takes_the_cost_at_each_disposal() {
+    let report = tax_report(2025, TaxMethod::MovingAverage, &executions());
+    use TaxEntryKind::*;
+    assert_eq!(
+        rounded(&report),
+        vec![
+            (Acquisition, dec!(0.999), dec!(0), dec!(0)),
+            // 4,300,000 for 1.0989 before the sale.
+            (Disposal, dec!(0.5005), dec!(1958458.46), dec!(541541.54)),
+            (Acquisition, dec!(0.4995), dec!(0), dec!(0)),
+            // 2,341,541.54 left for 0.5984, plus 3,000,000 for 0.4995.
+            (Disposal, dec!(0.4004), dec!(1948040.11), dec!(851959.89)),
+        ]
+    );
+
+    let btc = &report.currencies[0];
+    assert_eq!(btc.opening_size, dec!(0.0999));
+    assert_eq!(btc.opening_cost, dec!(300000));
+    assert_eq!(btc.acquired_size, dec!(1.4985));
+    assert_eq!(btc.acquisition_cost, dec!(7000000));
+    assert_eq!(btc.disposed_size, dec!(0.9009));
+    assert_eq!(btc.proceeds, dec!(5300000));
+    assert_eq!(btc.closing_size, dec!(0.6975));
+    assert_eq!(btc.closing_cost.round_dp(2), dec!(3393501.43));
+    assert_eq!(report.spot_gain().round_dp(2), dec!(1393501.43));
+}
+
+#[test]
+fn total_average_takes_one_cost_for_the_year() {
+    let report = tax_report(2025, TaxMethod::TotalAverage, &executions());
+    use TaxEntryKind::*;
+    // (300,000 + 7,000,000) / (0.0999 + 1.4985) for every disposal.
+    assert_eq!(
+        rounded(&report),
+        vec![
+            (Acquisition, dec!(0.999), dec!(0), dec!(0)),
+            (Disposal, dec!(0.5005), dec!(2285817.07), dec!(214182.93)),
+            (Acquisition, dec!(0.4995), dec!(0), dec!(0)),
+            (Disposal, dec!(0.4004), dec!(1828653.65), dec!(971346.35)),
+        ]
+    );
+
+    let btc = &report.currencies[0];
+    assert_eq!(btc.opening_size, dec!(0.0999));
+    assert_eq!(btc.opening_cost, dec!(300000));
+    assert_eq!(btc.closing_size, dec!(0.6975));
+    assert_eq!(btc.closing_cost.round_dp(2), dec!(3185529.28));
+    assert_eq!(report.spot_gain().round_dp(2), dec!(1185529.28));
+}
+
+#[test]
+fn the_next_year_opens_with_the_closing_holdings() {
+    for method in [TaxMethod::MovingAverage, TaxMethod::TotalAverage] {
+        let year = tax_report(2025, method, &executions());
+        let next = tax_report(2026, method, &executions());
+        assert!(next.entries.is_empty());
+        let (closing, opening) = (&year.currencies[0], &next.currencies[0]);
+        assert_eq!(opening.opening_size, closing.closing_size, "{method:?}");
+        assert_eq!(opening.opening_cost, closing.closing_cost, "{method:?}");
+        assert_eq!(opening.closing_size, closing.closing_size, "{method:?}");
+    }
+}
+
+#[test]
+fn margin_pnl_counts_the_positions_closed_in_the_year() {
+    let executions = executions_of(
+        ProductCode::FxBtcJpy,
+        &[
+            // Closed in 2024.
+            ["2024-03-01T00:00:00", "BUY", "3000000", "0.1", "0"],
+            ["2024-04-01T00:00:00", "SELL", "3500000", "0.1", "0"],
+            // Opened in 2024 and closed in 2025, with a commission on the close.
+            ["2024-12-01T00:00:00", "SELL", "5000000", "0.2", "0"],
+            ["2025-02-01T00:00:00", "BUY", "4000000", "0.2", "0.0001"],
+            // Still open at the end of 2025.
+            ["2025-12-01T00:00:00", "BUY", "6000000", "0.3", "0"],
+        ],
+    );
+    assert_eq!(
+        tax_report(2024, TaxMethod::MovingAverage, &executions).margin_pnl,
+        dec!(50000)
+    );
+    let report = tax_report(2025, TaxMethod::MovingAverage, &executions);
+    // 200,000 less 400 of commission.
+    assert_eq!(report.margin_pnl, dec!(199600));
+    assert!(report.currencies.is_empty());
+    assert_eq!(report.taxable_gain(), dec!(199600));
+}